@@ -1,152 +1,309 @@
+use std::collections::HashSet;
 use std::fs;
 use std::io::{Read, Write};
-use std::path::Path;
+use std::path::{Path, PathBuf};
 
 const CIRCLE_FLAGS_URL: &str = "https://github.com/HatScripts/circle-flags/archive/refs/heads/gh-pages.zip";
 const FLAGS_DIR: &str = "assets/flags";
-const ICON_SIZE: u32 = 64;
+/// Traditional 4:3 flags for `[icons] style = "rectangle"`; optional, the style falls
+/// back to circle flags when these could not be downloaded
+const RECT_FLAGS_URL: &str = "https://github.com/lipis/flag-icons/archive/refs/heads/main.zip";
+const RECT_FLAGS_DIR: &str = "assets/flags-rect";
+/// Hand-drawn artwork for states without a country flag
+const STATUS_DIR: &str = "assets/status";
+const STATUS_ICONS: &[&str] = &["globe", "unknown", "offline", "wifi", "ethernet", "vpn"];
+/// Extra flags to embed when the `all-flags` feature is off, e.g. "vn,us,de"
+const FLAGS_ENV: &str = "NETWORK_MONITOR_FLAGS";
+/// Directory of `<code>.svg` flags used instead of circle-flags, for branded packages
+const FLAGS_DIR_ENV: &str = "NETWORK_MONITOR_FLAGS_DIR";
+/// Tray pixmap sizes, e.g. "22,44"; artwork is rendered at each
+const ICON_SIZES_ENV: &str = "NETWORK_MONITOR_ICON_SIZES";
+
+/// ISO 3166-1 codes per `flags-<continent>` feature
+const CONTINENTS: &[(&str, &[&str])] = &[
+    ("AFRICA", &[
+        "ao", "bf", "bi", "bj", "bw", "cd", "cf", "cg", "ci", "cm", "cv", "dj", "dz", "eg", "eh", "er",
+        "et", "ga", "gh", "gm", "gn", "gq", "gw", "ke", "km", "lr", "ls", "ly", "ma", "mg", "ml", "mr",
+        "mu", "mw", "mz", "na", "ne", "ng", "re", "rw", "sc", "sd", "sh", "sl", "sn", "so", "ss", "st",
+        "sz", "td", "tg", "tn", "tz", "ug", "yt", "za", "zm", "zw",
+    ]),
+    ("AMERICAS", &[
+        "ag", "ai", "ar", "aw", "bb", "bl", "bm", "bo", "bq", "br", "bs", "bz", "ca", "cl", "co", "cr",
+        "cu", "cw", "dm", "do", "ec", "fk", "gd", "gf", "gl", "gp", "gs", "gt", "gy", "hn", "ht", "jm",
+        "kn", "ky", "lc", "mf", "mq", "ms", "mx", "ni", "pa", "pe", "pm", "pr", "py", "sr", "sv", "sx",
+        "tc", "tt", "us", "uy", "vc", "ve", "vg", "vi",
+    ]),
+    ("ASIA", &[
+        "ae", "af", "am", "az", "bd", "bh", "bn", "bt", "cc", "cn", "cx", "cy", "ge", "hk", "id", "il",
+        "in", "io", "iq", "ir", "jo", "jp", "kg", "kh", "kp", "kr", "kw", "kz", "la", "lb", "lk", "mm",
+        "mn", "mo", "mv", "my", "np", "om", "ph", "pk", "ps", "qa", "sa", "sg", "sy", "th", "tj", "tl",
+        "tm", "tr", "tw", "uz", "vn", "ye",
+    ]),
+    ("EUROPE", &[
+        "ad", "al", "at", "ax", "ba", "be", "bg", "by", "ch", "cz", "de", "dk", "ee", "es", "fi", "fo",
+        "fr", "gb", "gg", "gi", "gr", "hr", "hu", "ie", "im", "is", "it", "je", "li", "lt", "lu", "lv",
+        "mc", "md", "me", "mk", "mt", "nl", "no", "pl", "pt", "ro", "rs", "ru", "se", "si", "sj", "sk",
+        "sm", "ua", "va", "xk",
+    ]),
+    ("OCEANIA", &[
+        "as", "au", "ck", "fj", "fm", "gu", "ki", "mh", "mp", "nc", "nf", "nr", "nu", "nz", "pf", "pg",
+        "pn", "pw", "sb", "tk", "to", "tv", "um", "vu", "wf", "ws",
+    ]),
+];
+/// Pixmap sizes offered to the host, which picks the closest to its panel size; one 64px
+/// icon looks blurry on HiDPI panels once the host scales it
+const DEFAULT_ICON_SIZES: &[u32] = &[22, 32, 48, 64, 128];
+const ICON_SIZE_RANGE: std::ops::RangeInclusive<u32> = 16..=512;
+/// Pre-rendered tray pixmaps are written here within OUT_DIR
+const PIXMAPS_DIR: &str = "pixmaps";
 
 fn main() {
     println!("cargo:rerun-if-changed=build.rs");
-
-    let flags_dir = Path::new(FLAGS_DIR);
+    println!("cargo:rerun-if-changed={}", STATUS_DIR);
+    println!("cargo:rerun-if-env-changed={}", FLAGS_ENV);
+    println!("cargo:rerun-if-env-changed={}", FLAGS_DIR_ENV);
+    println!("cargo:rerun-if-env-changed={}", ICON_SIZES_ENV);
+
+    let sizes = icon_sizes();
+
+    let custom_dir = std::env::var_os(FLAGS_DIR_ENV).map(PathBuf::from);
+    let flags_dir = match &custom_dir {
+        Some(dir) => {
+            println!("cargo:rerun-if-changed={}", dir.display());
+            dir.clone()
+        }
+        None => PathBuf::from(FLAGS_DIR),
+    };
+    let rect_dir = Path::new(RECT_FLAGS_DIR);
 
     // Download and extract flags if not present
-    if !flags_dir.exists() || fs::read_dir(flags_dir).map(|d| d.count()).unwrap_or(0) < 10 {
-        download_and_extract_flags(flags_dir);
+    if let Some(dir) = &custom_dir {
+        if !has_flags(dir) {
+            panic!("{} = {} has no flag SVGs", FLAGS_DIR_ENV, dir.display());
+        }
+    } else if !has_flags(&flags_dir) {
+        println!("cargo:warning=Downloading circle-flags...");
+        download_flags(CIRCLE_FLAGS_URL, "/flags/", &flags_dir)
+            .unwrap_or_else(|e| panic!("Failed to download circle-flags: {}", e));
+        println!("cargo:warning=Circle-flags downloaded successfully");
+    }
+    if !has_flags(rect_dir) {
+        println!("cargo:warning=Downloading flag-icons...");
+        if let Err(e) = download_flags(RECT_FLAGS_URL, "/flags/4x3/", rect_dir) {
+            println!("cargo:warning=No rectangular flags, style = \"rectangle\" will show circle flags: {}", e);
+        }
     }
 
-    // Convert SVGs to PNGs
-    convert_svgs_to_pngs(flags_dir);
+    generate_icons_module(&flags_dir, rect_dir, &sizes, selected_flags().as_ref());
+}
 
-    // Generate icons module code
-    generate_icons_module(flags_dir);
+/// Sorted pixmap sizes from `NETWORK_MONITOR_ICON_SIZES`, or the defaults
+fn icon_sizes() -> Vec<u32> {
+    let Ok(list) = std::env::var(ICON_SIZES_ENV) else { return DEFAULT_ICON_SIZES.to_vec() };
+    let mut sizes: Vec<u32> = list
+        .split(',')
+        .map(str::trim)
+        .filter(|s| !s.is_empty())
+        .map(|s| match s.parse() {
+            Ok(size) if ICON_SIZE_RANGE.contains(&size) => size,
+            _ => panic!(
+                "{}: '{}' is not a size from {} to {}",
+                ICON_SIZES_ENV,
+                s,
+                ICON_SIZE_RANGE.start(),
+                ICON_SIZE_RANGE.end()
+            ),
+        })
+        .collect();
+    sizes.sort_unstable();
+    sizes.dedup();
+    if sizes.is_empty() {
+        panic!("{} lists no sizes", ICON_SIZES_ENV);
+    }
+    sizes
 }
 
-fn download_and_extract_flags(flags_dir: &Path) {
-    println!("cargo:warning=Downloading circle-flags...");
+fn has_flags(dir: &Path) -> bool {
+    fs::read_dir(dir).map(|d| d.count()).unwrap_or(0) >= 10
+}
 
-    fs::create_dir_all(flags_dir).expect("Failed to create flags directory");
+/// Flags to embed, or None for all of them (`all-flags` feature)
+fn selected_flags() -> Option<HashSet<String>> {
+    if std::env::var_os("CARGO_FEATURE_ALL_FLAGS").is_some() {
+        return None;
+    }
 
-    let response = reqwest::blocking::get(CIRCLE_FLAGS_URL)
-        .expect("Failed to download circle-flags");
+    let mut selected: HashSet<String> = CONTINENTS
+        .iter()
+        .filter(|(name, _)| std::env::var_os(format!("CARGO_FEATURE_FLAGS_{}", name)).is_some())
+        .flat_map(|(_, codes)| codes.iter().map(|c| c.to_string()))
+        .collect();
+    if let Ok(list) = std::env::var(FLAGS_ENV) {
+        selected.extend(list.split(',').map(|c| c.trim().to_lowercase()).filter(|c| !c.is_empty()));
+    }
+    Some(selected)
+}
 
-    let bytes = response.bytes().expect("Failed to read response");
-    let reader = std::io::Cursor::new(bytes);
-    let mut archive = zip::ZipArchive::new(reader).expect("Failed to open zip");
+/// True if the flag named `stem` should be embedded; subdivisions ("gb-sct") follow their country
+fn is_selected(stem: &str, selection: Option<&HashSet<String>>) -> bool {
+    let Some(selection) = selection else { return true };
+    let stem = stem.to_lowercase();
+    let country = stem.split('-').next().unwrap_or_default();
+    selection.contains(&stem) || selection.contains(country)
+}
+
+/// Extracts the SVGs under `member` (e.g. "/flags/") from the zip at `url` into `dest`
+fn download_flags(url: &str, member: &str, dest: &Path) -> Result<(), Box<dyn std::error::Error>> {
+    let bytes = reqwest::blocking::get(url)?.error_for_status()?.bytes()?;
+    let mut archive = zip::ZipArchive::new(std::io::Cursor::new(bytes))?;
+    fs::create_dir_all(dest)?;
 
-    // Extract only SVG files from flags directory
     for i in 0..archive.len() {
-        let mut file = archive.by_index(i).expect("Failed to get zip entry");
+        let mut file = archive.by_index(i)?;
         let name = file.name().to_string();
 
-        // Look for SVG files in the flags directory
-        if name.contains("/flags/") && name.ends_with(".svg") {
+        // Look for SVG files directly in the member directory
+        let in_member = name
+            .split_once(member)
+            .is_some_and(|(_, rest)| !rest.contains('/') && rest.ends_with(".svg"));
+        if in_member {
             if let Some(filename) = Path::new(&name).file_name() {
-                let dest_path = flags_dir.join(filename);
-                let mut dest_file = fs::File::create(&dest_path)
-                    .expect("Failed to create SVG file");
                 let mut contents = Vec::new();
-                file.read_to_end(&mut contents).expect("Failed to read SVG");
-                dest_file.write_all(&contents).expect("Failed to write SVG");
-            }
-        }
-    }
-
-    println!("cargo:warning=Circle-flags downloaded successfully");
-}
-
-fn convert_svgs_to_pngs(flags_dir: &Path) {
-    let entries = fs::read_dir(flags_dir).expect("Failed to read flags directory");
-
-    for entry in entries.flatten() {
-        let path = entry.path();
-        if path.extension().map(|e| e == "svg").unwrap_or(false) {
-            let png_path = path.with_extension("png");
-
-            // Skip if PNG already exists and is newer
-            if png_path.exists() {
-                let svg_modified = fs::metadata(&path).and_then(|m| m.modified()).ok();
-                let png_modified = fs::metadata(&png_path).and_then(|m| m.modified()).ok();
-                if let (Some(svg_time), Some(png_time)) = (svg_modified, png_modified) {
-                    if png_time > svg_time {
-                        continue;
-                    }
-                }
-            }
-
-            if let Err(e) = convert_svg_to_png(&path, &png_path) {
-                println!("cargo:warning=Failed to convert {:?}: {}", path, e);
+                file.read_to_end(&mut contents)?;
+                fs::File::create(dest.join(filename))?.write_all(&contents)?;
             }
         }
     }
+    Ok(())
 }
 
-fn convert_svg_to_png(svg_path: &Path, png_path: &Path) -> Result<(), Box<dyn std::error::Error>> {
+fn load_svg(svg_path: &Path) -> Result<resvg::usvg::Tree, Box<dyn std::error::Error>> {
     let svg_data = fs::read(svg_path)?;
+    Ok(resvg::usvg::Tree::from_data(&svg_data, &resvg::usvg::Options::default())?)
+}
 
-    let options = resvg::usvg::Options::default();
-    let tree = resvg::usvg::Tree::from_data(&svg_data, &options)?;
-
+/// Renders `tree` into an `icon_size` square
+fn render_square(tree: &resvg::usvg::Tree, icon_size: u32) -> Result<resvg::tiny_skia::Pixmap, Box<dyn std::error::Error>> {
     let size = tree.size();
-    let scale = ICON_SIZE as f32 / size.width().max(size.height());
+    let scale = icon_size as f32 / size.width().max(size.height());
 
-    let width = (size.width() * scale) as u32;
-    let height = (size.height() * scale) as u32;
+    let width = size.width() * scale;
+    let height = size.height() * scale;
 
-    let mut pixmap = resvg::tiny_skia::Pixmap::new(width, height)
+    // Always square: 4:3 flags are centered with transparent bars
+    let mut pixmap = resvg::tiny_skia::Pixmap::new(icon_size, icon_size)
         .ok_or("Failed to create pixmap")?;
 
-    let transform = resvg::tiny_skia::Transform::from_scale(scale, scale);
-    resvg::render(&tree, transform, &mut pixmap.as_mut());
-
-    pixmap.save_png(png_path)?;
+    let transform = resvg::tiny_skia::Transform::from_scale(scale, scale)
+        .post_translate((icon_size as f32 - width) / 2.0, (icon_size as f32 - height) / 2.0);
+    resvg::render(tree, transform, &mut pixmap.as_mut());
+    Ok(pixmap)
+}
 
+/// Renders `svg_path` at each of `sizes` as straight-alpha ARGB32 in network byte order, the
+/// tray's pixmap format, so the icon is handed over without decoding or scaling. Each size is
+/// rendered from the SVG, which is sharper than scaling the largest down. Written to `dest`,
+/// deflated with the `compressed-icons` feature.
+fn write_pixmaps(svg_path: &Path, dest: &Path, sizes: &[u32]) -> Result<(), Box<dyn std::error::Error>> {
+    let tree = load_svg(svg_path)?;
+    let mut argb = Vec::new();
+    for &size in sizes {
+        for pixel in render_square(&tree, size)?.pixels() {
+            let color = pixel.demultiply();
+            argb.extend([color.alpha(), color.red(), color.green(), color.blue()]);
+        }
+    }
+    if std::env::var_os("CARGO_FEATURE_COMPRESSED_ICONS").is_some() {
+        let mut encoder = flate2::write::ZlibEncoder::new(Vec::new(), flate2::Compression::best());
+        encoder.write_all(&argb)?;
+        argb = encoder.finish()?;
+    }
+    fs::write(dest, argb)?;
     Ok(())
 }
 
-fn generate_icons_module(flags_dir: &Path) {
+/// Emits `<constant>` with the pixmaps of `svg_path`; returns whether it could be rendered
+fn embed_pixmaps(code: &mut String, svg_path: &Path, constant: &str, sizes: &[u32]) -> bool {
+    let out_dir = std::env::var("OUT_DIR").expect("OUT_DIR not set");
+    let dest = Path::new(&out_dir).join(PIXMAPS_DIR).join(format!("{}.argb", constant.to_lowercase()));
+    if let Err(e) = write_pixmaps(svg_path, &dest, sizes) {
+        println!("cargo:warning=Not embedding {:?}: {}", svg_path, e);
+        return false;
+    }
+    code.push_str(&format!("pub const {}: &[u8] = include_bytes!(\"{}\");\n", constant, dest.display()));
+    true
+}
+
+/// Writes icons_data.rs: the pixmaps of every selected flag in `flags_dir` and `rect_dir`,
+/// and of the status artwork
+fn generate_icons_module(flags_dir: &Path, rect_dir: &Path, sizes: &[u32], selection: Option<&HashSet<String>>) {
     let out_dir = std::env::var("OUT_DIR").expect("OUT_DIR not set");
     let dest_path = Path::new(&out_dir).join("icons_data.rs");
+    let pixmaps_dir = Path::new(&out_dir).join(PIXMAPS_DIR);
+    let _ = fs::remove_dir_all(&pixmaps_dir);
+    fs::create_dir_all(&pixmaps_dir).expect("Failed to create the pixmaps directory");
 
     let mut code = String::new();
     code.push_str("// Auto-generated by build.rs - do not edit\n");
-    code.push_str("use std::collections::HashMap;\n");
-    code.push_str("use std::sync::LazyLock;\n\n");
-
-    let mut flag_names = Vec::new();
-
-    // Generate constants for each PNG
-    let entries = fs::read_dir(flags_dir).expect("Failed to read flags directory");
-    for entry in entries.flatten() {
-        let path = entry.path();
-        if path.extension().map(|e| e == "png").unwrap_or(false) {
-            if let Some(stem) = path.file_stem().and_then(|s| s.to_str()) {
-                let const_name = stem.to_uppercase().replace('-', "_");
-                let abs_path = fs::canonicalize(&path).expect("Failed to get absolute path");
-
-                code.push_str(&format!(
-                    "pub const FLAG_{}: &[u8] = include_bytes!(\"{}\");\n",
-                    const_name,
-                    abs_path.display()
-                ));
-
-                flag_names.push((stem.to_lowercase(), const_name));
-            }
+    let icon_size = *sizes.last().expect("at least one icon size");
+    code.push_str(&format!("pub const ICON_SIZE: u32 = {};\n", icon_size));
+    code.push_str(&format!("pub const PIXMAP_SIZES: &[u32] = &{:?};\n\n", sizes));
+
+    let flags = embed_flags(&mut code, flags_dir, "FLAG", sizes, selection);
+    let rects = embed_flags(&mut code, rect_dir, "RECT", sizes, selection);
+
+    // Status artwork is tracked in git, so it has no selection and must render
+    code.push('\n');
+    for name in STATUS_ICONS {
+        let svg_path = Path::new(STATUS_DIR).join(format!("{}.svg", name));
+        let constant = format!("STATUS_{}", name.to_uppercase());
+        if !embed_pixmaps(&mut code, &svg_path, &constant, sizes) {
+            panic!("Failed to render {:?}", svg_path);
         }
     }
 
-    // Generate lookup map
-    code.push_str("\npub static FLAGS: LazyLock<HashMap<&'static str, &'static [u8]>> = LazyLock::new(|| {\n");
-    code.push_str("    let mut m = HashMap::new();\n");
+    push_table(&mut code, "FLAGS", &flags);
+    push_table(&mut code, "RECT_FLAGS", &rects);
 
-    for (code_name, const_name) in &flag_names {
-        code.push_str(&format!("    m.insert(\"{}\", FLAG_{});\n", code_name, const_name));
-    }
+    fs::write(&dest_path, code).expect("Failed to write icons_data.rs");
 
-    code.push_str("    m\n});\n");
+    println!("cargo:warning=Generated {} flag icons ({} rectangular)", flags.len(), rects.len());
+}
 
-    fs::write(&dest_path, code).expect("Failed to write icons_data.rs");
+/// Embeds the pixmaps of each selected SVG in `dir` as `<prefix>_<CODE>`; returns
+/// (code, constant) pairs sorted by code
+fn embed_flags(
+    code: &mut String,
+    dir: &Path,
+    prefix: &str,
+    sizes: &[u32],
+    selection: Option<&HashSet<String>>,
+) -> Vec<(String, String)> {
+    let Ok(entries) = fs::read_dir(dir) else { return Vec::new() };
+    let mut svgs: Vec<(String, PathBuf)> = entries
+        .flatten()
+        .map(|entry| entry.path())
+        .filter(|path| path.extension().is_some_and(|e| e == "svg"))
+        .filter_map(|path| Some((path.file_stem()?.to_str()?.to_lowercase(), path.clone())))
+        .filter(|(stem, _)| is_selected(stem, selection))
+        .collect();
+    svgs.sort_unstable();
+
+    let mut names = Vec::new();
+    for (stem, path) in svgs {
+        let constant = format!("{}_{}", prefix, stem.to_uppercase().replace('-', "_"));
+        if embed_pixmaps(code, &path, &constant, sizes) {
+            names.push((stem, constant));
+        }
+    }
+    names
+}
 
-    println!("cargo:warning=Generated {} flag icons", flag_names.len());
+/// Emits `table` as a slice of (code, pixmaps) sorted by code, for binary search
+fn push_table(code: &mut String, table: &str, names: &[(String, String)]) {
+    code.push_str(&format!("\npub static {}: &[(&str, &[u8])] = &[\n", table));
+    for (code_name, constant) in names {
+        code.push_str(&format!("    (\"{}\", {}),\n", code_name, constant));
+    }
+    code.push_str("];\n");
 }
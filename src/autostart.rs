@@ -90,6 +90,19 @@ pub fn is_autostart_enabled() -> bool {
         .unwrap_or(false)
 }
 
+/// Program the autostart entry launches (its `Exec=` line), if the entry exists
+pub fn autostart_exec() -> Option<String> {
+    let content = fs::read_to_string(desktop_file_path().ok()?).ok()?;
+    exec_line(&content)
+}
+
+fn exec_line(content: &str) -> Option<String> {
+    content
+        .lines()
+        .find_map(|l| l.strip_prefix("Exec="))
+        .map(|exec| exec.trim().to_string())
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -100,5 +113,6 @@ mod tests {
         assert!(content.contains("[Desktop Entry]"));
         assert!(content.contains("Type=Application"));
         assert!(content.contains("Exec=/usr/bin/network-monitor"));
+        assert_eq!(exec_line(&content).as_deref(), Some("/usr/bin/network-monitor"));
     }
 }
@@ -0,0 +1,174 @@
+//! Config and state backup/restore module
+//! Bundles everything under the app's config and data directories into a single
+//! zip archive, for migrating between machines.
+
+use crate::updater::VERSION;
+use std::fs;
+use std::io::{Read, Write};
+use std::path::{Component, Path, PathBuf};
+use thiserror::Error;
+use zip::write::SimpleFileOptions;
+
+/// Archive comment prefix identifying our backups
+const BACKUP_MARKER: &str = "network-monitor backup";
+
+/// Machine-specific files that must not travel between installs
+/// (`version` is owned by install.sh, the others are update-check state)
+const EXCLUDED_FILES: &[&str] = &["version", "last-check", "update-available"];
+
+/// Errors during backup or restore
+#[derive(Debug, Error)]
+pub enum BackupError {
+    #[error("IO error: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("Archive error: {0}")]
+    Zip(#[from] zip::result::ZipError),
+    #[error("Could not determine config directory")]
+    NoConfigDir,
+    #[error("Not a network-monitor backup")]
+    NotABackup,
+    #[error("Unsafe path in archive: {0}")]
+    UnsafePath(String),
+}
+
+/// Archive prefixes mapped to the directories they are restored into
+fn backup_roots() -> Result<Vec<(&'static str, PathBuf)>, BackupError> {
+    let config = dirs::config_dir().ok_or(BackupError::NoConfigDir)?;
+    let mut roots = vec![("config", config.join("network-monitor"))];
+    if let Some(data) = dirs::data_dir() {
+        roots.push(("data", data.join("network-monitor")));
+    }
+    Ok(roots)
+}
+
+/// Recursively collects files under `dir`, relative to `dir`
+fn collect_files(dir: &Path, relative: &Path, out: &mut Vec<PathBuf>) -> std::io::Result<()> {
+    let Ok(entries) = fs::read_dir(dir.join(relative)) else {
+        return Ok(());
+    };
+    for entry in entries.flatten() {
+        let path = relative.join(entry.file_name());
+        let file_type = entry.file_type()?;
+        if file_type.is_dir() {
+            collect_files(dir, &path, out)?;
+        } else if file_type.is_file() {
+            out.push(path);
+        }
+    }
+    Ok(())
+}
+
+/// Writes a backup archive of the config and data directories, returning the file count
+pub fn backup(dest: &Path) -> Result<usize, BackupError> {
+    backup_from(&backup_roots()?, dest)
+}
+
+/// Restores a backup archive over the config and data directories, returning the file count
+pub fn restore(src: &Path) -> Result<usize, BackupError> {
+    restore_to(&backup_roots()?, src)
+}
+
+fn backup_from(roots: &[(&str, PathBuf)], dest: &Path) -> Result<usize, BackupError> {
+    let mut archive = zip::ZipWriter::new(fs::File::create(dest)?);
+    archive.set_comment(format!("{} {}", BACKUP_MARKER, VERSION));
+    let options = SimpleFileOptions::default().compression_method(zip::CompressionMethod::Deflated);
+
+    let mut count = 0;
+    for (prefix, dir) in roots {
+        let mut files = Vec::new();
+        collect_files(dir, Path::new(""), &mut files)?;
+        files.sort();
+
+        for relative in files {
+            let excluded = relative.to_str().is_some_and(|name| EXCLUDED_FILES.contains(&name));
+            if excluded {
+                continue;
+            }
+            let name = format!("{}/{}", prefix, relative.to_string_lossy());
+            archive.start_file(name, options)?;
+            archive.write_all(&fs::read(dir.join(&relative))?)?;
+            count += 1;
+        }
+    }
+
+    archive.finish()?;
+    Ok(count)
+}
+
+fn restore_to(roots: &[(&str, PathBuf)], src: &Path) -> Result<usize, BackupError> {
+    let mut archive = zip::ZipArchive::new(fs::File::open(src)?)?;
+    if !String::from_utf8_lossy(archive.comment()).starts_with(BACKUP_MARKER) {
+        return Err(BackupError::NotABackup);
+    }
+
+    let mut count = 0;
+    for i in 0..archive.len() {
+        let mut file = archive.by_index(i)?;
+        if file.is_dir() {
+            continue;
+        }
+        let name = file.name().to_string();
+        let Some((prefix, relative)) = name.split_once('/') else {
+            return Err(BackupError::UnsafePath(name));
+        };
+        let relative = Path::new(relative);
+        let safe = relative.components().all(|c| matches!(c, Component::Normal(_)));
+        let Some((_, dir)) = roots.iter().find(|(p, _)| *p == prefix).filter(|_| safe) else {
+            return Err(BackupError::UnsafePath(name));
+        };
+
+        let dest = dir.join(relative);
+        if let Some(parent) = dest.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        let mut contents = Vec::new();
+        file.read_to_end(&mut contents)?;
+        fs::write(&dest, contents)?;
+        count += 1;
+    }
+    Ok(count)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn temp_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("network-monitor-test-{}-{}", name, std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    #[test]
+    fn test_backup_restore_roundtrip() {
+        let source = temp_dir("backup-src");
+        fs::create_dir_all(source.join("profiles")).unwrap();
+        fs::write(source.join("config.toml"), "interval = 60\n").unwrap();
+        fs::write(source.join("profiles/home.toml"), "name = \"home\"\n").unwrap();
+        fs::write(source.join("version"), "v0.1.4").unwrap();
+
+        let archive = temp_dir("backup-archive").join("backup.zip");
+        let written = backup_from(&[("config", source.clone())], &archive).unwrap();
+        assert_eq!(written, 2, "machine-specific files must be excluded");
+
+        let target = temp_dir("backup-dst");
+        let restored = restore_to(&[("config", target.clone())], &archive).unwrap();
+        assert_eq!(restored, 2);
+        assert_eq!(fs::read_to_string(target.join("profiles/home.toml")).unwrap(), "name = \"home\"\n");
+        assert!(!target.join("version").exists());
+    }
+
+    #[test]
+    fn test_restore_rejects_foreign_archive() {
+        let path = temp_dir("backup-foreign").join("other.zip");
+        let mut archive = zip::ZipWriter::new(fs::File::create(&path).unwrap());
+        archive.start_file("config/x", SimpleFileOptions::default()).unwrap();
+        archive.finish().unwrap();
+
+        assert!(matches!(
+            restore_to(&[("config", temp_dir("backup-foreign-dst"))], &path),
+            Err(BackupError::NotABackup)
+        ));
+    }
+}
@@ -0,0 +1,296 @@
+//! Badge compositor for the tray icon
+//! Draws small status markers (e.g. "update available") into a corner of the flag
+//! before it is handed to the tray, so state is visible without opening the menu.
+
+use crate::icons::Pixmap;
+
+/// Badge diameter relative to the icon size
+const BADGE_RATIO: f32 = 0.375;
+/// Outline width relative to the icon size, keeps the dot visible on any flag
+const OUTLINE_RATIO: f32 = 0.05;
+const OUTLINE: [u8; 3] = [0xff, 0xff, 0xff];
+/// Frames of one spinner turn
+pub const SPINNER_FRAMES: u8 = 8;
+
+/// Corner of the icon a badge is drawn in
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Corner {
+    TopLeft,
+    TopRight,
+    BottomLeft,
+    BottomRight,
+}
+
+/// A status marker on the tray icon
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Badge {
+    /// A newer release is available
+    Update,
+    /// The location is not the expected country
+    Attention,
+    /// Traffic leaves through a VPN, drawn as a padlock
+    Vpn,
+    /// The public IP is a Tor exit node
+    Tor,
+    /// On untrusted Wi-Fi with no VPN up
+    PublicWifi,
+    /// A lookup is in flight; a three-quarter ring turned by `frame` of `SPINNER_FRAMES`
+    Spinner(u8),
+}
+
+impl Badge {
+    pub fn corner(&self) -> Corner {
+        match self {
+            Badge::Update => Corner::TopRight,
+            // Over the attention dot, for the moment a lookup takes
+            Badge::Attention | Badge::Spinner(_) => Corner::BottomRight,
+            // Where the padlock goes once a VPN is up
+            Badge::Vpn | Badge::PublicWifi => Corner::BottomLeft,
+            Badge::Tor => Corner::TopLeft,
+        }
+    }
+
+    fn color(&self) -> [u8; 3] {
+        match self {
+            Badge::Update => [0xe5, 0x39, 0x35],
+            Badge::Attention => [0xff, 0xa0, 0x00],
+            Badge::Vpn => [0x2e, 0x7d, 0x32],
+            // Tor Project purple
+            Badge::Tor => [0x7d, 0x46, 0x98],
+            Badge::PublicWifi => [0xf4, 0x51, 0x1e],
+            Badge::Spinner(_) => [0x19, 0x76, 0xd2],
+        }
+    }
+}
+
+/// Draws `badges` onto `icon`; a later badge in the same corner covers an earlier one
+pub fn composite(icon: &mut Pixmap, badges: &[Badge]) {
+    for badge in badges {
+        match badge {
+            Badge::Vpn => draw_lock(icon, badge.corner(), badge.color()),
+            Badge::Spinner(frame) => draw_spinner(icon, badge.corner(), badge.color(), *frame),
+            _ => draw_dot(icon, badge.corner(), badge.color()),
+        }
+    }
+}
+
+/// Draws an outlined, anti-aliased dot tucked into `corner`
+fn draw_dot(icon: &mut Pixmap, corner: Corner, color: [u8; 3]) {
+    let size = icon.size as f32;
+    let radius = size * BADGE_RATIO / 2.0;
+    let outline = (size * OUTLINE_RATIO).max(1.0);
+    let outer = radius + outline;
+
+    let cx = match corner {
+        Corner::TopLeft | Corner::BottomLeft => outer,
+        Corner::TopRight | Corner::BottomRight => size - outer,
+    };
+    let cy = match corner {
+        Corner::TopLeft | Corner::TopRight => outer,
+        Corner::BottomLeft | Corner::BottomRight => size - outer,
+    };
+
+    for (x, y, pixel) in pixels(icon) {
+        let dx = x as f32 + 0.5 - cx;
+        let dy = y as f32 + 0.5 - cy;
+        let distance = (dx * dx + dy * dy).sqrt();
+        if distance > outer + 1.0 {
+            continue;
+        }
+        // Coverage with a one-pixel soft edge
+        let ring = (outer - distance + 0.5).clamp(0.0, 1.0);
+        let fill = (radius - distance + 0.5).clamp(0.0, 1.0);
+        blend(pixel, OUTLINE, ring);
+        blend(pixel, color, fill);
+    }
+}
+
+/// Draws an outlined padlock (body plus shackle) tucked into `corner`.
+/// The shape is a signed distance field so the outline and soft edge come for free.
+fn draw_lock(icon: &mut Pixmap, corner: Corner, color: [u8; 3]) {
+    let size = icon.size as f32;
+    let extent = size * BADGE_RATIO;
+    let outline = (size * OUTLINE_RATIO).max(1.0);
+
+    let left = match corner {
+        Corner::TopLeft | Corner::BottomLeft => outline,
+        Corner::TopRight | Corner::BottomRight => size - outline - extent,
+    };
+    let top = match corner {
+        Corner::TopLeft | Corner::TopRight => outline,
+        Corner::BottomLeft | Corner::BottomRight => size - outline - extent,
+    };
+
+    // Body in the lower 55%, shackle an arch above it whose legs run into the body
+    let body_top = extent * 0.45;
+    let half_width = extent * 0.5;
+    let half_height = (extent - body_top) * 0.5;
+    let arch_radius = extent * 0.25;
+    let arch_width = extent * 0.08;
+
+    for (x, y, pixel) in pixels(icon) {
+        let u = x as f32 + 0.5 - left;
+        let v = y as f32 + 0.5 - top;
+        if u < -outline - 1.0 || v < -outline - 1.0 || u > extent + outline + 1.0 || v > extent + outline + 1.0 {
+            continue;
+        }
+        let body = {
+            let dx = (u - half_width).abs() - half_width;
+            let dy = (v - body_top - half_height).abs() - half_height;
+            let outside = (dx.max(0.0).powi(2) + dy.max(0.0).powi(2)).sqrt();
+            outside + dx.max(dy).min(0.0)
+        };
+        let radial = {
+            let dx = u - half_width;
+            let dy = (v - body_top).min(0.0);
+            (dx * dx + dy * dy).sqrt()
+        };
+        // Legs stop halfway down the body
+        let arch = ((radial - arch_radius).abs() - arch_width).max(v - body_top - half_height);
+        let distance = body.min(arch);
+        // No outline inside the shackle, the flag shows through the hole
+        let hole = if v < body_top { arch_radius - arch_width - radial } else { f32::MIN };
+        let ring = (outline - distance + 0.5).clamp(0.0, 1.0) * (0.5 - hole).clamp(0.0, 1.0);
+        let fill = (0.5 - distance).clamp(0.0, 1.0);
+        blend(pixel, OUTLINE, ring);
+        blend(pixel, color, fill);
+    }
+}
+
+/// Draws an outlined three-quarter ring with round caps, its gap turned by `frame`
+fn draw_spinner(icon: &mut Pixmap, corner: Corner, color: [u8; 3], frame: u8) {
+    let size = icon.size as f32;
+    let outer_radius = size * BADGE_RATIO / 2.0;
+    let outline = (size * OUTLINE_RATIO).max(1.0);
+    let half_width = outer_radius * 0.22;
+    let radius = outer_radius - half_width;
+    let outer = outer_radius + outline;
+
+    let cx = match corner {
+        Corner::TopLeft | Corner::BottomLeft => outer,
+        Corner::TopRight | Corner::BottomRight => size - outer,
+    };
+    let cy = match corner {
+        Corner::TopLeft | Corner::TopRight => outer,
+        Corner::BottomLeft | Corner::BottomRight => size - outer,
+    };
+
+    // The arc runs clockwise (in screen coordinates) from `start` over three quarters
+    let sweep = std::f32::consts::TAU * 0.75;
+    let start = std::f32::consts::TAU * (frame % SPINNER_FRAMES) as f32 / SPINNER_FRAMES as f32;
+    let caps = [start, start + sweep].map(|a| (cx + radius * a.cos(), cy + radius * a.sin()));
+
+    for (x, y, pixel) in pixels(icon) {
+        let px = x as f32 + 0.5;
+        let py = y as f32 + 0.5;
+        let (dx, dy) = (px - cx, py - cy);
+        if dx.abs() > outer + 1.0 || dy.abs() > outer + 1.0 {
+            continue;
+        }
+        let angle = (dy.atan2(dx) - start).rem_euclid(std::f32::consts::TAU);
+        let distance = if angle <= sweep {
+            ((dx * dx + dy * dy).sqrt() - radius).abs() - half_width
+        } else {
+            caps.iter()
+                .map(|(ex, ey)| ((px - ex).powi(2) + (py - ey).powi(2)).sqrt())
+                .fold(f32::MAX, f32::min)
+                - half_width
+        };
+        let ring = (outline - distance + 0.5).clamp(0.0, 1.0);
+        let fill = (0.5 - distance).clamp(0.0, 1.0);
+        blend(pixel, OUTLINE, ring);
+        blend(pixel, color, fill);
+    }
+}
+
+/// Pixels of `icon` with their coordinates; each is `[a, r, g, b]`
+fn pixels(icon: &mut Pixmap) -> impl Iterator<Item = (u32, u32, &mut [u8])> {
+    let size = icon.size;
+    icon.argb.chunks_exact_mut(4).enumerate().map(move |(i, pixel)| (i as u32 % size, i as u32 / size, pixel))
+}
+
+/// Source-over blend of an opaque color with coverage `alpha`
+fn blend(pixel: &mut [u8], color: [u8; 3], alpha: f32) {
+    if alpha <= 0.0 {
+        return;
+    }
+    let dst_alpha = pixel[0] as f32 / 255.0;
+    let out_alpha = alpha + dst_alpha * (1.0 - alpha);
+    for i in 0..3 {
+        let src = color[i] as f32 * alpha;
+        let dst = pixel[i + 1] as f32 * dst_alpha * (1.0 - alpha);
+        pixel[i + 1] = ((src + dst) / out_alpha).round() as u8;
+    }
+    pixel[0] = (out_alpha * 255.0).round() as u8;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn filled(size: u32, argb: [u8; 4]) -> Pixmap {
+        Pixmap { size, argb: argb.repeat((size * size) as usize) }
+    }
+
+    fn pixel(icon: &Pixmap, x: u32, y: u32) -> [u8; 4] {
+        let at = ((y * icon.size + x) * 4) as usize;
+        icon.argb[at..at + 4].try_into().unwrap()
+    }
+
+    #[test]
+    fn test_update_dot_in_top_right() {
+        let mut icon = filled(64, [255, 0, 0, 255]);
+        composite(&mut icon, &[Badge::Update]);
+
+        // Centre of the dot is the badge colour, the opposite corner is untouched
+        let size = 64.0 * BADGE_RATIO / 2.0 + 64.0 * OUTLINE_RATIO;
+        let c = (64.0 - size) as u32;
+        assert_eq!(pixel(&icon, c, size as u32), [255, 0xe5, 0x39, 0x35]);
+        assert_eq!(pixel(&icon, 4, 60), [255, 0, 0, 255]);
+    }
+
+    #[test]
+    fn test_vpn_lock_in_bottom_left() {
+        let mut icon = filled(64, [255, 0, 0, 255]);
+        composite(&mut icon, &[Badge::Vpn]);
+
+        // Middle of the lock body, and the gap inside the shackle shows the flag
+        let outline = 64.0 * OUTLINE_RATIO;
+        let extent = 64.0 * BADGE_RATIO;
+        let (left, top) = (outline, 64.0 - outline - extent);
+        let body = pixel(&icon, (left + extent / 2.0) as u32, (top + extent * 0.75) as u32);
+        assert_eq!(body, [255, 0x2e, 0x7d, 0x32]);
+        let gap = pixel(&icon, (left + extent / 2.0) as u32, (top + extent * 0.35) as u32);
+        assert_eq!(gap, [255, 0, 0, 255]);
+        assert_eq!(pixel(&icon, 60, 4), [255, 0, 0, 255]);
+    }
+
+    #[test]
+    fn test_spinner_turns() {
+        let background = [255, 0, 0, 0];
+        let mut first = filled(64, background);
+        draw_spinner(&mut first, Corner::BottomRight, [0x19, 0x76, 0xd2], 0);
+        let mut second = filled(64, background);
+        draw_spinner(&mut second, Corner::BottomRight, [0x19, 0x76, 0xd2], 2);
+
+        // Frame 0 leaves its gap in the upper right, a quarter turn later it is in the lower right
+        let (cx, cy, r) = (64.0 - 12.0 - 3.2, 64.0 - 12.0 - 3.2, 9.0);
+        let at = |icon: &Pixmap, angle: f32| pixel(icon, (cx + r * angle.cos()) as u32, (cy + r * angle.sin()) as u32);
+        assert_ne!(at(&first, 5.5), [255, 0x19, 0x76, 0xd2]);
+        assert_eq!(at(&second, 5.5), [255, 0x19, 0x76, 0xd2]);
+        assert_eq!(first, {
+            let mut wrapped = filled(64, background);
+            draw_spinner(&mut wrapped, Corner::BottomRight, [0x19, 0x76, 0xd2], SPINNER_FRAMES);
+            wrapped
+        });
+        assert_eq!(pixel(&first, 4, 4), background);
+    }
+
+    #[test]
+    fn test_dot_on_transparent_pixels() {
+        let mut icon = filled(64, [0; 4]);
+        composite(&mut icon, &[Badge::Update]);
+        assert_eq!(pixel(&icon, 52, 12)[0], 255);
+        assert_eq!(pixel(&icon, 3, 3)[0], 0);
+    }
+}
@@ -0,0 +1,437 @@
+//! Command line parsing into typed subcommands, on top of pico-args
+//! `network-monitor <command> [flags]`; the pre-subcommand flags (`--update`, `--check`,
+//! `--mtu`, ...) are still accepted since install.sh and existing docs use them.
+
+use network_monitor::config::MIN_REFRESH_INTERVAL;
+use network_monitor::history::{Filter, Format};
+use network_monitor::mtu;
+use network_monitor::updater::Channel;
+use pico_args::Arguments;
+use std::ffi::OsString;
+use std::net::SocketAddr;
+use std::path::PathBuf;
+use std::time::Duration;
+use thiserror::Error;
+
+/// Errors in the command line, printed with a pointer to --help
+#[derive(Debug, Error, PartialEq)]
+pub enum CliError {
+    #[error("unknown command '{0}'")]
+    UnknownCommand(String),
+    #[error("unknown flag '{flag}' for '{command}'")]
+    UnknownFlag { command: &'static str, flag: String },
+    #[error("'{0}' needs a value")]
+    MissingValue(String),
+    #[error("invalid value '{value}' for '{flag}': {reason}")]
+    InvalidValue { flag: String, value: String, reason: String },
+    #[error("'{command}' needs {what}")]
+    MissingArgument { command: &'static str, what: &'static str },
+    #[error("unexpected argument '{0}'")]
+    UnexpectedArgument(String),
+    #[error("{0}")]
+    Malformed(String),
+}
+
+/// What to do, as given on the command line
+#[derive(Debug, PartialEq)]
+pub enum Command {
+    /// Start the tray app (the default)
+    Run(RunArgs),
+    /// Location and update state of the running instance
+    Status { json: bool },
+    /// One-shot lookup without the tray
+    Query { json: bool },
+    Check,
+    /// Environment health check
+    Doctor,
+    Update(UpdateArgs),
+    /// Recorded locations
+    History { filter: Filter, format: Format },
+    /// Recorded app events
+    Events { since: Option<Duration>, json: bool },
+    /// Outage stats per connection
+    Outages { csv: bool },
+    Config(ConfigAction),
+    Secrets(SecretsAction),
+    /// Print the last raw provider responses (`debug dump`), IPs stripped with `redact`
+    DebugDump { redact: bool },
+    Backup(PathBuf),
+    Restore(PathBuf),
+    Mtu(String),
+    Version,
+    Help,
+}
+
+/// Flags of `run`
+#[derive(Debug, Default, PartialEq)]
+pub struct RunArgs {
+    /// Monitor from inside this network namespace
+    pub netns: Option<String>,
+    /// Overrides `refresh_interval` from config.toml
+    pub interval: Option<Duration>,
+    /// Print Waybar JSON on stdout instead of showing a tray icon
+    pub waybar: bool,
+    /// Serve Prometheus metrics on this address
+    pub metrics: Option<SocketAddr>,
+}
+
+/// Flags of `update`
+#[derive(Debug, Default, PartialEq)]
+pub struct UpdateArgs {
+    /// Install without asking
+    pub yes: bool,
+    /// Overrides `[updates] channel` for this run
+    pub channel: Option<Channel>,
+    /// Only report whether an update is available
+    pub dry_run: bool,
+}
+
+/// `config` subcommands
+#[derive(Debug, PartialEq)]
+pub enum ConfigAction {
+    /// Print the config file location
+    Path,
+    /// Validate the config file (`check` is kept as an alias)
+    Validate,
+    /// Write a commented default config file
+    Init { force: bool },
+}
+
+/// `secrets` subcommands
+#[derive(Debug, PartialEq)]
+pub enum SecretsAction {
+    /// Store a secret read from stdin, e.g. "mqtt.password"
+    Set(String),
+    /// Move plaintext secrets from config.toml into the keyring
+    Migrate,
+}
+
+impl From<pico_args::Error> for CliError {
+    fn from(e: pico_args::Error) -> Self {
+        match e {
+            pico_args::Error::OptionWithoutAValue(flag) => CliError::MissingValue(flag.to_string()),
+            other => CliError::Malformed(other.to_string()),
+        }
+    }
+}
+
+/// Parses `std::env::args()` (including the program name)
+pub fn parse(args: impl IntoIterator<Item = String>) -> Result<Command, CliError> {
+    let mut args = Arguments::from_vec(args.into_iter().skip(1).map(OsString::from).collect());
+    let Some(name) = args.subcommand()? else {
+        return parse_legacy(args);
+    };
+
+    let (command, name) = match name.as_str() {
+        "run" => (Command::Run(parse_run(&mut args)?), "run"),
+        "status" => (Command::Status { json: args.contains("--json") }, "status"),
+        "check" => (Command::Check, "check"),
+        "doctor" => (Command::Doctor, "doctor"),
+        "update" => (Command::Update(parse_update(&mut args)?), "update"),
+        "version" => (Command::Version, "version"),
+        "help" => (Command::Help, "help"),
+        "query" => (Command::Query { json: args.contains("--json") }, "query"),
+        "history" => (parse_history(&mut args)?, "history"),
+        "events" => {
+            let since = value(&mut args, "--since", parse_since)?;
+            (Command::Events { since, json: args.contains("--json") }, "events")
+        }
+        "outages" => (Command::Outages { csv: args.contains("--csv") }, "outages"),
+        "config" => match args.subcommand()?.as_deref() {
+            Some("path") => (Command::Config(ConfigAction::Path), "config path"),
+            Some("validate" | "check") => (Command::Config(ConfigAction::Validate), "config validate"),
+            Some("init") => (Command::Config(ConfigAction::Init { force: args.contains("--force") }), "config init"),
+            Some(other) => return Err(CliError::UnknownCommand(format!("config {}", other))),
+            None => return Err(CliError::MissingArgument { command: "config", what: "'path', 'validate' or 'init'" }),
+        },
+        "secrets" => match args.subcommand()?.as_deref() {
+            Some("migrate") => (Command::Secrets(SecretsAction::Migrate), "secrets migrate"),
+            Some("set") => {
+                let key = args
+                    .subcommand()?
+                    .ok_or(CliError::MissingArgument { command: "secrets set", what: "a key like mqtt.password" })?;
+                (Command::Secrets(SecretsAction::Set(key)), "secrets set")
+            }
+            Some(other) => return Err(CliError::UnknownCommand(format!("secrets {}", other))),
+            None => return Err(CliError::MissingArgument { command: "secrets", what: "'set' or 'migrate'" }),
+        },
+        "debug" => match args.subcommand()?.as_deref() {
+            Some("dump") => (Command::DebugDump { redact: args.contains("--redact") }, "debug dump"),
+            Some(other) => return Err(CliError::UnknownCommand(format!("debug {}", other))),
+            None => return Err(CliError::MissingArgument { command: "debug", what: "'dump'" }),
+        },
+        "backup" => (Command::Backup(file(&mut args, "backup")?), "backup"),
+        "restore" => (Command::Restore(file(&mut args, "restore")?), "restore"),
+        "mtu" => {
+            let target = args.subcommand()?.unwrap_or_else(|| mtu::DEFAULT_TARGET.to_string());
+            (Command::Mtu(target), "mtu")
+        }
+        other => return Err(CliError::UnknownCommand(other.to_string())),
+    };
+    finish(args, name)?;
+    Ok(command)
+}
+
+/// Fails on the first argument no flag or subcommand took
+fn finish(args: Arguments, command: &'static str) -> Result<(), CliError> {
+    let Some(arg) = args.finish().into_iter().next() else {
+        return Ok(());
+    };
+    let arg = arg.to_string_lossy().into_owned();
+    if arg.starts_with('-') {
+        Err(CliError::UnknownFlag { command, flag: arg })
+    } else {
+        Err(CliError::UnexpectedArgument(arg))
+    }
+}
+
+/// Value of `flag` (`--flag value` or `--flag=value`), checked by `parse`
+fn value<T>(
+    args: &mut Arguments,
+    flag: &'static str,
+    parse: impl FnOnce(&str) -> Result<T, String>,
+) -> Result<Option<T>, CliError> {
+    let Some(value) = args.opt_value_from_str::<_, String>(flag)? else {
+        return Ok(None);
+    };
+    parse(&value).map(Some).map_err(|reason| CliError::InvalidValue { flag: flag.to_string(), value, reason })
+}
+
+fn file(args: &mut Arguments, command: &'static str) -> Result<PathBuf, CliError> {
+    let file = args.subcommand()?.ok_or(CliError::MissingArgument { command, what: "a file" })?;
+    Ok(PathBuf::from(file))
+}
+
+/// Flags without a subcommand: the old one-flag actions, otherwise `run` flags
+fn parse_legacy(mut args: Arguments) -> Result<Command, CliError> {
+    let (command, name) = if args.contains(["-h", "--help"]) {
+        (Command::Help, "--help")
+    } else if args.contains(["-v", "--version"]) {
+        (Command::Version, "--version")
+    } else if args.contains(["-u", "--update"]) {
+        (Command::Update(parse_update(&mut args)?), "update")
+    } else if args.contains(["-c", "--check"]) {
+        (Command::Check, "--check")
+    } else if let Some(target) = mtu_target(&mut args)? {
+        (Command::Mtu(target), "--mtu")
+    } else {
+        (Command::Run(parse_run(&mut args)?), "run")
+    };
+    finish(args, name)?;
+    Ok(command)
+}
+
+/// Target of the legacy `--mtu [target]` flag, which unlike `--netns` may go without a value
+fn mtu_target(args: &mut Arguments) -> Result<Option<String>, CliError> {
+    match args.opt_value_from_str("--mtu") {
+        Err(pico_args::Error::OptionWithoutAValue(_)) => {
+            args.contains("--mtu");
+            Ok(Some(mtu::DEFAULT_TARGET.to_string()))
+        }
+        target => Ok(target?),
+    }
+}
+
+fn parse_run(args: &mut Arguments) -> Result<RunArgs, CliError> {
+    Ok(RunArgs {
+        netns: args.opt_value_from_str("--netns")?,
+        interval: value(args, "--interval", |v| {
+            let secs: u64 = v.parse().map_err(|_| "expected seconds".to_string())?;
+            if secs < MIN_REFRESH_INTERVAL.as_secs() {
+                return Err(format!("must be at least {}s", MIN_REFRESH_INTERVAL.as_secs()));
+            }
+            Ok(Duration::from_secs(secs))
+        })?,
+        waybar: args.contains("--waybar"),
+        metrics: value(args, "--metrics", |v| {
+            v.parse().map_err(|_| "expected an address like 127.0.0.1:9184".to_string())
+        })?,
+    })
+}
+
+fn parse_update(args: &mut Arguments) -> Result<UpdateArgs, CliError> {
+    Ok(UpdateArgs {
+        channel: value(args, "--channel", |v| {
+            Channel::parse(v).ok_or_else(|| format!("expected one of {}", Channel::NAMES.join(", ")))
+        })?,
+        yes: args.contains(["-y", "--yes"]),
+        dry_run: args.contains(["-n", "--dry-run"]),
+    })
+}
+
+fn parse_history(args: &mut Arguments) -> Result<Command, CliError> {
+    let filter = Filter {
+        since: value(args, "--since", parse_since)?,
+        country: value(args, "--country", |v| {
+            if v.len() != 2 || !v.chars().all(|c| c.is_ascii_alphabetic()) {
+                return Err("expected a two-letter country code".to_string());
+            }
+            Ok(v.to_ascii_uppercase())
+        })?,
+    };
+    let mut format = value(args, "--format", |v| {
+        Format::parse(v).ok_or_else(|| format!("expected one of {}", Format::NAMES.join(", ")))
+    })?
+    .unwrap_or_default();
+    if args.contains("--csv") {
+        format = Format::Csv;
+    }
+    if args.contains("--json") {
+        format = Format::Json;
+    }
+    Ok(Command::History { filter, format })
+}
+
+fn parse_since(value: &str) -> Result<Duration, String> {
+    parse_age(value).ok_or_else(|| "expected an age like 30m, 12h, 7d or 2w".to_string())
+}
+
+/// "90s", "30m", "12h", "7d", "2w"
+fn parse_age(value: &str) -> Option<Duration> {
+    let split = value.find(|c: char| !c.is_ascii_digit())?;
+    let (count, unit) = value.split_at(split);
+    let unit_secs = match unit {
+        "s" => 1,
+        "m" => 60,
+        "h" => 60 * 60,
+        "d" => 24 * 60 * 60,
+        "w" => 7 * 24 * 60 * 60,
+        _ => return None,
+    };
+    count.parse::<u64>().ok()?.checked_mul(unit_secs).map(Duration::from_secs)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse_str(line: &str) -> Result<Command, CliError> {
+        parse(std::iter::once("network-monitor").chain(line.split_whitespace()).map(String::from))
+    }
+
+    #[test]
+    fn test_subcommands() {
+        assert_eq!(parse_str("").unwrap(), Command::Run(RunArgs::default()));
+        assert_eq!(
+            parse_str("run --interval 300 --netns=vpn").unwrap(),
+            Command::Run(RunArgs {
+                netns: Some("vpn".into()),
+                interval: Some(Duration::from_secs(300)),
+                waybar: false,
+                metrics: None,
+            })
+        );
+        assert_eq!(parse_str("outages --csv").unwrap(), Command::Outages { csv: true });
+        assert_eq!(
+            parse_str("history --since 7d --format csv --country vn").unwrap(),
+            Command::History {
+                filter: Filter { since: Some(Duration::from_secs(7 * 24 * 3600)), country: Some("VN".into()) },
+                format: Format::Csv,
+            }
+        );
+        assert_eq!(
+            parse_str("events --since 1h --json").unwrap(),
+            Command::Events { since: Some(Duration::from_secs(3600)), json: true }
+        );
+        assert_eq!(parse_str("query --json").unwrap(), Command::Query { json: true });
+        assert_eq!(parse_str("status --json").unwrap(), Command::Status { json: true });
+        assert_eq!(parse_str("config path").unwrap(), Command::Config(ConfigAction::Path));
+        assert_eq!(parse_str("config init --force").unwrap(), Command::Config(ConfigAction::Init { force: true }));
+        assert_eq!(parse_str("doctor").unwrap(), Command::Doctor);
+        assert_eq!(parse_str("secrets set mqtt.password").unwrap(), Command::Secrets(SecretsAction::Set("mqtt.password".into())));
+        assert_eq!(
+            parse_str("update --dry-run --channel=beta").unwrap(),
+            Command::Update(UpdateArgs { yes: false, channel: Some(Channel::Beta), dry_run: true })
+        );
+        assert_eq!(parse_str("backup out.zip").unwrap(), Command::Backup("out.zip".into()));
+        assert_eq!(parse_str("debug dump --redact").unwrap(), Command::DebugDump { redact: true });
+        assert_eq!(parse_str("check").unwrap(), Command::Check);
+        assert_eq!(parse_str("version").unwrap(), Command::Version);
+        assert_eq!(parse_str("help").unwrap(), Command::Help);
+        assert_eq!(parse_str("status").unwrap(), Command::Status { json: false });
+        assert_eq!(parse_str("query").unwrap(), Command::Query { json: false });
+        assert_eq!(parse_str("outages").unwrap(), Command::Outages { csv: false });
+        assert_eq!(parse_str("events").unwrap(), Command::Events { since: None, json: false });
+        assert_eq!(
+            parse_str("history --json").unwrap(),
+            Command::History { filter: Filter::default(), format: Format::Json }
+        );
+        assert_eq!(parse_str("config validate").unwrap(), Command::Config(ConfigAction::Validate));
+        assert_eq!(parse_str("config check").unwrap(), Command::Config(ConfigAction::Validate));
+        assert_eq!(parse_str("config init").unwrap(), Command::Config(ConfigAction::Init { force: false }));
+        assert_eq!(parse_str("secrets migrate").unwrap(), Command::Secrets(SecretsAction::Migrate));
+        assert_eq!(parse_str("debug dump").unwrap(), Command::DebugDump { redact: false });
+        assert_eq!(parse_str("restore out.zip").unwrap(), Command::Restore("out.zip".into()));
+        assert_eq!(parse_str("mtu").unwrap(), Command::Mtu(mtu::DEFAULT_TARGET.to_string()));
+        assert_eq!(parse_str("mtu 9.9.9.9").unwrap(), Command::Mtu("9.9.9.9".into()));
+        assert_eq!(
+            parse_str("update -y -n").unwrap(),
+            Command::Update(UpdateArgs { yes: true, channel: None, dry_run: true })
+        );
+        assert_eq!(
+            parse_str("run --waybar --metrics 127.0.0.1:9184").unwrap(),
+            Command::Run(RunArgs {
+                netns: None,
+                interval: None,
+                waybar: true,
+                metrics: Some("127.0.0.1:9184".parse().unwrap()),
+            })
+        );
+    }
+
+    #[test]
+    fn test_legacy_flags() {
+        assert_eq!(parse_str("--update").unwrap(), Command::Update(UpdateArgs::default()));
+        assert_eq!(
+            parse_str("--update --yes").unwrap(),
+            Command::Update(UpdateArgs { yes: true, ..Default::default() })
+        );
+        assert_eq!(parse_str("--mtu").unwrap(), Command::Mtu(mtu::DEFAULT_TARGET.to_string()));
+        assert_eq!(parse_str("--mtu 1.1.1.1").unwrap(), Command::Mtu("1.1.1.1".into()));
+        assert!(matches!(parse_str("--netns vpn").unwrap(), Command::Run(RunArgs { netns: Some(_), .. })));
+        assert!(matches!(parse_str("--waybar").unwrap(), Command::Run(RunArgs { waybar: true, .. })));
+        assert!(matches!(parse_str("--metrics=[::1]:9184").unwrap(), Command::Run(RunArgs { metrics: Some(_), .. })));
+        assert!(matches!(parse_str("--interval 60").unwrap(), Command::Run(RunArgs { interval: Some(_), .. })));
+        assert_eq!(parse_str("-h").unwrap(), Command::Help);
+        assert_eq!(parse_str("--help").unwrap(), Command::Help);
+        assert_eq!(parse_str("-v").unwrap(), Command::Version);
+        assert_eq!(parse_str("--version").unwrap(), Command::Version);
+        assert_eq!(parse_str("-c").unwrap(), Command::Check);
+        assert_eq!(parse_str("--check").unwrap(), Command::Check);
+        assert_eq!(
+            parse_str("-u -y --channel beta").unwrap(),
+            Command::Update(UpdateArgs { yes: true, channel: Some(Channel::Beta), dry_run: false })
+        );
+        assert_eq!(
+            parse_str("--update --dry-run").unwrap(),
+            Command::Update(UpdateArgs { dry_run: true, ..Default::default() })
+        );
+        assert_eq!(parse_str("--mtu=1.1.1.1").unwrap(), Command::Mtu("1.1.1.1".into()));
+    }
+
+    #[test]
+    fn test_errors() {
+        assert!(matches!(parse_str("run --interval 5"), Err(CliError::InvalidValue { .. })));
+        assert!(matches!(parse_str("run --interval"), Err(CliError::MissingValue(_))));
+        assert!(matches!(parse_str("run --metrics 9184"), Err(CliError::InvalidValue { .. })));
+        assert!(matches!(parse_str("run --verbose"), Err(CliError::UnknownFlag { .. })));
+        assert!(matches!(parse_str("history --since 7"), Err(CliError::InvalidValue { .. })));
+        assert!(matches!(parse_str("events --csv"), Err(CliError::UnknownFlag { .. })));
+        assert!(matches!(parse_str("stats"), Err(CliError::UnknownCommand(_))));
+        assert!(matches!(parse_str("status now"), Err(CliError::UnexpectedArgument(_))));
+        assert!(matches!(parse_str("update --channel nightly"), Err(CliError::InvalidValue { .. })));
+        assert!(matches!(parse_str("update --force"), Err(CliError::UnknownFlag { .. })));
+        assert!(matches!(parse_str("backup"), Err(CliError::MissingArgument { .. })));
+        assert!(matches!(parse_str("config"), Err(CliError::MissingArgument { .. })));
+        assert!(matches!(parse_str("config reset"), Err(CliError::UnknownCommand(_))));
+        assert!(matches!(parse_str("secrets set"), Err(CliError::MissingArgument { .. })));
+        assert!(matches!(parse_str("debug trace"), Err(CliError::UnknownCommand(_))));
+        assert!(matches!(parse_str("history --country vnm"), Err(CliError::InvalidValue { .. })));
+        assert!(matches!(parse_str("history --format xml"), Err(CliError::InvalidValue { .. })));
+        assert!(matches!(parse_str("--check --waybar"), Err(CliError::UnknownFlag { .. })));
+        assert_eq!(
+            parse_str("restore a.zip b.zip"),
+            Err(CliError::UnexpectedArgument("b.zip".into()))
+        );
+    }
+}
@@ -0,0 +1,132 @@
+//! Clock skew detection
+//! Compares the `Date` header of geo responses with the local clock; a clock that is far
+//! off breaks TLS and the updater in confusing ways, so the user is warned and offered NTP.
+
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Mutex;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use thiserror::Error;
+use zbus::{proxy, Connection};
+
+/// Skew beyond this is reported; Date headers have one-second resolution and responses
+/// take a moment, so small differences are noise
+pub const SKEW_THRESHOLD: Duration = Duration::from_secs(5 * 60);
+const MONTHS: [&str; 12] = ["Jan", "Feb", "Mar", "Apr", "May", "Jun", "Jul", "Aug", "Sep", "Oct", "Nov", "Dec"];
+
+/// Local minus server time in seconds, from the last response with a Date header
+static SKEW: Mutex<Option<i64>> = Mutex::new(None);
+/// The warning is shown once per run
+static WARNED: AtomicBool = AtomicBool::new(false);
+
+#[derive(Debug, Error)]
+pub enum ClockError {
+    #[error("D-Bus error: {0}")]
+    DBus(#[from] zbus::Error),
+}
+
+#[proxy(
+    interface = "org.freedesktop.timedate1",
+    default_service = "org.freedesktop.timedate1",
+    default_path = "/org/freedesktop/timedate1"
+)]
+trait Timedate {
+    #[zbus(name = "SetNTP")]
+    fn set_ntp(&self, use_ntp: bool, interactive: bool) -> zbus::Result<()>;
+}
+
+/// Parses an IMF-fixdate (`Sun, 06 Nov 1994 08:49:37 GMT`) into Unix seconds
+fn parse_http_date(value: &str) -> Option<i64> {
+    let parts: Vec<&str> = value.split_whitespace().collect();
+    let [_, day, month, year, time, "GMT"] = parts[..] else { return None };
+    let day: i64 = day.parse().ok()?;
+    let month = MONTHS.iter().position(|m| *m == month)? as i64 + 1;
+    let year: i64 = year.parse().ok()?;
+    let mut hms = time.split(':').map(|p| p.parse::<i64>().ok());
+    let (h, m, s) = (hms.next()??, hms.next()??, hms.next()??);
+
+    // Days from the civil calendar (Howard Hinnant's algorithm)
+    let y = if month <= 2 { year - 1 } else { year };
+    let era = y.div_euclid(400);
+    let yoe = y - era * 400;
+    let doy = (153 * ((month + 9) % 12) + 2) / 5 + day - 1;
+    let doe = yoe * 365 + yoe / 4 - yoe / 100 + doy;
+    let days = era * 146_097 + doe - 719_468;
+    Some(days * 86_400 + h * 3600 + m * 60 + s)
+}
+
+/// Local clock minus server clock, None if the header doesn't parse
+fn skew_from(date: &str, now: SystemTime) -> Option<i64> {
+    let server = parse_http_date(date)?;
+    let local = match now.duration_since(UNIX_EPOCH) {
+        Ok(d) => d.as_secs() as i64,
+        Err(e) => -(e.duration().as_secs() as i64),
+    };
+    Some(local - server)
+}
+
+/// Last measured skew if it is beyond `SKEW_THRESHOLD`
+pub fn skew() -> Option<i64> {
+    SKEW.lock().ok().and_then(|s| *s).filter(|s| s.unsigned_abs() > SKEW_THRESHOLD.as_secs())
+}
+
+/// "2h ahead" / "15m behind"
+pub fn describe(skew: i64) -> String {
+    let amount = crate::tray::format_duration(Duration::from_secs(skew.unsigned_abs()));
+    format!("{} {}", amount, if skew > 0 { "ahead" } else { "behind" })
+}
+
+/// Records the skew seen in a response's Date header, warning the first time it is large
+pub fn observe(date: Option<&str>) {
+    let Some(skew) = date.and_then(|d| skew_from(d, SystemTime::now())) else { return };
+    if let Ok(mut last) = SKEW.lock() {
+        *last = Some(skew);
+    }
+    if skew.unsigned_abs() <= SKEW_THRESHOLD.as_secs() || WARNED.swap(true, Ordering::Relaxed) {
+        return;
+    }
+    tracing::warn!("System clock is {} (from a server Date header)", describe(skew));
+    let message = format!(
+        "Your clock is {}. HTTPS lookups and update checks may fail. Turn on automatic time sync?",
+        describe(skew)
+    );
+    tokio::spawn(async move {
+        match crate::notifier::confirm(message.clone(), "Sync Clock", 20000).await {
+            Some(true) => sync().await,
+            Some(false) => {}
+            None => crate::notifier::notify(message, 10000),
+        }
+    });
+}
+
+/// Turns on NTP via systemd-timedated (polkit may ask for a password), reporting the outcome
+pub async fn sync() {
+    match enable_ntp().await {
+        Ok(()) => crate::notifier::notify("Automatic time sync turned on", 5000),
+        Err(e) => {
+            tracing::warn!("Failed to enable NTP: {}", e);
+            crate::notifier::notify(format!("Could not turn on time sync: {}", e), 5000);
+        }
+    }
+}
+
+async fn enable_ntp() -> Result<(), ClockError> {
+    let connection = Connection::system().await?;
+    TimedateProxy::new(&connection).await?.set_ntp(true, true).await?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_http_date() {
+        assert_eq!(parse_http_date("Sun, 06 Nov 1994 08:49:37 GMT"), Some(784_111_777));
+        assert_eq!(parse_http_date("Thu, 01 Jan 1970 00:00:00 GMT"), Some(0));
+        assert_eq!(parse_http_date("Sunday, 06-Nov-94 08:49:37 GMT"), None);
+
+        let now = UNIX_EPOCH + Duration::from_secs(784_111_777 + 7200);
+        assert_eq!(skew_from("Sun, 06 Nov 1994 08:49:37 GMT", now), Some(7200));
+        assert_eq!(describe(7200), "2h ahead");
+    }
+}
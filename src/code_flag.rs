@@ -0,0 +1,52 @@
+//! Generated stand-in for flags that are not embedded
+//! New or exotic ISO codes get their two letters on a neutral circle, so the icon still
+//! names the country instead of falling back to the globe or question mark.
+
+use crate::icons::ICON_SIZE;
+use crate::pixel_font;
+use image::RgbaImage;
+
+/// Material gray 600, readable on light and dark panels
+const CIRCLE_COLOR: [u8; 3] = [0x75, 0x75, 0x75];
+const TEXT_COLOR: [u8; 3] = [0xff, 0xff, 0xff];
+/// Share of the circle's diameter the code may use
+const TEXT_RATIO: f32 = 0.66;
+
+/// Whether `code` looks like an ISO 3166-1 alpha-2 code the pixel font can draw
+pub fn can_render(code: &str) -> bool {
+    code.len() == 2 && code.chars().all(|c| c.is_ascii_alphabetic())
+}
+
+/// Renders `code` in capitals on a gray circle at `ICON_SIZE`
+pub fn render(code: &str) -> RgbaImage {
+    let center = ICON_SIZE as f32 / 2.0;
+    let radius = center - 1.0;
+    let code = code.to_ascii_uppercase();
+
+    let mut icon = RgbaImage::new(ICON_SIZE, ICON_SIZE);
+    crate::symbolic::cover(&mut icon, CIRCLE_COLOR, |x, y| {
+        ((x - center).powi(2) + (y - center).powi(2)).sqrt() - radius
+    });
+    let room = (radius * 2.0 * TEXT_RATIO) as u32;
+    let cell = pixel_font::fit_cell(&code, room, room);
+    pixel_font::draw_text(&mut icon, &code, cell, (ICON_SIZE / 2, ICON_SIZE / 2), TEXT_COLOR);
+    icon
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_render() {
+        assert!(can_render("xk") && can_render("XK"));
+        assert!(!can_render("gb-sct") && !can_render("1A") && !can_render(""));
+
+        let icon = render("xk");
+        assert_eq!(icon, render("XK"));
+        assert_ne!(icon, render("XA"));
+        assert_eq!(icon.get_pixel(0, 0)[3], 0);
+        assert_eq!(icon.get_pixel(ICON_SIZE / 2, 4).0, [0x75, 0x75, 0x75, 255]);
+        assert!(icon.pixels().any(|p| p.0 == [0xff, 0xff, 0xff, 255]));
+    }
+}
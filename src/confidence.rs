@@ -0,0 +1,112 @@
+//! Geo accuracy confidence heuristics
+//! Flags lookups where the city shown is probably a datacenter or carrier gateway
+//! rather than the user's actual town.
+
+use crate::geo::GeoInfo;
+
+/// ISP name fragments typical for hosting/VPN infrastructure
+const HOSTING_KEYWORDS: &[&str] = &[
+    "hosting", "cloud", "data center", "datacenter", "server", "vps",
+    "amazon", "google llc", "microsoft", "digitalocean", "hetzner", "ovh",
+    "linode", "akamai", "vultr", "m247", "datacamp", "leaseweb", "choopa",
+];
+
+/// How much the displayed city can be trusted
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Confidence {
+    /// Residential/business line: city is usually close
+    High,
+    /// Mobile carrier: often the carrier's regional gateway
+    Medium,
+    /// Datacenter/VPN exit or missing city: location of the server, not the user
+    Low,
+}
+
+impl Confidence {
+    pub fn label(&self) -> &'static str {
+        match self {
+            Confidence::High => "high",
+            Confidence::Medium => "medium",
+            Confidence::Low => "low",
+        }
+    }
+}
+
+/// Accuracy assessment with a human readable reason
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Assessment {
+    pub confidence: Confidence,
+    pub reason: Option<&'static str>,
+}
+
+impl Assessment {
+    /// Short hint for the menu, e.g. "low confidence: datacenter IP"
+    pub fn hint(&self) -> String {
+        match self.reason {
+            Some(reason) => format!("{} confidence: {}", self.confidence.label(), reason),
+            None => format!("{} confidence", self.confidence.label()),
+        }
+    }
+}
+
+/// Assesses how accurate the city in `info` is likely to be
+pub fn assess(info: &GeoInfo) -> Assessment {
+    let isp = info.isp.to_lowercase();
+
+    let (confidence, reason) = if info.city.trim().is_empty() {
+        (Confidence::Low, Some("no city reported"))
+    } else if info.hosting {
+        (Confidence::Low, Some("datacenter IP"))
+    } else if HOSTING_KEYWORDS.iter().any(|k| isp.contains(k)) {
+        (Confidence::Low, Some("hosting provider"))
+    } else if info.mobile {
+        (Confidence::Medium, Some("mobile carrier"))
+    } else {
+        (Confidence::High, None)
+    };
+
+    Assessment { confidence, reason }
+}
+
+/// Menu badges for what kind of address the egress is, as the provider classifies it
+pub fn classification(info: &GeoInfo) -> Vec<&'static str> {
+    [
+        (info.proxy, "🕵 Proxy/VPN exit"),
+        (info.hosting, "🏢 Datacenter IP"),
+        (info.mobile, "📶 Mobile carrier NAT"),
+    ]
+    .into_iter()
+    .filter_map(|(set, label)| set.then_some(label))
+    .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_assess() {
+        let residential = GeoInfo {
+            city: "Hanoi".to_string(),
+            isp: "Viettel Group".to_string(),
+            ..Default::default()
+        };
+        assert_eq!(assess(&residential).confidence, Confidence::High);
+
+        let mobile = GeoInfo { mobile: true, ..residential.clone() };
+        assert_eq!(assess(&mobile).confidence, Confidence::Medium);
+
+        let datacenter = GeoInfo { hosting: true, ..residential.clone() };
+        assert_eq!(assess(&datacenter).hint(), "low confidence: datacenter IP");
+
+        let vpn = GeoInfo { isp: "M247 Europe SRL".to_string(), ..residential };
+        assert_eq!(assess(&vpn).confidence, Confidence::Low);
+    }
+
+    #[test]
+    fn test_classification() {
+        let exit = GeoInfo { proxy: true, hosting: true, ..Default::default() };
+        assert_eq!(classification(&exit), ["🕵 Proxy/VPN exit", "🏢 Datacenter IP"]);
+        assert!(classification(&GeoInfo::default()).is_empty());
+    }
+}
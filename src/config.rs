@@ -0,0 +1,1439 @@
+//! Configuration module for ~/.config/network-monitor/config.toml
+//! Parses settings with defaults for anything missing, and watches the file
+//! with inotify so edits apply without restarting.
+
+use crate::icons::{FallbackPolicy, FlagStyle, IconMode};
+use crate::network::ConnectionInfo;
+use crate::rules::{Action, Condition, Rule};
+use crate::schedule::BlackoutWindow;
+use crate::updater::Channel;
+use std::collections::BTreeMap;
+use std::ffi::CString;
+use std::fs;
+use std::io::Write;
+use std::os::unix::ffi::OsStrExt;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, LazyLock, RwLock};
+use std::time::Duration;
+use thiserror::Error;
+use tokio::sync::mpsc;
+use toml_edit::{Item, Table};
+
+const CONFIG_FILENAME: &str = "config.toml";
+/// Lower bound for the refresh interval (keeps well below provider rate limits)
+pub const MIN_REFRESH_INTERVAL: Duration = Duration::from_secs(10);
+
+/// Currently active configuration, replaced on reload
+static CURRENT: LazyLock<RwLock<Arc<Config>>> = LazyLock::new(|| RwLock::new(Arc::new(Config::default())));
+
+/// Errors while loading the configuration
+#[derive(Debug, Error)]
+pub enum ConfigError {
+    #[error("IO error: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("Parse error: {0}")]
+    Parse(#[from] toml_edit::TomlError),
+    #[error("Invalid value for '{key}'{}: {message}", at(.position))]
+    Invalid { key: String, message: String, position: Option<Position> },
+    #[error("Could not determine config directory")]
+    NoConfigDir,
+}
+
+/// 1-based line and column in config.toml
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Position {
+    pub line: usize,
+    pub column: usize,
+}
+
+impl Position {
+    fn from_offset(content: &str, offset: usize) -> Self {
+        let before = &content[..offset.min(content.len())];
+        let line_start = before.rfind('\n').map_or(0, |i| i + 1);
+        Self {
+            line: before.matches('\n').count() + 1,
+            column: before[line_start..].chars().count() + 1,
+        }
+    }
+}
+
+impl std::fmt::Display for Position {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "line {}, column {}", self.line, self.column)
+    }
+}
+
+fn at(position: &Option<Position>) -> String {
+    position.map(|p| format!(" at {}", p)).unwrap_or_default()
+}
+
+/// A key the config file sets but nothing reads (usually a typo)
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ConfigWarning {
+    pub key: String,
+    pub position: Option<Position>,
+    /// Known key with a similar spelling
+    pub suggestion: Option<&'static str>,
+    /// Byte offset of the key, resolved to `position` once parsing is done
+    offset: Option<usize>,
+}
+
+impl std::fmt::Display for ConfigWarning {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "unknown key '{}'{}", self.key, at(&self.position))?;
+        if let Some(suggestion) = self.suggestion {
+            write!(f, " (did you mean '{}'?)", suggestion)?;
+        }
+        Ok(())
+    }
+}
+
+/// Application settings
+#[derive(Debug, Clone, PartialEq)]
+pub struct Config {
+    /// How often the location is refreshed automatically; zero means manual refreshes only
+    pub refresh_interval: Duration,
+    /// Enforce the autostart entry on startup/reload (unset: leave it to the menu toggle)
+    pub autostart: Option<bool>,
+    pub geo: GeoConfig,
+    pub notifications: NotificationConfig,
+    pub schedule: ScheduleConfig,
+    pub untrusted: UntrustedConfig,
+    pub icons: IconsConfig,
+    pub updates: UpdatesConfig,
+    pub menu: MenuConfig,
+    pub tray: TrayConfig,
+    pub mqtt: MqttConfig,
+    pub webhook: WebhookConfig,
+    pub history: HistoryConfig,
+    pub privacy: PrivacyConfig,
+    pub watchdog: WatchdogConfig,
+    pub shortcuts: ShortcutsConfig,
+    /// `[[rules]]` entries, in file order
+    pub rules: Vec<Rule>,
+    /// Per-network overrides, keyed by connection name or UUID
+    pub networks: BTreeMap<String, NetworkOverride>,
+}
+
+impl Config {
+    /// Override for `connection`; a UUID entry wins over a name entry
+    pub fn network_override(&self, connection: Option<&ConnectionInfo>) -> Option<&NetworkOverride> {
+        let connection = connection?;
+        self.networks.get(&connection.uuid).or_else(|| self.networks.get(&connection.name))
+    }
+
+    /// Geo provider to use on `connection`
+    pub fn provider_for(&self, connection: Option<&ConnectionInfo>) -> &str {
+        self.network_override(connection)
+            .and_then(|o| o.provider.as_deref())
+            .unwrap_or(&self.geo.provider)
+    }
+
+    /// Refresh interval to use on `connection`
+    pub fn refresh_interval_for(&self, connection: Option<&ConnectionInfo>) -> Duration {
+        self.network_override(connection)
+            .and_then(|o| o.refresh_interval)
+            .unwrap_or(self.refresh_interval)
+    }
+}
+
+/// `[networks."<name or uuid>"]` section
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct NetworkOverride {
+    /// Geo provider used while on this network
+    pub provider: Option<String>,
+    /// Replaces `refresh_interval` while on this network (0 = off)
+    pub refresh_interval: Option<Duration>,
+}
+
+/// `[geo]` section
+#[derive(Debug, Clone, PartialEq)]
+pub struct GeoConfig {
+    /// Primary geo-IP provider name
+    pub provider: String,
+    /// Try the other providers when the primary fails or rate-limits
+    pub fallback: bool,
+    /// GeoLite2 database used by the "offline" provider
+    pub mmdb_path: PathBuf,
+    /// Also detect the public address of the other IP family
+    pub dual_stack: bool,
+    /// Check the public IP against the Tor exit list
+    pub tor_check: bool,
+    /// Optional fields requested from providers that support field selection
+    pub fields: Vec<String>,
+    /// Query the first two providers at once and take whichever answers first
+    pub race: bool,
+    /// Look up the PTR name of the public IP
+    pub reverse_dns: bool,
+}
+
+/// `[notifications]` section
+#[derive(Debug, Clone, PartialEq)]
+pub struct NotificationConfig {
+    /// Show desktop notifications at all
+    pub enabled: bool,
+    /// Queue non-critical notifications while Do Not Disturb is active
+    pub respect_dnd: bool,
+    /// Announce when a refresh finds a different country
+    pub country_change: bool,
+    /// More public IP changes than this per hour raise one instability warning
+    /// instead of individual alerts (0 disables)
+    pub max_ip_changes_per_hour: u32,
+}
+
+/// `[schedule]` section
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ScheduleConfig {
+    /// Daily windows without automatic refreshes or probes
+    pub blackout: Vec<BlackoutWindow>,
+}
+
+/// `[untrusted]` section: rules for networks not marked as trusted
+#[derive(Debug, Clone, PartialEq)]
+pub struct UntrustedConfig {
+    /// Refresh interval used instead of the normal one, when shorter; unset keeps the normal one
+    pub refresh_interval: Option<Duration>,
+    /// Warn when joining without an active firewall
+    pub firewall_warning: bool,
+    /// Raise a critical alert while no VPN is connected
+    pub require_vpn: bool,
+    /// Warning badge and a "Connect VPN" menu action while on untrusted Wi-Fi without a VPN
+    pub public_wifi_warning: bool,
+}
+
+/// `[icons]` section
+#[derive(Debug, Clone, PartialEq)]
+pub struct IconsConfig {
+    /// Country flag, or a connection type glyph
+    pub mode: IconMode,
+    /// Circle or rectangular flags
+    pub style: FlagStyle,
+    /// Color of the `symbolic` mode icon
+    pub symbolic_color: [u8; 3],
+    /// Icon shown when there is no flag for the current location
+    pub fallback: FallbackPolicy,
+    /// Where flags missing from the binary are downloaded from; `{code}` is the
+    /// lowercase country code, and the server may return SVG or PNG
+    pub flag_url: String,
+    /// Directory of `<cc>.png` (or `.svg`) flags that replace the embedded ones
+    pub theme_dir: Option<PathBuf>,
+    /// Lock badge on the flag while a VPN or WireGuard connection is up
+    pub vpn_badge: bool,
+    /// Gray the flag once this many refresh intervals pass without a successful lookup (0: never)
+    pub stale_after: u32,
+}
+
+impl Default for IconsConfig {
+    fn default() -> Self {
+        Self {
+            mode: IconMode::default(),
+            style: FlagStyle::default(),
+            symbolic_color: crate::symbolic::DEFAULT_COLOR,
+            fallback: FallbackPolicy::default(),
+            flag_url: "https://hatscripts.github.io/circle-flags/flags/{code}.svg".to_string(),
+            theme_dir: None,
+            vpn_badge: true,
+            stale_after: 3,
+        }
+    }
+}
+
+/// `[updates]` section
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct UpdatesConfig {
+    /// Release channel offered by the update check
+    pub channel: Channel,
+}
+
+/// `[menu]` section
+#[derive(Debug, Clone, PartialEq)]
+pub struct MenuConfig {
+    /// Names of the submenus to show (`tray::SECTIONS`), in order
+    pub sections: Vec<String>,
+    /// Page opened by "Open IP Details", `{ip}` is the public IP
+    pub ip_details_url: String,
+}
+
+impl Default for MenuConfig {
+    fn default() -> Self {
+        Self {
+            sections: crate::tray::SECTIONS.iter().map(|s| s.name().to_string()).collect(),
+            ip_details_url: "https://ipinfo.io/{ip}".to_string(),
+        }
+    }
+}
+
+/// `[tray]` section; hosts are matched by program name (e.g. "waybar", "plasmashell")
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct TrayConfig {
+    /// Wait for one of these hosts at startup before registering the icon
+    pub prefer_hosts: Vec<String>,
+    /// Hosts that don't count as showing the icon; with only these, notifications are used
+    pub exclude_hosts: Vec<String>,
+}
+
+/// `[mqtt]` section; publishing is off until `broker` is set
+#[derive(Debug, Clone, PartialEq)]
+pub struct MqttConfig {
+    /// "host" or "host:port" (plain TCP)
+    pub broker: Option<String>,
+    pub username: Option<String>,
+    pub password: Option<String>,
+    /// Base topic; the state goes to `<topic>/state`
+    pub topic: String,
+    /// Home Assistant discovery prefix
+    pub discovery_prefix: String,
+}
+
+impl Default for MqttConfig {
+    fn default() -> Self {
+        Self {
+            broker: None,
+            username: None,
+            password: None,
+            topic: "network-monitor".to_string(),
+            discovery_prefix: "homeassistant".to_string(),
+        }
+    }
+}
+
+/// `[webhook]` section; nothing is sent until `url` is set
+#[derive(Debug, Clone, PartialEq)]
+pub struct WebhookConfig {
+    /// Receives a POST with the old and new location
+    pub url: Option<String>,
+    /// Extra attempts after a failed delivery
+    pub retries: u32,
+}
+
+impl Default for WebhookConfig {
+    fn default() -> Self {
+        Self { url: None, retries: 3 }
+    }
+}
+
+/// `[history]` section
+#[derive(Debug, Clone, PartialEq)]
+pub struct HistoryConfig {
+    /// Record observed locations to disk
+    pub enabled: bool,
+    /// Days entries are kept for; 0 keeps them forever
+    pub retention_days: u32,
+}
+
+impl Default for HistoryConfig {
+    fn default() -> Self {
+        Self { enabled: true, retention_days: 90 }
+    }
+}
+
+/// `[privacy]` section
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct PrivacyConfig {
+    /// Browser User-Agents, random provider order and jittered refreshes
+    pub minimize_fingerprint: bool,
+    /// Keep location and outage history off disk and IP addresses out of logs
+    pub no_log: bool,
+    /// Show only country and city: no IP in the menu, tooltip, notifications or logs
+    pub hide_ip: bool,
+    /// Check for DNS leaks whenever a VPN connects
+    pub dns_leak_check: bool,
+}
+
+/// `[watchdog]` section
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct WatchdogConfig {
+    /// ISO country code the location should be in (e.g. the VPN exit); alerts otherwise
+    pub expected_country: Option<String>,
+}
+
+/// `[shortcuts]` section
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ShortcutsConfig {
+    /// Preferred trigger for the "show location" shortcut, e.g. "CTRL+ALT+L"
+    pub show_status: Option<String>,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            refresh_interval: Duration::from_secs(60),
+            autostart: None,
+            geo: GeoConfig {
+                provider: "ip-api".to_string(),
+                fallback: true,
+                mmdb_path: PathBuf::from("/var/lib/GeoIP/GeoLite2-City.mmdb"),
+                dual_stack: true,
+                tor_check: true,
+                fields: crate::geo::OPTIONAL_FIELDS.iter().map(|f| f.to_string()).collect(),
+                race: false,
+                reverse_dns: true,
+            },
+            notifications: NotificationConfig {
+                enabled: true,
+                respect_dnd: true,
+                country_change: true,
+                max_ip_changes_per_hour: 5,
+            },
+            schedule: ScheduleConfig::default(),
+            untrusted: UntrustedConfig {
+                refresh_interval: None,
+                firewall_warning: true,
+                require_vpn: false,
+                public_wifi_warning: true,
+            },
+            icons: IconsConfig::default(),
+            updates: UpdatesConfig::default(),
+            menu: MenuConfig::default(),
+            tray: TrayConfig::default(),
+            mqtt: MqttConfig::default(),
+            webhook: WebhookConfig::default(),
+            history: HistoryConfig::default(),
+            privacy: PrivacyConfig::default(),
+            watchdog: WatchdogConfig::default(),
+            shortcuts: ShortcutsConfig::default(),
+            rules: Vec::new(),
+            networks: BTreeMap::new(),
+        }
+    }
+}
+
+/// Gets the config directory path
+pub fn config_dir() -> Option<PathBuf> {
+    dirs::config_dir().map(|p| p.join("network-monitor"))
+}
+
+/// Gets the config file path
+pub fn config_path() -> Option<PathBuf> {
+    config_dir().map(|p| p.join(CONFIG_FILENAME))
+}
+
+/// Returns the active configuration
+pub fn current() -> Arc<Config> {
+    CURRENT.read().map(|c| c.clone()).unwrap_or_default()
+}
+
+/// Replaces the active configuration
+pub fn set_current(config: Config) {
+    if let Ok(mut current) = CURRENT.write() {
+        *current = Arc::new(config);
+    }
+}
+
+/// Loads the config file; a missing file yields the defaults
+pub fn load() -> Result<Config, ConfigError> {
+    let (config, warnings) = load_validated()?;
+    log_warnings(&warnings);
+    Ok(config)
+}
+
+/// Loads the config file, also returning the unknown keys it sets
+pub fn load_validated() -> Result<(Config, Vec<ConfigWarning>), ConfigError> {
+    let path = config_path().ok_or(ConfigError::NoConfigDir)?;
+    match fs::read_to_string(&path) {
+        Ok(content) => validate(&content),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok((Config::default(), Vec::new())),
+        Err(e) => Err(e.into()),
+    }
+}
+
+/// Writes `default_toml()` to config.toml; an existing file is only replaced with `force`
+pub fn init(force: bool) -> Result<PathBuf, ConfigError> {
+    let path = config_path().ok_or(ConfigError::NoConfigDir)?;
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    let mut file = fs::OpenOptions::new();
+    file.write(true);
+    if force {
+        file.create(true).truncate(true);
+    } else {
+        file.create_new(true);
+    }
+    file.open(&path)?.write_all(default_toml().as_bytes())?;
+    Ok(path)
+}
+
+/// A config.toml with every option set to its compiled-in default, each explained;
+/// options that are off by default are commented out
+pub fn default_toml() -> String {
+    let defaults = Config::default();
+    let quoted = |items: &mut dyn Iterator<Item = &str>| {
+        items.map(|s| format!("\"{}\"", s)).collect::<Vec<_>>().join(", ")
+    };
+    let mut providers: Vec<_> = crate::geo::PROVIDERS.iter().map(|p| p.name()).collect();
+    providers.push(crate::geo::OFFLINE_PROVIDER);
+    let sections = quoted(&mut defaults.menu.sections.iter().map(String::as_str));
+    let fields = quoted(&mut defaults.geo.fields.iter().map(String::as_str));
+    format!(
+        r#"# network-monitor configuration, generated by `network-monitor config init`.
+# Every value below is the built-in default; edits are applied while the app runs.
+
+# Seconds between automatic refreshes (minimum 10, 0 for manual refreshes only)
+refresh_interval = {refresh}
+
+# Pin the autostart entry on or off; leave unset to use the "Launch on Login" toggle
+# autostart = true
+
+[geo]
+# Geo-IP service: {providers}
+provider = "{provider}"
+# Try the other services when the provider fails or rate-limits
+fallback = {fallback}
+# GeoLite2 database used by the "offline" provider
+mmdb_path = "{mmdb}"
+# Also detect the public address of the other IP family, by asking the provider once more
+# over it (one extra request per refresh; skipped with "offline")
+dual_stack = {dual_stack}
+# Show "Via Tor" when the public IP is a Tor exit (downloads the exit list hourly)
+tor_check = {tor_check}
+# Fields asked from ip-api and ipwho.is besides IP and country; drop the ones you don't need
+fields = [{fields}]
+# Ask the first two services at once and use the fastest answer (needs fallback; doubles API use)
+race = {race}
+# Show the reverse DNS name of the public IP (asks the system resolver)
+reverse_dns = {reverse_dns}
+
+[notifications]
+# Desktop notifications; critical alerts are always shown
+enabled = {notify}
+# Hold back notifications while Do Not Disturb is on
+respect_dnd = {dnd}
+# Notify when a refresh finds a different country, e.g. after a VPN drop
+country_change = {country_change}
+# More IP changes per hour than this give one "unstable connection" warning instead of
+# alerts, webhooks, hooks and rule actions per change (0 = off)
+max_ip_changes_per_hour = {max_ip_changes}
+
+[schedule]
+# Daily local-time windows without automatic refreshes or probes
+blackout = []
+
+[untrusted]
+# Rules for networks not ticked "Trusted Network" in the menu
+# refresh_interval = 30   # seconds, used there when shorter than the normal interval
+# Warn on join when no firewall (ufw, firewalld, nftables) is running
+firewall_warning = {firewall}
+# Critical alert while no VPN is connected
+require_vpn = {vpn}
+# On untrusted Wi-Fi without a VPN: warning badge on the icon and "Connect VPN" in the menu
+public_wifi_warning = {public_wifi}
+
+[updates]
+# {channels}
+channel = "{channel}"
+
+[menu]
+# Submenus in order; leave one out to hide it
+sections = [{sections}]
+# Opened by Info → "Open IP Details"; {{ip}} is replaced with the public IP
+ip_details_url = "{ip_details_url}"
+
+[tray]
+# StatusNotifier hosts by program name, for sessions with several (e.g. waybar on two outputs
+# plus a dock). Every host shows the same icon; the protocol has no per-host registration.
+# Wait up to 30 seconds at startup for one of these to appear
+prefer_hosts = []
+# Hosts that don't count: if only these are running, location changes become notifications
+exclude_hosts = []
+
+[icons]
+# What the icon shows: {modes} (connection: Wi-Fi/Ethernet/VPN/offline glyph with the
+# country in the tooltip; symbolic: monochrome globe with the country code)
+mode = "{icon_mode}"
+# Flag shape: {styles} (rectangular flags are easier to tell apart at 22px)
+style = "{flag_style}"
+# Icon color in symbolic mode as #rrggbb (dark panels want a light one)
+symbolic_color = "{symbolic_color}"
+# No flag or country code for the location (codes without a flag are drawn as text): {fallbacks}
+fallback = "{fallback_icon}"
+# Where flags not built in are downloaded from (SVG or PNG); {{code}} is the country code
+flag_url = "{flag_url}"
+# Your own flags as <code>.png or <code>.svg (e.g. de.svg, gb-sct.png), used instead of the
+# built-in ones; edits show at the next refresh
+# theme_dir = "~/.local/share/icons/network-monitor"
+# Lock badge on the flag while a VPN is connected
+vpn_badge = {vpn_badge}
+# Gray the flag after this many refresh intervals without a successful lookup, and right
+# after a failed one (0: never)
+stale_after = {stale_after}
+
+[mqtt]
+# Publish location changes for Home Assistant; off until broker is set
+# broker = "192.168.1.5:1883"
+# username = "ha"
+# password = "secret"  (or "@keyring", see `network-monitor secrets`)
+topic = "{topic}"
+discovery_prefix = "{discovery}"
+
+[webhook]
+# POST the old and new location when the country, ISP or IP changes; off until url is set
+# url = "https://example.com/hook"
+retries = {retries}
+
+[history]
+# Record every distinct location (see "Recent locations" in the menu)
+enabled = {history}
+# Days to keep entries for; 0 keeps them forever
+retention_days = {retention}
+
+[privacy]
+# Send a common browser User-Agent, try providers in random order and jitter refreshes
+minimize_fingerprint = {fingerprint}
+# Shared machines: no location or outage history on disk, IP addresses redacted from logs
+no_log = {no_log}
+# Screen sharing and streaming: keep the public IP out of the menu, tooltip, notifications and logs
+hide_ip = {hide_ip}
+# Compare the DNS resolver's country and network with the VPN exit after every VPN connect
+dns_leak_check = {dns_leak_check}
+
+[watchdog]
+# Critical alert and attention icon whenever the location is not in this country
+# expected_country = "CH"
+
+[shortcuts]
+# Global shortcut (via the desktop portal) that shows the location in a notification
+# show_status = "CTRL+ALT+L"
+
+# Alert rules, checked after every refresh; all conditions must hold
+# [[rules]]
+# name = "VPN not via Mullvad"
+# isp_not = "Mullvad"     # conditions: country, country_not, asn, asn_not, isp, isp_not, vpn
+# vpn = true
+# notify = "{{isp}} ({{asn}}) is not your VPN provider"  # actions: notify (+ critical), run, badge
+# critical = true
+
+# Per-network overrides, by connection name or UUID
+# [networks."Corp LAN"]
+# provider = "offline"
+# refresh_interval = 30   # seconds, replaces the global and untrusted ones here (0 = off)
+"#,
+        refresh = defaults.refresh_interval.as_secs(),
+        providers = providers.join(", "),
+        provider = defaults.geo.provider,
+        fallback = defaults.geo.fallback,
+        mmdb = defaults.geo.mmdb_path.display(),
+        dual_stack = defaults.geo.dual_stack,
+        tor_check = defaults.geo.tor_check,
+        fields = fields,
+        race = defaults.geo.race,
+        reverse_dns = defaults.geo.reverse_dns,
+        notify = defaults.notifications.enabled,
+        dnd = defaults.notifications.respect_dnd,
+        country_change = defaults.notifications.country_change,
+        max_ip_changes = defaults.notifications.max_ip_changes_per_hour,
+        firewall = defaults.untrusted.firewall_warning,
+        vpn = defaults.untrusted.require_vpn,
+        public_wifi = defaults.untrusted.public_wifi_warning,
+        channels = Channel::NAMES.join(" or ") + "; beta also offers pre-releases",
+        channel = defaults.updates.channel.as_str(),
+        sections = sections,
+        modes = IconMode::NAMES.join(", "),
+        icon_mode = defaults.icons.mode.as_str(),
+        styles = FlagStyle::NAMES.join(", "),
+        flag_style = defaults.icons.style.as_str(),
+        symbolic_color = crate::symbolic::format_color(defaults.icons.symbolic_color),
+        fallbacks = FallbackPolicy::NAMES.join(", "),
+        fallback_icon = defaults.icons.fallback.as_str(),
+        ip_details_url = defaults.menu.ip_details_url,
+        flag_url = defaults.icons.flag_url,
+        vpn_badge = defaults.icons.vpn_badge,
+        stale_after = defaults.icons.stale_after,
+        topic = defaults.mqtt.topic,
+        discovery = defaults.mqtt.discovery_prefix,
+        retries = defaults.webhook.retries,
+        history = defaults.history.enabled,
+        retention = defaults.history.retention_days,
+        fingerprint = defaults.privacy.minimize_fingerprint,
+        no_log = defaults.privacy.no_log,
+        hide_ip = defaults.privacy.hide_ip,
+        dns_leak_check = defaults.privacy.dns_leak_check,
+    )
+}
+
+fn log_warnings(warnings: &[ConfigWarning]) {
+    for warning in warnings {
+        tracing::warn!("config.toml: {}", warning);
+    }
+}
+
+/// Sets `[section] key = "value"` in config.toml (used by menu toggles), leaving
+/// the rest of the file as written. The watcher picks up the change.
+pub fn save_str(section: &str, key: &str, value: &str) -> Result<(), ConfigError> {
+    save_with(|content| set_entry(content, section, key, &format!("\"{}\"", value)))
+}
+
+/// Sets `[section] key = true|false` in config.toml, like `save_str`
+pub fn save_bool(section: &str, key: &str, value: bool) -> Result<(), ConfigError> {
+    save_with(|content| set_entry(content, section, key, &value.to_string()))
+}
+
+/// Sets the top-level `refresh_interval` in config.toml (0: manual only)
+pub fn save_refresh_interval(interval: Duration) -> Result<(), ConfigError> {
+    save_with(|content| set_root(content, "refresh_interval", &interval.as_secs().to_string()))
+}
+
+/// Rewrites config.toml with `edit` applied, if the result still parses
+fn save_with(edit: impl FnOnce(&str) -> String) -> Result<(), ConfigError> {
+    let path = config_path().ok_or(ConfigError::NoConfigDir)?;
+    let content = match fs::read_to_string(&path) {
+        Ok(content) => content,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => String::new(),
+        Err(e) => return Err(e.into()),
+    };
+    let updated = edit(&content);
+    parse(&updated)?;
+
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    fs::write(path, updated)?;
+    Ok(())
+}
+
+/// Replaces or inserts a raw entry before the first section, line by line
+fn set_root(content: &str, key: &str, value: &str) -> String {
+    let mut lines: Vec<String> = content.lines().map(str::to_string).collect();
+    let entry = format!("{} = {}", key, value);
+    let end = lines.iter().position(|l| l.trim_start().starts_with('[')).unwrap_or(lines.len());
+    match (0..end).find(|&i| lines[i].split_once('=').is_some_and(|(k, _)| k.trim() == key)) {
+        Some(i) => lines[i] = entry,
+        None if end < lines.len() => {
+            lines.insert(end, String::new());
+            lines.insert(end, entry);
+        }
+        None => lines.push(entry),
+    }
+    lines.join("\n") + "\n"
+}
+
+/// Replaces or inserts a raw entry in a top-level section, line by line
+fn set_entry(content: &str, section: &str, key: &str, value: &str) -> String {
+    let mut lines: Vec<String> = content.lines().map(str::to_string).collect();
+    let header = format!("[{}]", section);
+    let entry = format!("{} = {}", key, value);
+
+    match lines.iter().position(|l| l.trim() == header) {
+        Some(start) => {
+            let end = lines[start + 1..]
+                .iter()
+                .position(|l| l.trim_start().starts_with('['))
+                .map_or(lines.len(), |i| start + 1 + i);
+            let existing = (start + 1..end)
+                .find(|&i| lines[i].split_once('=').is_some_and(|(k, _)| k.trim() == key));
+            match existing {
+                Some(i) => lines[i] = entry,
+                None => lines.insert(start + 1, entry),
+            }
+        }
+        None => {
+            if lines.last().is_some_and(|l| !l.trim().is_empty()) {
+                lines.push(String::new());
+            }
+            lines.push(header);
+            lines.push(entry);
+        }
+    }
+    lines.join("\n") + "\n"
+}
+
+/// Expands a leading `~/` to the home directory
+fn expand_home(path: &str) -> PathBuf {
+    match (path.strip_prefix("~/"), dirs::home_dir()) {
+        (Some(rest), Some(home)) => home.join(rest),
+        _ => PathBuf::from(path),
+    }
+}
+
+fn invalid(key: &str, message: impl Into<String>) -> ConfigError {
+    ConfigError::Invalid {
+        key: key.to_string(),
+        message: message.into(),
+        position: None,
+    }
+}
+
+/// Byte range of the value at dotted `path` (section names may contain dots)
+fn span_of(table: &Table, path: &str) -> Option<std::ops::Range<usize>> {
+    table.iter().find_map(|(key, item)| {
+        if path == key {
+            return item.span().or_else(|| table.key(key)?.span());
+        }
+        let rest = path.strip_prefix(key)?.strip_prefix('.')?;
+        match item.as_array_of_tables() {
+            // "rules.0.name": index into [[rules]]
+            Some(tables) => {
+                let (index, rest) = rest.split_once('.').unwrap_or((rest, ""));
+                let table = tables.get(index.parse().ok()?)?;
+                if rest.is_empty() { table.span() } else { span_of(table, rest) }
+            }
+            None => span_of(item.as_table()?, rest),
+        }
+    })
+}
+
+/// Edit distance, for "did you mean" suggestions
+fn distance(a: &str, b: &str) -> usize {
+    let b: Vec<char> = b.chars().collect();
+    let mut row: Vec<usize> = (0..=b.len()).collect();
+    for (i, ca) in a.chars().enumerate() {
+        let mut previous = row[0];
+        row[0] = i + 1;
+        for (j, &cb) in b.iter().enumerate() {
+            let substitution = previous + usize::from(ca != cb);
+            previous = row[j + 1];
+            row[j + 1] = substitution.min(row[j] + 1).min(previous + 1);
+        }
+    }
+    row[b.len()]
+}
+
+fn get_bool(table: &Table, key: &str, path: &str) -> Result<Option<bool>, ConfigError> {
+    table
+        .get(key)
+        .map(|item| item.as_bool().ok_or_else(|| invalid(path, "expected true or false")))
+        .transpose()
+}
+
+fn get_str<'a>(table: &'a Table, key: &str, path: &str) -> Result<Option<&'a str>, ConfigError> {
+    table
+        .get(key)
+        .map(|item| item.as_str().ok_or_else(|| invalid(path, "expected a string")))
+        .transpose()
+}
+
+fn get_secs(table: &Table, key: &str, path: &str) -> Result<Option<Duration>, ConfigError> {
+    table
+        .get(key)
+        .map(|item| match item.as_integer() {
+            Some(secs) if secs >= 0 => Ok(Duration::from_secs(secs as u64)),
+            _ => Err(invalid(path, "expected a number of seconds")),
+        })
+        .transpose()
+}
+
+/// Reads a refresh interval, enforcing `MIN_REFRESH_INTERVAL`
+fn get_interval(table: &Table, key: &str, path: &str) -> Result<Option<Duration>, ConfigError> {
+    match get_secs(table, key, path)? {
+        Some(interval) if interval < MIN_REFRESH_INTERVAL => Err(invalid(
+            path,
+            format!("must be at least {} seconds", MIN_REFRESH_INTERVAL.as_secs()),
+        )),
+        interval => Ok(interval),
+    }
+}
+
+/// `refresh_interval`: at least `MIN_REFRESH_INTERVAL`, or 0 to turn automatic
+/// refreshes off (network changes and the menu still refresh)
+fn get_refresh_interval(table: &Table, path: &str) -> Result<Option<Duration>, ConfigError> {
+    match get_secs(table, "refresh_interval", path)? {
+        Some(Duration::ZERO) => Ok(Some(Duration::ZERO)),
+        _ => get_interval(table, "refresh_interval", path),
+    }
+}
+
+fn get_str_array<'a>(table: &'a Table, key: &str, path: &str) -> Result<Option<Vec<&'a str>>, ConfigError> {
+    let Some(item) = table.get(key) else {
+        return Ok(None);
+    };
+    let array = item.as_array().ok_or_else(|| invalid(path, "expected a list of strings"))?;
+    array
+        .iter()
+        .map(|v| v.as_str().ok_or_else(|| invalid(path, "expected a list of strings")))
+        .collect::<Result<Vec<_>, _>>()
+        .map(Some)
+}
+
+fn get_table<'a>(table: &'a Table, key: &str) -> Result<Option<&'a Table>, ConfigError> {
+    match table.get(key) {
+        None => Ok(None),
+        Some(Item::Table(t)) => Ok(Some(t)),
+        Some(_) => Err(invalid(key, "expected a [section]")),
+    }
+}
+
+/// Reads a `provider` key, checking it names a known provider
+fn get_provider(table: &Table, path: &str) -> Result<Option<String>, ConfigError> {
+    let Some(provider) = get_str(table, "provider", path)? else {
+        return Ok(None);
+    };
+    if provider != crate::geo::OFFLINE_PROVIDER && crate::geo::find_provider(provider).is_none() {
+        let mut known: Vec<_> = crate::geo::PROVIDERS.iter().map(|p| p.name()).collect();
+        known.push(crate::geo::OFFLINE_PROVIDER);
+        return Err(invalid(
+            path,
+            format!("unknown provider '{}' (known: {})", provider, known.join(", ")),
+        ));
+    }
+    Ok(Some(provider.to_string()))
+}
+
+/// Collects keys we don't understand (typos would otherwise be silently ignored)
+fn warn_unknown_keys(table: &Table, known: &[&'static str], prefix: &str, warnings: &mut Vec<ConfigWarning>) {
+    for (key, _) in table.iter() {
+        if !known.contains(&key) {
+            warnings.push(ConfigWarning {
+                key: format!("{}{}", prefix, key),
+                position: None,
+                suggestion: known.iter().copied().filter(|k| distance(k, key) <= 2).min_by_key(|k| distance(k, key)),
+                offset: table.key(key).and_then(|k| k.span()).map(|s| s.start),
+            });
+        }
+    }
+}
+
+/// Parses config file content, filling in defaults for missing keys.
+/// Unknown keys are logged as warnings.
+pub fn parse(content: &str) -> Result<Config, ConfigError> {
+    let (config, warnings) = validate(content)?;
+    log_warnings(&warnings);
+    Ok(config)
+}
+
+/// Parses config file content, returning the unknown keys alongside; errors and
+/// warnings carry the line and column they refer to
+pub fn validate(content: &str) -> Result<(Config, Vec<ConfigWarning>), ConfigError> {
+    let doc = toml_edit::Document::parse(content)?;
+    let mut warnings = Vec::new();
+    let config = parse_table(doc.as_table(), &mut warnings).map_err(|e| match e {
+        ConfigError::Invalid { key, message, .. } => {
+            let position = span_of(doc.as_table(), &key).map(|s| Position::from_offset(content, s.start));
+            ConfigError::Invalid { key, message, position }
+        }
+        e => e,
+    })?;
+    for warning in &mut warnings {
+        warning.position = warning.offset.map(|o| Position::from_offset(content, o));
+    }
+    Ok((config, warnings))
+}
+
+fn parse_table(root: &Table, warnings: &mut Vec<ConfigWarning>) -> Result<Config, ConfigError> {
+    let mut config = Config::default();
+
+    warn_unknown_keys(root, &["refresh_interval", "autostart", "geo", "notifications", "schedule", "untrusted", "icons", "updates", "menu", "tray", "mqtt", "webhook", "history", "privacy", "watchdog", "shortcuts", "rules", "networks"], "", warnings);
+
+    if let Some(interval) = get_refresh_interval(root, "refresh_interval")? {
+        config.refresh_interval = interval;
+    }
+    config.autostart = get_bool(root, "autostart", "autostart")?;
+
+    if let Some(geo) = get_table(root, "geo")? {
+        warn_unknown_keys(geo, &["provider", "fallback", "mmdb_path", "dual_stack", "tor_check", "fields", "race", "reverse_dns"], "geo.", warnings);
+        if let Some(provider) = get_provider(geo, "geo.provider")? {
+            config.geo.provider = provider;
+        }
+        if let Some(fallback) = get_bool(geo, "fallback", "geo.fallback")? {
+            config.geo.fallback = fallback;
+        }
+        if let Some(path) = get_str(geo, "mmdb_path", "geo.mmdb_path")? {
+            config.geo.mmdb_path = expand_home(path);
+        }
+        if let Some(dual_stack) = get_bool(geo, "dual_stack", "geo.dual_stack")? {
+            config.geo.dual_stack = dual_stack;
+        }
+        if let Some(tor_check) = get_bool(geo, "tor_check", "geo.tor_check")? {
+            config.geo.tor_check = tor_check;
+        }
+        if let Some(names) = get_str_array(geo, "fields", "geo.fields")? {
+            let known = crate::geo::OPTIONAL_FIELDS;
+            if let Some(unknown) = names.iter().find(|n| !known.contains(n)) {
+                return Err(invalid("geo.fields", format!("unknown field '{}', expected any of {}", unknown, known.join(", "))));
+            }
+            let mut fields: Vec<String> = Vec::new();
+            for name in names {
+                if !fields.iter().any(|f| f == name) {
+                    fields.push(name.to_string());
+                }
+            }
+            config.geo.fields = fields;
+        }
+        if let Some(race) = get_bool(geo, "race", "geo.race")? {
+            config.geo.race = race;
+        }
+        if let Some(reverse_dns) = get_bool(geo, "reverse_dns", "geo.reverse_dns")? {
+            config.geo.reverse_dns = reverse_dns;
+        }
+    }
+
+    if let Some(notifications) = get_table(root, "notifications")? {
+        warn_unknown_keys(notifications, &["enabled", "respect_dnd", "country_change", "max_ip_changes_per_hour"], "notifications.", warnings);
+        if let Some(enabled) = get_bool(notifications, "enabled", "notifications.enabled")? {
+            config.notifications.enabled = enabled;
+        }
+        if let Some(respect_dnd) = get_bool(notifications, "respect_dnd", "notifications.respect_dnd")? {
+            config.notifications.respect_dnd = respect_dnd;
+        }
+        if let Some(country_change) = get_bool(notifications, "country_change", "notifications.country_change")? {
+            config.notifications.country_change = country_change;
+        }
+        if let Some(limit) = notifications.get("max_ip_changes_per_hour") {
+            config.notifications.max_ip_changes_per_hour = match limit.as_integer() {
+                Some(n @ 0..=1000) => n as u32,
+                _ => return Err(invalid("notifications.max_ip_changes_per_hour", "expected a number of changes (0 disables)")),
+            };
+        }
+    }
+
+    if let Some(schedule) = get_table(root, "schedule")? {
+        warn_unknown_keys(schedule, &["blackout"], "schedule.", warnings);
+        for window in get_str_array(schedule, "blackout", "schedule.blackout")?.unwrap_or_default() {
+            let parsed = BlackoutWindow::parse(window).ok_or_else(|| {
+                invalid("schedule.blackout", format!("'{}' is not a window like \"01:00-05:00\"", window))
+            })?;
+            config.schedule.blackout.push(parsed);
+        }
+    }
+
+    if let Some(untrusted) = get_table(root, "untrusted")? {
+        warn_unknown_keys(untrusted, &["refresh_interval", "firewall_warning", "require_vpn", "public_wifi_warning"], "untrusted.", warnings);
+        if let Some(interval) = get_interval(untrusted, "refresh_interval", "untrusted.refresh_interval")? {
+            config.untrusted.refresh_interval = Some(interval);
+        }
+        if let Some(warn) = get_bool(untrusted, "firewall_warning", "untrusted.firewall_warning")? {
+            config.untrusted.firewall_warning = warn;
+        }
+        if let Some(require_vpn) = get_bool(untrusted, "require_vpn", "untrusted.require_vpn")? {
+            config.untrusted.require_vpn = require_vpn;
+        }
+        if let Some(warn) = get_bool(untrusted, "public_wifi_warning", "untrusted.public_wifi_warning")? {
+            config.untrusted.public_wifi_warning = warn;
+        }
+    }
+
+    if let Some(icons) = get_table(root, "icons")? {
+        warn_unknown_keys(icons, &["mode", "style", "symbolic_color", "fallback", "flag_url", "theme_dir", "vpn_badge", "stale_after"], "icons.", warnings);
+        if let Some(name) = get_str(icons, "mode", "icons.mode")? {
+            config.icons.mode = IconMode::parse(name)
+                .ok_or_else(|| invalid("icons.mode", format!("expected one of {}", IconMode::NAMES.join(", "))))?;
+        }
+        if let Some(name) = get_str(icons, "style", "icons.style")? {
+            config.icons.style = FlagStyle::parse(name)
+                .ok_or_else(|| invalid("icons.style", format!("expected one of {}", FlagStyle::NAMES.join(", "))))?;
+        }
+        if let Some(color) = get_str(icons, "symbolic_color", "icons.symbolic_color")? {
+            config.icons.symbolic_color = crate::symbolic::parse_color(color)
+                .ok_or_else(|| invalid("icons.symbolic_color", "expected a color like \"#eeeeec\""))?;
+        }
+        if let Some(name) = get_str(icons, "fallback", "icons.fallback")? {
+            config.icons.fallback = FallbackPolicy::parse(name).ok_or_else(|| {
+                invalid("icons.fallback", format!("expected one of {}", FallbackPolicy::NAMES.join(", ")))
+            })?;
+        }
+        if let Some(url) = get_str(icons, "flag_url", "icons.flag_url")? {
+            if !url.contains("{code}") || !url.starts_with("https://") {
+                return Err(invalid("icons.flag_url", "expected an https:// URL containing {code}"));
+            }
+            config.icons.flag_url = url.to_string();
+        }
+        if let Some(dir) = get_str(icons, "theme_dir", "icons.theme_dir")? {
+            config.icons.theme_dir = Some(expand_home(dir));
+        }
+        if let Some(enabled) = get_bool(icons, "vpn_badge", "icons.vpn_badge")? {
+            config.icons.vpn_badge = enabled;
+        }
+        if let Some(intervals) = icons.get("stale_after") {
+            config.icons.stale_after = match intervals.as_integer() {
+                Some(n @ 0..=1000) => n as u32,
+                _ => return Err(invalid("icons.stale_after", "expected a number of refresh intervals (0 disables)")),
+            };
+        }
+    }
+
+    if let Some(updates) = get_table(root, "updates")? {
+        warn_unknown_keys(updates, &["channel"], "updates.", warnings);
+        if let Some(name) = get_str(updates, "channel", "updates.channel")? {
+            config.updates.channel = Channel::parse(name).ok_or_else(|| {
+                invalid("updates.channel", format!("expected one of {}", Channel::NAMES.join(", ")))
+            })?;
+        }
+    }
+
+    if let Some(menu) = get_table(root, "menu")? {
+        warn_unknown_keys(menu, &["sections", "ip_details_url"], "menu.", warnings);
+        if let Some(names) = get_str_array(menu, "sections", "menu.sections")? {
+            let mut sections = Vec::new();
+            for name in names {
+                if crate::tray::find_section(name).is_none() {
+                    let known: Vec<&str> = crate::tray::SECTIONS.iter().map(|s| s.name()).collect();
+                    return Err(invalid("menu.sections", format!("unknown section '{}', expected {}", name, known.join(", "))));
+                }
+                if sections.iter().any(|s| s == name) {
+                    return Err(invalid("menu.sections", format!("'{}' is listed twice", name)));
+                }
+                sections.push(name.to_string());
+            }
+            config.menu.sections = sections;
+        }
+        if let Some(url) = get_str(menu, "ip_details_url", "menu.ip_details_url")? {
+            if !url.contains("{ip}") || !(url.starts_with("https://") || url.starts_with("http://")) {
+                return Err(invalid("menu.ip_details_url", "expected an http(s):// URL containing {ip}"));
+            }
+            config.menu.ip_details_url = url.to_string();
+        }
+    }
+
+    if let Some(tray) = get_table(root, "tray")? {
+        warn_unknown_keys(tray, &["prefer_hosts", "exclude_hosts"], "tray.", warnings);
+        if let Some(hosts) = get_str_array(tray, "prefer_hosts", "tray.prefer_hosts")? {
+            config.tray.prefer_hosts = hosts.into_iter().map(str::to_string).collect();
+        }
+        if let Some(hosts) = get_str_array(tray, "exclude_hosts", "tray.exclude_hosts")? {
+            config.tray.exclude_hosts = hosts.into_iter().map(str::to_string).collect();
+        }
+    }
+
+    if let Some(mqtt) = get_table(root, "mqtt")? {
+        warn_unknown_keys(mqtt, &["broker", "username", "password", "topic", "discovery_prefix"], "mqtt.", warnings);
+        config.mqtt.broker = get_str(mqtt, "broker", "mqtt.broker")?
+            .map(|b| b.trim_start_matches("mqtt://").trim_end_matches('/').to_string())
+            .filter(|b| !b.is_empty());
+        config.mqtt.username = get_str(mqtt, "username", "mqtt.username")?.map(str::to_string);
+        config.mqtt.password = get_str(mqtt, "password", "mqtt.password")?.map(str::to_string);
+        for (key, value) in [("topic", &mut config.mqtt.topic), ("discovery_prefix", &mut config.mqtt.discovery_prefix)] {
+            let path = format!("mqtt.{}", key);
+            if let Some(topic) = get_str(mqtt, key, &path)? {
+                if topic.is_empty() || topic.contains(['+', '#']) {
+                    return Err(invalid(&path, "expected a topic without wildcards"));
+                }
+                *value = topic.trim_end_matches('/').to_string();
+            }
+        }
+    }
+
+    if let Some(webhook) = get_table(root, "webhook")? {
+        warn_unknown_keys(webhook, &["url", "retries"], "webhook.", warnings);
+        if let Some(url) = get_str(webhook, "url", "webhook.url")? {
+            if !url.starts_with("https://") && !url.starts_with("http://") && !crate::secrets::is_keyring(url) {
+                return Err(invalid("webhook.url", "expected an http:// or https:// URL, or \"@keyring\""));
+            }
+            config.webhook.url = Some(url.to_string());
+        }
+        if let Some(retries) = webhook.get("retries") {
+            config.webhook.retries = match retries.as_integer() {
+                Some(n @ 0..=10) => n as u32,
+                _ => return Err(invalid("webhook.retries", "expected a number from 0 to 10")),
+            };
+        }
+    }
+
+    if let Some(history) = get_table(root, "history")? {
+        warn_unknown_keys(history, &["enabled", "retention_days"], "history.", warnings);
+        if let Some(enabled) = get_bool(history, "enabled", "history.enabled")? {
+            config.history.enabled = enabled;
+        }
+        if let Some(days) = history.get("retention_days") {
+            config.history.retention_days = match days.as_integer() {
+                Some(n @ 0..=36_500) => n as u32,
+                _ => return Err(invalid("history.retention_days", "expected a number of days (0 keeps everything)")),
+            };
+        }
+    }
+
+    if let Some(privacy) = get_table(root, "privacy")? {
+        warn_unknown_keys(privacy, &["minimize_fingerprint", "no_log", "hide_ip", "dns_leak_check"], "privacy.", warnings);
+        if let Some(minimize) = get_bool(privacy, "minimize_fingerprint", "privacy.minimize_fingerprint")? {
+            config.privacy.minimize_fingerprint = minimize;
+        }
+        if let Some(no_log) = get_bool(privacy, "no_log", "privacy.no_log")? {
+            config.privacy.no_log = no_log;
+        }
+        if let Some(hide) = get_bool(privacy, "hide_ip", "privacy.hide_ip")? {
+            config.privacy.hide_ip = hide;
+        }
+        if let Some(check) = get_bool(privacy, "dns_leak_check", "privacy.dns_leak_check")? {
+            config.privacy.dns_leak_check = check;
+        }
+    }
+
+    if let Some(watchdog) = get_table(root, "watchdog")? {
+        warn_unknown_keys(watchdog, &["expected_country"], "watchdog.", warnings);
+        if let Some(code) = get_str(watchdog, "expected_country", "watchdog.expected_country")? {
+            if !crate::countries::is_valid_code(code) {
+                return Err(invalid("watchdog.expected_country", format!("'{}' is not an ISO 3166 country code", code)));
+            }
+            config.watchdog.expected_country = Some(code.to_ascii_uppercase());
+        }
+    }
+
+    if let Some(shortcuts) = get_table(root, "shortcuts")? {
+        warn_unknown_keys(shortcuts, &["show_status"], "shortcuts.", warnings);
+        if let Some(trigger) = get_str(shortcuts, "show_status", "shortcuts.show_status")? {
+            if trigger.trim().is_empty() {
+                return Err(invalid("shortcuts.show_status", "expected a trigger like \"CTRL+ALT+L\""));
+            }
+            config.shortcuts.show_status = Some(trigger.trim().to_string());
+        }
+    }
+
+    if let Some(item) = root.get("rules") {
+        let Some(rules) = item.as_array_of_tables() else {
+            return Err(invalid("rules", "expected [[rules]] sections"));
+        };
+        for (i, table) in rules.iter().enumerate() {
+            let rule = parse_rule(table, &format!("rules.{}", i), warnings)?;
+            if config.rules.iter().any(|r| r.name == rule.name) {
+                return Err(invalid(&format!("rules.{}.name", i), format!("rule '{}' is defined twice", rule.name)));
+            }
+            config.rules.push(rule);
+        }
+    }
+
+    if let Some(networks) = get_table(root, "networks")? {
+        for (name, item) in networks.iter() {
+            let path = format!("networks.{}", name);
+            let Some(network) = item.as_table() else {
+                return Err(invalid(&path, "expected a [networks.\"<name>\"] section"));
+            };
+            warn_unknown_keys(network, &["provider", "refresh_interval"], &format!("{}.", path), warnings);
+            let overrides = NetworkOverride {
+                provider: get_provider(network, &format!("{}.provider", path))?,
+                refresh_interval: get_refresh_interval(network, &format!("{}.refresh_interval", path))?,
+            };
+            config.networks.insert(name.to_string(), overrides);
+        }
+    }
+
+    Ok(config)
+}
+
+/// One `[[rules]]` table; `path` is "rules.<index>"
+fn parse_rule(table: &Table, path: &str, warnings: &mut Vec<ConfigWarning>) -> Result<Rule, ConfigError> {
+    warn_unknown_keys(
+        table,
+        &["name", "country", "country_not", "asn", "asn_not", "isp", "isp_not", "vpn", "notify", "critical", "run", "badge"],
+        &format!("{}.", path),
+        warnings,
+    );
+    let key = |name: &str| format!("{}.{}", path, name);
+    let name = get_str(table, "name", &key("name"))?.ok_or_else(|| invalid(&key("name"), "every rule needs a name"))?;
+    let mut rule = Rule { name: name.to_string(), ..Default::default() };
+
+    for (field, negate) in [("country", false), ("country_not", true)] {
+        if let Some(code) = get_str(table, field, &key(field))? {
+            if !crate::countries::is_valid_code(code) {
+                return Err(invalid(&key(field), format!("'{}' is not an ISO 3166 country code", code)));
+            }
+            rule.conditions.push(Condition::Country { code: code.to_ascii_uppercase(), negate });
+        }
+    }
+    for (field, negate) in [("asn", false), ("asn_not", true)] {
+        if let Some(value) = table.get(field) {
+            let asn = value
+                .as_integer()
+                .and_then(|n| u32::try_from(n).ok())
+                .ok_or_else(|| invalid(&key(field), "expected an AS number like 3303"))?;
+            rule.conditions.push(Condition::Asn { asn, negate });
+        }
+    }
+    for (field, negate) in [("isp", false), ("isp_not", true)] {
+        if let Some(text) = get_str(table, field, &key(field))? {
+            rule.conditions.push(Condition::Isp { text: text.to_string(), negate });
+        }
+    }
+    if let Some(vpn) = get_bool(table, "vpn", &key("vpn"))? {
+        rule.conditions.push(Condition::Vpn(vpn));
+    }
+    if rule.conditions.is_empty() {
+        return Err(invalid(path, "a rule needs at least one condition (country, asn, isp or vpn)"));
+    }
+
+    let critical = get_bool(table, "critical", &key("critical"))?.unwrap_or(false);
+    if let Some(message) = get_str(table, "notify", &key("notify"))? {
+        rule.actions.push(Action::Notify { message: message.to_string(), critical });
+    }
+    if let Some(script) = get_str(table, "run", &key("run"))? {
+        rule.actions.push(Action::Run(expand_home(script)));
+    }
+    if get_bool(table, "badge", &key("badge"))?.unwrap_or(false) {
+        rule.actions.push(Action::Badge);
+    }
+    if rule.actions.is_empty() {
+        return Err(invalid(path, "a rule needs an action (notify, run or badge)"));
+    }
+    Ok(rule)
+}
+
+/// Watches the config directory with inotify and sends `()` whenever config.toml changes.
+/// Watching the directory (not the file) also catches editors that save via rename.
+pub fn watch(tx: mpsc::Sender<()>) -> Result<(), ConfigError> {
+    let dir = config_dir().ok_or(ConfigError::NoConfigDir)?;
+    fs::create_dir_all(&dir)?;
+
+    // SAFETY: plain syscall, the returned fd is owned by the watcher thread below
+    let fd = unsafe { libc::inotify_init1(libc::IN_CLOEXEC) };
+    if fd < 0 {
+        return Err(std::io::Error::last_os_error().into());
+    }
+
+    let dir_c = CString::new(dir.as_os_str().as_bytes()).map_err(std::io::Error::other)?;
+    let mask = libc::IN_CLOSE_WRITE | libc::IN_MOVED_TO | libc::IN_CREATE | libc::IN_DELETE;
+    // SAFETY: fd is a valid inotify instance and dir_c is NUL-terminated
+    if unsafe { libc::inotify_add_watch(fd, dir_c.as_ptr(), mask) } < 0 {
+        let err = std::io::Error::last_os_error();
+        unsafe { libc::close(fd) };
+        return Err(err.into());
+    }
+
+    std::thread::spawn(move || {
+        let mut buf = [0u8; 4096];
+        loop {
+            // SAFETY: buf is valid for buf.len() bytes
+            let n = unsafe { libc::read(fd, buf.as_mut_ptr().cast(), buf.len()) };
+            if n <= 0 {
+                tracing::warn!("Config watcher stopped: {}", std::io::Error::last_os_error());
+                break;
+            }
+            if event_names(&buf[..n as usize]).iter().any(|name| name == Path::new(CONFIG_FILENAME))
+                && tx.blocking_send(()).is_err()
+            {
+                break;
+            }
+        }
+        unsafe { libc::close(fd) };
+    });
+
+    Ok(())
+}
+
+/// Extracts file names from a buffer of `inotify_event` records
+fn event_names(buf: &[u8]) -> Vec<PathBuf> {
+    let header = std::mem::size_of::<libc::inotify_event>();
+    let mut names = Vec::new();
+    let mut offset = 0;
+
+    while offset + header <= buf.len() {
+        // SAFETY: bounds checked above; read_unaligned handles packing
+        let event: libc::inotify_event =
+            unsafe { std::ptr::read_unaligned(buf[offset..].as_ptr().cast()) };
+        let name_start = offset + header;
+        let name_end = (name_start + event.len as usize).min(buf.len());
+        let raw = &buf[name_start..name_end];
+        let name = raw.split(|b| *b == 0).next().unwrap_or_default();
+        names.push(PathBuf::from(std::ffi::OsStr::from_bytes(name)));
+        offset = name_end;
+    }
+    names
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_defaults_for_empty_file() {
+        assert_eq!(parse("").unwrap(), Config::default());
+    }
+
+    #[test]
+    fn test_parse_full_config() {
+        let config = parse(
+            "refresh_interval = 300\nautostart = false\n\n[geo]\nprovider = \"ipwho.is\"\nfallback = false\n\n\
+             [notifications]\nenabled = false\n\n[schedule]\nblackout = [\"23:00-02:00\"]\n",
+        )
+        .unwrap();
+        assert_eq!(config.refresh_interval, Duration::from_secs(300));
+        assert_eq!(config.autostart, Some(false));
+        assert_eq!((config.geo.provider.as_str(), config.geo.fallback), ("ipwho.is", false));
+        assert!(!config.notifications.enabled);
+        assert!(config.notifications.respect_dnd);
+        assert_eq!(config.schedule.blackout, vec![BlackoutWindow { start: 23 * 60, end: 120 }]);
+        assert_eq!(config.menu.sections, MenuConfig::default().sections);
+        assert_eq!(config.mqtt.broker, None);
+
+        let mqtt = parse("[mqtt]\nbroker = \"mqtt://192.168.1.5:1883/\"\ntopic = \"home/laptop/\"\n").unwrap().mqtt;
+        assert_eq!(mqtt.broker.as_deref(), Some("192.168.1.5:1883"));
+        assert_eq!(mqtt.topic, "home/laptop");
+    }
+
+    #[test]
+    fn test_set_entry() {
+        let content = "refresh_interval = 120\n\n[updates]\nchannel = \"stable\"\n\n[geo]\nfallback = false\n";
+        let updated = set_entry(content, "updates", "channel", "\"beta\"");
+        assert_eq!(parse(&updated).unwrap().updates.channel, Channel::Beta);
+        assert!(updated.contains("fallback = false"));
+
+        let appended = set_entry("refresh_interval = 120\n", "updates", "channel", "\"beta\"");
+        assert_eq!(appended, "refresh_interval = 120\n\n[updates]\nchannel = \"beta\"\n");
+        let toggled = set_entry("[privacy]\nhide_ip = false\n", "privacy", "hide_ip", "true");
+        assert!(parse(&toggled).unwrap().privacy.hide_ip);
+
+        let root = set_root(content, "refresh_interval", "0");
+        assert_eq!(parse(&root).unwrap().refresh_interval, Duration::ZERO);
+        assert_eq!(set_root("[geo]\nfallback = false\n", "refresh_interval", "300"), "refresh_interval = 300\n\n[geo]\nfallback = false\n");
+    }
+
+    #[test]
+    fn test_network_overrides() {
+        let config = parse("[networks.\"Corp LAN\"]\nprovider = \"offline\"\nrefresh_interval = 900\n").unwrap();
+        let corp = ConnectionInfo {
+            uuid: "5f1c".to_string(),
+            name: "Corp LAN".to_string(),
+            kind: "802-3-ethernet".to_string(),
+            open: false,
+        };
+        let home = ConnectionInfo { name: "Home".to_string(), ..corp.clone() };
+        assert_eq!(config.provider_for(Some(&corp)), "offline");
+        assert_eq!(config.provider_for(Some(&home)), "ip-api");
+        assert_eq!(config.provider_for(None), "ip-api");
+        assert_eq!(config.refresh_interval_for(Some(&corp)), Duration::from_secs(900));
+        assert_eq!(config.refresh_interval_for(Some(&home)), config.refresh_interval);
+        assert!(parse("[networks.Home]\nrefresh_interval = 5").is_err());
+        assert!(parse("[networks.Home]\nprovider = \"nope\"").is_err());
+    }
+
+    #[test]
+    fn test_invalid_values() {
+        assert!(matches!(parse("refresh_interval = 5"), Err(ConfigError::Invalid { .. })));
+        assert!(matches!(parse("refresh_interval = \"fast\""), Err(ConfigError::Invalid { .. })));
+        assert!(matches!(parse("[geo]\nprovider = \"nope\""), Err(ConfigError::Invalid { .. })));
+        assert!(matches!(parse("[icons]\nfallback = \"blank\""), Err(ConfigError::Invalid { .. })));
+        assert!(matches!(parse("[geo]\nfields = [\"zip\"]"), Err(ConfigError::Invalid { .. })));
+        assert!(matches!(parse("[menu]\nsections = [\"info\", \"info\"]"), Err(ConfigError::Invalid { .. })));
+        assert!(matches!(parse("[icons]\nflag_url = \"https://cdn.example/vn.png\""), Err(ConfigError::Invalid { .. })));
+        assert!(matches!(parse("[icons]\nmode = \"country\""), Err(ConfigError::Invalid { .. })));
+        assert!(matches!(parse("[icons]\nstyle = \"square\""), Err(ConfigError::Invalid { .. })));
+        assert!(matches!(parse("[icons]\nsymbolic_color = \"white\""), Err(ConfigError::Invalid { .. })));
+        assert!(matches!(parse("[icons]\nstale_after = -1"), Err(ConfigError::Invalid { .. })));
+        assert!(matches!(parse("[icons]\ntheme_dir = 3"), Err(ConfigError::Invalid { .. })));
+        assert!(matches!(parse("[menu]\nip_details_url = \"ipinfo.io/{ip}\""), Err(ConfigError::Invalid { .. })));
+        assert!(matches!(parse("[schedule]\nblackout = [\"1am\"]"), Err(ConfigError::Invalid { .. })));
+        assert!(matches!(parse("[mqtt]\ntopic = \"home/#\""), Err(ConfigError::Invalid { .. })));
+        assert!(matches!(parse("[webhook]\nurl = \"ntfy.sh/home\""), Err(ConfigError::Invalid { .. })));
+        assert!(matches!(parse("[watchdog]\nexpected_country = \"SW\""), Err(ConfigError::Invalid { .. })));
+        assert!(matches!(parse("refresh_interval = "), Err(ConfigError::Parse(_))));
+    }
+
+    #[test]
+    fn test_rules() {
+        let config = parse("[[rules]]\nname = \"leak\"\nisp_not = \"Mullvad\"\nvpn = true\nnotify = \"{isp}\"\nbadge = true\n").unwrap();
+        assert_eq!(config.rules.len(), 1);
+        assert_eq!(config.rules[0].conditions.len(), 2);
+        assert_eq!(config.rules[0].actions[1], Action::Badge);
+
+        let err = validate("[[rules]]\nname = \"a\"\nvpn = true\nbadge = true\n[[rules]]\nname = \"b\"\ncountry = \"XX\"\n").unwrap_err();
+        assert!(matches!(err, ConfigError::Invalid { position: Some(Position { line: 7, .. }), .. }));
+        assert!(matches!(parse("[[rules]]\nname = \"a\"\nvpn = true\n"), Err(ConfigError::Invalid { .. })));
+    }
+
+    #[test]
+    fn test_default_toml_round_trips() {
+        let (config, warnings) = validate(&default_toml()).unwrap();
+        assert_eq!(config, Config::default());
+        assert!(warnings.is_empty());
+    }
+
+    #[test]
+    fn test_validate_positions() {
+        let err = validate("[geo]\nfallback = true\nprovider = \"nope\"\n").unwrap_err();
+        assert!(matches!(err, ConfigError::Invalid { position: Some(Position { line: 3, column: 12 }), .. }));
+        assert!(err.to_string().starts_with("Invalid value for 'geo.provider' at line 3, column 12: unknown provider"));
+
+        let (_, warnings) = validate("refresh_interval = 60\n[geo]\n  provier = \"ipinfo\"\n").unwrap();
+        assert_eq!(warnings.len(), 1);
+        assert_eq!(
+            warnings[0].to_string(),
+            "unknown key 'geo.provier' at line 3, column 3 (did you mean 'provider'?)"
+        );
+    }
+}
@@ -0,0 +1,102 @@
+//! Embedded ISO 3166-1 country dataset with localized names
+//! Country display uses these names instead of whatever language the geo provider returns.
+//! Part of the public library API.
+
+use std::collections::HashMap;
+use std::sync::LazyLock;
+
+/// Tab-separated dataset: code, then one name per language in `LANGUAGES` order
+const DATASET: &str = include_str!("../assets/countries.tsv");
+
+/// Languages available in the dataset (column order)
+pub const LANGUAGES: &[&str] = &["en", "de", "fr", "es"];
+
+/// Country code (uppercase) -> names per language
+static COUNTRIES: LazyLock<HashMap<&'static str, Vec<&'static str>>> = LazyLock::new(|| {
+    DATASET
+        .lines()
+        .filter(|l| !l.is_empty() && !l.starts_with('#'))
+        .filter_map(|l| {
+            let mut fields = l.split('\t');
+            let code = fields.next()?;
+            Some((code, fields.collect()))
+        })
+        .collect()
+});
+
+/// Returns the country name for `code` in `lang`, falling back to English
+pub fn name(code: &str, lang: &str) -> Option<&'static str> {
+    let names = COUNTRIES.get(code.to_uppercase().as_str())?;
+    let index = LANGUAGES.iter().position(|l| *l == lang).unwrap_or(0);
+    names.get(index).or_else(|| names.first()).copied()
+}
+
+/// Returns the country name for `code` in the user's language
+pub fn localized_name(code: &str) -> Option<&'static str> {
+    name(code, &system_language())
+}
+
+/// Checks whether `code` is a known ISO 3166-1 alpha-2 code
+pub fn is_valid_code(code: &str) -> bool {
+    COUNTRIES.contains_key(code.to_uppercase().as_str())
+}
+
+/// All ISO 3166-1 alpha-2 codes in the dataset (uppercase, sorted)
+pub fn codes() -> Vec<&'static str> {
+    let mut codes: Vec<&'static str> = COUNTRIES.keys().copied().collect();
+    codes.sort_unstable();
+    codes
+}
+
+/// Detects the user's language from LC_ALL / LC_MESSAGES / LANG (e.g. "de_DE.UTF-8" -> "de")
+pub fn system_language() -> String {
+    ["LC_ALL", "LC_MESSAGES", "LANG"]
+        .iter()
+        .filter_map(|var| std::env::var(var).ok())
+        .find(|v| !v.is_empty())
+        .map(|v| parse_language(&v))
+        .unwrap_or_else(|| "en".to_string())
+}
+
+/// Extracts the language part of a POSIX locale string
+fn parse_language(locale: &str) -> String {
+    let lang: String = locale
+        .chars()
+        .take_while(|c| c.is_ascii_alphabetic())
+        .collect::<String>()
+        .to_lowercase();
+    match lang.as_str() {
+        "" | "c" | "posix" => "en".to_string(),
+        _ => lang,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_localized_names() {
+        assert_eq!(name("DE", "en"), Some("Germany"));
+        assert_eq!(name("de", "de"), Some("Deutschland"));
+        assert_eq!(name("VN", "fr"), Some("Viêt Nam"));
+        // Unsupported language falls back to English
+        assert_eq!(name("CH", "vi"), Some("Switzerland"));
+        assert_eq!(name("ZZ", "en"), None);
+    }
+
+    #[test]
+    fn test_dataset_is_complete() {
+        assert!(COUNTRIES.len() >= 249);
+        assert!(COUNTRIES.values().all(|names| names.len() == LANGUAGES.len()));
+        assert!(is_valid_code("xk"));
+        assert_eq!(codes().len(), COUNTRIES.len());
+    }
+
+    #[test]
+    fn test_parse_language() {
+        assert_eq!(parse_language("de_DE.UTF-8"), "de");
+        assert_eq!(parse_language("C.UTF-8"), "en");
+        assert_eq!(parse_language("POSIX"), "en");
+    }
+}
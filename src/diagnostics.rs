@@ -0,0 +1,220 @@
+//! Diagnostics module for bug reports
+//! Tracks the active backend and last error, and builds prefilled GitHub issue URLs
+//! and diagnostics bundles with IP addresses and home paths redacted.
+
+use crate::updater::VERSION;
+use crate::usage;
+use std::fs;
+use std::io::Write;
+use std::net::IpAddr;
+use std::path::PathBuf;
+use std::process::{Command, Stdio};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{LazyLock, Mutex};
+
+const ISSUES_URL: &str = "https://github.com/btxTruong/network-monitor/issues/new";
+
+/// Runtime facts worth including in bug reports
+#[derive(Debug, Default)]
+struct DiagnosticState {
+    /// Network change backend in use (e.g. "NetworkManager", "polling")
+    backend: Option<String>,
+    /// Most recent error message (unsanitized)
+    last_error: Option<String>,
+    /// Provider name and body of the last geo response, while capturing
+    raw_response: Option<(String, String)>,
+}
+
+static STATE: LazyLock<Mutex<DiagnosticState>> = LazyLock::new(|| Mutex::new(DiagnosticState::default()));
+/// Debug toggle: keep the raw body of geo responses
+static RAW_CAPTURE: AtomicBool = AtomicBool::new(false);
+
+/// Records which network change backend is active
+pub fn set_backend(name: &str) {
+    if let Ok(mut state) = STATE.lock() {
+        state.backend = Some(name.to_string());
+    }
+}
+
+/// Records the most recent error for bug reports
+pub fn record_error(message: impl Into<String>) {
+    let message = message.into();
+    crate::events::record(crate::events::Kind::Error, message.as_str());
+    if let Ok(mut state) = STATE.lock() {
+        state.last_error = Some(message);
+    }
+}
+
+/// Starts or stops keeping raw geo responses; stopping drops the kept one
+pub fn set_raw_capture(enabled: bool) {
+    RAW_CAPTURE.store(enabled, Ordering::Relaxed);
+    if !enabled {
+        if let Ok(mut state) = STATE.lock() {
+            state.raw_response = None;
+        }
+    }
+}
+
+pub fn raw_capture() -> bool {
+    RAW_CAPTURE.load(Ordering::Relaxed)
+}
+
+/// Keeps `body` as the last raw response from `provider`, if capturing
+pub fn record_raw_response(provider: &str, body: &[u8]) {
+    if !raw_capture() {
+        return;
+    }
+    if let Ok(mut state) = STATE.lock() {
+        state.raw_response = Some((provider.to_string(), String::from_utf8_lossy(body).into_owned()));
+    }
+}
+
+/// Provider and body of the last captured response, JSON pretty-printed when it parses
+pub fn raw_response() -> Option<(String, String)> {
+    let (provider, body) = STATE.lock().ok()?.raw_response.clone()?;
+    let pretty = serde_json::from_str::<serde_json::Value>(&body)
+        .and_then(|v| serde_json::to_string_pretty(&v))
+        .unwrap_or(body);
+    Some((provider, pretty))
+}
+
+/// Copies `text` with wl-copy (Wayland) or xclip (X11)
+pub fn copy_to_clipboard(text: &str) -> std::io::Result<()> {
+    let tools: [(&str, &[&str]); 2] = [("wl-copy", &[]), ("xclip", &["-selection", "clipboard"])];
+    let mut last_error = std::io::Error::other("no clipboard tool");
+    for (program, args) in tools {
+        match Command::new(program).args(args).stdin(Stdio::piped()).spawn() {
+            Ok(mut child) => {
+                if let Some(mut stdin) = child.stdin.take() {
+                    stdin.write_all(text.as_bytes())?;
+                }
+                child.wait()?;
+                return Ok(());
+            }
+            Err(e) => last_error = e,
+        }
+    }
+    Err(last_error)
+}
+
+/// Replaces IP addresses with `<ip>` and the home directory with `~`
+pub fn sanitize(text: &str) -> String {
+    let text = match dirs::home_dir() {
+        Some(home) => text.replace(home.to_string_lossy().as_ref(), "~"),
+        None => text.to_string(),
+    };
+    redact_ips(&text)
+}
+
+/// Replaces IPv4 and IPv6 addresses with `<ip>`
+pub fn redact_ips(text: &str) -> String {
+    let mut out = String::with_capacity(text.len());
+    let mut token = String::new();
+    let flush = |token: &mut String, out: &mut String| {
+        // Sentence punctuation ("... 203.0.113.7: timeout") is not part of the address
+        let candidate = token.trim_end_matches(['.', ':']);
+        let looks_like_ip = candidate.contains('.') || candidate.matches(':').count() >= 2;
+        if looks_like_ip && candidate.parse::<IpAddr>().is_ok() {
+            out.push_str("<ip>");
+            out.push_str(&token[candidate.len()..]);
+        } else {
+            out.push_str(token);
+        }
+        token.clear();
+    };
+
+    for c in text.chars() {
+        if c.is_ascii_hexdigit() || c == '.' || c == ':' {
+            token.push(c);
+        } else {
+            flush(&mut token, &mut out);
+            out.push(c);
+        }
+    }
+    flush(&mut token, &mut out);
+    out
+}
+
+/// Describes the desktop session, e.g. "GNOME (wayland)"
+fn desktop_environment() -> String {
+    let desktop = std::env::var("XDG_CURRENT_DESKTOP").unwrap_or_else(|_| "unknown".to_string());
+    let session = std::env::var("XDG_SESSION_TYPE").unwrap_or_else(|_| "unknown".to_string());
+    format!("{} ({})", desktop, session)
+}
+
+/// Builds the sanitized diagnostics report
+pub fn report() -> String {
+    let (backend, last_error) = STATE
+        .lock()
+        .map(|s| (s.backend.clone(), s.last_error.clone()))
+        .unwrap_or_default();
+
+    let mut lines = vec![
+        format!("- Version: {}", VERSION),
+        format!("- Desktop: {}", desktop_environment()),
+        format!("- Backend: {}", backend.as_deref().unwrap_or("not started")),
+        format!("- Last error: {}", last_error.as_deref().unwrap_or("none")),
+    ];
+    for provider in usage::snapshot() {
+        lines.push(format!("- API calls today ({}): {}", provider.provider, provider.today));
+    }
+    sanitize(&lines.join("\n"))
+}
+
+/// Writes the diagnostics bundle to the cache dir and returns its path
+pub fn write_bundle() -> std::io::Result<PathBuf> {
+    let dir = dirs::cache_dir()
+        .ok_or_else(|| std::io::Error::other("no cache directory"))?
+        .join("network-monitor");
+    fs::create_dir_all(&dir)?;
+
+    let path = dir.join("diagnostics.txt");
+    let mut content = format!("Network Monitor diagnostics\n\n{}\n", report());
+    if let Some((provider, body)) = raw_response() {
+        content.push_str(&format!("\nLast {} response:\n{}\n", provider, sanitize(&body)));
+    }
+    fs::write(&path, content)?;
+    Ok(path)
+}
+
+/// Builds a prefilled "new issue" URL; mentions the bundle so the user can attach it
+pub fn issue_url(bundle: Option<&PathBuf>) -> String {
+    let mut body = format!(
+        "**Describe the problem**\n\n\n**Environment**\n{}\n",
+        report()
+    );
+    if let Some(path) = bundle {
+        body.push_str(&format!(
+            "\n<!-- A diagnostics bundle was saved to {} - drag it here to attach it -->\n",
+            sanitize(&path.to_string_lossy())
+        ));
+    }
+
+    reqwest::Url::parse_with_params(ISSUES_URL, &[("title", "Bug: "), ("body", body.as_str())])
+        .map(|u| u.to_string())
+        .unwrap_or_else(|_| ISSUES_URL.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_sanitize_redacts_ips() {
+        assert_eq!(
+            sanitize("Refresh failed for 203.0.113.7: timeout"),
+            "Refresh failed for <ip>: timeout"
+        );
+        assert_eq!(sanitize("via 2001:db8::1 ok"), "via <ip> ok");
+        // Version strings and times are not addresses
+        assert_eq!(sanitize("v0.1.4 at 12:30"), "v0.1.4 at 12:30");
+    }
+
+    #[test]
+    fn test_issue_url_is_prefilled() {
+        let url = issue_url(None);
+        assert!(url.starts_with(ISSUES_URL));
+        assert!(url.contains("body="));
+        assert!(url.contains(VERSION));
+    }
+}
@@ -0,0 +1,112 @@
+//! DNS leak check
+//! Resolves `whoami.akamai.net` through the system resolver, which answers with the address
+//! of the recursive resolver that asked; that resolver is geo-located and compared with the exit.
+
+use crate::geo::{GeoError, GeoInfo};
+use std::net::IpAddr;
+use std::sync::Mutex;
+use std::time::Duration;
+use thiserror::Error;
+
+/// Akamai answers A queries for this name with the querying resolver's address
+const PROBE_HOST: &str = "whoami.akamai.net";
+const RESOLVE_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Result of the last check, shown in the menu
+static LAST: Mutex<Option<Report>> = Mutex::new(None);
+
+#[derive(Debug, Error)]
+pub enum DnsLeakError {
+    #[error("DNS lookup failed: {0}")]
+    Resolve(#[from] std::io::Error),
+    #[error("DNS lookup timed out")]
+    Timeout,
+    #[error("No answer for {PROBE_HOST}")]
+    NoAnswer,
+    #[error("Resolver lookup failed: {0}")]
+    Geo(#[from] GeoError),
+    #[error("Network namespace error: {0}")]
+    Netns(#[from] crate::netns::NetnsError),
+}
+
+/// The resolver seen by the probe and, if it doesn't match the exit, why
+#[derive(Debug, Clone, PartialEq)]
+pub struct Report {
+    pub resolver: GeoInfo,
+    pub leak: Option<String>,
+}
+
+impl Report {
+    /// Menu and notification text
+    pub fn summary(&self) -> String {
+        match &self.leak {
+            Some(reason) => format!("⚠ DNS leak: {}", reason),
+            None => format!("DNS: no leak (resolver in {}, {})", self.resolver.country_code, self.resolver.isp),
+        }
+    }
+}
+
+/// The last check's report, if one ran
+pub fn last() -> Option<Report> {
+    LAST.lock().ok().and_then(|r| r.clone())
+}
+
+/// Address of the recursive resolver the system resolver forwards to
+async fn resolver_address() -> Result<IpAddr, DnsLeakError> {
+    let answers = tokio::time::timeout(RESOLVE_TIMEOUT, tokio::net::lookup_host((PROBE_HOST, 0)))
+        .await
+        .map_err(|_| DnsLeakError::Timeout)??;
+    answers.map(|a| a.ip()).next().ok_or(DnsLeakError::NoAnswer)
+}
+
+/// Why `resolver` doesn't belong with `exit`: another country, or (when both are known)
+/// another network than the VPN's
+fn compare(resolver: &GeoInfo, exit: &GeoInfo) -> Option<String> {
+    let name = |info: &GeoInfo| match info.asn {
+        Some(asn) => format!("{}, AS{} {}", info.country_code, asn, info.isp),
+        None => format!("{}, {}", info.country_code, info.isp),
+    };
+    let country = !resolver.country_code.eq_ignore_ascii_case(&exit.country_code);
+    let network = matches!((resolver.asn, exit.asn), (Some(a), Some(b)) if a != b);
+    (country || network).then(|| {
+        format!("resolver {} ({}) is not your exit ({})", resolver.query, name(resolver), name(exit))
+    })
+}
+
+/// Runs the check against the current exit location and remembers the result
+pub async fn check(exit: &GeoInfo) -> Result<Report, DnsLeakError> {
+    let address = resolver_address().await?;
+    let resolver = crate::geo::lookup_address(address).await?;
+    let report = Report { leak: compare(&resolver, exit), resolver };
+    if let Ok(mut last) = LAST.lock() {
+        *last = Some(report.clone());
+    }
+    Ok(report)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn geo(code: &str, asn: Option<u32>, isp: &str) -> GeoInfo {
+        GeoInfo {
+            query: "198.51.100.53".to_string(),
+            country_code: code.to_string(),
+            isp: isp.to_string(),
+            asn,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_compare() {
+        let exit = geo("CH", Some(39351), "31173 Services AB");
+        assert_eq!(compare(&geo("CH", Some(39351), "31173 Services AB"), &exit), None);
+        assert_eq!(compare(&geo("CH", None, "Other"), &exit), None);
+        assert_eq!(
+            compare(&geo("VN", Some(7552), "Viettel"), &exit).as_deref(),
+            Some("resolver 198.51.100.53 (VN, AS7552 Viettel) is not your exit (CH, AS39351 31173 Services AB)")
+        );
+        assert!(compare(&geo("CH", Some(3303), "Swisscom"), &exit).is_some());
+    }
+}
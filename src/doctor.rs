@@ -0,0 +1,248 @@
+//! Environment health check (`network-monitor doctor`)
+//! Verifies the pieces the app depends on — buses, NetworkManager, a tray host, the geo
+//! provider, config and autostart — and says what to do about each problem.
+
+use crate::autostart::{autostart_exec, is_autostart_enabled};
+use crate::clock;
+use crate::config;
+use crate::flatpak;
+use crate::geo;
+use crate::hosts;
+use std::path::Path;
+use zbus::Connection;
+
+const NM_BUS_NAME: &str = "org.freedesktop.NetworkManager";
+const SNI_WATCHER: &str = "org.kde.StatusNotifierWatcher";
+const SNI_WATCHER_PATH: &str = "/StatusNotifierWatcher";
+
+/// Result of a single check
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Outcome {
+    Ok,
+    /// Works, but degraded
+    Warn,
+    /// Broken; the app can't do its job
+    Fail,
+}
+
+impl Outcome {
+    /// Mark printed before the check's name
+    pub fn mark(self) -> &'static str {
+        match self {
+            Outcome::Ok => "✓",
+            Outcome::Warn => "!",
+            Outcome::Fail => "✗",
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Check {
+    pub name: &'static str,
+    pub outcome: Outcome,
+    pub detail: String,
+    /// What to do about a warning or failure
+    pub hint: Option<String>,
+}
+
+impl Check {
+    fn ok(name: &'static str, detail: impl Into<String>) -> Self {
+        Self { name, outcome: Outcome::Ok, detail: detail.into(), hint: None }
+    }
+
+    fn problem(name: &'static str, outcome: Outcome, detail: impl Into<String>, hint: impl Into<String>) -> Self {
+        Self { name, outcome, detail: detail.into(), hint: Some(hint.into()) }
+    }
+}
+
+/// Runs all checks in order; the config is loaded (and made current) first so the
+/// geo check uses the configured provider
+pub async fn run() -> Vec<Check> {
+    let mut checks = vec![check_config()];
+    if flatpak::is_sandboxed() {
+        checks.push(match flatpak::missing() {
+            [] => Check::ok("Flatpak", "sandbox grants bus, tray and network access"),
+            missing => Check::problem(
+                "Flatpak",
+                Outcome::Warn,
+                missing.iter().map(|p| p.describe()).collect::<Vec<_>>().join("; "),
+                missing.iter().map(|p| p.fix()).collect::<Vec<_>>().join(" && "),
+            ),
+        });
+    }
+
+    let session = Connection::session().await;
+    checks.push(match &session {
+        Ok(_) => Check::ok("Session bus", "reachable"),
+        Err(e) => Check::problem(
+            "Session bus",
+            Outcome::Fail,
+            e.to_string(),
+            "Run inside a desktop session (DBUS_SESSION_BUS_ADDRESS must be set)",
+        ),
+    });
+    let system = Connection::system().await;
+    checks.push(match &system {
+        Ok(_) => Check::ok("System bus", "reachable"),
+        Err(e) => Check::problem(
+            "System bus",
+            Outcome::Warn,
+            e.to_string(),
+            "Network changes are detected by polling without the system bus",
+        ),
+    });
+
+    checks.push(match &system {
+        Ok(system) if has_owner(system, NM_BUS_NAME).await => Check::ok("NetworkManager", "running"),
+        Ok(_) => Check::problem(
+            "NetworkManager",
+            Outcome::Warn,
+            "not running",
+            "Without NetworkManager changes are detected by polling, and trusted networks and VPN detection are off",
+        ),
+        Err(_) => Check::problem("NetworkManager", Outcome::Warn, "not checked", "Needs the system bus"),
+    });
+
+    checks.push(match &session {
+        Ok(session) => check_tray_host(session).await,
+        Err(_) => Check::problem("Tray host", Outcome::Warn, "not checked", "Needs the session bus"),
+    });
+
+    checks.push(match geo::fetch_location().await {
+        Ok(info) => Check::ok("Geo lookup", format!("{} ({}) via {}", info.query, info.country_code, config::current().geo.provider)),
+        Err(e) => Check::problem(
+            "Geo lookup",
+            Outcome::Fail,
+            e.to_string(),
+            "Check the internet connection, or set [geo] provider to another service",
+        ),
+    });
+
+    // The geo check above measured the skew from the provider's Date header
+    checks.push(match clock::skew() {
+        None => Check::ok("Clock", "in sync with the geo provider"),
+        Some(skew) => Check::problem(
+            "Clock",
+            Outcome::Warn,
+            format!("{} compared to the geo provider", clock::describe(skew)),
+            "Enable NTP: timedatectl set-ntp true (HTTPS and update checks fail with a wrong clock)",
+        ),
+    });
+    checks.push(check_autostart());
+    checks
+}
+
+fn check_config() -> Check {
+    let path = config::config_path().map(|p| p.display().to_string()).unwrap_or_default();
+    match config::load_validated() {
+        Ok((loaded, warnings)) => {
+            config::set_current(loaded);
+            if let Some(first) = warnings.first() {
+                return Check::problem(
+                    "Config",
+                    Outcome::Warn,
+                    format!("{} ({} ignored key(s))", first, warnings.len()),
+                    "Run 'network-monitor config validate' to list them",
+                );
+            }
+            let detail = if Path::new(&path).exists() { path } else { "no config file, using defaults".to_string() };
+            Check::ok("Config", detail)
+        }
+        Err(e) => Check::problem(
+            "Config",
+            Outcome::Fail,
+            e.to_string(),
+            format!("Fix {} (the running app keeps its previous settings)", path),
+        ),
+    }
+}
+
+async fn has_owner(connection: &Connection, name: &str) -> bool {
+    let Ok(dbus) = zbus::fdo::DBusProxy::new(connection).await else { return false };
+    let Ok(name) = zbus::names::BusName::try_from(name) else { return false };
+    dbus.name_has_owner(name).await.unwrap_or(false)
+}
+
+async fn check_tray_host(session: &Connection) -> Check {
+    let hint = "Install a StatusNotifier host (on GNOME: the AppIndicator extension); until then location changes are shown as notifications";
+    if !has_owner(session, SNI_WATCHER).await {
+        return Check::problem("Tray host", Outcome::Warn, "no StatusNotifierWatcher", hint);
+    }
+    let registered = async {
+        let proxy = zbus::Proxy::new(session, SNI_WATCHER, SNI_WATCHER_PATH, SNI_WATCHER).await.ok()?;
+        proxy.get_property::<bool>("IsStatusNotifierHostRegistered").await.ok()
+    }
+    .await;
+    if registered != Some(true) {
+        return Check::problem("Tray host", Outcome::Warn, "watcher running but no host registered", hint);
+    }
+    let running = hosts::list(session).await.unwrap_or_default();
+    let names = running.iter().map(hosts::Host::label).collect::<Vec<_>>().join(", ");
+    if hosts::only_excluded(&running, &config::current().tray.exclude_hosts) {
+        return Check::problem(
+            "Tray host",
+            Outcome::Warn,
+            format!("only excluded hosts running ({})", names),
+            "Remove one from [tray] exclude_hosts or start another tray host; until then location changes are shown as notifications",
+        );
+    }
+    if names.is_empty() {
+        Check::ok("Tray host", "StatusNotifier host registered")
+    } else {
+        Check::ok("Tray host", format!("StatusNotifier host registered ({})", names))
+    }
+}
+
+fn check_autostart() -> Check {
+    autostart_check(is_autostart_enabled(), config::current().autostart, autostart_exec())
+}
+
+/// Judges the autostart entry against the `autostart` pin and the binary it launches
+fn autostart_check(enabled: bool, pinned: Option<bool>, exec: Option<String>) -> Check {
+    if let Some(want) = pinned.filter(|&want| want != enabled) {
+        return Check::problem(
+            "Autostart",
+            Outcome::Warn,
+            format!("config says autostart = {}, but the entry is {}", want, if enabled { "present" } else { "missing" }),
+            "The app applies the config on its next start",
+        );
+    }
+    if !enabled {
+        return Check::ok("Autostart", "off");
+    }
+    match exec {
+        Some(exec) if Path::new(&exec).exists() => Check::ok("Autostart", format!("on ({})", exec)),
+        exec => Check::problem(
+            "Autostart",
+            Outcome::Fail,
+            format!("entry launches {}, which does not exist", exec.as_deref().unwrap_or("nothing")),
+            "Toggle \"Launch on Login\" off and on in the menu to point it at this binary",
+        ),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_autostart_check() {
+        let exists = Some("/bin/sh".to_string());
+        assert_eq!(autostart_check(true, None, exists.clone()).outcome, Outcome::Ok);
+        assert_eq!(autostart_check(false, None, None).outcome, Outcome::Ok);
+        assert_eq!(autostart_check(false, Some(false), None).outcome, Outcome::Ok);
+        // The app applies the pin at startup, so a mismatch is only a warning
+        assert_eq!(autostart_check(false, Some(true), None).outcome, Outcome::Warn);
+        assert_eq!(autostart_check(true, Some(false), exists).outcome, Outcome::Warn);
+
+        let moved = autostart_check(true, None, Some("/nonexistent/network-monitor".to_string()));
+        assert_eq!(moved.outcome, Outcome::Fail);
+        assert!(moved.detail.contains("/nonexistent/network-monitor") && moved.hint.is_some());
+        assert_eq!(autostart_check(true, None, None).detail, "entry launches nothing, which does not exist");
+    }
+
+    #[test]
+    fn test_outcome_marks() {
+        assert_eq!([Outcome::Ok, Outcome::Warn, Outcome::Fail].map(Outcome::mark), ["✓", "!", "✗"]);
+    }
+}
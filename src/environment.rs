@@ -0,0 +1,102 @@
+//! Runtime environment detection (WSL, containers, virtual machines)
+//! Lets the app pick backends that work where NetworkManager or autostart are unavailable.
+
+use std::fs;
+use std::path::Path;
+
+/// Files whose presence marks a container runtime (Docker, Podman, systemd-nspawn)
+const CONTAINER_MARKERS: &[&str] = &["/.dockerenv", "/run/.containerenv", "/run/systemd/container"];
+
+/// DMI vendor/product strings reported by common hypervisors
+const HYPERVISOR_VENDORS: &[&str] = &["qemu", "kvm", "vmware", "virtualbox", "innotek", "xen", "bochs", "parallels"];
+
+/// Where the app is running
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Environment {
+    /// Regular Linux desktop
+    Native,
+    /// Windows Subsystem for Linux (1 or 2)
+    Wsl,
+    /// Docker, Podman or another container runtime
+    Container,
+    /// Virtual machine guest
+    VirtualMachine,
+}
+
+impl Environment {
+    /// Detects the current environment from kernel, filesystem and DMI hints
+    pub fn detect() -> Self {
+        let osrelease = fs::read_to_string("/proc/sys/kernel/osrelease").unwrap_or_default();
+        let in_container = std::env::var_os("container").is_some()
+            || CONTAINER_MARKERS.iter().any(|p| Path::new(p).exists());
+        let cpuinfo = fs::read_to_string("/proc/cpuinfo").unwrap_or_default();
+        let dmi = ["/sys/class/dmi/id/sys_vendor", "/sys/class/dmi/id/product_name"]
+            .iter()
+            .filter_map(|p| fs::read_to_string(p).ok())
+            .collect::<Vec<_>>()
+            .join(" ");
+
+        let env = classify(&osrelease, in_container, &cpuinfo, &dmi);
+        tracing::debug!("Detected environment: {:?}", env);
+        env
+    }
+
+    /// Short label for the details menu
+    pub fn label(&self) -> &'static str {
+        match self {
+            Environment::Native => "Native",
+            Environment::Wsl => "WSL",
+            Environment::Container => "Container",
+            Environment::VirtualMachine => "Virtual machine",
+        }
+    }
+
+    /// Whether NetworkManager can be expected on the system bus
+    pub fn has_network_manager(&self) -> bool {
+        matches!(self, Environment::Native | Environment::VirtualMachine)
+    }
+
+    /// Whether XDG autostart makes sense (no login session inside WSL/containers)
+    pub fn supports_autostart(&self) -> bool {
+        matches!(self, Environment::Native | Environment::VirtualMachine)
+    }
+}
+
+/// Classifies the environment from raw hints (split out for testing)
+fn classify(osrelease: &str, in_container: bool, cpuinfo: &str, dmi: &str) -> Environment {
+    let osrelease = osrelease.to_lowercase();
+    if osrelease.contains("microsoft") || osrelease.contains("wsl") {
+        return Environment::Wsl;
+    }
+    if in_container {
+        return Environment::Container;
+    }
+
+    let dmi = dmi.to_lowercase();
+    let hypervisor_flag = cpuinfo
+        .lines()
+        .filter(|l| l.starts_with("flags"))
+        .any(|l| l.split_whitespace().any(|f| f == "hypervisor"));
+    if hypervisor_flag || HYPERVISOR_VENDORS.iter().any(|v| dmi.contains(v)) {
+        return Environment::VirtualMachine;
+    }
+
+    Environment::Native
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_classify() {
+        assert_eq!(classify("5.15.90.1-microsoft-standard-WSL2", false, "", ""), Environment::Wsl);
+        assert_eq!(classify("6.8.0-45-generic", true, "", ""), Environment::Container);
+        assert_eq!(
+            classify("6.8.0-45-generic", false, "flags\t\t: fpu vme hypervisor", ""),
+            Environment::VirtualMachine
+        );
+        assert_eq!(classify("6.8.0-45-generic", false, "", "QEMU Standard PC"), Environment::VirtualMachine);
+        assert_eq!(classify("6.8.0-45-generic", false, "flags\t\t: fpu vme", "LENOVO"), Environment::Native);
+    }
+}
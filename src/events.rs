@@ -0,0 +1,164 @@
+//! Structured event log
+//! Refreshes, location changes, network events, menu commands and errors are appended to
+//! ~/.local/share/network-monitor/events.jsonl for `network-monitor events`.
+
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::io::Write;
+use std::path::PathBuf;
+use std::sync::Mutex;
+use std::time::Duration;
+
+const EVENTS_FILENAME: &str = "events.jsonl";
+/// The file is cut back to this many events once it grows a tenth past it
+const MAX_EVENTS: usize = 5_000;
+
+/// Events in the file, counted on the first append
+static COUNT: Mutex<Option<usize>> = Mutex::new(None);
+
+/// What kind of thing happened
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Kind {
+    /// A successful location lookup
+    Refresh,
+    /// Country, ISP or public IP changed
+    Location,
+    /// Connected or disconnected
+    Network,
+    /// A tray menu or shortcut command
+    Command,
+    Error,
+}
+
+impl Kind {
+    fn name(&self) -> &'static str {
+        match self {
+            Kind::Refresh => "refresh",
+            Kind::Location => "location",
+            Kind::Network => "network",
+            Kind::Command => "command",
+            Kind::Error => "error",
+        }
+    }
+}
+
+/// One event, timestamp in Unix seconds
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Event {
+    pub timestamp: u64,
+    pub kind: Kind,
+    pub message: String,
+}
+
+fn events_file() -> Option<PathBuf> {
+    dirs::data_dir().map(|p| p.join("network-monitor").join(EVENTS_FILENAME))
+}
+
+/// One JSON object per line; lines that don't parse (e.g. a torn write) are skipped
+fn parse(content: &str) -> Vec<Event> {
+    content
+        .lines()
+        .filter_map(|line| serde_json::from_str(line).ok())
+        .collect()
+}
+
+fn serialize(event: &Event) -> String {
+    serde_json::to_string(event).unwrap_or_default() + "\n"
+}
+
+/// Appends an event; nothing is written while `[privacy] no_log` is on, and IPs are
+/// redacted while `hide_ip` is
+pub fn record(kind: Kind, message: impl Into<String>) {
+    if crate::privacy::no_log() {
+        return;
+    }
+    let Some(path) = events_file() else { return };
+    let mut message = message.into();
+    if crate::privacy::hide_ip() {
+        message = crate::diagnostics::redact_ips(&message);
+    }
+    let event = Event { timestamp: crate::history::now(), kind, message };
+    let Ok(mut count) = COUNT.lock() else { return };
+    let count = count.get_or_insert_with(|| fs::read_to_string(&path).map(|c| c.lines().count()).unwrap_or(0));
+
+    if let Some(parent) = path.parent() {
+        let _ = fs::create_dir_all(parent);
+    }
+    if *count >= MAX_EVENTS + MAX_EVENTS / 10 {
+        let mut events = fs::read_to_string(&path).map(|c| parse(&c)).unwrap_or_default();
+        events.push(event);
+        events.drain(..events.len().saturating_sub(MAX_EVENTS));
+        *count = events.len();
+        let content: String = events.iter().map(serialize).collect();
+        if let Err(e) = fs::write(&path, content) {
+            tracing::warn!("Failed to save event log: {}", e);
+        }
+        return;
+    }
+    let result = fs::OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(&path)
+        .and_then(|mut f| f.write_all(serialize(&event).as_bytes()));
+    match result {
+        Ok(()) => *count += 1,
+        Err(e) => tracing::warn!("Failed to append to event log: {}", e),
+    }
+}
+
+/// Events filtered to those at most `since` old, oldest first
+fn filter(events: Vec<Event>, since: Option<Duration>, now: u64) -> Vec<Event> {
+    let cutoff = since.map_or(0, |since| now.saturating_sub(since.as_secs()));
+    events.into_iter().filter(|e| e.timestamp >= cutoff).collect()
+}
+
+/// Recorded events at most `since` old, oldest first
+pub fn select(since: Option<Duration>) -> Vec<Event> {
+    let events = events_file()
+        .and_then(|p| fs::read_to_string(p).ok())
+        .map(|c| parse(&c))
+        .unwrap_or_default();
+    filter(events, since, crate::history::now())
+}
+
+/// Aligned columns in local time, or a JSON array
+pub fn render(events: &[Event], json: bool) -> String {
+    if json {
+        return serde_json::to_string_pretty(events).unwrap_or_default() + "\n";
+    }
+    let mut out = format!("{:<16}  {:<9} {}\n", "TIME", "KIND", "MESSAGE");
+    for e in events {
+        let time = std::time::UNIX_EPOCH + Duration::from_secs(e.timestamp);
+        out.push_str(&format!("{:<16}  {:<9} {}\n", crate::schedule::format_local_date(time), e.kind.name(), e.message));
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_and_filter() {
+        let content = "{\"timestamp\":100,\"kind\":\"refresh\",\"message\":\"VN 203.0.113.7\"}\n\
+                       {\"timestamp\":3600,\"kind\":\"command\",\"message\":\"Refresh\"}\n\
+                       {\"timestamp\":3700,\"kind\":\"err";
+        let events = parse(content);
+        assert_eq!(events.len(), 2);
+        assert_eq!(events[0].kind, Kind::Refresh);
+
+        let recent = filter(events.clone(), Some(Duration::from_secs(60 * 60)), 4000);
+        assert_eq!(recent.len(), 1);
+        assert_eq!(recent[0].message, "Refresh");
+        assert_eq!(filter(events, None, 4000).len(), 2);
+    }
+
+    #[test]
+    fn test_json_round_trip() {
+        let events = vec![Event { timestamp: 1, kind: Kind::Network, message: "connected".into() }];
+        let json = render(&events, true);
+        assert!(json.contains("\"kind\": \"network\""));
+        assert_eq!(serde_json::from_str::<Vec<Event>>(&json).unwrap(), events);
+    }
+}
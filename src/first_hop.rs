@@ -0,0 +1,144 @@
+//! First external hop diagnostic
+//! Traces the route to an anycast target and geo-locates the first public hop, which shows
+//! traffic leaving the country before it reaches an egress IP that looks local.
+
+use crate::geo::{GeoError, GeoInfo};
+use std::net::{IpAddr, Ipv4Addr};
+use std::process::Stdio;
+use std::sync::Mutex;
+use std::time::Duration;
+use thiserror::Error;
+use tokio::io::{AsyncBufReadExt, BufReader};
+use tokio::process::Command;
+
+/// Traced towards the same anycast target as the MTU diagnostic
+const TARGET: &str = crate::mtu::DEFAULT_TARGET;
+const MAX_HOPS: &str = "15";
+/// Upper bound for the whole trace; silent hops each cost a probe timeout
+const TRACE_TIMEOUT: Duration = Duration::from_secs(40);
+
+/// Result of the last check, shown in the menu
+static LAST: Mutex<Option<Report>> = Mutex::new(None);
+
+#[derive(Debug, Error)]
+pub enum FirstHopError {
+    #[error("Failed to run traceroute or tracepath: {0}")]
+    Spawn(#[from] std::io::Error),
+    #[error("Trace timed out")]
+    Timeout,
+    #[error("No public hop answered")]
+    NoPublicHop,
+    #[error("Hop lookup failed: {0}")]
+    Geo(#[from] GeoError),
+}
+
+/// The first public hop and whether it is in another country than the exit
+#[derive(Debug, Clone, PartialEq)]
+pub struct Report {
+    /// Hop number on the path
+    pub hop: u32,
+    pub router: GeoInfo,
+    /// Exit country, when the hop is elsewhere
+    pub exit_country: Option<String>,
+}
+
+impl Report {
+    /// Menu and notification text
+    pub fn summary(&self) -> String {
+        let address = if crate::privacy::hide_ip() { String::new() } else { format!(" {}", self.router.query) };
+        let place = format!("{} ({})", self.router.country_name(), self.router.country_code);
+        match &self.exit_country {
+            Some(exit) => format!("⚠ First external hop{} (#{}) is in {}, not {}", address, self.hop, place, exit),
+            None => format!("First external hop{} (#{}): {}, {}", address, self.hop, place, self.router.isp),
+        }
+    }
+}
+
+/// The last check's report, if one ran
+pub fn last() -> Option<Report> {
+    LAST.lock().ok().and_then(|r| r.clone())
+}
+
+/// Addresses that don't say where traffic goes: RFC 1918, CGNAT, loopback, link-local
+fn is_public(ip: Ipv4Addr) -> bool {
+    let [a, b, ..] = ip.octets();
+    let shared = a == 100 && (64..128).contains(&b);
+    !(ip.is_private() || ip.is_loopback() || ip.is_link_local() || ip.is_unspecified() || shared)
+}
+
+/// Hop number and address from a traceroute ("  3  203.0.113.1  8.1 ms") or
+/// tracepath (" 3:  203.0.113.1  8.1ms") line
+fn parse_hop(line: &str) -> Option<(u32, Ipv4Addr)> {
+    let mut fields = line.split_whitespace();
+    let hop = fields.next()?.trim_end_matches([':', '?']).parse().ok()?;
+    let ip = fields.find_map(|f| f.parse().ok())?;
+    Some((hop, ip))
+}
+
+/// Runs `program` and returns the first public hop, stopping the trace there
+async fn trace(program: &str, args: &[&str]) -> Result<Option<(u32, Ipv4Addr)>, FirstHopError> {
+    let mut child = Command::new(program)
+        .args(args)
+        .stdout(Stdio::piped())
+        .stderr(Stdio::null())
+        .kill_on_drop(true)
+        .spawn()?;
+    let Some(stdout) = child.stdout.take() else { return Ok(None) };
+    let mut lines = BufReader::new(stdout).lines();
+    while let Some(line) = lines.next_line().await? {
+        if let Some((hop, ip)) = parse_hop(&line).filter(|(_, ip)| is_public(*ip)) {
+            return Ok(Some((hop, ip)));
+        }
+    }
+    Ok(None)
+}
+
+async fn first_public_hop() -> Result<(u32, Ipv4Addr), FirstHopError> {
+    let traced = match trace("traceroute", &["-4", "-n", "-q", "1", "-w", "2", "-m", MAX_HOPS, TARGET]).await {
+        // tracepath ships with iputils, which is there wherever ping is
+        Err(FirstHopError::Spawn(e)) if e.kind() == std::io::ErrorKind::NotFound => {
+            trace("tracepath", &["-4", "-n", "-m", MAX_HOPS, TARGET]).await?
+        }
+        result => result?,
+    };
+    traced.ok_or(FirstHopError::NoPublicHop)
+}
+
+/// Traces the path, locates the first public hop against `exit` and remembers the result
+pub async fn check(exit: &GeoInfo) -> Result<Report, FirstHopError> {
+    let (hop, ip) = tokio::time::timeout(TRACE_TIMEOUT, first_public_hop())
+        .await
+        .map_err(|_| FirstHopError::Timeout)??;
+    let router = crate::geo::lookup_address(IpAddr::V4(ip)).await?;
+    let exit_country = (!router.country_code.eq_ignore_ascii_case(&exit.country_code))
+        .then(|| format!("{} ({})", exit.country_name(), exit.country_code));
+    let report = Report { hop, router, exit_country };
+    tracing::info!("{}", report.summary());
+    if let Ok(mut last) = LAST.lock() {
+        *last = Some(report.clone());
+    }
+    Ok(report)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_hop() {
+        assert_eq!(parse_hop(" 3  203.0.113.1  8.104 ms"), Some((3, Ipv4Addr::new(203, 0, 113, 1))));
+        assert_eq!(parse_hop(" 2:  100.64.0.1                                            4.312ms"), Some((2, Ipv4Addr::new(100, 64, 0, 1))));
+        assert_eq!(parse_hop(" 4  *"), None);
+        assert_eq!(parse_hop(" 1?: [LOCALHOST]                      pmtu 1500"), None);
+        assert_eq!(parse_hop("traceroute to 1.1.1.1 (1.1.1.1), 15 hops max"), None);
+    }
+
+    #[test]
+    fn test_is_public() {
+        assert!(!is_public(Ipv4Addr::new(192, 168, 1, 1)));
+        assert!(!is_public(Ipv4Addr::new(100, 72, 5, 1)));
+        assert!(!is_public(Ipv4Addr::new(10, 8, 0, 1)));
+        assert!(is_public(Ipv4Addr::new(100, 128, 0, 1)));
+        assert!(is_public(Ipv4Addr::new(203, 0, 113, 1)));
+    }
+}
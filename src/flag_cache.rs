@@ -0,0 +1,149 @@
+//! Runtime download of flags that are not embedded
+//! Minimal builds embed only a subset of flags; missing ones are fetched once from the
+//! configured CDN (`icons.flag_url`) and kept as PNG in $XDG_CACHE_HOME/network-monitor/flags.
+
+use crate::icons::{FlagIcon, ICON_SIZE};
+use std::collections::HashMap;
+use std::fs;
+use std::path::PathBuf;
+use std::sync::{LazyLock, Mutex};
+use std::time::{Duration, Instant};
+use thiserror::Error;
+
+const FETCH_TIMEOUT: Duration = Duration::from_secs(10);
+/// A failed download is not retried for this long (lookups run every minute)
+const RETRY_AFTER: Duration = Duration::from_secs(3600);
+const PNG_SIGNATURE: &[u8] = b"\x89PNG\r\n\x1a\n";
+
+/// Codes whose download failed, and when
+static FAILED: LazyLock<Mutex<HashMap<String, Instant>>> = LazyLock::new(|| Mutex::new(HashMap::new()));
+
+/// Flags loaded from the cache dir; bounded by the number of countries
+static LOADED: LazyLock<Mutex<HashMap<String, FlagIcon>>> = LazyLock::new(|| Mutex::new(HashMap::new()));
+
+/// Errors while downloading a flag
+#[derive(Debug, Error)]
+pub enum FlagCacheError {
+    #[error("Request failed: {0}")]
+    Request(#[from] reqwest::Error),
+    #[error("IO error: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("Failed to render flag: {0}")]
+    Render(String),
+    #[error("Invalid country code '{0}'")]
+    InvalidCode(String),
+    #[error("Could not determine cache directory")]
+    NoCacheDir,
+}
+
+fn cache_dir() -> Option<PathBuf> {
+    dirs::cache_dir().map(|p| p.join("network-monitor").join("flags"))
+}
+
+/// Only plain alpha-2 codes are fetched; the code comes from a remote API and ends up in a path
+fn validate(code: &str) -> Result<String, FlagCacheError> {
+    let code = code.to_lowercase();
+    if code.len() == 2 && code.bytes().all(|b| b.is_ascii_lowercase()) {
+        Ok(code)
+    } else {
+        Err(FlagCacheError::InvalidCode(code))
+    }
+}
+
+/// Gets a previously downloaded flag, decoded once
+pub fn cached(code: &str) -> Option<FlagIcon> {
+    let code = validate(code).ok()?;
+    let mut loaded = LOADED.lock().ok()?;
+    if let Some(icon) = loaded.get(&code) {
+        return Some(icon.clone());
+    }
+    let data = fs::read(cache_dir()?.join(format!("{}.png", code))).ok()?;
+    let icon = FlagIcon::from_rgba(&image::load_from_memory(&data).ok()?.to_rgba8());
+    loaded.insert(code, icon.clone());
+    Some(icon)
+}
+
+/// Makes sure a flag is available for `code`, downloading it if neither embedded nor cached
+pub async fn ensure(code: &str) {
+    if crate::icons::get_flag(code).is_some() {
+        return;
+    }
+    let recently_failed = FAILED
+        .lock()
+        .ok()
+        .and_then(|f| f.get(&code.to_lowercase()).copied())
+        .is_some_and(|t| t.elapsed() < RETRY_AFTER);
+    if recently_failed {
+        return;
+    }
+
+    match fetch(code).await {
+        Ok(path) => tracing::info!("Downloaded flag for {} to {}", code, path.display()),
+        Err(e) => {
+            tracing::warn!("Failed to download flag for {}: {}", code, e);
+            if let Ok(mut failed) = FAILED.lock() {
+                failed.insert(code.to_lowercase(), Instant::now());
+            }
+        }
+    }
+}
+
+async fn fetch(code: &str) -> Result<PathBuf, FlagCacheError> {
+    let code = validate(code)?;
+    let dir = cache_dir().ok_or(FlagCacheError::NoCacheDir)?;
+
+    let url = crate::config::current().icons.flag_url.replace("{code}", &code);
+    let data = reqwest::Client::new()
+        .get(url)
+        .header("User-Agent", "network-monitor")
+        .timeout(FETCH_TIMEOUT)
+        .send()
+        .await?
+        .error_for_status()?
+        .bytes()
+        .await?;
+    let png = if data.starts_with(PNG_SIGNATURE) {
+        image::load_from_memory(&data).map_err(|e| FlagCacheError::Render(e.to_string()))?;
+        data.to_vec()
+    } else {
+        render(&data)?
+    };
+
+    fs::create_dir_all(&dir)?;
+    let path = dir.join(format!("{}.png", code));
+    fs::write(&path, png)?;
+    Ok(path)
+}
+
+/// Renders an SVG flag to an ICON_SIZE PNG, like build.rs does for embedded flags
+pub(crate) fn render(svg: &[u8]) -> Result<Vec<u8>, FlagCacheError> {
+    let render_error = |e: &dyn std::fmt::Display| FlagCacheError::Render(e.to_string());
+    let tree = resvg::usvg::Tree::from_data(svg, &resvg::usvg::Options::default()).map_err(|e| render_error(&e))?;
+
+    let size = tree.size();
+    let scale = ICON_SIZE as f32 / size.width().max(size.height());
+    let mut pixmap = resvg::tiny_skia::Pixmap::new((size.width() * scale) as u32, (size.height() * scale) as u32)
+        .ok_or_else(|| render_error(&"empty image"))?;
+    resvg::render(&tree, resvg::tiny_skia::Transform::from_scale(scale, scale), &mut pixmap.as_mut());
+    pixmap.encode_png().map_err(|e| render_error(&e))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_validate_code() {
+        assert_eq!(validate("VN").unwrap(), "vn");
+        assert!(validate("../x").is_err());
+        assert!(validate("gb-sct").is_err());
+    }
+
+    #[test]
+    fn test_render() {
+        let svg = br##"<svg xmlns="http://www.w3.org/2000/svg" viewBox="0 0 512 512"><circle cx="256" cy="256" r="256" fill="#d80027"/></svg>"##;
+        let png = render(svg).unwrap();
+        assert!(png.starts_with(b"\x89PNG"));
+        assert!(render(b"not svg").is_err());
+    }
+}
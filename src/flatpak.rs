@@ -0,0 +1,132 @@
+//! Flatpak sandbox permission check
+//! Reads /.flatpak-info to see whether the sandbox lets us reach NetworkManager, the tray
+//! host and the network, and says which `flatpak override` fixes what is missing.
+
+use std::fs;
+use std::sync::LazyLock;
+
+const INFO_FILE: &str = "/.flatpak-info";
+const NM_BUS_NAME: &str = "org.freedesktop.NetworkManager";
+const SNI_WATCHER: &str = "org.kde.StatusNotifierWatcher";
+
+static MISSING: LazyLock<Vec<Permission>> = LazyLock::new(|| match fs::read_to_string(INFO_FILE) {
+    Ok(info) => missing_permissions(&info),
+    Err(_) => Vec::new(),
+});
+
+/// A sandbox permission the app needs
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Permission {
+    /// Talk to NetworkManager on the system bus
+    NetworkManager { app_id: String },
+    /// Talk to the StatusNotifierWatcher on the session bus
+    Tray { app_id: String },
+    /// Network access at all
+    Network { app_id: String },
+}
+
+impl Permission {
+    /// What breaks without it
+    pub fn describe(&self) -> &'static str {
+        match self {
+            Permission::NetworkManager { .. } => "no NetworkManager access: changes are polled, VPN detection is off",
+            Permission::Tray { .. } => "no tray access: no icon, notifications only",
+            Permission::Network { .. } => "no network access: lookups fail",
+        }
+    }
+
+    /// The command that grants it
+    pub fn fix(&self) -> String {
+        match self {
+            Permission::NetworkManager { app_id } => {
+                format!("flatpak override --user --system-talk-name={} {}", NM_BUS_NAME, app_id)
+            }
+            Permission::Tray { app_id } => format!("flatpak override --user --talk-name={} {}", SNI_WATCHER, app_id),
+            Permission::Network { app_id } => format!("flatpak override --user --share=network {}", app_id),
+        }
+    }
+}
+
+/// Whether we run inside a Flatpak sandbox
+pub fn is_sandboxed() -> bool {
+    std::path::Path::new(INFO_FILE).exists()
+}
+
+/// Permissions the sandbox lacks; empty outside Flatpak
+pub fn missing() -> &'static [Permission] {
+    &MISSING
+}
+
+/// `key=value` lines of one `[group]` of the keyfile
+fn group<'a>(info: &'a str, name: &str) -> Vec<(&'a str, &'a str)> {
+    let header = format!("[{}]", name);
+    info.lines()
+        .map(str::trim)
+        .skip_while(|line| *line != header)
+        .skip(1)
+        .take_while(|line| !line.starts_with('['))
+        .filter_map(|line| line.split_once('='))
+        .collect()
+}
+
+/// Whether a bus policy group lets us call `name`; `org.kde.*` style wildcards count
+fn can_talk(policy: &[(&str, &str)], name: &str) -> bool {
+    policy.iter().any(|(pattern, level)| {
+        let matches = match pattern.strip_suffix(".*") {
+            Some(prefix) => name.starts_with(prefix) && name[prefix.len()..].starts_with('.'),
+            None => *pattern == name,
+        };
+        matches && matches!(*level, "talk" | "own")
+    })
+}
+
+fn missing_permissions(info: &str) -> Vec<Permission> {
+    let app_id = group(info, "Application")
+        .into_iter()
+        .find(|(key, _)| *key == "name")
+        .map(|(_, value)| value.to_string())
+        .unwrap_or_else(|| "<app-id>".to_string());
+    let context = group(info, "Context");
+    let list = |key: &str| -> Vec<&str> {
+        context
+            .iter()
+            .find(|(k, _)| *k == key)
+            .map(|(_, v)| v.split(';').filter(|s| !s.is_empty()).collect())
+            .unwrap_or_default()
+    };
+    let sockets = list("sockets");
+    let shared = list("shared");
+
+    let mut missing = Vec::new();
+    if !sockets.contains(&"system-bus") && !can_talk(&group(info, "System Bus Policy"), NM_BUS_NAME) {
+        missing.push(Permission::NetworkManager { app_id: app_id.clone() });
+    }
+    if !sockets.contains(&"session-bus") && !can_talk(&group(info, "Session Bus Policy"), SNI_WATCHER) {
+        missing.push(Permission::Tray { app_id: app_id.clone() });
+    }
+    if !shared.contains(&"network") {
+        missing.push(Permission::Network { app_id });
+    }
+    missing
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_missing_permissions() {
+        let info = "[Application]\nname=org.btxtruong.NetworkMonitor\n\n[Context]\nshared=network;ipc;\nsockets=wayland;\n\n\
+                    [Session Bus Policy]\norg.kde.*=talk\n\n[System Bus Policy]\norg.freedesktop.NetworkManager=see\n";
+        let missing = missing_permissions(info);
+        assert_eq!(missing, vec![Permission::NetworkManager { app_id: "org.btxtruong.NetworkMonitor".into() }]);
+        assert_eq!(
+            missing[0].fix(),
+            "flatpak override --user --system-talk-name=org.freedesktop.NetworkManager org.btxtruong.NetworkMonitor"
+        );
+
+        let locked = missing_permissions("[Application]\nname=app\n");
+        assert_eq!(locked.len(), 3);
+        assert!(missing_permissions("[Context]\nshared=network;\nsockets=system-bus;session-bus;\n").is_empty());
+    }
+}
@@ -1,15 +1,53 @@
-//! Geo-IP location module using ip-api.com
-//! Fetches public IP address and geographic location information.
+//! Geo-IP location module with a fallback chain of providers
+//! Fetches public IP address and geographic location information from the configured
+//! provider (or a local MMDB file), falling back to the others when it fails or rate-limits.
 
+use crate::mmdb::{self, Value};
 use serde::Deserialize;
+use std::collections::HashMap;
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, LazyLock, Mutex};
+use std::time::{Duration, Instant, SystemTime};
 use thiserror::Error;
 
-// Note: ip-api.com free tier only supports HTTP. HTTPS requires paid API key.
-// This is acceptable as we only fetch public IP metadata (no sensitive data).
-const API_URL: &str = "http://ip-api.com/json/?fields=status,message,country,countryCode,city,isp,query";
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(10);
+/// How long a provider that answered 429 is skipped
+const RATE_LIMIT_BACKOFF: Duration = Duration::from_secs(60);
+/// Config name for the local MMDB lookup
+pub const OFFLINE_PROVIDER: &str = "offline";
+/// Plain-text "what is my IP" endpoints used by the offline mode
+const IP_ENDPOINTS: &[&str] = &["https://api.ipify.org", "https://ifconfig.co/ip"];
+/// Short timeout: a missing address family should not delay the lookup
+const FAMILY_TIMEOUT: Duration = Duration::from_secs(3);
+
+/// System language -> `lang` code, for the languages ip-api and ipwho.is localize names into
+const PROVIDER_LANGUAGES: &[(&str, &str)] = &[
+    ("de", "de"),
+    ("es", "es"),
+    ("fr", "fr"),
+    ("ja", "ja"),
+    ("pt", "pt-BR"),
+    ("ru", "ru"),
+    ("zh", "zh-CN"),
+];
+
+/// Fields besides the IP and country that `[geo] fields` can leave out of requests
+pub const OPTIONAL_FIELDS: &[&str] = &["city", "isp", "asn", "org", "location", "mobile", "hosting", "proxy"];
+
+/// All providers, in default fallback order
+pub static PROVIDERS: &[&dyn GeoProvider] = &[&IpApi, &IpInfo, &IpWhoIs, &IfconfigCo];
+
+/// Providers that rate-limited us, and when they may be used again
+static RATE_LIMITED: LazyLock<Mutex<HashMap<&'static str, Instant>>> =
+    LazyLock::new(|| Mutex::new(HashMap::new()));
+
+/// Loaded MMDB file, keyed by path and modification time so updates are picked up
+type CachedDatabase = (PathBuf, Option<SystemTime>, Arc<mmdb::Reader>);
+static DATABASE: LazyLock<Mutex<Option<CachedDatabase>>> = LazyLock::new(|| Mutex::new(None));
 
 /// Geographic location information from IP lookup
-#[derive(Debug, Clone, Deserialize)]
+#[derive(Debug, Clone, Default, PartialEq, Deserialize)]
 pub struct GeoInfo {
     /// Public IP address
     pub query: String,
@@ -22,19 +60,73 @@ pub struct GeoInfo {
     pub city: String,
     /// Internet Service Provider name
     pub isp: String,
+    /// Autonomous system number of the address, when the provider reports it
+    #[serde(default)]
+    pub asn: Option<u32>,
+    /// Name of the autonomous system (e.g. "SWISSCOM"), when the provider reports it
+    #[serde(default)]
+    pub as_name: String,
+    /// Organization the address is registered to, often the VPN or hosting customer
+    #[serde(default)]
+    pub org: String,
+    /// Approximate coordinates (latitude, longitude) of the address
+    #[serde(skip)]
+    pub coordinates: Option<(f64, f64)>,
+    /// IP belongs to a mobile carrier
+    #[serde(default)]
+    pub mobile: bool,
+    /// IP belongs to a hosting provider / datacenter
+    #[serde(default)]
+    pub hosting: bool,
+    /// IP is a known proxy, VPN or Tor exit (ip-api only)
+    #[serde(default)]
+    pub proxy: bool,
+    /// IP is a known Tor exit node
+    #[serde(default)]
+    pub tor: bool,
+    /// Reverse DNS (PTR) name of the public IP
+    #[serde(default)]
+    pub hostname: Option<String>,
+    /// Public IPv4 address, if the connection has one
+    #[serde(default)]
+    pub ipv4: Option<String>,
+    /// Public IPv6 address, if the connection has one
+    #[serde(default)]
+    pub ipv6: Option<String>,
+    /// System language the provider localized `country` and `city` into, if it did
+    #[serde(skip)]
+    pub language: Option<String>,
+    /// When this location was last seen, if it comes from the per-network cache
+    /// instead of a lookup
+    #[serde(skip)]
+    pub cached_at: Option<SystemTime>,
 }
 
-/// API response wrapper to handle success/error status
-#[derive(Debug, Deserialize)]
-struct ApiResponse {
-    status: String,
-    message: Option<String>,
-    query: Option<String>,
-    country: Option<String>,
-    #[serde(rename = "countryCode")]
-    country_code: Option<String>,
-    city: Option<String>,
-    isp: Option<String>,
+/// Whether going from `old` to `new` is a move worth telling about: another country, ISP or
+/// IP, but not just a different city guess for the same address
+pub fn is_change(old: &GeoInfo, new: &GeoInfo) -> bool {
+    old.country_code != new.country_code || old.isp != new.isp || old.query != new.query
+}
+
+impl GeoInfo {
+    /// Country name in the user's language: the provider's when it localized the response,
+    /// else from the embedded ISO dataset, else the provider-supplied name
+    pub fn country_name(&self) -> String {
+        if self.language.is_some() {
+            return self.country.clone();
+        }
+        crate::countries::localized_name(&self.country_code)
+            .map(str::to_string)
+            .unwrap_or_else(|| self.country.clone())
+    }
+
+    /// Address lines for display: "IPv4: …" and "IPv6: …" on dual-stack, else "IP: …"
+    pub fn address_lines(&self) -> Vec<String> {
+        match (&self.ipv4, &self.ipv6) {
+            (Some(v4), Some(v6)) => vec![format!("IPv4: {}", v4), format!("IPv6: {}", v6)],
+            _ => vec![format!("IP: {}", self.query)],
+        }
+    }
 }
 
 /// Errors that can occur during geo-IP lookup
@@ -46,35 +138,611 @@ pub enum GeoError {
     ApiError(String),
     #[error("Invalid response: missing fields")]
     InvalidResponse,
+    #[error("{0} rate limit reached")]
+    RateLimited(&'static str),
+    #[error("Offline database error: {0}")]
+    Mmdb(#[from] mmdb::MmdbError),
+    #[error("{0} not found in offline database")]
+    NotInDatabase(IpAddr),
+    #[error("Network namespace error: {0}")]
+    Netns(#[from] crate::netns::NetnsError),
+}
+
+/// A geo-IP web service
+pub trait GeoProvider: Send + Sync {
+    /// Name used in config.toml
+    fn name(&self) -> &'static str;
+    /// Host name, used for API usage accounting
+    fn host(&self) -> &'static str;
+    /// Lookup URL for the caller's own public IP
+    fn url(&self) -> &'static str;
+    /// Lookup URL asking only for `fields` (see `OPTIONAL_FIELDS`), for providers that
+    /// support field selection
+    fn url_for(&self, _fields: &[String]) -> String {
+        self.url().to_string()
+    }
+    /// Whether the provider takes a `lang` parameter with `PROVIDER_LANGUAGES` codes
+    fn localizes(&self) -> bool {
+        false
+    }
+    /// Parses a successful response body
+    fn parse(&self, body: &[u8]) -> Result<GeoInfo, GeoError>;
+}
+
+/// Looks up a provider by its config name
+pub fn find_provider(name: &str) -> Option<&'static dyn GeoProvider> {
+    PROVIDERS.iter().find(|p| p.name() == name).copied()
+}
+
+/// Primary provider first, then (if enabled) the others in default order
+fn provider_chain(primary: &str, fallback: bool) -> Vec<&'static dyn GeoProvider> {
+    let mut chain: Vec<_> = find_provider(primary).into_iter().collect();
+    if fallback || chain.is_empty() {
+        chain.extend(PROVIDERS.iter().copied().filter(|p| p.name() != primary));
+    }
+    chain
 }
 
-/// Fetches current geographic location based on public IP
+fn is_rate_limited(provider: &dyn GeoProvider) -> bool {
+    RATE_LIMITED
+        .lock()
+        .map(|limited| limited.get(provider.name()).is_some_and(|until| Instant::now() < *until))
+        .unwrap_or(false)
+}
+
+fn mark_rate_limited(provider: &dyn GeoProvider) {
+    if let Ok(mut limited) = RATE_LIMITED.lock() {
+        limited.insert(provider.name(), Instant::now() + RATE_LIMIT_BACKOFF);
+    }
+}
+
+/// Fetches current geographic location based on public IP, trying providers in turn.
+/// On dual-stack connections the address of the other family is filled in as well.
 pub async fn fetch_location() -> Result<GeoInfo, GeoError> {
-    let client = reqwest::Client::new();
-
-    let response: ApiResponse = client
-        .get(API_URL)
-        .timeout(std::time::Duration::from_secs(10))
-        .send()
-        .await?
-        .json()
-        .await?;
-
-    if response.status == "fail" {
-        return Err(GeoError::ApiError(
-            response.message.unwrap_or_else(|| "Unknown error".to_string())
-        ));
+    let info = lookup().await?;
+    let config = crate::config::current();
+    match other_family_provider(&config, crate::network::current_connection().as_ref()) {
+        Some(provider) => Ok(add_other_family(info, provider).await),
+        None => Ok(info),
+    }
+}
+
+/// The provider asked a second time over the other IP family: the one configured for
+/// `connection`. None without `dual_stack` and for "offline", which sends nothing to a geo
+/// service.
+fn other_family_provider(
+    config: &crate::config::Config,
+    connection: Option<&crate::network::ConnectionInfo>,
+) -> Option<&'static dyn GeoProvider> {
+    if !config.geo.dual_stack {
+        return None;
+    }
+    find_provider(config.provider_for(connection))
+}
+
+/// Fills in `ipv4`/`ipv6` from the looked-up address plus the answer `provider` gives over
+/// the other family
+async fn add_other_family(mut info: GeoInfo, provider: &dyn GeoProvider) -> GeoInfo {
+    let Ok(ip) = info.query.parse::<IpAddr>() else {
+        return info;
+    };
+    // Binding to the unspecified address of a family forces connections over that family
+    let bind = match ip {
+        IpAddr::V4(_) => IpAddr::V6(Ipv6Addr::UNSPECIFIED),
+        IpAddr::V6(_) => IpAddr::V4(Ipv4Addr::UNSPECIFIED),
+    };
+
+    let other = async {
+        if is_rate_limited(provider) {
+            return None;
+        }
+        let client = reqwest::Client::builder()
+            .local_address(bind)
+            .timeout(FAMILY_TIMEOUT)
+            .build()
+            .ok()?;
+        fetch_from(&client, provider)
+            .await
+            .ok()?
+            .query
+            .parse::<IpAddr>()
+            .ok()
+            .filter(|other| other.is_ipv4() != ip.is_ipv4())
+    }
+    .await;
+
+    let (v4, v6) = match ip {
+        IpAddr::V4(_) => (Some(ip), other),
+        IpAddr::V6(_) => (other, Some(ip)),
+    };
+    info.ipv4 = v4.map(|a| a.to_string());
+    info.ipv6 = v6.map(|a| a.to_string());
+    info
+}
+
+/// Looks up the location of the public IP the default route uses
+async fn lookup() -> Result<GeoInfo, GeoError> {
+    let config = crate::config::current();
+    let provider = config.provider_for(crate::network::current_connection().as_ref());
+    let mut chain = provider_chain(provider, config.geo.fallback);
+    if config.privacy.minimize_fingerprint && config.geo.fallback {
+        crate::privacy::shuffle(&mut chain);
+    }
+    let client = reqwest::Client::builder().timeout(REQUEST_TIMEOUT).build()?;
+
+    if provider == OFFLINE_PROVIDER {
+        match fetch_offline(&client, &config.geo.mmdb_path).await {
+            Ok(info) => return Ok(info),
+            Err(e) if !config.geo.fallback => return Err(e),
+            Err(e) => tracing::warn!("Offline lookup failed: {}", e),
+        }
+    }
+
+    let mut last_error = None;
+    if config.geo.race {
+        let racers: Vec<_> = chain.iter().copied().filter(|p| !is_rate_limited(*p)).take(2).collect();
+        if racers.len() == 2 {
+            match race(&client, &racers).await {
+                Ok(info) => return Ok(info),
+                Err(e) => {
+                    tracing::warn!("Lookup via {} and {} failed: {}", racers[0].name(), racers[1].name(), e);
+                    chain.retain(|p| racers.iter().all(|r| r.name() != p.name()));
+                    last_error = Some(e);
+                }
+            }
+        }
+    }
+    for provider in chain {
+        if is_rate_limited(provider) {
+            tracing::debug!("Skipping {} (rate limited)", provider.name());
+            last_error = Some(GeoError::RateLimited(provider.host()));
+            continue;
+        }
+        match fetch_from(&client, provider).await {
+            Ok(info) => return Ok(info),
+            Err(e) => {
+                if matches!(e, GeoError::RateLimited(_)) {
+                    mark_rate_limited(provider);
+                }
+                tracing::warn!("Lookup via {} failed: {}", provider.name(), e);
+                last_error = Some(e);
+            }
+        }
+    }
+    Err(last_error.unwrap_or(GeoError::InvalidResponse))
+}
+
+/// Looks up another address, e.g. a DNS resolver: in the offline database when that is
+/// the configured provider, otherwise via ipwho.is
+pub async fn lookup_address(ip: IpAddr) -> Result<GeoInfo, GeoError> {
+    let config = crate::config::current();
+    if config.provider_for(crate::network::current_connection().as_ref()) == OFFLINE_PROVIDER {
+        let path = config.geo.mmdb_path.clone();
+        let record = tokio::task::spawn_blocking(move || open_database(&path)?.lookup(ip).map_err(GeoError::from))
+            .await
+            .map_err(|_| GeoError::InvalidResponse)??
+            .ok_or(GeoError::NotInDatabase(ip))?;
+        return geo_from_record(ip, &record);
+    }
+
+    let client = reqwest::Client::builder().timeout(REQUEST_TIMEOUT).build()?;
+    crate::usage::record(IpWhoIs.host());
+    let response = get(&client, &format!("{}{}", IpWhoIs.url(), ip)).send().await?;
+    if response.status() == reqwest::StatusCode::TOO_MANY_REQUESTS {
+        return Err(GeoError::RateLimited(IpWhoIs.host()));
+    }
+    IpWhoIs.parse(&response.error_for_status()?.bytes().await?)
+}
+
+/// Queries `racers` concurrently and takes the first success; the slower request is
+/// dropped, which cancels it
+async fn race(client: &reqwest::Client, racers: &[&'static dyn GeoProvider]) -> Result<GeoInfo, GeoError> {
+    let attempts = racers.iter().map(|&provider| {
+        Box::pin(async move {
+            let result = fetch_from(client, provider).await;
+            if matches!(result, Err(GeoError::RateLimited(_))) {
+                mark_rate_limited(provider);
+            }
+            result.map(|info| (provider.name(), info))
+        })
+    });
+    let ((winner, info), _) = futures_util::future::select_ok(attempts).await?;
+    tracing::debug!("{} answered first", winner);
+    Ok(info)
+}
+
+/// Fetches location from a single provider
+async fn fetch_from(client: &reqwest::Client, provider: &dyn GeoProvider) -> Result<GeoInfo, GeoError> {
+    crate::usage::record(provider.host());
+
+    let mut url = provider.url_for(&crate::config::current().geo.fields);
+    let language = request_language(provider);
+    if let Some((_, code)) = &language {
+        url.push(if url.contains('?') { '&' } else { '?' });
+        url.push_str(&format!("lang={}", code));
+    }
+    let response = get(client, &url).send().await?;
+    crate::clock::observe(response.headers().get(reqwest::header::DATE).and_then(|d| d.to_str().ok()));
+    if response.status() == reqwest::StatusCode::TOO_MANY_REQUESTS {
+        return Err(GeoError::RateLimited(provider.host()));
+    }
+    let body = response.error_for_status()?.bytes().await?;
+    crate::diagnostics::record_raw_response(provider.name(), &body);
+    crate::raw_log::record(provider.name(), &body);
+    let mut info = provider.parse(&body)?;
+    info.language = language.map(|(lang, _)| lang);
+    Ok(info)
+}
+
+/// The user's language and its `lang` code, when `provider` should be asked for names in it.
+/// English is the providers' default, and a locale-specific parameter is left out while
+/// minimizing the fingerprint; the embedded dataset covers those cases.
+fn request_language(provider: &dyn GeoProvider) -> Option<(String, &'static str)> {
+    if !provider.localizes() || crate::privacy::enabled() {
+        return None;
+    }
+    let lang = crate::countries::system_language();
+    provider_language(&lang).map(|code| (lang, code))
+}
+
+fn provider_language(lang: &str) -> Option<&'static str> {
+    PROVIDER_LANGUAGES.iter().find(|(l, _)| *l == lang).map(|(_, code)| *code)
+}
+
+/// GET with the User-Agent `privacy` picks for this request
+fn get(client: &reqwest::Client, url: &str) -> reqwest::RequestBuilder {
+    client.get(url).header(reqwest::header::USER_AGENT, crate::privacy::user_agent())
+}
+
+/// Detects the public IP via a plain-text endpoint
+async fn public_ip(client: &reqwest::Client) -> Result<IpAddr, GeoError> {
+    let mut last_error = GeoError::InvalidResponse;
+    for url in IP_ENDPOINTS {
+        let host = url.trim_start_matches("https://");
+        crate::usage::record(host);
+        let result = async {
+            let text = get(client, url).send().await?.error_for_status()?.text().await?;
+            text.trim().parse().map_err(|_| GeoError::InvalidResponse)
+        }
+        .await;
+        match result {
+            Ok(ip) => return Ok(ip),
+            Err(e) => last_error = e,
+        }
+    }
+    Err(last_error)
+}
+
+/// Returns the database at `path`, reusing the loaded copy while the file is unchanged
+fn open_database(path: &Path) -> Result<Arc<mmdb::Reader>, GeoError> {
+    let modified = std::fs::metadata(path).and_then(|m| m.modified()).ok();
+    let mut cache = DATABASE.lock().map_err(|_| GeoError::InvalidResponse)?;
+    if let Some((cached_path, cached_modified, reader)) = cache.as_ref() {
+        if cached_path == path && *cached_modified == modified {
+            return Ok(reader.clone());
+        }
     }
 
+    tracing::info!("Loading offline database {}", path.display());
+    let reader = Arc::new(mmdb::Reader::open(path)?);
+    *cache = Some((path.to_path_buf(), modified, reader.clone()));
+    Ok(reader)
+}
+
+/// Resolves the public IP against a local GeoLite2 City (or Country) database
+async fn fetch_offline(client: &reqwest::Client, path: &Path) -> Result<GeoInfo, GeoError> {
+    let ip = public_ip(client).await?;
+    let path = path.to_path_buf();
+    let record = tokio::task::spawn_blocking(move || open_database(&path)?.lookup(ip).map_err(GeoError::from))
+        .await
+        .map_err(|_| GeoError::InvalidResponse)??
+        .ok_or(GeoError::NotInDatabase(ip))?;
+    geo_from_record(ip, &record)
+}
+
+/// Maps a GeoLite2 record to GeoInfo
+fn geo_from_record(ip: IpAddr, record: &Value) -> Result<GeoInfo, GeoError> {
+    let text = |keys: &[&str]| record.path(keys).and_then(Value::as_str).map(str::to_string);
+    let country_code = text(&["country", "iso_code"]).ok_or(GeoError::InvalidResponse)?;
+
     Ok(GeoInfo {
-        query: response.query.ok_or(GeoError::InvalidResponse)?,
-        country: response.country.ok_or(GeoError::InvalidResponse)?,
-        country_code: response.country_code.ok_or(GeoError::InvalidResponse)?,
-        city: response.city.ok_or(GeoError::InvalidResponse)?,
-        isp: response.isp.ok_or(GeoError::InvalidResponse)?,
+        query: ip.to_string(),
+        country: text(&["country", "names", "en"]).unwrap_or_else(|| country_for_code(&country_code)),
+        country_code,
+        city: text(&["city", "names", "en"]).unwrap_or_default(),
+        isp: text(&["autonomous_system_organization"]).unwrap_or_default(),
+        as_name: text(&["autonomous_system_organization"]).unwrap_or_default(),
+        coordinates: record
+            .path(&["location", "latitude"])
+            .and_then(Value::as_f64)
+            .zip(record.path(&["location", "longitude"]).and_then(Value::as_f64)),
+        asn: record
+            .get("autonomous_system_number")
+            .and_then(Value::as_uint)
+            .and_then(|n| u32::try_from(n).ok()),
+        ..Default::default()
     })
 }
 
+fn parse_json<'a, T: Deserialize<'a>>(body: &'a [u8]) -> Result<T, GeoError> {
+    serde_json::from_slice(body).map_err(|_| GeoError::InvalidResponse)
+}
+
+/// Parses "47.3769,8.5417"
+fn parse_coordinates(text: &str) -> Option<(f64, f64)> {
+    let (lat, lon) = text.split_once(',')?;
+    Some((lat.trim().parse().ok()?, lon.trim().parse().ok()?))
+}
+
+/// Parses "AS3303" or "AS3303 Swisscom (Schweiz) AG" into the number
+fn parse_asn(text: &str) -> Option<u32> {
+    text.split_whitespace().next()?.strip_prefix("AS")?.parse().ok()
+}
+
+/// English country name for providers that only return the code
+fn country_for_code(code: &str) -> String {
+    crate::countries::name(code, "en").unwrap_or(code).to_string()
+}
+
+/// ip-api.com (free tier, HTTP only)
+pub struct IpApi;
+
+/// ip-api.com response wrapper to handle success/error status
+#[derive(Debug, Deserialize)]
+struct IpApiResponse {
+    status: String,
+    message: Option<String>,
+    query: Option<String>,
+    country: Option<String>,
+    #[serde(rename = "countryCode")]
+    country_code: Option<String>,
+    city: Option<String>,
+    isp: Option<String>,
+    #[serde(rename = "as")]
+    asn: Option<String>,
+    asname: Option<String>,
+    org: Option<String>,
+    lat: Option<f64>,
+    lon: Option<f64>,
+    mobile: Option<bool>,
+    hosting: Option<bool>,
+    proxy: Option<bool>,
+}
+
+impl GeoProvider for IpApi {
+    fn name(&self) -> &'static str {
+        "ip-api"
+    }
+
+    fn host(&self) -> &'static str {
+        "ip-api.com"
+    }
+
+    // Note: ip-api.com free tier only supports HTTP. HTTPS requires paid API key.
+    // This is acceptable as we only fetch public IP metadata (no sensitive data).
+    fn url(&self) -> &'static str {
+        "http://ip-api.com/json/?fields=status,message,country,countryCode,city,isp,as,asname,org,lat,lon,mobile,hosting,proxy,query"
+    }
+
+    fn url_for(&self, fields: &[String]) -> String {
+        let mut names = vec!["status", "message", "query", "country", "countryCode"];
+        names.extend(fields.iter().map(|f| match f.as_str() {
+            "asn" => "as,asname",
+            "location" => "lat,lon",
+            other => other,
+        }));
+        format!("http://ip-api.com/json/?fields={}", names.join(","))
+    }
+
+    fn localizes(&self) -> bool {
+        true
+    }
+
+    fn parse(&self, body: &[u8]) -> Result<GeoInfo, GeoError> {
+        let response: IpApiResponse = parse_json(body)?;
+        if response.status == "fail" {
+            return Err(GeoError::ApiError(
+                response.message.unwrap_or_else(|| "Unknown error".to_string())
+            ));
+        }
+
+        Ok(GeoInfo {
+            query: response.query.ok_or(GeoError::InvalidResponse)?,
+            country: response.country.ok_or(GeoError::InvalidResponse)?,
+            country_code: response.country_code.ok_or(GeoError::InvalidResponse)?,
+            city: response.city.unwrap_or_default(),
+            isp: response.isp.unwrap_or_default(),
+            asn: response.asn.as_deref().and_then(parse_asn),
+            as_name: response.asname.unwrap_or_default(),
+            org: response.org.unwrap_or_default(),
+            coordinates: response.lat.zip(response.lon),
+            mobile: response.mobile.unwrap_or(false),
+            hosting: response.hosting.unwrap_or(false),
+            proxy: response.proxy.unwrap_or(false),
+            ..Default::default()
+        })
+    }
+}
+
+/// ipinfo.io (returns only the country code; ISP comes as "AS123 Name")
+pub struct IpInfo;
+
+#[derive(Debug, Deserialize)]
+struct IpInfoResponse {
+    ip: String,
+    country: Option<String>,
+    city: Option<String>,
+    org: Option<String>,
+    /// "47.3769,8.5417"
+    loc: Option<String>,
+    #[serde(default)]
+    bogon: bool,
+}
+
+impl GeoProvider for IpInfo {
+    fn name(&self) -> &'static str {
+        "ipinfo"
+    }
+
+    fn host(&self) -> &'static str {
+        "ipinfo.io"
+    }
+
+    fn url(&self) -> &'static str {
+        "https://ipinfo.io/json"
+    }
+
+    fn parse(&self, body: &[u8]) -> Result<GeoInfo, GeoError> {
+        let response: IpInfoResponse = parse_json(body)?;
+        if response.bogon {
+            return Err(GeoError::ApiError("bogon address".to_string()));
+        }
+        let country_code = response.country.ok_or(GeoError::InvalidResponse)?;
+        let org = response.org.unwrap_or_default();
+        let asn = parse_asn(&org);
+        let isp = match org.split_once(' ') {
+            Some((asn, name)) if asn.starts_with("AS") => name.to_string(),
+            _ => org,
+        };
+
+        Ok(GeoInfo {
+            query: response.ip,
+            country: country_for_code(&country_code),
+            country_code,
+            city: response.city.unwrap_or_default(),
+            as_name: isp.clone(),
+            isp,
+            asn,
+            coordinates: response.loc.as_deref().and_then(parse_coordinates),
+            ..Default::default()
+        })
+    }
+}
+
+/// ipwho.is
+pub struct IpWhoIs;
+
+#[derive(Debug, Deserialize)]
+struct IpWhoIsResponse {
+    success: bool,
+    message: Option<String>,
+    ip: Option<String>,
+    country: Option<String>,
+    country_code: Option<String>,
+    city: Option<String>,
+    latitude: Option<f64>,
+    longitude: Option<f64>,
+    connection: Option<IpWhoIsConnection>,
+}
+
+#[derive(Debug, Deserialize)]
+struct IpWhoIsConnection {
+    isp: Option<String>,
+    org: Option<String>,
+    asn: Option<u32>,
+}
+
+impl GeoProvider for IpWhoIs {
+    fn name(&self) -> &'static str {
+        "ipwho.is"
+    }
+
+    fn host(&self) -> &'static str {
+        "ipwho.is"
+    }
+
+    fn url(&self) -> &'static str {
+        "https://ipwho.is/"
+    }
+
+    /// ipwho.is has no mobile/hosting flags; nested fields use dots
+    fn url_for(&self, fields: &[String]) -> String {
+        let mut names = vec!["success", "message", "ip", "country", "country_code"];
+        for field in fields {
+            match field.as_str() {
+                "city" => names.push("city"),
+                "isp" => names.push("connection.isp"),
+                "asn" => names.push("connection.asn"),
+                "org" => names.push("connection.org"),
+                "location" => names.extend(["latitude", "longitude"]),
+                _ => {}
+            }
+        }
+        format!("{}?fields={}", self.url(), names.join(","))
+    }
+
+    fn localizes(&self) -> bool {
+        true
+    }
+
+    fn parse(&self, body: &[u8]) -> Result<GeoInfo, GeoError> {
+        let response: IpWhoIsResponse = parse_json(body)?;
+        if !response.success {
+            return Err(GeoError::ApiError(
+                response.message.unwrap_or_else(|| "Unknown error".to_string())
+            ));
+        }
+
+        Ok(GeoInfo {
+            query: response.ip.ok_or(GeoError::InvalidResponse)?,
+            country: response.country.ok_or(GeoError::InvalidResponse)?,
+            country_code: response.country_code.ok_or(GeoError::InvalidResponse)?,
+            city: response.city.unwrap_or_default(),
+            coordinates: response.latitude.zip(response.longitude),
+            asn: response.connection.as_ref().and_then(|c| c.asn),
+            org: response.connection.as_ref().and_then(|c| c.org.clone()).unwrap_or_default(),
+            isp: response.connection.and_then(|c| c.isp).unwrap_or_default(),
+            ..Default::default()
+        })
+    }
+}
+
+/// ifconfig.co
+pub struct IfconfigCo;
+
+#[derive(Debug, Deserialize)]
+struct IfconfigCoResponse {
+    ip: String,
+    country: Option<String>,
+    country_iso: Option<String>,
+    city: Option<String>,
+    latitude: Option<f64>,
+    longitude: Option<f64>,
+    asn: Option<String>,
+    asn_org: Option<String>,
+}
+
+impl GeoProvider for IfconfigCo {
+    fn name(&self) -> &'static str {
+        "ifconfig.co"
+    }
+
+    fn host(&self) -> &'static str {
+        "ifconfig.co"
+    }
+
+    fn url(&self) -> &'static str {
+        "https://ifconfig.co/json"
+    }
+
+    fn parse(&self, body: &[u8]) -> Result<GeoInfo, GeoError> {
+        let response: IfconfigCoResponse = parse_json(body)?;
+        let country_code = response.country_iso.ok_or(GeoError::InvalidResponse)?;
+
+        Ok(GeoInfo {
+            query: response.ip,
+            country: response.country.unwrap_or_else(|| country_for_code(&country_code)),
+            country_code,
+            city: response.city.unwrap_or_default(),
+            coordinates: response.latitude.zip(response.longitude),
+            as_name: response.asn_org.clone().unwrap_or_default(),
+            isp: response.asn_org.unwrap_or_default(),
+            asn: response.asn.as_deref().and_then(parse_asn),
+            ..Default::default()
+        })
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -94,4 +762,115 @@ mod tests {
         assert!(!info.country.is_empty());
         assert_eq!(info.country_code.len(), 2);
     }
+
+    #[test]
+    fn test_is_change() {
+        let home = GeoInfo {
+            query: "203.0.113.7".to_string(),
+            country_code: "VN".to_string(),
+            city: "Hanoi".to_string(),
+            isp: "Viettel".to_string(),
+            ..Default::default()
+        };
+        assert!(!is_change(&home, &GeoInfo { city: "Hai Phong".to_string(), ..home.clone() }));
+        assert!(is_change(&home, &GeoInfo { query: "203.0.113.8".to_string(), ..home.clone() }));
+        assert!(is_change(&home, &GeoInfo { country_code: "DE".to_string(), ..home.clone() }));
+        assert!(is_change(&home, &GeoInfo { isp: "VNPT".to_string(), ..home.clone() }));
+    }
+
+    #[test]
+    fn test_provider_responses() {
+        let ip_api = IpApi
+            .parse(br#"{"status":"success","country":"Vietnam","countryCode":"VN","city":"Hanoi","isp":"Viettel Group","mobile":true,"hosting":false,"query":"203.0.113.7"}"#)
+            .unwrap();
+        assert_eq!((ip_api.country_code.as_str(), ip_api.mobile), ("VN", true));
+        let org = IpApi
+            .parse(br#"{"status":"success","country":"Netherlands","countryCode":"NL","as":"AS9009 M247 Europe SRL","asname":"M247","org":"Mullvad VPN","query":"203.0.113.7"}"#)
+            .unwrap();
+        assert_eq!((org.asn, org.as_name.as_str(), org.org.as_str()), (Some(9009), "M247", "Mullvad VPN"));
+        assert!(matches!(
+            IpApi.parse(br#"{"status":"fail","message":"reserved range"}"#),
+            Err(GeoError::ApiError(_))
+        ));
+
+        let ipinfo = IpInfo
+            .parse(br#"{"ip":"203.0.113.7","city":"Zurich","country":"CH","org":"AS3303 Swisscom (Schweiz) AG"}"#)
+            .unwrap();
+        assert_eq!(ipinfo.country, "Switzerland");
+        assert_eq!(ipinfo.isp, "Swisscom (Schweiz) AG");
+        assert_eq!(ipinfo.asn, Some(3303));
+        assert_eq!(parse_coordinates("47.3769,8.5417"), Some((47.3769, 8.5417)));
+
+        let ipwho = IpWhoIs
+            .parse(br#"{"ip":"203.0.113.7","success":true,"country":"Germany","country_code":"DE","city":"Berlin","connection":{"isp":"Deutsche Telekom AG"}}"#)
+            .unwrap();
+        assert_eq!(ipwho.isp, "Deutsche Telekom AG");
+
+        let ifconfig = IfconfigCo
+            .parse(br#"{"ip":"203.0.113.7","country":"France","country_iso":"FR","asn_org":"Orange"}"#)
+            .unwrap();
+        assert_eq!((ifconfig.country_code.as_str(), ifconfig.city.as_str()), ("FR", ""));
+    }
+
+    #[test]
+    fn test_field_selection() {
+        let fields = vec!["city".to_string(), "asn".to_string()];
+        assert_eq!(IpApi.url_for(&fields), "http://ip-api.com/json/?fields=status,message,query,country,countryCode,city,as,asname");
+        assert_eq!(IpWhoIs.url_for(&fields), "https://ipwho.is/?fields=success,message,ip,country,country_code,city,connection.asn");
+        assert_eq!(IpInfo.url_for(&fields), IpInfo.url());
+        assert!(IpApi.localizes() && !IpInfo.localizes());
+        assert_eq!(provider_language("pt"), Some("pt-BR"));
+        assert_eq!(provider_language("en"), None);
+
+        let trimmed = IpApi
+            .parse(br#"{"status":"success","country":"Vietnam","countryCode":"VN","query":"203.0.113.7"}"#)
+            .unwrap();
+        assert_eq!((trimmed.city.as_str(), trimmed.isp.as_str()), ("", ""));
+    }
+
+    #[test]
+    fn test_geo_from_record() {
+        let record = Value::Map(vec![(
+            "country".to_string(),
+            Value::Map(vec![("iso_code".to_string(), Value::String("CH".to_string()))]),
+        )]);
+        let info = geo_from_record("203.0.113.7".parse().unwrap(), &record).unwrap();
+        assert_eq!((info.country.as_str(), info.city.as_str()), ("Switzerland", ""));
+    }
+
+    #[test]
+    fn test_country_name() {
+        let mut info = GeoInfo { country: "Japón".to_string(), country_code: "JP".to_string(), ..Default::default() };
+        info.language = Some("es".to_string());
+        assert_eq!(info.country_name(), "Japón");
+        info.country_code = "ZZ".to_string();
+        info.language = None;
+        assert_eq!(info.country_name(), "Japón");
+    }
+
+    #[test]
+    fn test_address_lines() {
+        let mut info = GeoInfo { query: "203.0.113.7".to_string(), ..Default::default() };
+        assert_eq!(info.address_lines(), ["IP: 203.0.113.7"]);
+        info.ipv4 = Some("203.0.113.7".to_string());
+        info.ipv6 = Some("2001:db8::7".to_string());
+        assert_eq!(info.address_lines(), ["IPv4: 203.0.113.7", "IPv6: 2001:db8::7"]);
+    }
+
+    #[test]
+    fn test_provider_chain() {
+        let names = |chain: Vec<&dyn GeoProvider>| chain.iter().map(|p| p.name()).collect::<Vec<_>>();
+        assert_eq!(names(provider_chain("ipinfo", true)), ["ipinfo", "ip-api", "ipwho.is", "ifconfig.co"]);
+        assert_eq!(names(provider_chain("ipinfo", false)), ["ipinfo"]);
+    }
+
+    #[test]
+    fn test_other_family_provider() {
+        let config = crate::config::parse("[geo]\nprovider = \"ipinfo\"\n").unwrap();
+        assert_eq!(other_family_provider(&config, None).map(|p| p.name()), Some("ipinfo"));
+        let offline = crate::config::parse("[geo]\nprovider = \"offline\"\n").unwrap();
+        assert!(other_family_provider(&offline, None).is_none());
+        let single = crate::config::parse("[geo]\nprovider = \"ipinfo\"\ndual_stack = false\n").unwrap();
+        assert!(other_family_provider(&single, None).is_none());
+    }
 }
@@ -0,0 +1,356 @@
+//! Location history
+//! Appends every distinct location observed (IP, country, city, ISP) to
+//! ~/.local/share/network-monitor/history.jsonl, pruned after `[history] retention_days`.
+
+use crate::geo::GeoInfo;
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::io::Write;
+use std::path::PathBuf;
+use std::sync::{LazyLock, Mutex};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+const HISTORY_FILENAME: &str = "history.jsonl";
+/// Oldest entries are dropped beyond this many, whatever the retention
+const MAX_ENTRIES: usize = 10_000;
+const DAY: u64 = 24 * 60 * 60;
+
+/// Entries on disk, oldest first
+static HISTORY: LazyLock<Mutex<Vec<Entry>>> = LazyLock::new(|| Mutex::new(load()));
+/// Current public IP and when it was first seen, tracked even with history off
+static IP_SINCE: Mutex<Option<(String, u64)>> = Mutex::new(None);
+
+/// Output of `network-monitor history`
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Format {
+    /// Aligned columns, local time
+    #[default]
+    Table,
+    Csv,
+    /// Array of entries
+    Json,
+}
+
+impl Format {
+    pub const NAMES: &'static [&'static str] = &["table", "csv", "json"];
+
+    pub fn parse(name: &str) -> Option<Self> {
+        match name {
+            "table" => Some(Self::Table),
+            "csv" => Some(Self::Csv),
+            "json" => Some(Self::Json),
+            _ => None,
+        }
+    }
+}
+
+/// Which entries to export
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Filter {
+    /// Only entries at most this old
+    pub since: Option<Duration>,
+    /// Only entries in this country (ISO code, any case)
+    pub country: Option<String>,
+}
+
+impl Filter {
+    fn matches(&self, entry: &Entry, now: u64) -> bool {
+        let recent = self.since.is_none_or(|since| entry.timestamp >= now.saturating_sub(since.as_secs()));
+        let country = self
+            .country
+            .as_deref()
+            .is_none_or(|code| entry.country_code.eq_ignore_ascii_case(code));
+        recent && country
+    }
+}
+
+/// One observed location, timestamp in Unix seconds
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Entry {
+    pub timestamp: u64,
+    pub ip: String,
+    pub country: String,
+    pub country_code: String,
+    pub city: String,
+    pub isp: String,
+}
+
+impl Entry {
+    pub fn new(info: &GeoInfo, timestamp: u64) -> Self {
+        Self {
+            timestamp,
+            ip: info.query.clone(),
+            country: info.country.clone(),
+            country_code: info.country_code.clone(),
+            city: info.city.clone(),
+            isp: info.isp.clone(),
+        }
+    }
+
+    pub fn time(&self) -> SystemTime {
+        UNIX_EPOCH + Duration::from_secs(self.timestamp)
+    }
+
+    /// The entry as a location, without the fields history doesn't keep
+    pub fn to_geo(&self) -> GeoInfo {
+        GeoInfo {
+            query: self.ip.clone(),
+            country: self.country.clone(),
+            country_code: self.country_code.clone(),
+            city: self.city.clone(),
+            isp: self.isp.clone(),
+            ..Default::default()
+        }
+    }
+
+    /// Same place and address, ignoring when it was seen
+    pub fn same_location(&self, other: &Entry) -> bool {
+        (&self.ip, &self.country_code, &self.city, &self.isp)
+            == (&other.ip, &other.country_code, &other.city, &other.isp)
+    }
+}
+
+fn history_file() -> Option<PathBuf> {
+    dirs::data_dir().map(|p| p.join("network-monitor").join(HISTORY_FILENAME))
+}
+
+pub(crate) fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or(Duration::ZERO)
+        .as_secs()
+}
+
+/// One JSON object per line; lines that don't parse (e.g. a torn write) are skipped
+fn parse(content: &str) -> Vec<Entry> {
+    content
+        .lines()
+        .filter_map(|line| serde_json::from_str(line).ok())
+        .collect()
+}
+
+fn serialize(entry: &Entry) -> String {
+    serde_json::to_string(entry).unwrap_or_default() + "\n"
+}
+
+fn load() -> Vec<Entry> {
+    let mut entries = history_file()
+        .and_then(|p| fs::read_to_string(p).ok())
+        .map(|c| parse(&c))
+        .unwrap_or_default();
+    if prune(&mut entries, now(), crate::config::current().history.retention_days) {
+        save(&entries);
+    }
+    entries
+}
+
+/// Drops entries older than `retention_days` (0 keeps them) and beyond `MAX_ENTRIES`;
+/// returns true if anything was removed
+fn prune(entries: &mut Vec<Entry>, now: u64, retention_days: u32) -> bool {
+    let before = entries.len();
+    if retention_days > 0 {
+        let cutoff = now.saturating_sub(retention_days as u64 * DAY);
+        entries.retain(|e| e.timestamp >= cutoff);
+    }
+    if entries.len() > MAX_ENTRIES {
+        entries.drain(..entries.len() - MAX_ENTRIES);
+    }
+    entries.len() != before
+}
+
+fn save(entries: &[Entry]) {
+    let Some(path) = history_file() else { return };
+    if let Some(parent) = path.parent() {
+        let _ = fs::create_dir_all(parent);
+    }
+    let content: String = entries.iter().map(serialize).collect();
+    if let Err(e) = fs::write(path, content) {
+        tracing::warn!("Failed to save location history: {}", e);
+    }
+}
+
+fn append(entry: &Entry) {
+    let Some(path) = history_file() else { return };
+    if let Some(parent) = path.parent() {
+        let _ = fs::create_dir_all(parent);
+    }
+    let result = fs::OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(path)
+        .and_then(|mut f| f.write_all(serialize(entry).as_bytes()));
+    if let Err(e) = result {
+        tracing::warn!("Failed to append to location history: {}", e);
+    }
+}
+
+/// Since when the newest entries have all had `ip`, None if the newest is another IP
+fn stable_since(entries: &[Entry], ip: &str) -> Option<u64> {
+    entries
+        .iter()
+        .rev()
+        .take_while(|e| e.ip == ip)
+        .last()
+        .map(|e| e.timestamp)
+}
+
+/// Starts the stability clock when the public IP changes; the first IP seen after a
+/// restart is looked up in the history so the clock survives restarts
+fn note_ip(ip: &str, now: u64) {
+    let Ok(mut since) = IP_SINCE.lock() else { return };
+    if since.as_ref().is_some_and(|(current, _)| current == ip) {
+        return;
+    }
+    let started = if since.is_none() && crate::config::current().history.enabled {
+        HISTORY.lock().ok().and_then(|e| stable_since(&e, ip))
+    } else {
+        None
+    };
+    *since = Some((ip.to_string(), started.unwrap_or(now)));
+}
+
+/// How long the public IP has been unchanged, e.g. for "IP stable for 3d 14h"
+pub fn ip_stable_for() -> Option<Duration> {
+    let since = IP_SINCE.lock().ok()?.as_ref()?.1;
+    Some(Duration::from_secs(now().saturating_sub(since)))
+}
+
+/// Records `info` unless it matches the newest entry
+pub fn record(info: &GeoInfo) {
+    if !info.query.is_empty() && info.cached_at.is_none() {
+        note_ip(&info.query, now());
+    }
+    let config = crate::config::current().history.clone();
+    // A cached location is a replay, not an observation
+    if !config.enabled || info.query.is_empty() || info.cached_at.is_some() || crate::privacy::no_log() {
+        return;
+    }
+    let Ok(mut entries) = HISTORY.lock() else { return };
+    let entry = Entry::new(info, now());
+    if entries.last().is_some_and(|last| last.same_location(&entry)) {
+        return;
+    }
+    entries.push(entry.clone());
+    if prune(&mut entries, entry.timestamp, config.retention_days) {
+        save(&entries);
+    } else {
+        append(&entry);
+    }
+}
+
+/// All recorded entries, oldest first
+pub fn entries() -> Vec<Entry> {
+    HISTORY.lock().map(|e| e.clone()).unwrap_or_default()
+}
+
+/// The last `count` entries, newest first
+pub fn recent(count: usize) -> Vec<Entry> {
+    let Ok(entries) = HISTORY.lock() else { return Vec::new() };
+    entries.iter().rev().take(count).cloned().collect()
+}
+
+/// Recorded entries matching `filter`, oldest first
+pub fn select(filter: &Filter) -> Vec<Entry> {
+    let now = now();
+    entries().into_iter().filter(|e| filter.matches(e, now)).collect()
+}
+
+/// Formats `entries` for printing
+pub fn render(entries: &[Entry], format: Format) -> String {
+    match format {
+        Format::Table => render_table(entries),
+        Format::Csv => render_csv(entries),
+        Format::Json => serde_json::to_string_pretty(entries).unwrap_or_default() + "\n",
+    }
+}
+
+fn render_table(entries: &[Entry]) -> String {
+    let mut out = format!("{:<16}  {:<4} {:<20} {:<39} {}\n", "TIME", "CC", "CITY", "IP", "ISP");
+    for e in entries {
+        out.push_str(&format!(
+            "{:<16}  {:<4} {:<20} {:<39} {}\n",
+            crate::schedule::format_local_date(e.time()),
+            e.country_code,
+            e.city,
+            e.ip,
+            e.isp
+        ));
+    }
+    out
+}
+
+fn render_csv(entries: &[Entry]) -> String {
+    use crate::outages::csv_field;
+    let mut csv = String::from("timestamp,ip,country_code,country,city,isp\n");
+    for e in entries {
+        csv.push_str(&format!(
+            "{},{},{},{},{},{}\n",
+            e.timestamp,
+            e.ip,
+            e.country_code,
+            csv_field(&e.country),
+            csv_field(&e.city),
+            csv_field(&e.isp)
+        ));
+    }
+    csv
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry(timestamp: u64, ip: &str) -> Entry {
+        Entry {
+            timestamp,
+            ip: ip.to_string(),
+            country: "Vietnam".to_string(),
+            country_code: "VN".to_string(),
+            city: "Hanoi".to_string(),
+            isp: "Viettel".to_string(),
+        }
+    }
+
+    #[test]
+    fn test_parse_round_trip() {
+        let entries = vec![entry(1, "203.0.113.7"), entry(2, "203.0.113.8")];
+        let content: String = entries.iter().map(serialize).collect::<String>() + "{\"timest";
+        assert_eq!(parse(&content), entries);
+        assert!(entries[0].same_location(&entry(5, "203.0.113.7")));
+    }
+
+    #[test]
+    fn test_stable_since() {
+        let entries = vec![entry(1, "a"), entry(2, "b"), entry(3, "b")];
+        assert_eq!(stable_since(&entries, "b"), Some(2));
+        assert_eq!(stable_since(&entries, "a"), None);
+        assert_eq!(stable_since(&[], "a"), None);
+    }
+
+    #[test]
+    fn test_prune() {
+        let now = 100 * DAY;
+        let mut entries = vec![entry(now - 40 * DAY, "a"), entry(now - DAY, "b")];
+        assert!(!prune(&mut entries, now, 0));
+        assert!(prune(&mut entries, now, 30));
+        assert_eq!(entries, vec![entry(now - DAY, "b")]);
+    }
+
+    #[test]
+    fn test_filter_and_csv() {
+        let now = 10 * DAY;
+        let mut abroad = entry(now - DAY, "198.51.100.1");
+        abroad.country_code = "DE".to_string();
+        abroad.city = "Frankfurt, Main".to_string();
+        let filter = Filter { since: Some(Duration::from_secs(2 * DAY)), country: Some("de".to_string()) };
+        assert!(filter.matches(&abroad, now));
+        assert!(!filter.matches(&entry(now - DAY, "a"), now));
+        let week = Filter { since: Some(Duration::from_secs(7 * DAY)), country: None };
+        assert!(!week.matches(&entry(now - 8 * DAY, "a"), now));
+
+        assert_eq!(
+            render(&[abroad], Format::Csv),
+            "timestamp,ip,country_code,country,city,isp\n777600,198.51.100.1,DE,Vietnam,\"Frankfurt, Main\",Viettel\n"
+        );
+    }
+}
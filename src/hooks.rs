@@ -0,0 +1,134 @@
+//! User hook scripts
+//! Runs executables from ~/.config/network-monitor/hooks/ (`on-location-change`,
+//! `on-connect`, `on-disconnect`) with the location in `NETWORK_MONITOR_*` variables.
+
+use crate::geo::GeoInfo;
+use std::os::unix::fs::{MetadataExt, PermissionsExt};
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+use tokio::process::Command;
+
+const HOOKS_DIR: &str = "hooks";
+/// Hooks still running after this are killed
+const HOOK_TIMEOUT: Duration = Duration::from_secs(60);
+
+/// What happened
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HookEvent {
+    /// Country, ISP or public IP changed
+    LocationChange,
+    Connect,
+    Disconnect,
+}
+
+impl HookEvent {
+    /// Script file name, also passed as `NETWORK_MONITOR_EVENT`
+    pub fn name(&self) -> &'static str {
+        match self {
+            HookEvent::LocationChange => "on-location-change",
+            HookEvent::Connect => "on-connect",
+            HookEvent::Disconnect => "on-disconnect",
+        }
+    }
+}
+
+/// ~/.config/network-monitor/hooks
+pub fn hooks_dir() -> Option<PathBuf> {
+    crate::config::config_dir().map(|p| p.join(HOOKS_DIR))
+}
+
+/// Environment for a hook: the current location and, for location changes, the old one
+fn environment(event: &str, info: Option<&GeoInfo>, old: Option<&GeoInfo>) -> Vec<(String, String)> {
+    let mut env = vec![("NETWORK_MONITOR_EVENT".to_string(), event.to_string())];
+    for (prefix, info) in [("NETWORK_MONITOR_", info), ("NETWORK_MONITOR_OLD_", old)] {
+        let Some(info) = info else { continue };
+        for (key, value) in [
+            ("IP", &info.query),
+            ("COUNTRY", &info.country),
+            ("COUNTRY_CODE", &info.country_code),
+            ("CITY", &info.city),
+            ("ISP", &info.isp),
+        ] {
+            env.push((format!("{}{}", prefix, key), value.clone()));
+        }
+    }
+    env
+}
+
+/// Runs the hook for `event` in the background, if the user installed one
+pub fn run(event: HookEvent, info: Option<&GeoInfo>, old: Option<&GeoInfo>) {
+    let Some(path) = hooks_dir().map(|d| d.join(event.name())) else { return };
+    if path.exists() {
+        spawn(path, event.name(), environment(event.name(), info, old));
+    }
+}
+
+/// Runs a `[[rules]]` script, with `NETWORK_MONITOR_EVENT=rule` and `NETWORK_MONITOR_RULE`
+pub fn run_for_rule(script: &Path, rule: &str, info: &GeoInfo) {
+    let mut env = environment("rule", Some(info), None);
+    env.push(("NETWORK_MONITOR_RULE".to_string(), rule.to_string()));
+    spawn(script.to_path_buf(), "rule", env);
+}
+
+/// Starts `path` unless it is unsafe to run, killing it after `HOOK_TIMEOUT`
+fn spawn(path: PathBuf, name: &'static str, env: Vec<(String, String)>) {
+    let metadata = match std::fs::metadata(&path) {
+        Ok(metadata) => metadata,
+        Err(e) => {
+            tracing::warn!("Hook {} unavailable: {}", path.display(), e);
+            return;
+        }
+    };
+    let mode = metadata.permissions().mode();
+    if mode & 0o111 == 0 {
+        tracing::warn!("Hook {} is not executable, skipping", path.display());
+        return;
+    }
+    // SAFETY: getuid has no preconditions and cannot fail
+    let uid = unsafe { libc::getuid() };
+    // Someone else could otherwise get code run as this user
+    if mode & 0o022 != 0 || metadata.uid() != uid {
+        tracing::warn!("Hook {} is writable by others or not owned by you, skipping", path.display());
+        return;
+    }
+
+    tokio::spawn(async move {
+        let child = Command::new(&path).envs(env).kill_on_drop(true).spawn();
+        let mut child = match child {
+            Ok(child) => child,
+            Err(e) => {
+                tracing::warn!("Failed to run hook {}: {}", path.display(), e);
+                return;
+            }
+        };
+        match tokio::time::timeout(HOOK_TIMEOUT, child.wait()).await {
+            Ok(Ok(status)) if status.success() => tracing::debug!("Hook {} finished", name),
+            Ok(Ok(status)) => tracing::warn!("Hook {} exited with {}", name, status),
+            Ok(Err(e)) => tracing::warn!("Hook {} failed: {}", name, e),
+            Err(_) => tracing::warn!("Hook {} timed out after {:?}, killed", name, HOOK_TIMEOUT),
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_environment() {
+        let new = GeoInfo {
+            query: "198.51.100.1".to_string(),
+            country_code: "DE".to_string(),
+            ..Default::default()
+        };
+        let old = GeoInfo { country_code: "VN".to_string(), ..new.clone() };
+        let env = environment(HookEvent::LocationChange.name(), Some(&new), Some(&old));
+        let get = |key: &str| env.iter().find(|(k, _)| k == key).map(|(_, v)| v.as_str());
+        assert_eq!(get("NETWORK_MONITOR_EVENT"), Some("on-location-change"));
+        assert_eq!(get("NETWORK_MONITOR_IP"), Some("198.51.100.1"));
+        assert_eq!(get("NETWORK_MONITOR_OLD_COUNTRY_CODE"), Some("VN"));
+
+        let env = environment(HookEvent::Disconnect.name(), None, None);
+        assert_eq!(env.len(), 1);
+    }
+}
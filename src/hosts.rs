@@ -0,0 +1,96 @@
+//! StatusNotifier host discovery
+//! Hosts (panels, docks, each waybar bar) own `org.kde.StatusNotifierHost-<pid>-<n>` names on
+//! the session bus; `[tray] prefer_hosts` and `exclude_hosts` match them by program name.
+
+use std::fs;
+use std::time::Duration;
+use zbus::fdo::DBusProxy;
+use zbus::Connection;
+
+const HOST_PREFIXES: &[&str] = &["org.kde.StatusNotifierHost-", "org.freedesktop.StatusNotifierHost-"];
+/// How long startup waits for a preferred host
+pub const PREFER_WAIT: Duration = Duration::from_secs(30);
+const POLL_INTERVAL: Duration = Duration::from_secs(1);
+
+/// A registered StatusNotifier host
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Host {
+    /// Bus name the host owns
+    pub name: String,
+    /// Program name from /proc, unless the process is out of sight (e.g. in a sandbox)
+    pub program: Option<String>,
+}
+
+impl Host {
+    /// Program name, or the bus name when unknown
+    pub fn label(&self) -> &str {
+        self.program.as_deref().unwrap_or(&self.name)
+    }
+
+    fn is_one_of(&self, programs: &[String]) -> bool {
+        self.program.as_ref().is_some_and(|program| programs.contains(program))
+    }
+}
+
+/// Hosts on the session bus, in bus name order
+pub async fn list(session: &Connection) -> zbus::Result<Vec<Host>> {
+    let dbus = DBusProxy::new(session).await?;
+    let mut hosts = Vec::new();
+    for name in dbus.list_names().await? {
+        if !HOST_PREFIXES.iter().any(|prefix| name.starts_with(prefix)) {
+            continue;
+        }
+        let program = match dbus.get_connection_unix_process_id(name.as_ref()).await {
+            Ok(pid) => program_name(pid),
+            Err(_) => None,
+        };
+        hosts.push(Host { name: name.to_string(), program });
+    }
+    hosts.sort_by(|a, b| a.name.cmp(&b.name));
+    Ok(hosts)
+}
+
+fn program_name(pid: u32) -> Option<String> {
+    let comm = fs::read_to_string(format!("/proc/{}/comm", pid)).ok()?;
+    Some(comm.trim().to_string()).filter(|c| !c.is_empty())
+}
+
+/// Waits up to `PREFER_WAIT` for one of `programs` to register as a host
+pub async fn wait_for_preferred(session: &Connection, programs: &[String]) -> bool {
+    let deadline = tokio::time::Instant::now() + PREFER_WAIT;
+    loop {
+        if list(session).await.is_ok_and(|hosts| hosts.iter().any(|h| h.is_one_of(programs))) {
+            return true;
+        }
+        if tokio::time::Instant::now() >= deadline {
+            return false;
+        }
+        tokio::time::sleep(POLL_INTERVAL).await;
+    }
+}
+
+/// Whether hosts are running but every one of them is excluded
+pub fn only_excluded(hosts: &[Host], exclude: &[String]) -> bool {
+    !hosts.is_empty() && hosts.iter().all(|h| h.is_one_of(exclude))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_only_excluded() {
+        let host = |name: &str, program: Option<&str>| Host { name: name.to_string(), program: program.map(str::to_string) };
+        let dock = host("org.kde.StatusNotifierHost-812-1", Some("plank"));
+        let bar = host("org.kde.StatusNotifierHost-904-2", Some("waybar"));
+        let exclude = vec!["plank".to_string()];
+
+        assert!(only_excluded(std::slice::from_ref(&dock), &exclude));
+        assert!(!only_excluded(&[dock, bar], &exclude));
+        assert!(!only_excluded(&[], &exclude));
+        // Unknown programs are never excluded
+        let sandboxed = host("org.kde.StatusNotifierHost-3-1", None);
+        assert!(!only_excluded(std::slice::from_ref(&sandboxed), &exclude));
+        assert_eq!(sandboxed.label(), "org.kde.StatusNotifierHost-3-1");
+    }
+}
@@ -0,0 +1,114 @@
+//! Country-change hysteresis to suppress flapping
+//! When the country flips shortly after a previous change (flaky VPN, anycast quirks),
+//! the new country must be confirmed by consecutive lookups before it is displayed.
+
+use crate::geo::GeoInfo;
+use std::time::{Duration, Instant};
+
+/// A change within this long after the previous one counts as flapping
+pub const FLAP_WINDOW: Duration = Duration::from_secs(5 * 60);
+/// Consecutive lookups needed to accept a country while flapping
+pub const CONFIRMATIONS: u32 = 2;
+
+/// Outcome of feeding a lookup through the hysteresis
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Decision {
+    /// Show this location
+    Accept,
+    /// Keep showing the current location; the new country is not confirmed yet
+    Hold,
+}
+
+/// Tracks displayed country and pending flaps
+#[derive(Debug, Default)]
+pub struct CountryHysteresis {
+    /// Country currently displayed
+    displayed: Option<String>,
+    /// When the displayed country last changed
+    last_change: Option<Instant>,
+    /// Candidate country and how many consecutive lookups reported it
+    pending: Option<(String, u32)>,
+}
+
+impl CountryHysteresis {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Feeds a lookup result observed at `now` and decides whether to display it
+    pub fn observe(&mut self, info: &GeoInfo, now: Instant) -> Decision {
+        let country = info.country_code.as_str();
+
+        let Some(displayed) = self.displayed.as_deref() else {
+            self.displayed = Some(country.to_string());
+            return Decision::Accept;
+        };
+
+        if displayed == country {
+            // Still in the same country: the flap (if any) resolved itself
+            self.pending = None;
+            return Decision::Accept;
+        }
+
+        let flapping = self.last_change.is_some_and(|t| now.duration_since(t) < FLAP_WINDOW);
+        if flapping {
+            let seen = match &self.pending {
+                Some((candidate, count)) if candidate == country => count + 1,
+                _ => 1,
+            };
+            if seen < CONFIRMATIONS {
+                self.pending = Some((country.to_string(), seen));
+                return Decision::Hold;
+            }
+        }
+
+        self.displayed = Some(country.to_string());
+        self.last_change = Some(now);
+        self.pending = None;
+        Decision::Accept
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn geo(country_code: &str) -> GeoInfo {
+        GeoInfo {
+            query: "203.0.113.1".to_string(),
+            country_code: country_code.to_string(),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_first_change_is_immediate() {
+        let mut h = CountryHysteresis::new();
+        let t = Instant::now();
+        assert_eq!(h.observe(&geo("VN"), t), Decision::Accept);
+        assert_eq!(h.observe(&geo("CH"), t + Duration::from_secs(60)), Decision::Accept);
+    }
+
+    #[test]
+    fn test_flap_needs_confirmation() {
+        let mut h = CountryHysteresis::new();
+        let t = Instant::now();
+        h.observe(&geo("VN"), t);
+        h.observe(&geo("CH"), t + Duration::from_secs(60));
+
+        // Flipping back within the window is held until confirmed
+        assert_eq!(h.observe(&geo("VN"), t + Duration::from_secs(120)), Decision::Hold);
+        assert_eq!(h.observe(&geo("CH"), t + Duration::from_secs(180)), Decision::Accept);
+        assert_eq!(h.observe(&geo("VN"), t + Duration::from_secs(240)), Decision::Hold);
+        assert_eq!(h.observe(&geo("VN"), t + Duration::from_secs(300)), Decision::Accept);
+    }
+
+    #[test]
+    fn test_change_after_window_is_immediate() {
+        let mut h = CountryHysteresis::new();
+        let t = Instant::now();
+        h.observe(&geo("VN"), t);
+        h.observe(&geo("CH"), t + Duration::from_secs(60));
+        assert_eq!(h.observe(&geo("VN"), t + FLAP_WINDOW + Duration::from_secs(61)), Decision::Accept);
+    }
+}
@@ -0,0 +1,114 @@
+//! User-supplied flag artwork from `[icons] theme_dir`
+//! `<cc>.png` files there (and `<cc>.svg` in builds with SVG rendering) replace the embedded
+//! flags. Files are looked up on every icon update, so edits show at the next refresh.
+
+use crate::icons::{FlagIcon, ICON_SIZE};
+use image::{imageops, RgbaImage};
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::{LazyLock, Mutex};
+use std::time::SystemTime;
+use thiserror::Error;
+
+#[cfg(feature = "flag-download")]
+const EXTENSIONS: &[&str] = &["png", "svg"];
+#[cfg(not(feature = "flag-download"))]
+const EXTENSIONS: &[&str] = &["png"];
+
+/// Loaded files with their modification time; `None` for ones that failed to load, so a
+/// broken file is reported once per edit
+type Loaded = HashMap<PathBuf, (SystemTime, Option<FlagIcon>)>;
+static LOADED: LazyLock<Mutex<Loaded>> = LazyLock::new(|| Mutex::new(HashMap::new()));
+
+/// Errors while loading a theme flag
+#[derive(Debug, Error)]
+pub enum IconThemeError {
+    #[error("IO error: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("Invalid image: {0}")]
+    Image(#[from] image::ImageError),
+    #[error("Failed to render SVG: {0}")]
+    Render(String),
+}
+
+/// Codes end up in a path: letters and the dash of subdivisions like "gb-sct" only
+fn file_stem(code: &str) -> Option<String> {
+    let code = code.to_lowercase();
+    let valid = !code.is_empty() && code.bytes().all(|b| b.is_ascii_lowercase() || b == b'-');
+    valid.then_some(code)
+}
+
+/// Gets the theme's flag for `code`, if `theme_dir` is set and has one
+pub fn lookup(code: &str) -> Option<FlagIcon> {
+    let dir = crate::config::current().icons.theme_dir.clone()?;
+    let stem = file_stem(code)?;
+    let mut loaded = LOADED.lock().ok()?;
+    for extension in EXTENSIONS {
+        let path = dir.join(format!("{}.{}", stem, extension));
+        let Ok(modified) = fs::metadata(&path).and_then(|m| m.modified()) else { continue };
+        if let Some((at, icon)) = loaded.get(&path) {
+            if *at == modified {
+                return icon.clone();
+            }
+        }
+        let icon = match load(&path) {
+            Ok(icon) => Some(icon),
+            Err(e) => {
+                tracing::warn!("Ignoring theme flag {}: {}", path.display(), e);
+                None
+            }
+        };
+        loaded.insert(path, (modified, icon.clone()));
+        if icon.is_some() {
+            return icon;
+        }
+    }
+    None
+}
+
+fn load(path: &Path) -> Result<FlagIcon, IconThemeError> {
+    let data = fs::read(path)?;
+    #[cfg(feature = "flag-download")]
+    let data = if path.extension().is_some_and(|e| e == "svg") {
+        crate::flag_cache::render(&data).map_err(|e| IconThemeError::Render(e.to_string()))?
+    } else {
+        data
+    };
+    Ok(FlagIcon::from_rgba(&fit(&image::load_from_memory(&data)?.to_rgba8())))
+}
+
+/// Scales `image` to fit `ICON_SIZE` and centers it on a transparent square, like the
+/// embedded 4:3 flags
+fn fit(image: &RgbaImage) -> RgbaImage {
+    let (width, height) = image.dimensions();
+    let scale = ICON_SIZE as f32 / width.max(height).max(1) as f32;
+    let (w, h) = (((width as f32 * scale) as u32).max(1), ((height as f32 * scale) as u32).max(1));
+    let scaled = imageops::resize(image, w, h, imageops::FilterType::Lanczos3);
+    let mut icon = RgbaImage::new(ICON_SIZE, ICON_SIZE);
+    imageops::overlay(&mut icon, &scaled, ((ICON_SIZE - w) / 2) as i64, ((ICON_SIZE - h) / 2) as i64);
+    icon
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_file_stem() {
+        assert_eq!(file_stem("DE").as_deref(), Some("de"));
+        assert_eq!(file_stem("gb-sct").as_deref(), Some("gb-sct"));
+        assert_eq!(file_stem("../de"), None);
+        assert_eq!(file_stem(""), None);
+    }
+
+    #[test]
+    fn test_fit() {
+        let flag = RgbaImage::from_pixel(40, 30, image::Rgba([255, 0, 0, 255]));
+        let icon = fit(&flag);
+        assert_eq!(icon.dimensions(), (ICON_SIZE, ICON_SIZE));
+        // 4:3 leaves transparent bars above and below
+        assert_eq!(icon.get_pixel(ICON_SIZE / 2, 2)[3], 0);
+        assert_eq!(icon.get_pixel(ICON_SIZE / 2, ICON_SIZE / 2).0, [255, 0, 0, 255]);
+    }
+}
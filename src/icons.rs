@@ -1,40 +1,280 @@
 //! Flag icons module - provides embedded country flag lookup
-//! Flags are generated at build time from circle-flags SVGs.
+//! Flags are generated at build time from circle-flags SVGs. Part of the public
+//! library API: the functions and types here are kept stable across releases.
 
-// Include auto-generated flag data
-include!(concat!(env!("OUT_DIR"), "/icons_data.rs"));
+use image::{imageops, RgbaImage};
+use std::sync::{Arc, Mutex};
 
-/// Icon dimensions for tray display
-pub const ICON_SIZE: u32 = 64;
+/// Auto-generated flag data; private so the generated names are not part of the API
+mod data {
+    include!(concat!(env!("OUT_DIR"), "/icons_data.rs"));
+}
+use data::{FLAGS, RECT_FLAGS, STATUS_ETHERNET, STATUS_GLOBE, STATUS_OFFLINE, STATUS_UNKNOWN, STATUS_VPN, STATUS_WIFI};
+
+/// Width and height, in pixels, of the largest of `PIXMAP_SIZES`
+pub const ICON_SIZE: u32 = data::ICON_SIZE;
+/// Pixmap sizes offered to the tray host, ascending (`NETWORK_MONITOR_ICON_SIZES` at build time)
+pub const PIXMAP_SIZES: &[u32] = data::PIXMAP_SIZES;
+
+/// A square ARGB32 image (straight alpha, network byte order), the tray's pixmap format
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Pixmap {
+    pub size: u32,
+    pub argb: Vec<u8>,
+}
 
-/// Represents a flag icon with PNG data
+/// A flag or status icon as one pixmap per `PIXMAP_SIZES` entry, smallest first. Clones
+/// share the pixmaps.
+#[derive(Debug, Clone, PartialEq, Eq)]
 pub struct FlagIcon {
-    /// Raw PNG bytes
-    pub data: &'static [u8],
+    pub pixmaps: Arc<[Pixmap]>,
+}
+
+impl FlagIcon {
+    /// `rgba` scaled to every `PIXMAP_SIZES` entry, for artwork that is not embedded
+    pub fn from_rgba(rgba: &RgbaImage) -> Self {
+        let pixmaps = PIXMAP_SIZES
+            .iter()
+            .map(|&size| {
+                let resized;
+                let rgba = if rgba.dimensions() == (size, size) {
+                    rgba
+                } else {
+                    resized = imageops::resize(rgba, size, size, imageops::FilterType::Lanczos3);
+                    &resized
+                };
+                Pixmap { size, argb: rgba.pixels().flat_map(|p| [p[3], p[0], p[1], p[2]]).collect() }
+            })
+            .collect();
+        FlagIcon { pixmaps }
+    }
+
+    /// Fully transparent at every size
+    fn blank() -> Self {
+        let pixmaps = PIXMAP_SIZES.iter().map(|&size| Pixmap { size, argb: vec![0; (size * size * 4) as usize] }).collect();
+        FlagIcon { pixmaps }
+    }
+}
+
+/// What to show when there is no flag for the current location
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum FallbackPolicy {
+    /// Generic globe artwork
+    Globe,
+    /// Question-mark artwork
+    #[default]
+    Unknown,
+    /// Keep the last flag that was shown (question mark if there was none)
+    LastKnown,
+}
+
+impl FallbackPolicy {
+    pub const NAMES: &'static [&'static str] = &["globe", "unknown", "last-known"];
+
+    pub fn parse(name: &str) -> Option<Self> {
+        match name {
+            "globe" => Some(Self::Globe),
+            "unknown" => Some(Self::Unknown),
+            "last-known" => Some(Self::LastKnown),
+            _ => None,
+        }
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            FallbackPolicy::Globe => "globe",
+            FallbackPolicy::Unknown => "unknown",
+            FallbackPolicy::LastKnown => "last-known",
+        }
+    }
+}
+
+/// Shape of the flag artwork
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum FlagStyle {
+    /// circle-flags
+    #[default]
+    Circle,
+    /// Traditional 4:3 flags (flag-icons), easier to tell apart at small sizes;
+    /// circle flags are used where a rectangular one is not embedded
+    Rectangle,
+}
+
+impl FlagStyle {
+    pub const NAMES: &'static [&'static str] = &["circle", "rectangle"];
+
+    pub fn parse(name: &str) -> Option<Self> {
+        match name {
+            "circle" => Some(Self::Circle),
+            "rectangle" => Some(Self::Rectangle),
+            _ => None,
+        }
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            FlagStyle::Circle => "circle",
+            FlagStyle::Rectangle => "rectangle",
+        }
+    }
+}
+
+/// What the tray icon shows
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum IconMode {
+    /// Flag of the current country
+    #[default]
+    Flag,
+    /// Wi-Fi, Ethernet, VPN or offline glyph; the country moves to the tooltip and menu
+    Connection,
+    /// Monochrome globe with the country code, like GNOME's symbolic icons
+    Symbolic,
+}
+
+impl IconMode {
+    pub const NAMES: &'static [&'static str] = &["flag", "connection", "symbolic"];
+
+    pub fn parse(name: &str) -> Option<Self> {
+        match name {
+            "flag" => Some(Self::Flag),
+            "connection" => Some(Self::Connection),
+            "symbolic" => Some(Self::Symbolic),
+            _ => None,
+        }
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            IconMode::Flag => "flag",
+            IconMode::Connection => "connection",
+            IconMode::Symbolic => "symbolic",
+        }
+    }
+}
+
+/// Embedded artwork that is not a country flag
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StatusArt {
+    Globe,
+    Unknown,
+    Offline,
+    /// Connection type glyphs for `IconMode::Connection`
+    Wifi,
+    Ethernet,
+    Vpn,
 }
 
-/// Gets the flag icon for a given ISO 3166-1 alpha-2 country code.
-/// Returns a fallback globe icon if the country code is not found.
-pub fn get_flag(country_code: &str) -> FlagIcon {
+/// Last country code a flag was resolved for, used by `FallbackPolicy::LastKnown`
+static LAST_KNOWN: Mutex<Option<String>> = Mutex::new(None);
+
+/// Embedded artwork, by index into the generated tables
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Embedded {
+    Flag(usize),
+    Rect(usize),
+    Status(StatusArt),
+}
+
+impl Embedded {
+    fn data(self) -> &'static [u8] {
+        match self {
+            Embedded::Flag(index) => FLAGS[index].1,
+            Embedded::Rect(index) => RECT_FLAGS[index].1,
+            Embedded::Status(StatusArt::Globe) => STATUS_GLOBE,
+            Embedded::Status(StatusArt::Unknown) => STATUS_UNKNOWN,
+            Embedded::Status(StatusArt::Offline) => STATUS_OFFLINE,
+            Embedded::Status(StatusArt::Wifi) => STATUS_WIFI,
+            Embedded::Status(StatusArt::Ethernet) => STATUS_ETHERNET,
+            Embedded::Status(StatusArt::Vpn) => STATUS_VPN,
+        }
+    }
+}
+
+/// Embedded icons kept expanded by `expanded`
+const EXPANDED_CAPACITY: usize = 8;
+/// Least recently used first
+static EXPANDED: Mutex<Vec<(Embedded, FlagIcon)>> = Mutex::new(Vec::new());
+
+/// Index of `country_code` in a generated table, which is sorted by code
+fn lookup(table: &[(&str, &[u8])], country_code: &str) -> Option<usize> {
     let code = country_code.to_lowercase();
+    table.binary_search_by(|(c, _)| (*c).cmp(code.as_str())).ok()
+}
 
-    let data = FLAGS
-        .get(code.as_str())
-        .copied()
-        .unwrap_or_else(|| {
-            // Fallback: try "xx" (unknown) or use first available flag
-            FLAGS.get("xx").copied().unwrap_or_else(|| {
-                FLAGS.values().next().copied().unwrap_or(&[])
-            })
-        });
+/// The pixmaps of `art`. The last few stay expanded, so switching between a handful of
+/// countries (e.g. a VPN going up and down) neither inflates nor allocates again.
+fn expanded(art: Embedded) -> Option<FlagIcon> {
+    let mut cache = EXPANDED.lock().unwrap_or_else(|e| e.into_inner());
+    if let Some(index) = cache.iter().position(|(cached, _)| *cached == art) {
+        let entry = cache.remove(index);
+        let icon = entry.1.clone();
+        cache.push(entry);
+        return Some(icon);
+    }
+    let icon = FlagIcon { pixmaps: split_pixmaps(&expand(art.data())?)?.into() };
+    if cache.len() >= EXPANDED_CAPACITY {
+        cache.remove(0);
+    }
+    cache.push((art, icon.clone()));
+    Some(icon)
+}
 
-    FlagIcon { data }
+/// Gets the embedded artwork for a non-country state
+pub fn status_icon(art: StatusArt) -> FlagIcon {
+    // Generated by build.rs, so this only fails on a broken build
+    expanded(Embedded::Status(art)).unwrap_or_else(FlagIcon::blank)
 }
 
-/// Checks if a flag exists for the given country code
-#[allow(dead_code)]
+/// Gets the flag icon for a given ISO 3166-1 alpha-2 country code, if one is in the
+/// user's `theme_dir`, embedded, or was downloaded (see flag_cache)
+pub fn get_flag(country_code: &str) -> Option<FlagIcon> {
+    let icon = crate::icon_theme::lookup(country_code)
+        .or_else(|| expanded(Embedded::Flag(lookup(FLAGS, country_code)?)));
+    #[cfg(feature = "flag-download")]
+    let icon = icon.or_else(|| crate::flag_cache::cached(country_code));
+    icon
+}
+
+/// Gets the flag for `country_code` in `style`, falling back to the circle flag; the
+/// user's `theme_dir` wins over either
+pub fn get_styled_flag(country_code: &str, style: FlagStyle) -> Option<FlagIcon> {
+    match style {
+        FlagStyle::Rectangle => crate::icon_theme::lookup(country_code)
+            .or_else(|| expanded(Embedded::Rect(lookup(RECT_FLAGS, country_code)?)))
+            .or_else(|| get_flag(country_code)),
+        FlagStyle::Circle => get_flag(country_code),
+    }
+}
+
+/// Resolves the icon to display for `country_code`, applying `policy` when
+/// there is no code or no flag for it. Never returns empty data.
+pub fn resolve_icon(country_code: Option<&str>, policy: FallbackPolicy) -> FlagIcon {
+    resolve_styled_icon(country_code, policy, FlagStyle::Circle)
+}
+
+/// `resolve_icon` with flags drawn in `style`
+pub fn resolve_styled_icon(country_code: Option<&str>, policy: FallbackPolicy, style: FlagStyle) -> FlagIcon {
+    let mut last_known = LAST_KNOWN.lock().unwrap_or_else(|e| e.into_inner());
+    let flag = |code: &str| get_styled_flag(code, style);
+
+    if let Some((code, icon)) = country_code.and_then(|c| flag(c).map(|icon| (c, icon))) {
+        *last_known = Some(code.to_lowercase());
+        return icon;
+    }
+
+    match policy {
+        FallbackPolicy::Globe => status_icon(StatusArt::Globe),
+        FallbackPolicy::Unknown => status_icon(StatusArt::Unknown),
+        FallbackPolicy::LastKnown => last_known
+            .as_deref()
+            .and_then(flag)
+            .unwrap_or_else(|| status_icon(StatusArt::Unknown)),
+    }
+}
+
+/// Checks if a flag is embedded for the given country code
 pub fn has_flag(country_code: &str) -> bool {
-    FLAGS.contains_key(country_code.to_lowercase().as_str())
+    lookup(FLAGS, country_code).is_some()
 }
 
 /// Returns the number of available flag icons
@@ -42,31 +282,148 @@ pub fn flag_count() -> usize {
     FLAGS.len()
 }
 
+#[cfg(feature = "compressed-icons")]
+fn expand(embedded: &[u8]) -> Option<Vec<u8>> {
+    use std::io::Read;
+    let mut argb = Vec::new();
+    flate2::read::ZlibDecoder::new(embedded).read_to_end(&mut argb).ok()?;
+    Some(argb)
+}
+
+#[cfg(not(feature = "compressed-icons"))]
+fn expand(embedded: &[u8]) -> Option<Vec<u8>> {
+    Some(embedded.to_vec())
+}
+
+/// Splits the concatenated pixmaps, smallest first as in `PIXMAP_SIZES`
+fn split_pixmaps(mut argb: &[u8]) -> Option<Vec<Pixmap>> {
+    let mut pixmaps = Vec::with_capacity(PIXMAP_SIZES.len());
+    for &size in PIXMAP_SIZES {
+        let (pixels, rest) = argb.split_at_checked((size * size * 4) as usize)?;
+        pixmaps.push(Pixmap { size, argb: pixels.to_vec() });
+        argb = rest;
+    }
+    argb.is_empty().then_some(pixmaps)
+}
+
+/// Moves every pixel of ARGB data `amount` (0..=1) of the way to its gray value;
+/// 1 gives plain grayscale, alpha is kept
+pub fn desaturate(argb: &mut [u8], amount: f32) {
+    for pixel in argb.chunks_exact_mut(4) {
+        // Rec. 709 luma
+        let gray = 0.2126 * pixel[1] as f32 + 0.7152 * pixel[2] as f32 + 0.0722 * pixel[3] as f32;
+        for channel in &mut pixel[1..] {
+            *channel = (*channel as f32 + (gray - *channel as f32) * amount).round() as u8;
+        }
+    }
+}
+
+/// Lowercase codes of all embedded flags, sorted (includes subdivisions like "gb-sct")
+pub fn flag_codes() -> Vec<&'static str> {
+    FLAGS.iter().map(|(code, _)| *code).collect()
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
 
     #[test]
     fn test_get_flag_existing() {
-        let flag = get_flag("us");
-        assert!(!flag.data.is_empty());
+        let flag = get_flag("us").unwrap();
+        assert_eq!(flag.pixmaps.len(), PIXMAP_SIZES.len());
     }
 
     #[test]
     fn test_get_flag_uppercase() {
-        let flag = get_flag("US");
-        assert!(!flag.data.is_empty());
+        assert!(get_flag("US").is_some());
     }
 
     #[test]
     fn test_get_flag_nonexistent() {
-        // Should return fallback, not panic
-        let flag = get_flag("zz");
-        assert!(!flag.data.is_empty());
+        assert!(get_flag("zz").is_none());
+    }
+
+    #[test]
+    fn test_resolve_icon_fallback() {
+        let unknown = resolve_icon(Some("zz"), FallbackPolicy::Unknown);
+        assert_eq!(unknown, status_icon(StatusArt::Unknown));
+        assert_eq!(resolve_icon(None, FallbackPolicy::Globe), status_icon(StatusArt::Globe));
+
+        resolve_icon(Some("VN"), FallbackPolicy::LastKnown);
+        let last = resolve_icon(None, FallbackPolicy::LastKnown);
+        assert_eq!(last, get_flag("vn").unwrap());
+        assert_ne!(status_icon(StatusArt::Offline), FlagIcon::blank());
+    }
+
+    #[test]
+    fn test_flag_style() {
+        // Every style has a flag wherever a circle flag exists
+        let rect = get_styled_flag("us", FlagStyle::Rectangle).unwrap();
+        let expected = lookup(RECT_FLAGS, "us").map_or_else(|| get_flag("us"), |i| expanded(Embedded::Rect(i)));
+        assert_eq!(Some(rect), expected);
+        assert_eq!(get_styled_flag("us", FlagStyle::Circle), get_flag("us"));
+        assert!(FlagStyle::NAMES.iter().all(|n| FlagStyle::parse(n).is_some_and(|s| s.as_str() == *n)));
+    }
+
+    #[test]
+    fn test_expanded_cache() {
+        let us = get_flag("us").unwrap();
+        assert!(Arc::ptr_eq(&us.pixmaps, &get_flag("US").unwrap().pixmaps));
+        // Older entries are evicted once more flags have been expanded
+        for code in flag_codes().into_iter().filter(|&c| c != "us").take(EXPANDED_CAPACITY) {
+            get_flag(code);
+        }
+        let again = get_flag("us").unwrap();
+        assert!(!Arc::ptr_eq(&us.pixmaps, &again.pixmaps));
+        assert_eq!(us, again);
+    }
+
+    #[test]
+    fn test_pixmaps() {
+        for icon in [get_flag("us").unwrap(), status_icon(StatusArt::Offline)] {
+            let sizes: Vec<u32> = icon.pixmaps.iter().map(|p| p.size).collect();
+            assert_eq!(sizes, PIXMAP_SIZES);
+            assert!(icon.pixmaps.iter().all(|p| p.argb.len() == (p.size * p.size * 4) as usize));
+        }
+        // Circle flag: transparent corner, opaque middle
+        let flag = get_flag("us").unwrap();
+        let largest = flag.pixmaps.last().unwrap();
+        let alpha = |x: u32, y: u32| largest.argb[((y * largest.size + x) * 4) as usize];
+        assert_eq!((alpha(0, 0), alpha(largest.size / 2, largest.size / 2)), (0, 255));
+        assert!(split_pixmaps(&[0; 7]).is_none());
+    }
+
+    #[test]
+    fn test_from_rgba() {
+        let icon = FlagIcon::from_rgba(&RgbaImage::from_pixel(ICON_SIZE, ICON_SIZE, image::Rgba([255, 0, 0, 200])));
+        assert_eq!(icon.pixmaps.iter().map(|p| p.size).collect::<Vec<_>>(), PIXMAP_SIZES);
+        assert_eq!(icon.pixmaps.last().unwrap().argb[..4], [200, 255, 0, 0]);
+    }
+
+    #[test]
+    fn test_desaturate() {
+        let mut pixels = [200, 255, 0, 0, 200, 255, 0, 0];
+        desaturate(&mut pixels, 1.0);
+        assert_eq!(pixels, [200, 54, 54, 54, 200, 54, 54, 54]);
+
+        let mut pixel = [255, 255, 0, 0];
+        desaturate(&mut pixel, 0.5);
+        assert_eq!(pixel, [255, 155, 27, 27]);
+    }
+
+    #[test]
+    fn test_connection_art() {
+        for art in [StatusArt::Wifi, StatusArt::Ethernet, StatusArt::Vpn] {
+            assert_ne!(status_icon(art), FlagIcon::blank());
+        }
+        assert_eq!(IconMode::parse("connection"), Some(IconMode::Connection));
+        assert!(IconMode::NAMES.iter().all(|n| IconMode::parse(n).is_some_and(|m| m.as_str() == *n)));
     }
 
     #[test]
     fn test_flag_count() {
         assert!(flag_count() > 100, "Expected more than 100 flags");
+        assert_eq!(flag_codes().len(), flag_count());
+        assert!(flag_codes().iter().all(|c| has_flag(c)));
     }
 }
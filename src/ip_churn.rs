@@ -0,0 +1,92 @@
+//! IP change rate anomaly detection
+//! Counts public IP changes over the last hour; beyond `[notifications] max_ip_changes_per_hour`
+//! one aggregated warning replaces the per-change notifications until things calm down.
+
+use std::collections::VecDeque;
+use std::sync::{LazyLock, Mutex};
+use std::time::{Duration, Instant};
+
+/// Window the changes are counted in
+pub const WINDOW: Duration = Duration::from_secs(60 * 60);
+
+static TRACKER: LazyLock<Mutex<ChangeRate>> = LazyLock::new(|| Mutex::new(ChangeRate::default()));
+
+/// What to do about a change
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Verdict {
+    /// Normal rate, notify as usual
+    Normal,
+    /// Over the limit: `first` is true for the change that crossed it
+    Unstable { changes: usize, first: bool },
+}
+
+/// Recent IP changes and whether the warning was shown for the current burst
+#[derive(Debug, Default)]
+pub struct ChangeRate {
+    changes: VecDeque<Instant>,
+    warned: bool,
+}
+
+impl ChangeRate {
+    /// Records a change at `now`; `limit` 0 never flags
+    pub fn record(&mut self, now: Instant, limit: u32) -> Verdict {
+        while self.changes.front().is_some_and(|t| now.duration_since(*t) >= WINDOW) {
+            self.changes.pop_front();
+        }
+        self.changes.push_back(now);
+        if limit == 0 || self.changes.len() <= limit as usize {
+            self.warned = false;
+            return Verdict::Normal;
+        }
+        let first = !self.warned;
+        self.warned = true;
+        Verdict::Unstable { changes: self.changes.len(), first }
+    }
+}
+
+/// Tracks a move from `old_ip` to `new_ip`; returns true while per-change notifications
+/// should be held back. Crossing the limit shows the single aggregated warning.
+pub fn observe(old_ip: &str, new_ip: &str) -> bool {
+    if old_ip == new_ip || old_ip.is_empty() {
+        return false;
+    }
+    let limit = crate::config::current().notifications.max_ip_changes_per_hour;
+    let verdict = TRACKER.lock().map(|mut t| t.record(Instant::now(), limit)).unwrap_or(Verdict::Normal);
+    match verdict {
+        Verdict::Normal => false,
+        Verdict::Unstable { changes, first } => {
+            if first {
+                tracing::warn!("Public IP changed {} times in the last hour", changes);
+                crate::notifier::notify(
+                    format!(
+                        "Public IP changed {} times in the last hour; the connection or VPN looks unstable. \
+                         Further change alerts are held back until it settles.",
+                        changes
+                    ),
+                    10000,
+                );
+            }
+            true
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_record() {
+        let start = Instant::now();
+        let mut rate = ChangeRate::default();
+        for i in 0..3 {
+            assert_eq!(rate.record(start + Duration::from_secs(i), 3), Verdict::Normal);
+        }
+        assert_eq!(rate.record(start + Duration::from_secs(3), 3), Verdict::Unstable { changes: 4, first: true });
+        assert_eq!(rate.record(start + Duration::from_secs(4), 3), Verdict::Unstable { changes: 5, first: false });
+
+        // An hour later the old changes have aged out
+        assert_eq!(rate.record(start + WINDOW + Duration::from_secs(4), 3), Verdict::Normal);
+        assert_eq!(ChangeRate::default().record(start, 0), Verdict::Normal);
+    }
+}
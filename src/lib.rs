@@ -0,0 +1,114 @@
+//! Network Monitor library
+//! `icons` (embedded flags and fallback artwork), `countries` (ISO 3166 dataset) and `watcher`
+//! (a stream of location changes) are the stable public API for other tray/bar projects; the
+//! other modules back the binary.
+
+pub mod countries;
+pub mod icons;
+pub mod watcher;
+
+#[doc(hidden)]
+pub mod autostart;
+#[doc(hidden)]
+pub mod backup;
+#[doc(hidden)]
+pub mod badge;
+#[doc(hidden)]
+pub mod clock;
+#[doc(hidden)]
+pub mod code_flag;
+#[doc(hidden)]
+pub mod confidence;
+#[doc(hidden)]
+pub mod config;
+#[doc(hidden)]
+pub mod diagnostics;
+#[doc(hidden)]
+pub mod dns_leak;
+#[doc(hidden)]
+pub mod doctor;
+#[doc(hidden)]
+pub mod environment;
+#[doc(hidden)]
+pub mod events;
+#[doc(hidden)]
+pub mod first_hop;
+#[cfg(feature = "flag-download")]
+#[doc(hidden)]
+pub mod flag_cache;
+#[doc(hidden)]
+pub mod flatpak;
+#[doc(hidden)]
+pub mod geo;
+#[doc(hidden)]
+pub mod history;
+#[doc(hidden)]
+pub mod hooks;
+#[doc(hidden)]
+pub mod hosts;
+#[doc(hidden)]
+pub mod hysteresis;
+#[doc(hidden)]
+pub mod icon_theme;
+#[doc(hidden)]
+pub mod ip_churn;
+#[doc(hidden)]
+pub mod location_cache;
+#[doc(hidden)]
+pub mod metrics;
+#[doc(hidden)]
+pub mod mmdb;
+#[doc(hidden)]
+pub mod mtu;
+#[doc(hidden)]
+pub mod mqtt;
+#[doc(hidden)]
+pub mod netns;
+#[doc(hidden)]
+pub mod network;
+#[doc(hidden)]
+pub mod notifier;
+#[doc(hidden)]
+pub mod outages;
+#[doc(hidden)]
+pub mod pixel_font;
+#[doc(hidden)]
+pub mod privacy;
+#[doc(hidden)]
+pub mod raw_log;
+#[doc(hidden)]
+pub mod reverse_dns;
+#[doc(hidden)]
+pub mod rules;
+#[doc(hidden)]
+pub mod schedule;
+#[doc(hidden)]
+pub mod secrets;
+#[doc(hidden)]
+pub mod service;
+#[doc(hidden)]
+pub mod session_bus;
+#[doc(hidden)]
+pub mod shortcut;
+#[doc(hidden)]
+pub mod suspend;
+#[doc(hidden)]
+pub mod symbolic;
+#[doc(hidden)]
+pub mod tor;
+#[doc(hidden)]
+pub mod tray;
+#[doc(hidden)]
+pub mod trust;
+#[doc(hidden)]
+pub mod updater;
+#[doc(hidden)]
+pub mod usage;
+#[doc(hidden)]
+pub mod waybar;
+#[doc(hidden)]
+pub mod watchdog;
+#[doc(hidden)]
+pub mod webhook;
+#[doc(hidden)]
+pub mod wsl;
@@ -0,0 +1,98 @@
+//! Last known location per network
+//! Remembers the location seen on each NetworkManager connection (by UUID) in
+//! ~/.local/share/network-monitor/locations.json, reused when the geo lookup fails.
+
+use crate::geo::GeoInfo;
+use crate::history::Entry;
+use std::collections::HashMap;
+use std::fs;
+use std::path::PathBuf;
+use std::sync::{LazyLock, Mutex};
+use std::time::{Duration, UNIX_EPOCH};
+
+const CACHE_FILENAME: &str = "locations.json";
+/// Older entries are not trusted to still be right
+const MAX_AGE: Duration = Duration::from_secs(30 * 24 * 60 * 60);
+const REWRITE_AFTER: Duration = Duration::from_secs(24 * 60 * 60);
+
+/// Connection UUID → last location seen on it
+static CACHE: LazyLock<Mutex<HashMap<String, Entry>>> = LazyLock::new(|| Mutex::new(load()));
+
+fn cache_file() -> Option<PathBuf> {
+    dirs::data_dir().map(|p| p.join("network-monitor").join(CACHE_FILENAME))
+}
+
+fn load() -> HashMap<String, Entry> {
+    cache_file()
+        .and_then(|p| fs::read_to_string(p).ok())
+        .and_then(|c| serde_json::from_str(&c).ok())
+        .unwrap_or_default()
+}
+
+fn save(cache: &HashMap<String, Entry>) {
+    let Some(path) = cache_file() else { return };
+    if let Some(parent) = path.parent() {
+        let _ = fs::create_dir_all(parent);
+    }
+    let result = serde_json::to_string_pretty(cache)
+        .map_err(std::io::Error::other)
+        .and_then(|content| fs::write(path, content));
+    if let Err(e) = result {
+        tracing::warn!("Failed to save location cache: {}", e);
+    }
+}
+
+/// The cached location if it is recent enough, marked with when it was seen
+fn cached(entry: &Entry, now: u64) -> Option<GeoInfo> {
+    (now.saturating_sub(entry.timestamp) <= MAX_AGE.as_secs()).then(|| GeoInfo {
+        cached_at: Some(UNIX_EPOCH + Duration::from_secs(entry.timestamp)),
+        ..entry.to_geo()
+    })
+}
+
+/// Remembers `info` for the current network; skipped on a VPN, whose exit says
+/// nothing about the network underneath
+pub fn remember(info: &GeoInfo) {
+    if info.cached_at.is_some() || info.query.is_empty() || crate::network::vpn_active() {
+        return;
+    }
+    let Some(connection) = crate::network::current_connection() else { return };
+    let Ok(mut cache) = CACHE.lock() else { return };
+    let entry = Entry::new(info, crate::history::now());
+    // Unchanged entries only get their timestamp refreshed now and then
+    let unchanged = cache.get(&connection.uuid).is_some_and(|old| {
+        old.same_location(&entry) && entry.timestamp.saturating_sub(old.timestamp) < REWRITE_AFTER.as_secs()
+    });
+    if unchanged {
+        return;
+    }
+    cache.insert(connection.uuid, entry);
+    if !crate::privacy::no_log() {
+        save(&cache);
+    }
+}
+
+/// The last location seen on the current network, for when the lookup failed
+pub fn current() -> Option<GeoInfo> {
+    if crate::network::vpn_active() {
+        return None;
+    }
+    let connection = crate::network::current_connection()?;
+    let cache = CACHE.lock().ok()?;
+    cached(cache.get(&connection.uuid)?, crate::history::now())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_cached() {
+        let info = GeoInfo { query: "203.0.113.7".to_string(), country_code: "VN".to_string(), ..Default::default() };
+        let entry = Entry::new(&info, 1_000);
+        let hit = cached(&entry, 1_000 + 3600).unwrap();
+        assert_eq!(hit.country_code, "VN");
+        assert_eq!(hit.cached_at, Some(UNIX_EPOCH + Duration::from_secs(1_000)));
+        assert_eq!(cached(&entry, 1_000 + MAX_AGE.as_secs() + 1), None);
+    }
+}
@@ -3,107 +3,203 @@
 //! Features:
 //! - Displays country flag in system tray based on geo-IP location
 //! - Shows IP, country, city, ISP on click
-//! - Auto-refreshes every 1 minute
+//! - Auto-refreshes every minute (configurable in config.toml, reloaded live)
 //! - Refreshes on network connectivity changes
 //! - Optional autostart on login
+//! - Optional monitoring from inside a named network namespace
+//! - Adapts to WSL, containers and VMs where NetworkManager may be missing
+//! - On-demand path MTU / black-hole diagnostic
+//! - Respects the desktop's Do Not Disturb mode
+//! - Suppresses country flapping with hysteresis
+//! - Optional blackout windows without automatic refreshes
+//! - Trusted networks; stricter checks everywhere else
+//! - Outage history and reliability stats per connection
+//! - Session bus API (org.btxtruong.NetworkMonitor): status properties, GetLocation/Refresh/Pause
 
-mod autostart;
-mod geo;
-mod icons;
-mod network;
-mod tray;
-mod updater;
-
-use crate::autostart::{is_autostart_enabled, remove_autostart, setup_autostart};
-use crate::geo::{fetch_location, GeoInfo};
-use crate::network::{watch_network_changes, NetworkEvent};
-use crate::tray::{NetworkTray, TrayCommand};
-use ksni::TrayMethods;
+mod cli;
+
+use crate::cli::{Command, ConfigAction, SecretsAction, UpdateArgs};
+#[cfg(feature = "flag-download")]
+use network_monitor::flag_cache;
+use network_monitor::autostart::{is_autostart_enabled, remove_autostart, setup_autostart};
+use network_monitor::config::Config;
+use network_monitor::environment::Environment;
+use network_monitor::geo::{self, fetch_location, GeoError, GeoInfo};
+use network_monitor::hooks::HookEvent;
+use network_monitor::hysteresis::{CountryHysteresis, Decision};
+use network_monitor::icons::FlagStyle;
+use network_monitor::notifier::{self, notify, notify_with, Priority};
+use network_monitor::network::{poll_connectivity, ConnectionInfo, probe_connectivity, watch_network_changes, NetworkError, NetworkEvent};
+use network_monitor::tray::{NetworkTray, PreviousLocation, TrayCommand, TrayHandle};
+use network_monitor::{
+    backup, clock, config, diagnostics, dns_leak, doctor, events, first_hop, flatpak, history, hooks, icons, ip_churn, location_cache, metrics, mqtt, mtu, netns, network, outages, privacy, raw_log, reverse_dns, rules, schedule, secrets, service, session_bus, shortcut, suspend, tor, trust, updater, watchdog, webhook, wsl,
+};
+use std::io::{IsTerminal, Write};
 use std::sync::{Arc, Mutex};
-use std::time::Duration;
+use std::time::{Duration, Instant};
 use tokio::sync::mpsc;
-use notify_rust::Notification;
 use tracing::{error, info, warn};
+use tracing_subscriber::fmt::writer::BoxMakeWriter;
 
-/// Internal events for update check results
+/// Internal events for update check and install results
 enum UpdateResult {
     Available(String),
     UpToDate,
+    /// The user agreed to install this version
+    Confirmed(String),
+    Declined,
+    Installed(std::path::PathBuf),
+    InstallFailed(String),
 }
 
-const REFRESH_INTERVAL: Duration = Duration::from_secs(1 * 60); // 1 minutes
+/// Minimum gap between manual refreshes (ip-api allows 45 req/min per IP)
+const REFRESH_COOLDOWN: Duration = Duration::from_secs(10);
+/// Editors save in several steps; wait for them to settle before reloading the config
+const CONFIG_SETTLE: Duration = Duration::from_millis(300);
+/// How long the update confirmation waits for an answer
+const UPDATE_CONFIRM_TIMEOUT_MS: i32 = 60_000;
+/// How often blackout window boundaries are checked
+const BLACKOUT_CHECK_INTERVAL: Duration = Duration::from_secs(30);
+/// Timer period while automatic refreshes are off, tokio intervals can't be zero
+const MANUAL_ONLY_TICK: Duration = Duration::from_secs(24 * 60 * 60);
+/// How often relative times in the menu ("Refreshed 5m ago") are redrawn
+const MENU_REFRESH_INTERVAL: Duration = Duration::from_secs(60);
+/// How often to look for a suspend/resume
+const RESUME_CHECK_INTERVAL: Duration = Duration::from_secs(10);
+/// Wait after a resume before refreshing, unless NetworkManager reports the reconnect first
+const RESUME_SETTLE: Duration = Duration::from_secs(5);
 
 #[tokio::main]
 async fn main() {
-    // Parse command line arguments
-    let args: Vec<String> = std::env::args().collect();
-
-    // Handle --update flag
-    if args.iter().any(|a| a == "--update" || a == "-u") {
-        run_update();
-        return;
-    }
-
-    // Handle --check flag
-    if args.iter().any(|a| a == "--check" || a == "-c") {
-        run_check().await;
-        return;
-    }
-
-    // Handle --version flag
-    if args.iter().any(|a| a == "--version" || a == "-v") {
-        println!("network-monitor {}", updater::VERSION);
-        return;
-    }
+    let run = match cli::parse(std::env::args()) {
+        Ok(Command::Run(run)) => run,
+        Ok(command) => {
+            run_command(command).await;
+            return;
+        }
+        Err(e) => {
+            eprintln!("error: {}", e);
+            eprintln!();
+            eprintln!("Run 'network-monitor --help' for usage.");
+            std::process::exit(2);
+        }
+    };
 
-    // Handle --help flag
-    if args.iter().any(|a| a == "--help" || a == "-h") {
-        print_help();
-        return;
+    let netns = run.netns;
+    if let Some(ref name) = netns {
+        if let Err(e) = netns::validate(name) {
+            eprintln!("Cannot use network namespace '{}': {}", name, e);
+            std::process::exit(1);
+        }
     }
 
-    // Initialize logging
+    // Initialize logging (stdout carries the JSON in Waybar mode)
+    let log_writer = if run.waybar {
+        BoxMakeWriter::new(|| privacy::RedactingWriter(std::io::stderr()))
+    } else {
+        BoxMakeWriter::new(|| privacy::RedactingWriter(std::io::stdout()))
+    };
     tracing_subscriber::fmt()
         .with_env_filter(
             tracing_subscriber::EnvFilter::from_default_env()
                 .add_directive(tracing::Level::INFO.into()),
         )
+        .with_writer(log_writer)
         .init();
 
     info!("Network Monitor v{} starting...", updater::VERSION);
     info!("Loaded {} flag icons", icons::flag_count());
+    if let Some(ref name) = netns {
+        info!("Monitoring from network namespace '{}'", name);
+    }
+    if let Some(addr) = run.metrics {
+        if let Err(e) = metrics::serve(addr).await {
+            error!("{}", e);
+            std::process::exit(1);
+        }
+    }
+
+    let mut config = match config::load_validated() {
+        Ok((config, warnings)) => {
+            report_config_warnings(&warnings);
+            config
+        }
+        Err(e) => {
+            warn!("Failed to load config, using defaults: {}", e);
+            notify(format!("Config error: {} - using defaults", e), 8000);
+            Config::default()
+        }
+    };
+    // --interval wins over the file, also across reloads
+    let interval_override = run.interval;
+    override_interval(&mut config, interval_override);
+    config::set_current(config.clone());
+
+    let environment = Environment::detect();
+    if environment != Environment::Native {
+        info!("Running in environment: {}", environment.label());
+    }
+    let missing = flatpak::missing();
+    if !missing.is_empty() {
+        let lines: Vec<String> = missing.iter().map(|p| format!("{}:\n  {}", p.describe(), p.fix())).collect();
+        for line in &lines {
+            warn!("Flatpak sandbox: {}", line.replace('\n', ""));
+        }
+        notify(format!("Missing Flatpak permissions\n{}", lines.join("\n")), 15000);
+    }
+
+    // Command channel from tray menu and D-Bus
+    let (cmd_tx, mut cmd_rx) = mpsc::channel::<TrayCommand>(16);
+
+    shortcut::spawn(cmd_tx.clone());
+    session_bus::spawn(cmd_tx.clone());
+    if let Err(e) = service::start(cmd_tx.clone()).await {
+        warn!("Failed to register {} on the session bus: {}", service::BUS_NAME, e);
+    }
 
     // Fetch location FIRST before showing tray (so flag is ready)
     info!("Fetching initial location...");
-    let initial_geo = match fetch_location().await {
+    let initial_geo = match locate(netns.as_deref()).await {
         Ok(info) => {
             info!("Initial location: {} ({}) - {}", info.country, info.country_code, info.query);
+            service::publish_connectivity(service::Connectivity::Connected);
             Some(info)
         }
         Err(e) => {
             warn!("Failed to fetch initial location: {}", e);
+            diagnostics::record_error(format!("Failed to fetch initial location: {}", e));
             None
         }
     };
 
     // Shared state for geo info (pre-populated with initial fetch)
-    let geo_info: Arc<Mutex<Option<GeoInfo>>> = Arc::new(Mutex::new(initial_geo));
-
-    // Command channel from tray menu
-    let (cmd_tx, mut cmd_rx) = mpsc::channel::<TrayCommand>(16);
+    let geo_info: Arc<Mutex<Option<GeoInfo>>> = Arc::new(Mutex::new(None));
+    let previous: Arc<Mutex<Option<PreviousLocation>>> = Arc::new(Mutex::new(None));
+    let hysteresis = Arc::new(Mutex::new(CountryHysteresis::new()));
+    if let Some(info) = initial_geo {
+        store_location(&geo_info, &previous, &hysteresis, info);
+    }
 
     // Network event channel
     let (net_tx, mut net_rx) = mpsc::channel::<NetworkEvent>(16);
 
-    // Check autostart status
-    let autostart_enabled = is_autostart_enabled();
+    // Check autostart status (the config may pin it)
+    let autostart_enabled = enforce_autostart(config.autostart, is_autostart_enabled());
     info!("Autostart enabled: {}", autostart_enabled);
 
     // Create tray (geo_info already has location data)
-    let tray = NetworkTray::new(geo_info.clone(), cmd_tx.clone(), autostart_enabled);
+    let mut tray = NetworkTray::new(geo_info.clone(), previous.clone(), cmd_tx.clone(), autostart_enabled, environment);
+    if geo_info.lock().is_ok_and(|g| g.is_some()) {
+        tray.connectivity = service::Connectivity::Connected;
+    }
 
-    // Start tray service - icon will show correct flag immediately
-    let tray_handle = tray.spawn().await.expect("Failed to spawn tray service");
+    // Start tray service - icon will show correct flag immediately.
+    // Without a StatusNotifier host this falls back to notification mode.
+    let mut tray_handle = if run.waybar {
+        TrayHandle::waybar(tray).await
+    } else {
+        TrayHandle::spawn(tray).await
+    };
 
     // Load persisted update state or check for updates (once per day)
     if let Some(persisted_version) = updater::load_available_update() {
@@ -117,63 +213,186 @@ async fn main() {
             tray.update_available = Some(new_version.clone());
         }).await;
     }
+    service::publish_update_state(updater::state());
+
+    // Trust is tracked per NetworkManager connection
+    let trust_enabled = netns.is_none() && environment.has_network_manager();
+    let mut current_network = if trust_enabled {
+        update_network_trust(None, &tray_handle).await
+    } else {
+        None
+    };
 
     // Spawn network monitor task
     let net_tx_clone = net_tx.clone();
+    let netns_monitor = netns.clone();
     tokio::spawn(async move {
-        if let Err(e) = watch_network_changes(net_tx_clone).await {
+        if let Err(e) = monitor_network(net_tx_clone, netns_monitor, environment).await {
             error!("Network monitor error: {}", e);
+            diagnostics::record_error(format!("Network monitor error: {}", e));
         }
     });
 
-    // Spawn periodic refresh task
-    let geo_info_refresh = geo_info.clone();
-    let tray_handle_refresh = tray_handle.clone();
-    tokio::spawn(async move {
-        let mut interval = tokio::time::interval(REFRESH_INTERVAL);
-        interval.tick().await; // Skip immediate tick
+    // Main event loop
+    let mut current_autostart = autostart_enabled;
+    let mut last_manual_refresh: Option<Instant> = None;
+    // Channel for receiving update check results
+    let (update_tx, mut update_rx) = mpsc::channel::<UpdateResult>(4);
+    // Periodic refresh; recreated when the configured interval changes
+    let mut active_interval = trust::refresh_interval(current_network.as_ref());
+    let mut refresh_timer = refresh_interval(active_interval);
+    let initial_interval = active_interval;
+    tray_handle.update(move |tray: &mut NetworkTray| tray.refresh_interval = initial_interval).await;
+    let mut blackout_timer = tokio::time::interval(BLACKOUT_CHECK_INTERVAL);
+    let mut resume_timer = tokio::time::interval(RESUME_CHECK_INTERVAL);
+    resume_timer.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Skip);
+    let mut sleep_detector = suspend::Detector::default();
+    let mut menu_timer = tokio::time::interval(MENU_REFRESH_INTERVAL);
+    menu_timer.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Skip);
+    let mut blackout: Option<schedule::BlackoutWindow> = None;
+    // Automatic refreshes paused via D-Bus Pause()
+    let mut paused_until: Option<Instant> = None;
+    // "Pause Monitoring" in the menu, also holds off network-event refreshes
+    let mut monitoring_paused = false;
+    // Config file change notifications
+    let (config_tx, mut config_rx) = mpsc::channel::<()>(4);
+    if let Err(e) = config::watch(config_tx) {
+        warn!("Config live reload unavailable: {}", e);
+    }
 
-        loop {
-            interval.tick().await;
-            info!("Periodic refresh triggered");
+    loop {
+        tokio::select! {
+            // Periodic location refresh
+            _ = refresh_timer.tick() => {
+                // A tick right after waking up is the resume refresh
+                if let Some(slept) = sleep_detector.check() {
+                    info!("Resumed after {} asleep", network_monitor::tray::format_duration(slept));
+                }
+                if active_interval.is_zero() || monitoring_paused {
+                    continue;
+                }
+                if blackout.is_some() {
+                    tracing::debug!("Periodic refresh skipped (blackout window)");
+                    continue;
+                }
+                if let Some(until) = paused_until {
+                    if Instant::now() < until {
+                        tracing::debug!("Periodic refresh skipped (paused)");
+                        continue;
+                    }
+                    info!("Pause ended, resuming automatic refreshes");
+                    paused_until = None;
+                    tray_handle.update(|tray: &mut NetworkTray| tray.paused_until = None).await;
+                }
+                info!("Periodic refresh triggered");
+                if privacy::enabled() {
+                    refresh_timer.reset_after(privacy::next_refresh(active_interval));
+                }
 
-            match fetch_location().await {
-                Ok(info) => {
-                    info!("Location updated: {} ({})", info.country, info.country_code);
-                    if let Ok(mut guard) = geo_info_refresh.lock() {
-                        *guard = Some(info);
+                match tray_handle.spinning(locate(netns.as_deref())).await {
+                    Ok(info) => {
+                        info!("Location updated: {} ({})", info.country, info.country_code);
+                        store_location(&geo_info, &previous, &hysteresis, info);
+                        tray_handle.update(|_| {}).await;
+                    }
+                    Err(e) => {
+                        warn!("Failed to refresh location: {}", e);
+                        diagnostics::record_error(format!("Failed to refresh location: {}", e));
+                        // Grays the flag
+                        tray_handle.update(|_| {}).await;
                     }
-                    tray_handle_refresh.update(|_| {}).await;
                 }
-                Err(e) => {
-                    warn!("Failed to refresh location: {}", e);
+            }
+
+            // The tray host caches the menu, so ages in it only move when it is rebuilt
+            _ = menu_timer.tick() => {
+                tray_handle.update(|_| {}).await;
+            }
+
+            // After a resume, refresh once the network settled instead of whenever the
+            // monotonic timer (which stood still during the suspend) comes due
+            _ = resume_timer.tick() => {
+                let Some(slept) = sleep_detector.check() else { continue };
+                info!("Resumed after {} asleep, refreshing shortly", network_monitor::tray::format_duration(slept));
+                events::record(events::Kind::Network, format!("resumed after {}", network_monitor::tray::format_duration(slept)));
+                refresh_timer.reset_after(RESUME_SETTLE);
+            }
+
+            // Enter/leave blackout windows; leaving one triggers a catch-up refresh
+            _ = blackout_timer.tick() => {
+                let now_active = schedule::active_blackout();
+                if now_active == blackout {
+                    continue;
+                }
+                match (blackout, now_active) {
+                    (_, Some(window)) => info!("Blackout window {} started, automatic refreshes paused", window),
+                    (Some(window), None) => {
+                        info!("Blackout window {} ended, resuming refreshes", window);
+                        refresh_timer.reset_immediately();
+                    }
+                    (None, None) => {}
                 }
+                blackout = now_active;
+                tray_handle.update(move |tray: &mut NetworkTray| {
+                    tray.blackout = now_active;
+                }).await;
             }
-        }
-    });
 
-    // Main event loop
-    let mut current_autostart = autostart_enabled;
-    // Channel for receiving update check results
-    let (update_tx, mut update_rx) = mpsc::channel::<UpdateResult>(4);
+            // Apply config file edits
+            Some(()) = config_rx.recv() => {
+                tokio::time::sleep(CONFIG_SETTLE).await;
+                while config_rx.try_recv().is_ok() {}
+
+                let mut new_config = match config::load_validated() {
+                    Ok((new_config, warnings)) => {
+                        report_config_warnings(&warnings);
+                        new_config
+                    }
+                    Err(e) => {
+                        warn!("Invalid config, keeping previous settings: {}", e);
+                        notify(format!("Config error: {} - keeping previous settings", e), 5000);
+                        continue;
+                    }
+                };
+                override_interval(&mut new_config, interval_override);
+                let old_config = config::current();
+                if *old_config == new_config {
+                    continue;
+                }
+                info!("Config reloaded");
+
+                let autostart_changed = new_config.autostart != old_config.autostart;
+                let channel_changed = new_config.updates.channel != old_config.updates.channel;
+                let hide_ip_changed = new_config.privacy.hide_ip != old_config.privacy.hide_ip;
+                let icons_changed = new_config.icons != old_config.icons;
+                config::set_current(new_config);
+                if channel_changed {
+                    info!("Update channel: {}", config::current().updates.channel.as_str());
+                    service::publish_update_state(updater::state());
+                }
+                if channel_changed || hide_ip_changed || icons_changed {
+                    tray_handle.update(|_| {}).await;
+                }
+
+                let wanted = trust::refresh_interval(current_network.as_ref());
+                retime(&mut refresh_timer, &mut active_interval, wanted, &tray_handle).await;
+                if autostart_changed {
+                    current_autostart = enforce_autostart(config::current().autostart, current_autostart);
+                    let new_autostart = current_autostart;
+                    tray_handle.update(move |tray: &mut NetworkTray| {
+                        tray.autostart_enabled = new_autostart;
+                    }).await;
+                }
+            }
 
-    loop {
-        tokio::select! {
             // Handle update check results from background task
             Some(result) = update_rx.recv() => {
                 match result {
                     UpdateResult::Available(new_version) => {
                         info!("Update available: {}", new_version);
                         updater::save_available_update(&new_version);
-                        let msg = format!("Update {} available! Click tray menu to install.", new_version);
-                        tokio::task::spawn_blocking(move || {
-                            let _ = Notification::new()
-                                .summary("Network Monitor")
-                                .body(&msg)
-                                .icon("network-monitor")
-                                .timeout(5000)
-                                .show();
-                        });
+                        notify(format!("Update {} available! Click tray menu to install.", new_version), 5000);
+                        service::publish_update_state(updater::state());
                         tray_handle.update(move |tray: &mut NetworkTray| {
                             tray.checking_update = false;
                             tray.update_available = Some(new_version.clone());
@@ -181,38 +400,122 @@ async fn main() {
                     }
                     UpdateResult::UpToDate => {
                         info!("Already on latest version");
-                        tokio::task::spawn_blocking(|| {
-                            let _ = Notification::new()
-                                .summary("Network Monitor")
-                                .body("You're running the latest version!")
-                                .icon("network-monitor")
-                                .timeout(3000)
-                                .show();
-                        });
+                        notify("You're running the latest version!", 3000);
+                        service::publish_update_state(updater::state());
                         tray_handle.update(|tray: &mut NetworkTray| {
                             tray.checking_update = false;
                         }).await;
                     }
+                    UpdateResult::Confirmed(version) => {
+                        info!("Installing update {}...", version);
+                        tray_handle.update(|tray: &mut NetworkTray| {
+                            tray.confirming_update = false;
+                            tray.installing_update = true;
+                        }).await;
+                        notify(format!("Downloading {}...", version), 3000);
+
+                        let tx = update_tx.clone();
+                        tokio::spawn(async move {
+                            let _ = tx.send(match updater::install_update(&version).await {
+                                Ok(exe) => UpdateResult::Installed(exe),
+                                Err(e) => UpdateResult::InstallFailed(e.to_string()),
+                            }).await;
+                        });
+                    }
+                    UpdateResult::Declined => {
+                        info!("Update postponed");
+                        tray_handle.update(|tray: &mut NetworkTray| {
+                            tray.confirming_update = false;
+                        }).await;
+                    }
+                    UpdateResult::Installed(exe) => {
+                        info!("Update installed, relaunching");
+                        if let Err(e) = updater::relaunch(&exe) {
+                            error!("Failed to relaunch: {}", e);
+                            notify("Update installed - restart Network Monitor to use it", 5000);
+                            tray_handle.update(|tray: &mut NetworkTray| {
+                                tray.installing_update = false;
+                                tray.update_available = None;
+                            }).await;
+                            continue;
+                        }
+                        break;
+                    }
+                    UpdateResult::InstallFailed(message) => {
+                        error!("Update failed: {}", message);
+                        notify_with(format!("Update failed: {}", message), 8000, Priority::Critical);
+                        tray_handle.update(|tray: &mut NetworkTray| {
+                            tray.installing_update = false;
+                        }).await;
+                    }
                 }
             }
 
             // Handle tray menu commands
             Some(cmd) = cmd_rx.recv() => {
+                events::record(events::Kind::Command, format!("{:?}", cmd));
                 match cmd {
                     TrayCommand::Refresh => {
+                        if last_manual_refresh.is_some_and(|t| t.elapsed() < REFRESH_COOLDOWN) {
+                            info!("Manual refresh ignored (cooldown)");
+                            continue;
+                        }
                         info!("Manual refresh requested");
-                        match fetch_location().await {
+                        tray_handle.update(|tray: &mut NetworkTray| {
+                            tray.refreshing = true;
+                        }).await;
+
+                        match tray_handle.spinning(locate(netns.as_deref())).await {
                             Ok(info) => {
                                 info!("Location: {} ({})", info.country, info.country_code);
-                                if let Ok(mut guard) = geo_info.lock() {
-                                    *guard = Some(info);
-                                }
-                                tray_handle.update(|_| {}).await;
+                                store_location(&geo_info, &previous, &hysteresis, info);
                             }
                             Err(e) => {
                                 error!("Refresh failed: {}", e);
+                                diagnostics::record_error(format!("Refresh failed: {}", e));
                             }
                         }
+
+                        let now = Instant::now();
+                        last_manual_refresh = Some(now);
+                        tray_handle.update(move |tray: &mut NetworkTray| {
+                            tray.refreshing = false;
+                            tray.refresh_cooldown_until = Some(now + REFRESH_COOLDOWN);
+                        }).await;
+
+                        // Re-enable the menu item once the cooldown expires
+                        let tray_handle_cooldown = tray_handle.clone();
+                        tokio::spawn(async move {
+                            tokio::time::sleep(REFRESH_COOLDOWN).await;
+                            tray_handle_cooldown.update(|tray: &mut NetworkTray| {
+                                tray.refresh_cooldown_until = None;
+                            }).await;
+                        });
+                    }
+                    TrayCommand::DiagnoseMtu => {
+                        info!("Path MTU diagnostic requested");
+                        tray_handle.update(|tray: &mut NetworkTray| {
+                            tray.measuring_mtu = true;
+                        }).await;
+                        notify(format!("Measuring path MTU to {}...", mtu::DEFAULT_TARGET), 2000);
+
+                        let tray_handle_mtu = tray_handle.clone();
+                        tokio::spawn(async move {
+                            match mtu::measure(mtu::DEFAULT_TARGET).await {
+                                // A black hole breaks TLS silently, so it is worth interrupting DND
+                                Ok(report) if report.black_hole => {
+                                    notify_with(report.summary(), 8000, Priority::Critical);
+                                }
+                                Ok(report) => notify(report.summary(), 8000),
+                                Err(e) => {
+                                    warn!("Path MTU diagnostic failed: {}", e);
+                                    notify(format!("Path MTU diagnostic failed: {}", e), 5000);
+                                }
+                            }
+                            tray_handle_mtu.update(|tray: &mut NetworkTray| {
+                                tray.measuring_mtu = false;
+                            }).await;
+                        });
                     }
                     TrayCommand::ToggleAutostart => {
                         if current_autostart {
@@ -236,21 +539,114 @@ async fn main() {
                             tray.autostart_enabled = new_autostart;
                         }).await;
                     }
+                    TrayCommand::Pause(duration) => {
+                        paused_until = (!duration.is_zero()).then(|| Instant::now() + duration);
+                        match paused_until {
+                            Some(_) => info!("Automatic refreshes paused for {}s", duration.as_secs()),
+                            None => info!("Automatic refreshes resumed"),
+                        }
+                        tray_handle.update(move |tray: &mut NetworkTray| tray.paused_until = paused_until).await;
+                    }
+                    TrayCommand::TogglePauseMonitoring => {
+                        monitoring_paused = !monitoring_paused;
+                        if monitoring_paused {
+                            info!("Monitoring paused");
+                        } else {
+                            info!("Monitoring resumed, refreshing");
+                            refresh_timer.reset_immediately();
+                        }
+                        tray_handle.update(move |tray: &mut NetworkTray| tray.monitoring_paused = monitoring_paused).await;
+                    }
+                    TrayCommand::ToggleBetaChannel => {
+                        // Persisted in config.toml, and applied right away so the watcher sees no change
+                        let channel = match config::current().updates.channel {
+                            updater::Channel::Stable => updater::Channel::Beta,
+                            updater::Channel::Beta => updater::Channel::Stable,
+                        };
+                        if let Err(e) = config::save_str("updates", "channel", channel.as_str()) {
+                            error!("Failed to save update channel: {}", e);
+                            notify(format!("Failed to save update channel: {}", e), 5000);
+                            continue;
+                        }
+                        config::set_current(Config { updates: config::UpdatesConfig { channel }, ..(*config::current()).clone() });
+                        info!("Update channel: {}", channel.as_str());
+                        service::publish_update_state(updater::state());
+                        tray_handle.update(|_| {}).await;
+                    }
+                    TrayCommand::ToggleHideIp => {
+                        let privacy = config::PrivacyConfig { hide_ip: !privacy::hide_ip(), ..config::current().privacy.clone() };
+                        if let Err(e) = config::save_bool("privacy", "hide_ip", privacy.hide_ip) {
+                            error!("Failed to save privacy setting: {}", e);
+                            notify(format!("Failed to save privacy setting: {}", e), 5000);
+                            continue;
+                        }
+                        info!("IP address {}", if privacy.hide_ip { "hidden" } else { "shown" });
+                        config::set_current(Config { privacy, ..(*config::current()).clone() });
+                        tray_handle.update(|_| {}).await;
+                    }
+                    TrayCommand::ToggleFlagStyle => {
+                        let style = match config::current().icons.style {
+                            FlagStyle::Circle => FlagStyle::Rectangle,
+                            FlagStyle::Rectangle => FlagStyle::Circle,
+                        };
+                        if let Err(e) = config::save_str("icons", "style", style.as_str()) {
+                            error!("Failed to save flag style: {}", e);
+                            notify(format!("Failed to save flag style: {}", e), 5000);
+                            continue;
+                        }
+                        info!("Flag style: {}", style.as_str());
+                        let icons = config::IconsConfig { style, ..config::current().icons.clone() };
+                        config::set_current(Config { icons, ..(*config::current()).clone() });
+                        tray_handle.update(|_| {}).await;
+                    }
+                    TrayCommand::ConnectVpn(uuid) => {
+                        info!("Connecting VPN {}", uuid);
+                        // NetworkManager's active-connection change triggers the refresh
+                        if let Err(e) = network::activate(&uuid).await {
+                            error!("Failed to connect VPN: {}", e);
+                            notify(format!("Failed to connect VPN: {}", e), 5000);
+                        }
+                    }
+                    TrayCommand::SetRefreshInterval(interval) => {
+                        if let Err(e) = config::save_refresh_interval(interval) {
+                            error!("Failed to save refresh interval: {}", e);
+                            notify(format!("Failed to save refresh interval: {}", e), 5000);
+                            continue;
+                        }
+                        if interval_override.is_some() {
+                            notify("Saved; --interval on the command line still applies until restart".to_string(), 5000);
+                            continue;
+                        }
+                        config::set_current(Config { refresh_interval: interval, ..(*config::current()).clone() });
+                        let wanted = trust::refresh_interval(current_network.as_ref());
+                        retime(&mut refresh_timer, &mut active_interval, wanted, &tray_handle).await;
+                        tray_handle.update(|_| {}).await;
+                    }
+                    TrayCommand::ToggleTrust => {
+                        let Some((ref connection, ref mut trusted)) = current_network else {
+                            continue;
+                        };
+                        match trust::set_trusted(connection, !*trusted) {
+                            Ok(()) => {
+                                *trusted = !*trusted;
+                                info!("Network \"{}\" trusted: {}", connection.name, trusted);
+                            }
+                            Err(e) => error!("Failed to save trusted networks: {}", e),
+                        }
+                        let new_trusted = *trusted;
+                        tray_handle.update(move |tray: &mut NetworkTray| {
+                            tray.trusted = new_trusted;
+                        }).await;
+                        let wanted = trust::refresh_interval(current_network.as_ref());
+                        retime(&mut refresh_timer, &mut active_interval, wanted, &tray_handle).await;
+                    }
                     TrayCommand::CheckUpdate => {
                         info!("Check for updates requested");
                         // Show spinner
                         tray_handle.update(|tray: &mut NetworkTray| {
                             tray.checking_update = true;
                         }).await;
-                        // notify-rust uses block_on internally, wrap in spawn_blocking
-                        tokio::task::spawn_blocking(|| {
-                            let _ = Notification::new()
-                                .summary("Network Monitor")
-                                .body("Checking for updates...")
-                                .icon("network-monitor")
-                                .timeout(2000)
-                                .show();
-                        });
+                        notify("Checking for updates...", 2000);
 
                         // Spawn update check in background, send result via channel
                         let tx = update_tx.clone();
@@ -262,15 +658,158 @@ async fn main() {
                             }).await;
                         });
                     }
+                    TrayCommand::CheckDnsLeak => {
+                        let Some(exit) = geo_info.lock().ok().and_then(|g| g.clone()) else {
+                            notify("Location not known yet", 3000);
+                            continue;
+                        };
+                        info!("DNS leak check requested");
+                        tray_handle.update(|tray: &mut NetworkTray| tray.checking_dns = true).await;
+                        let tray_handle_dns = tray_handle.clone();
+                        let netns_dns = netns.clone();
+                        tokio::spawn(async move {
+                            let result = match netns_dns {
+                                Some(ref name) => netns::check_dns_leak(name, exit).await,
+                                None => dns_leak::check(&exit).await,
+                            };
+                            match result {
+                                Ok(report) if report.leak.is_some() => {
+                                    warn!("{}", report.summary());
+                                    notify_with(report.summary(), 10000, Priority::Critical);
+                                }
+                                Ok(report) => notify(report.summary(), 5000),
+                                Err(e) => {
+                                    warn!("DNS leak check failed: {}", e);
+                                    notify(format!("DNS leak check failed: {}", e), 5000);
+                                }
+                            }
+                            tray_handle_dns.update(|tray: &mut NetworkTray| tray.checking_dns = false).await;
+                        });
+                    }
+                    TrayCommand::LocateFirstHop => {
+                        let Some(exit) = geo_info.lock().ok().and_then(|g| g.clone()) else {
+                            notify("Location not known yet", 3000);
+                            continue;
+                        };
+                        info!("First hop trace requested");
+                        tray_handle.update(|tray: &mut NetworkTray| tray.locating_hop = true).await;
+                        let tray_handle_hop = tray_handle.clone();
+                        tokio::spawn(async move {
+                            match first_hop::check(&exit).await {
+                                Ok(report) => notify(report.summary(), 8000),
+                                Err(e) => {
+                                    warn!("First hop trace failed: {}", e);
+                                    notify(format!("First hop trace failed: {}", e), 5000);
+                                }
+                            }
+                            tray_handle_hop.update(|tray: &mut NetworkTray| tray.locating_hop = false).await;
+                        });
+                    }
+                    TrayCommand::SyncClock => {
+                        info!("NTP sync requested");
+                        tokio::spawn(clock::sync());
+                    }
+                    TrayCommand::ToggleRawResponse => {
+                        let enabled = !diagnostics::raw_capture();
+                        diagnostics::set_raw_capture(enabled);
+                        info!("Raw provider response capture {}", if enabled { "on" } else { "off" });
+                        if enabled {
+                            notify("Raw provider responses are kept from the next refresh on", 3000);
+                        }
+                        tray_handle.update(|_| {}).await;
+                    }
+                    TrayCommand::CopyRawResponse => {
+                        let Some((provider, body)) = diagnostics::raw_response() else { continue };
+                        let copy = tokio::task::spawn_blocking(move || diagnostics::copy_to_clipboard(&body)).await;
+                        match copy {
+                            Ok(Ok(())) => notify(format!("Raw {} response copied to the clipboard", provider), 3000),
+                            _ => match diagnostics::write_bundle() {
+                                Ok(path) => notify(format!("No clipboard tool found; the response is in {}", path.display()), 8000),
+                                Err(e) => warn!("Failed to save raw response: {}", e),
+                            },
+                        }
+                    }
+                    TrayCommand::OpenMap => {
+                        let coordinates = geo_info.lock().ok().and_then(|g| g.as_ref().and_then(|i| i.coordinates));
+                        let Some((lat, lon)) = coordinates else { continue };
+                        let url = format!("https://www.openstreetmap.org/?mlat={lat}&mlon={lon}#map=11/{lat}/{lon}");
+                        open_url(url);
+                    }
+                    TrayCommand::OpenIpDetails => {
+                        let Some(ip) = geo_info.lock().ok().and_then(|g| g.as_ref().map(|i| i.query.clone())) else { continue };
+                        let url = config::current().menu.ip_details_url.replace("{ip}", &ip);
+                        open_url(url);
+                    }
+                    TrayCommand::ShowStatus => {
+                        let info = geo_info.lock().ok().and_then(|g| g.clone());
+                        let message = match info {
+                            Some(geo) => {
+                                let mut lines = vec![format!("{} ({})", geo.country_name(), geo.country_code)];
+                                lines.extend(privacy::address_lines(&geo));
+                                lines.push(format!("City: {}", geo.city));
+                                lines.push(format!("ISP: {}", geo.isp));
+                                lines.join("\n")
+                            }
+                            None => "Location not known yet".to_string(),
+                        };
+                        notify(message, 5000);
+                    }
+                    TrayCommand::ReportIssue => {
+                        info!("Opening issue report");
+                        let bundle = match diagnostics::write_bundle() {
+                            Ok(path) => Some(path),
+                            Err(e) => {
+                                warn!("Failed to write diagnostics bundle: {}", e);
+                                None
+                            }
+                        };
+                        let url = diagnostics::issue_url(bundle.as_ref());
+                        open_url(url);
+                        if let Some(path) = bundle {
+                            notify(format!("Diagnostics saved to {} - attach it to the issue if helpful", path.display()), 8000);
+                        }
+                    }
                     TrayCommand::RunUpdate => {
-                        info!("Running update...");
-                        updater::clear_available_update();
-                        // Spawn update in background and quit
-                        std::process::Command::new("bash")
-                            .args(["-c", "curl -sSL https://raw.githubusercontent.com/btxTruong/network-monitor/main/install.sh | bash -s -- --update"])
-                            .spawn()
-                            .ok();
-                        break;
+                        let Some(version) = tray_handle
+                            .read(|tray| tray.update_available.clone().filter(|_| !tray.confirming_update && !tray.installing_update))
+                            .await
+                            .flatten()
+                        else {
+                            continue;
+                        };
+                        tray_handle.update(|tray: &mut NetworkTray| {
+                            tray.confirming_update = true;
+                        }).await;
+
+                        let tx = update_tx.clone();
+                        tokio::spawn(async move {
+                            let summary = updater::release_summary(&version).await.unwrap_or_else(|e| {
+                                warn!("Failed to fetch release notes for {}: {}", version, e);
+                                updater::ReleaseSummary::bare(&version)
+                            });
+                            // The menu click is the confirmation when notifications have no buttons
+                            let confirmed = notifier::confirm(summary.describe(), "Install", UPDATE_CONFIRM_TIMEOUT_MS)
+                                .await
+                                .unwrap_or(true);
+                            let _ = tx.send(if confirmed { UpdateResult::Confirmed(version) } else { UpdateResult::Declined }).await;
+                        });
+                    }
+                    TrayCommand::SessionBusRestored => {
+                        info!("Registering the tray and D-Bus service on the new session bus");
+                        match service::start(cmd_tx.clone()).await {
+                            Ok(()) => {
+                                if let Some(info) = geo_info.lock().ok().and_then(|g| g.clone()) {
+                                    service::publish_location(&info);
+                                }
+                                if let Some(connectivity) = tray_handle.read(|tray| tray.connectivity).await {
+                                    service::publish_connectivity(connectivity);
+                                }
+                                service::publish_update_state(updater::state());
+                            }
+                            Err(e) => warn!("Failed to register {} on the session bus: {}", service::BUS_NAME, e),
+                        }
+                        shortcut::spawn(cmd_tx.clone());
+                        tray_handle = tray_handle.respawn().await;
                     }
                     TrayCommand::Quit => {
                         info!("Quit requested");
@@ -282,27 +821,65 @@ async fn main() {
             // Handle network events
             Some(event) = net_rx.recv() => {
                 match event {
+                    NetworkEvent::Connected => {
+                        events::record(events::Kind::Network, "connected");
+                        outages::end();
+                        hooks::run(HookEvent::Connect, geo_info.lock().ok().and_then(|g| g.clone()).as_ref(), None);
+                        service::publish_connectivity(service::Connectivity::Connected);
+                        tray_handle.update(|tray| tray.connectivity = service::Connectivity::Connected).await;
+                    }
+                    NetworkEvent::Disconnected => {
+                        events::record(events::Kind::Network, "disconnected");
+                        service::publish_connectivity(service::Connectivity::Disconnected);
+                        tray_handle.update(|tray| tray.connectivity = service::Connectivity::Disconnected).await;
+                        let last = geo_info.lock().ok().and_then(|g| g.clone());
+                        hooks::run(HookEvent::Disconnect, last.as_ref(), None);
+                        let isp = last.map(|i| i.isp);
+                        outages::begin(current_network.as_ref().map(|(c, _)| c), &isp.unwrap_or_default());
+                    }
+                }
+                let vpn_before = network::vpn_active();
+                if trust_enabled && matches!(event, NetworkEvent::Connected) {
+                    current_network = update_network_trust(current_network.as_ref().map(|(c, _)| c), &tray_handle).await;
+                    let wanted = trust::refresh_interval(current_network.as_ref());
+                    retime(&mut refresh_timer, &mut active_interval, wanted, &tray_handle).await;
+                }
+
+                match event {
+                    NetworkEvent::Connected if monitoring_paused => {
+                        info!("Network connected - monitoring paused, not refreshing");
+                    }
+                    NetworkEvent::Connected if blackout.is_some() => {
+                        info!("Network connected - refresh deferred until the blackout window ends");
+                    }
                     NetworkEvent::Connected => {
                         info!("Network connected - refreshing location");
+                        // This refresh covers a pending resume as well
+                        sleep_detector.check();
                         // Small delay to allow network to stabilize
                         tokio::time::sleep(Duration::from_secs(2)).await;
 
-                        match fetch_location().await {
+                        match tray_handle.spinning(locate(netns.as_deref())).await {
                             Ok(info) => {
                                 info!("Location: {} ({})", info.country, info.country_code);
-                                if let Ok(mut guard) = geo_info.lock() {
-                                    *guard = Some(info);
-                                }
+                                store_location(&geo_info, &previous, &hysteresis, info);
                                 tray_handle.update(|_| {}).await;
+                                // No periodic refresh right on the heels of this one
+                                postpone(&mut refresh_timer, active_interval);
+                                // A fresh VPN tunnel is when a leaking resolver matters most
+                                if !vpn_before && network::vpn_active() && config::current().privacy.dns_leak_check {
+                                    let _ = cmd_tx.try_send(TrayCommand::CheckDnsLeak);
+                                }
                             }
                             Err(e) => {
                                 warn!("Failed to fetch location after connect: {}", e);
+                                diagnostics::record_error(format!("Failed to fetch location after connect: {}", e));
+                                tray_handle.update(|_| {}).await;
                             }
                         }
                     }
                     NetworkEvent::Disconnected => {
                         info!("Network disconnected");
-                        // Optionally clear geo info or show disconnected state
                     }
                 }
             }
@@ -318,29 +895,720 @@ fn print_help() {
     println!("System tray app displaying country flag based on network location.");
     println!();
     println!("USAGE:");
-    println!("    network-monitor [OPTIONS]");
+    println!("    network-monitor [run] [--netns <name>] [--interval <secs>] [--waybar]");
+    println!("                    [--metrics <addr:port>]");
+    println!("    network-monitor <command>");
+    println!();
+    println!("COMMANDS:");
+    println!("    run               Start the tray app (default)");
+    println!("    status [--json]   Show the location and update state of the running instance");
+    println!("    query [--json]    Look up the location once and exit (1: lookup failed, 3: rate limited)");
+    println!("    check             Check for updates");
+    println!("    doctor            Check D-Bus, NetworkManager, tray host, geo lookup, config and autostart");
+    println!("    update            Update to latest version (--yes, --channel stable|beta, --dry-run;");
+    println!("                      exit 1: failed, 3: update available but not installed)");
+    println!("    history           List recorded locations (--since 7d, --country VN, --format table|csv|json)");
+    println!("    events            List recorded app events (--since 1h, --json)");
+    println!("    outages [--csv]   Show outage stats per connection, or export all outages as CSV");
+    println!("    config path       Print the config file location");
+    println!("    config validate   Check the config file for errors and unknown keys");
+    println!("    config init       Write a commented default config (--force replaces an existing one)");
+    println!("    secrets set <key> Store a secret (mqtt.password, webhook.url) read from stdin in the keyring");
+    println!("    secrets migrate   Move plaintext secrets from the config file into the keyring");
+    println!("    debug dump        Print the last {} raw provider responses (--redact strips IPs)", raw_log::MAX_RESPONSES);
+    println!("    backup <file>     Save config, history and profiles to an archive");
+    println!("    restore <file>    Restore config, history and profiles from an archive");
+    println!("    mtu [target]      Measure path MTU and detect black holes (default {})", mtu::DEFAULT_TARGET);
+    println!("    version           Show version");
+    println!();
+    println!("RUN OPTIONS:");
+    println!("    --netns <name>    Monitor from inside a named network namespace");
+    println!("    --interval <secs> Refresh interval, overriding config.toml (min {}s)", config::MIN_REFRESH_INTERVAL.as_secs());
+    println!("    --waybar          No tray icon; print Waybar custom-module JSON on stdout");
+    println!("    --metrics <addr>  Serve Prometheus metrics, e.g. 127.0.0.1:9184");
     println!();
-    println!("OPTIONS:");
-    println!("    -h, --help      Show this help message");
-    println!("    -v, --version   Show version");
-    println!("    -c, --check     Check for updates");
-    println!("    -u, --update    Update to latest version");
+    println!("The older flags -h/--help, -v/--version, -c/--check, -u/--update and --mtu still work.");
+}
+
+/// Runs a one-shot command
+async fn run_command(command: Command) {
+    match command {
+        Command::Status { json } => run_status(json).await,
+        Command::Query { json } => run_query(json).await,
+        Command::Check => run_check().await,
+        Command::Doctor => run_doctor().await,
+        Command::Update(args) => run_update(args).await,
+        Command::History { filter, format } => run_history(&filter, format),
+        Command::Events { since, json } => run_events(since, json),
+        Command::Outages { csv } => run_outages(csv),
+        Command::Config(action) => run_config(action),
+        Command::Secrets(action) => run_secrets(action).await,
+        Command::DebugDump { redact } => run_debug_dump(redact),
+        Command::Backup(file) => run_backup(&file),
+        Command::Restore(file) => run_restore(&file),
+        Command::Mtu(target) => run_mtu(&target).await,
+        Command::Version => println!("network-monitor {}", updater::VERSION),
+        Command::Help => print_help(),
+        Command::Run(_) => unreachable!("run is handled by main"),
+    }
 }
 
-fn run_update() {
-    println!("Updating Network Monitor...");
-    let status = std::process::Command::new("bash")
-        .args(["-c", "curl -sSL https://raw.githubusercontent.com/btxTruong/network-monitor/main/install.sh | bash -s -- --update"])
-        .status();
+/// Makes config.toml current for a one-shot command; an invalid file leaves the defaults
+fn load_config() {
+    match config::load() {
+        Ok(config) => config::set_current(config),
+        Err(e) => eprintln!("Ignoring invalid config: {}", e),
+    }
+}
+
+fn run_config(action: ConfigAction) {
+    let Some(path) = config::config_path() else {
+        eprintln!("Could not determine config directory");
+        std::process::exit(1);
+    };
+    match action {
+        ConfigAction::Path => println!("{}", path.display()),
+        ConfigAction::Validate => match config::load_validated() {
+            Ok(_) if !path.exists() => println!("{} does not exist, defaults apply", path.display()),
+            Ok((_, warnings)) if warnings.is_empty() => println!("{} is valid", path.display()),
+            Ok((_, warnings)) => {
+                for warning in &warnings {
+                    println!("{}: warning: {}", path.display(), warning);
+                }
+                println!("{} is valid, but {} key(s) are ignored", path.display(), warnings.len());
+            }
+            Err(e) => {
+                eprintln!("{}: {}", path.display(), e);
+                std::process::exit(1);
+            }
+        },
+        ConfigAction::Init { force } => match config::init(force) {
+            Ok(path) => println!("Wrote {}", path.display()),
+            Err(config::ConfigError::Io(e)) if e.kind() == std::io::ErrorKind::AlreadyExists => {
+                eprintln!("{} already exists; use --force to replace it", path.display());
+                std::process::exit(1);
+            }
+            Err(e) => {
+                eprintln!("Failed to write {}: {}", path.display(), e);
+                std::process::exit(1);
+            }
+        },
+    }
+}
+
+/// Tells the user about ignored config keys (they are logged by `config` too)
+fn report_config_warnings(warnings: &[config::ConfigWarning]) {
+    let Some(first) = warnings.first() else { return };
+    let more = match warnings.len() {
+        1 => String::new(),
+        n => format!(" and {} more", n - 1),
+    };
+    notify(
+        format!("config.toml: {}{} - run 'network-monitor config validate'", first, more),
+        8000,
+    );
+}
+
+/// Runs the best available network change backend for this environment
+async fn monitor_network(
+    tx: mpsc::Sender<NetworkEvent>,
+    netns: Option<String>,
+    environment: Environment,
+) -> Result<(), NetworkError> {
+    // NetworkManager only sees the host namespace, so probe from inside the namespace instead
+    if let Some(name) = netns {
+        diagnostics::set_backend("netns polling");
+        return poll_connectivity(tx, || netns::probe_connectivity(&name)).await;
+    }
+
+    if environment == Environment::Wsl && wsl::is_wsl2() {
+        info!("Using WSL2 adapter watcher");
+        diagnostics::set_backend("WSL2 watcher");
+        return wsl::watch_wsl2_changes(tx).await;
+    }
 
-    match status {
-        Ok(s) if s.success() => {}
-        Ok(_) => eprintln!("Update failed"),
-        Err(e) => eprintln!("Failed to run update: {}", e),
+    if !environment.has_network_manager() {
+        info!("Skipping NetworkManager in {} environment, using polling backend", environment.label());
+        diagnostics::set_backend("polling");
+        return poll_connectivity(tx, probe_connectivity).await;
+    }
+
+    diagnostics::set_backend("NetworkManager");
+    match watch_network_changes(tx.clone()).await {
+        Err(NetworkError::Connection(e)) => {
+            warn!("NetworkManager unavailable ({}), falling back to polling backend", e);
+            diagnostics::set_backend("polling (NetworkManager unavailable)");
+            poll_connectivity(tx, probe_connectivity).await
+        }
+        result => result,
+    }
+}
+
+/// Creates the periodic refresh timer, skipping the immediate first tick.
+/// With manual refreshes only (zero) it ticks daily and the ticks are ignored.
+fn refresh_interval(period: Duration) -> tokio::time::Interval {
+    let period = if period.is_zero() { MANUAL_ONLY_TICK } else { period };
+    let mut interval = tokio::time::interval_at(tokio::time::Instant::now() + period, period);
+    interval.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+    interval
+}
+
+/// Restarts the refresh period from now, with the privacy jitter if enabled
+fn postpone(timer: &mut tokio::time::Interval, active: Duration) {
+    if active.is_zero() {
+        return;
+    }
+    if privacy::enabled() {
+        timer.reset_after(privacy::next_refresh(active));
+    } else {
+        timer.reset();
+    }
+}
+
+/// Applies `--interval`, which also beats the per-network intervals
+fn override_interval(config: &mut Config, interval: Option<Duration>) {
+    let Some(interval) = interval else { return };
+    config.refresh_interval = interval;
+    for network in config.networks.values_mut() {
+        network.refresh_interval = None;
+    }
+}
+
+/// Recreates the refresh timer if the wanted interval differs from the active one, and
+/// tells the tray, which judges staleness by it
+async fn retime(timer: &mut tokio::time::Interval, active: &mut Duration, wanted: Duration, tray_handle: &TrayHandle) {
+    if wanted != *active {
+        match wanted.as_secs() {
+            0 => info!("Automatic refreshes off, manual refreshes only"),
+            secs => info!("Refresh interval set to {}s", secs),
+        }
+        *active = wanted;
+        *timer = refresh_interval(wanted);
+        tray_handle.update(move |tray: &mut NetworkTray| tray.refresh_interval = wanted).await;
+    }
+}
+
+/// Opens `url` in the default browser, reaping xdg-open once it has handed the URL over
+fn open_url(url: String) {
+    tokio::spawn(async move {
+        match tokio::process::Command::new("xdg-open").arg(&url).spawn() {
+            Ok(mut child) => match child.wait().await {
+                Ok(status) if !status.success() => warn!("xdg-open exited with {}", status),
+                Ok(_) => {}
+                Err(e) => warn!("xdg-open failed: {}", e),
+            },
+            Err(e) => error!("Failed to open browser: {}", e),
+        }
+    });
+}
+
+/// Looks up the current network and its trust, applying the untrusted-network rules.
+/// Returns None when NetworkManager can't tell us which network we're on.
+async fn update_network_trust(
+    previous: Option<&ConnectionInfo>,
+    tray_handle: &TrayHandle,
+) -> Option<(ConnectionInfo, bool)> {
+    let mut vpns = Vec::new();
+    let vpn_profiles = network::vpn_profiles().await.unwrap_or_else(|e| {
+        warn!("Failed to list VPN profiles: {}", e);
+        Vec::new()
+    });
+    let current = match network::active_connections().await {
+        Ok(snapshot) => {
+            vpns = snapshot.active.iter().filter(|c| c.is_vpn()).map(|c| c.name.clone()).collect();
+            network::set_vpn_active(snapshot.vpn_active());
+            let current = snapshot.network().cloned().map(|c| {
+                let trusted = trust::is_trusted(&c.uuid);
+                (c, trusted)
+            });
+            if let Some((ref connection, false)) = current {
+                let newly_joined = previous.is_none_or(|p| p.uuid != connection.uuid);
+                trust::check_untrusted(&snapshot, newly_joined).await;
+            }
+            current
+        }
+        Err(e) => {
+            warn!("Failed to query active connections: {}", e);
+            None
+        }
+    };
+
+    let connection = current.as_ref().map(|(c, _)| c.clone());
+    if connection.as_ref().map(|c| &c.uuid) != previous.map(|p| &p.uuid) {
+        let config = config::current();
+        if let Some(ref c) = connection {
+            info!("On network \"{}\", using {} provider", c.name, config.provider_for(Some(c)));
+        }
+    }
+    network::set_current_connection(connection);
+
+    let tray_state = current.clone();
+    tray_handle.update(move |tray: &mut NetworkTray| {
+        tray.trusted = tray_state.as_ref().is_some_and(|(_, trusted)| *trusted);
+        tray.network = tray_state.map(|(c, _)| c);
+        tray.vpns = vpns;
+        tray.vpn_profiles = vpn_profiles;
+    }).await;
+    current
+}
+
+/// Applies the configured autostart preference, returning the resulting state
+fn enforce_autostart(wanted: Option<bool>, current: bool) -> bool {
+    match wanted {
+        Some(true) if !current => match setup_autostart() {
+            Ok(()) => {
+                info!("Autostart enabled by config");
+                true
+            }
+            Err(e) => {
+                error!("Failed to enable autostart: {}", e);
+                false
+            }
+        },
+        Some(false) if current => match remove_autostart() {
+            Ok(()) => {
+                info!("Autostart disabled by config");
+                false
+            }
+            Err(e) => {
+                error!("Failed to disable autostart: {}", e);
+                true
+            }
+        },
+        _ => current,
+    }
+}
+
+/// Stores a fresh lookup as the displayed location, unless hysteresis holds back a flap;
+/// history gets every lookup either way, so held flaps still show when the IP changed.
+/// The replaced location is kept as the previous one when it was somewhere else.
+fn store_location(
+    geo_info: &Mutex<Option<GeoInfo>>,
+    previous: &Mutex<Option<PreviousLocation>>,
+    hysteresis: &Mutex<CountryHysteresis>,
+    info: GeoInfo,
+) {
+    history::record(&info);
+    let decision = hysteresis
+        .lock()
+        .map(|mut h| h.observe(&info, Instant::now()))
+        .unwrap_or(Decision::Accept);
+
+    if decision == Decision::Hold {
+        info!("Country flap suppressed: raw lookup reported {} ({})", info.country_code, info.query);
+        return;
+    }
+
+    service::publish_location(&info);
+    location_cache::remember(&info);
+    if let Ok(mut guard) = geo_info.lock() {
+        if guard.as_ref() != Some(&info) {
+            mqtt::publish_location(&info);
+        }
+        // A flapping connection gets one aggregated warning instead of an alert, webhook, hook
+        // or rule action per change. The watchdog still speaks up, since a VPN dropping out is
+        // what it exists for; MQTT and the event log mirror state rather than alert.
+        let unstable = guard.as_ref().is_some_and(|old| ip_churn::observe(&old.query, &info.query));
+        if let Some(old) = guard.as_ref().filter(|_| !unstable) {
+            notifier::country_changed(old, &info);
+        }
+        watchdog::observe(guard.as_ref(), &info);
+        rules::evaluate(&info, unstable);
+        if let Some(old) = guard.as_ref().filter(|old| geo::is_change(old, &info)) {
+            events::record(
+                events::Kind::Location,
+                format!("{} ({}) -> {} ({})", old.country_code, old.query, info.country_code, info.query),
+            );
+            if !unstable {
+                webhook::send(old, &info);
+                hooks::run(HookEvent::LocationChange, Some(&info), Some(old));
+            }
+        }
+        if let Some(moved) = guard.take().and_then(|old| PreviousLocation::replaced(old, &info)) {
+            if let Ok(mut previous) = previous.lock() {
+                *previous = Some(moved);
+            }
+        }
+        *guard = Some(info);
+    }
+}
+
+/// Fetches location from the host network, or from inside a network namespace
+async fn locate(netns: Option<&str>) -> Result<GeoInfo, GeoError> {
+    let result = match netns {
+        Some(name) => netns::fetch_location(name).await,
+        None => fetch_location().await,
+    };
+    match result {
+        Ok(ref info) => {
+            metrics::record_refresh(info);
+            events::record(events::Kind::Refresh, format!("{} {} ({})", info.country_code, info.query, info.isp));
+        }
+        Err(_) => metrics::record_error(),
+    }
+    let info = match result {
+        Ok(mut info) => {
+            let config = config::current();
+            // Checked from where the lookup was made, or they'd describe the host's network
+            if config.geo.tor_check {
+                info.tor = match netns {
+                    Some(name) => netns::is_tor_exit(name, info.query.clone()).await,
+                    None => tor::is_exit(&info.query).await,
+                };
+            }
+            if config.geo.reverse_dns {
+                info.hostname = match netns {
+                    Some(name) => netns::reverse_lookup(name, info.query.clone()).await,
+                    None => reverse_dns::lookup(&info.query).await,
+                };
+            }
+            info
+        }
+        // Better a clearly marked last-known location for this network than none
+        Err(e) => match netns.is_none().then(location_cache::current).flatten() {
+            Some(cached) => {
+                warn!("Location lookup failed ({}), using the cached location for this network", e);
+                cached
+            }
+            None => return Err(e),
+        },
+    };
+    // Minimal builds fetch flags that are not embedded before the icon is redrawn
+    #[cfg(feature = "flag-download")]
+    flag_cache::ensure(&info.country_code).await;
+    Ok(info)
+}
+
+/// `update` exit code when a newer release exists but was not installed
+/// (dry run, declined, or no terminal to ask on without `--yes`)
+const EXIT_UPDATE_AVAILABLE: i32 = 3;
+
+async fn run_update(args: UpdateArgs) {
+    load_config();
+    let channel = updater::resolve_channel(args.channel, &config::current());
+    println!("Checking for updates ({} channel)...", channel.as_str());
+    let version = match updater::check_channel(channel).await {
+        Ok(Some(version)) => version,
+        Ok(None) => {
+            println!("Already on the latest version ({}).", updater::VERSION);
+            return;
+        }
+        Err(e) => {
+            eprintln!("{}", e);
+            std::process::exit(1);
+        }
+    };
+    println!("Update available: {} -> {}", updater::VERSION, version);
+    if args.dry_run {
+        std::process::exit(EXIT_UPDATE_AVAILABLE);
+    }
+
+    if !args.yes {
+        if !std::io::stdin().is_terminal() {
+            eprintln!("Not installing without confirmation; pass --yes to update non-interactively.");
+            std::process::exit(EXIT_UPDATE_AVAILABLE);
+        }
+        let summary = updater::release_summary(&version)
+            .await
+            .unwrap_or_else(|_| updater::ReleaseSummary::bare(&version));
+        for line in &summary.changelog {
+            println!("  • {}", line);
+        }
+        print!("Install {}? [y/N] ", version);
+        let _ = std::io::stdout().flush();
+        let mut answer = String::new();
+        let _ = std::io::stdin().read_line(&mut answer);
+        if !matches!(answer.trim(), "y" | "Y" | "yes") {
+            println!("Not updating.");
+            std::process::exit(EXIT_UPDATE_AVAILABLE);
+        }
+    }
+
+    println!("Updating Network Monitor {} -> {}...", updater::VERSION, version);
+    match updater::install_update(&version).await {
+        Ok(exe) => {
+            println!("Installed {} to {}", version, exe.display());
+            println!("Restart Network Monitor to use the new version.");
+        }
+        Err(e) => {
+            eprintln!("Update failed: {}", e);
+            std::process::exit(1);
+        }
+    }
+}
+
+async fn run_secrets(action: SecretsAction) {
+    let pending = match action {
+        SecretsAction::Set(key) => {
+            let mut value = String::new();
+            if let Err(e) = std::io::stdin().read_line(&mut value) {
+                eprintln!("Failed to read the secret from stdin: {}", e);
+                std::process::exit(1);
+            }
+            vec![(key, value.trim_end_matches(['\r', '\n']).to_string())]
+        }
+        SecretsAction::Migrate => {
+            let config = match config::load() {
+                Ok(config) => config,
+                Err(e) => {
+                    eprintln!("{}", e);
+                    std::process::exit(1);
+                }
+            };
+            let plaintext: Vec<_> = [("mqtt.password", config.mqtt.password.clone()), ("webhook.url", config.webhook.url.clone())]
+                .into_iter()
+                .filter_map(|(key, value)| Some((key.to_string(), value?)))
+                .filter(|(_, value)| !secrets::is_keyring(value))
+                .collect();
+            if plaintext.is_empty() {
+                println!("No plaintext secrets in the config file");
+            }
+            plaintext
+        }
+    };
+
+    for (key, value) in pending {
+        // The config keeps its plaintext value unless the keyring accepted the secret
+        if let Err(e) = secrets::set(&key, &value).await {
+            eprintln!("Failed to store {} in the keyring: {}", key, e);
+            std::process::exit(1);
+        }
+        let (section, name) = key.split_once('.').unwrap_or_default();
+        if let Err(e) = config::save_str(section, name, secrets::KEYRING_MARKER) {
+            eprintln!("Stored {} in the keyring, but updating the config failed: {}", key, e);
+            std::process::exit(1);
+        }
+        println!("{} is now read from the keyring", key);
+    }
+}
+
+fn run_backup(file: &std::path::Path) {
+    match backup::backup(file) {
+        Ok(count) => println!("Backed up {} files to {}", count, file.display()),
+        Err(e) => {
+            eprintln!("Backup failed: {}", e);
+            std::process::exit(1);
+        }
+    }
+}
+
+fn run_restore(file: &std::path::Path) {
+    match backup::restore(file) {
+        Ok(count) => {
+            println!("Restored {} files from {}", count, file.display());
+            println!("Restart Network Monitor to apply the restored settings.");
+        }
+        Err(e) => {
+            eprintln!("Restore failed: {}", e);
+            std::process::exit(1);
+        }
+    }
+}
+
+fn run_history(filter: &history::Filter, format: history::Format) {
+    let entries = history::select(filter);
+    if entries.is_empty() && format == history::Format::Table {
+        println!("No locations recorded{}.", if *filter == history::Filter::default() { " yet" } else { " matching the filter" });
+        return;
+    }
+    print!("{}", history::render(&entries, format));
+}
+
+fn run_events(since: Option<Duration>, json: bool) {
+    let recorded = events::select(since);
+    if recorded.is_empty() && !json {
+        println!("No events recorded{}.", if since.is_none() { " yet" } else { " in that time" });
+        return;
+    }
+    print!("{}", events::render(&recorded, json));
+}
+
+fn run_debug_dump(redact: bool) {
+    let responses = raw_log::load();
+    if responses.is_empty() {
+        println!("No raw responses recorded yet.");
+        return;
+    }
+    print!("{}", raw_log::render(&responses, redact));
+}
+
+fn run_outages(csv: bool) {
+    if csv {
+        print!("{}", outages::export_csv());
+        return;
+    }
+
+    let stats = outages::stats();
+    if stats.is_empty() {
+        println!("No outages recorded yet.");
+        return;
+    }
+    println!("{:<24} {:<24} {:>8} {:>12} {:>14}", "CONNECTION", "ISP", "OUTAGES", "DOWNTIME", "MEAN BETWEEN");
+    for s in stats {
+        let between = s
+            .mean_time_between
+            .map(|d| format!("{:.1}h", d.as_secs_f64() / 3600.0))
+            .unwrap_or_else(|| "-".to_string());
+        println!(
+            "{:<24} {:<24} {:>8} {:>11}m {:>14}",
+            s.connection,
+            s.isp,
+            s.outages,
+            s.downtime.as_secs() / 60,
+            between
+        );
+    }
+}
+
+async fn run_status(json: bool) {
+    let location = match service::query_location().await {
+        Ok(location) => location,
+        Err(zbus::Error::MethodError(name, _, _)) if name.as_str() == "org.freedesktop.DBus.Error.ServiceUnknown" => {
+            eprintln!("network-monitor is not running");
+            std::process::exit(1);
+        }
+        Err(e) => {
+            eprintln!("Failed to query network-monitor: {}", e);
+            std::process::exit(1);
+        }
+    };
+    let update = match service::query_update_state().await {
+        Ok(update) => update,
+        Err(e) => {
+            eprintln!("Failed to query network-monitor: {}", e);
+            std::process::exit(1);
+        }
+    };
+    if json {
+        let mut out: serde_json::Map<String, serde_json::Value> = location
+            .iter()
+            .map(|(key, value)| match key.as_str() {
+                "refreshed_at" => (key.clone(), value.parse::<u64>().map_or(serde_json::Value::Null, Into::into)),
+                "mobile" | "hosting" => (key.clone(), (value == "true").into()),
+                _ => (key.clone(), value.clone().into()),
+            })
+            .collect();
+        out.insert(
+            "update".to_string(),
+            serde_json::json!({
+                "version": update.version,
+                "available": update.available,
+                "channel": update.channel.as_str(),
+                "last_check": update.last_check,
+            }),
+        );
+        println!("{}", serde_json::to_string_pretty(&out).unwrap_or_default());
+        return;
+    }
+    let field = |key: &str| location.get(key).map(String::as_str).unwrap_or("-");
+
+    println!("IP:        {}", field("ip"));
+    for (key, label) in [("ipv4", "IPv4:"), ("ipv6", "IPv6:")] {
+        if location.get(key).is_some_and(|a| a != field("ip")) {
+            println!("{:<10} {}", label, field(key));
+        }
+    }
+    println!("Country:   {} ({})", field("country"), field("country_code"));
+    println!("City:      {}", field("city"));
+    println!("ISP:       {}", field("isp"));
+
+    let refreshed = location
+        .get("refreshed_at")
+        .and_then(|t| t.parse::<u64>().ok())
+        .map(|t| std::time::UNIX_EPOCH + Duration::from_secs(t))
+        .and_then(|t| t.elapsed().ok());
+    match refreshed {
+        Some(age) if age < Duration::from_secs(60) => println!("Refreshed: just now"),
+        Some(age) => println!("Refreshed: {} ago", network_monitor::tray::format_duration(age)),
+        None => println!("Refreshed: -"),
+    }
+
+    let available = update.available.map(|v| format!(", {} available", v)).unwrap_or_default();
+    println!("Version:   {} ({} channel{})", update.version, update.channel.as_str(), available);
+    let checked = update
+        .last_check
+        .and_then(|t| (std::time::UNIX_EPOCH + Duration::from_secs(t)).elapsed().ok());
+    match checked {
+        Some(age) if age < Duration::from_secs(60) => println!("Checked:   just now"),
+        Some(age) => println!("Checked:   {} ago", network_monitor::tray::format_duration(age)),
+        None => println!("Checked:   never"),
+    }
+}
+
+/// Exit status of `query` when every provider is rate-limited (1 is any other failure)
+const EXIT_RATE_LIMITED: i32 = 3;
+
+async fn run_query(json: bool) {
+    load_config();
+
+    let info = match fetch_location().await {
+        Ok(info) => info,
+        Err(e) => {
+            eprintln!("Lookup failed: {}", e);
+            let code = if matches!(e, GeoError::RateLimited(_)) { EXIT_RATE_LIMITED } else { 1 };
+            std::process::exit(code);
+        }
+    };
+
+    if json {
+        let timestamp = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .unwrap_or(Duration::ZERO)
+            .as_secs();
+        let output = serde_json::json!({
+            "ip": info.query,
+            "country": info.country,
+            "countryCode": info.country_code,
+            "city": info.city,
+            "isp": info.isp,
+            "timestamp": timestamp,
+        });
+        println!("{}", output);
+    } else {
+        println!("IP:        {}", info.query);
+        println!("Country:   {} ({})", info.country_name(), info.country_code);
+        println!("City:      {}", info.city);
+        println!("ISP:       {}", info.isp);
+    }
+}
+
+async fn run_mtu(target: &str) {
+    println!("Measuring path MTU to {}...", target);
+
+    match mtu::measure(target).await {
+        Ok(report) => {
+            println!("{}", report.summary());
+            if let Some(reported) = report.reported_mtu {
+                println!("ICMP \"fragmentation needed\" reported MTU {}", reported);
+            }
+            if report.black_hole {
+                println!();
+                println!("Large packets vanish without ICMP feedback. This is a classic VPN symptom:");
+                println!("pings work but TLS handshakes stall. Try lowering the tunnel MTU to {}.", report.path_mtu);
+            }
+        }
+        Err(e) => {
+            eprintln!("Path MTU diagnostic failed: {}", e);
+            std::process::exit(1);
+        }
+    }
+}
+
+async fn run_doctor() {
+    let checks = doctor::run().await;
+    for check in &checks {
+        println!("{} {:<15} {}", check.outcome.mark(), check.name, check.detail);
+        if let Some(ref hint) = check.hint {
+            println!("  {:<15} → {}", "", hint);
+        }
+    }
+    if checks.iter().any(|c| c.outcome == doctor::Outcome::Fail) {
+        std::process::exit(1);
     }
 }
 
 async fn run_check() {
+    load_config();
     println!("network-monitor {}", updater::VERSION);
     println!();
     println!("Checking for updates...");
@@ -349,7 +1617,7 @@ async fn run_check() {
         Some(new_version) => {
             println!("Update available: {}", new_version);
             println!();
-            println!("Run 'network-monitor --update' to update.");
+            println!("Run 'network-monitor update' to update.");
         }
         None => {
             println!("You're up to date!");
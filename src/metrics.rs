@@ -0,0 +1,217 @@
+//! Prometheus metrics endpoint
+//! `network-monitor run --metrics 127.0.0.1:9184` serves refresh counters and the last
+//! looked-up location (as an info metric) in the text exposition format on /metrics.
+
+use crate::geo::GeoInfo;
+use std::net::SocketAddr;
+use std::sync::{LazyLock, Mutex};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use thiserror::Error;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::{TcpListener, TcpStream};
+
+/// Requests larger than this are answered with 400
+const MAX_REQUEST: usize = 8192;
+/// Slow clients are dropped after this
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(5);
+
+static METRICS: LazyLock<Mutex<Metrics>> = LazyLock::new(|| Mutex::new(Metrics::default()));
+
+#[derive(Debug, Error)]
+pub enum MetricsError {
+    #[error("cannot listen on {addr}: {source}")]
+    Bind { addr: SocketAddr, source: std::io::Error },
+}
+
+#[derive(Debug, Default)]
+struct Metrics {
+    refreshes: u64,
+    errors: u64,
+    /// Unix time of the last successful lookup
+    last_refresh: u64,
+    /// Latest successful lookup
+    location: Option<GeoInfo>,
+    /// The most recent lookup failed
+    last_failed: bool,
+}
+
+impl Metrics {
+    /// Text exposition format, version 0.0.4
+    fn render(&self) -> String {
+        let mut out = String::new();
+        let mut metric = |name: &str, kind: &str, help: &str, samples: &[(String, u64)]| {
+            out.push_str(&format!("# HELP {} {}\n# TYPE {} {}\n", name, help, name, kind));
+            for (labels, value) in samples {
+                out.push_str(&format!("{}{} {}\n", name, labels, value));
+            }
+        };
+
+        metric(
+            "network_monitor_refresh_total",
+            "counter",
+            "Location lookups attempted",
+            &[(String::new(), self.refreshes)],
+        );
+        metric(
+            "network_monitor_refresh_errors_total",
+            "counter",
+            "Location lookups that failed",
+            &[(String::new(), self.errors)],
+        );
+        metric(
+            "network_monitor_last_refresh_timestamp",
+            "gauge",
+            "Unix time of the last successful lookup",
+            &[(String::new(), self.last_refresh)],
+        );
+        let info: Vec<(String, u64)> = self
+            .location
+            .iter()
+            .map(|geo| {
+                let labels = format!(
+                    "{{country_code=\"{}\",country=\"{}\",city=\"{}\",isp=\"{}\",ip=\"{}\"}}",
+                    label_value(&geo.country_code),
+                    label_value(&geo.country),
+                    label_value(&geo.city),
+                    label_value(&geo.isp),
+                    label_value(&geo.query)
+                );
+                (labels, 1)
+            })
+            .collect();
+        metric("network_monitor_location_info", "gauge", "Last looked-up location", &info);
+        out
+    }
+}
+
+/// Escapes a label value (backslash, double quote and newline)
+fn label_value(value: &str) -> String {
+    value.replace('\\', "\\\\").replace('"', "\\\"").replace('\n', "\\n")
+}
+
+fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or(Duration::ZERO)
+        .as_secs()
+}
+
+/// Counts a successful lookup
+pub fn record_refresh(info: &GeoInfo) {
+    if let Ok(mut metrics) = METRICS.lock() {
+        metrics.refreshes += 1;
+        metrics.last_refresh = now();
+        metrics.location = Some(info.clone());
+        metrics.last_failed = false;
+    }
+}
+
+/// Time since the last successful lookup, None before the first
+pub fn last_refresh_age() -> Option<Duration> {
+    let last = METRICS.lock().ok().map(|m| m.last_refresh).filter(|&t| t > 0)?;
+    Some(Duration::from_secs(now().saturating_sub(last)))
+}
+
+/// Counts a failed lookup
+pub fn record_error() {
+    if let Ok(mut metrics) = METRICS.lock() {
+        metrics.refreshes += 1;
+        metrics.errors += 1;
+        metrics.last_failed = true;
+    }
+}
+
+/// Whether the most recent lookup failed
+pub fn last_refresh_failed() -> bool {
+    METRICS.lock().is_ok_and(|m| m.last_failed)
+}
+
+/// Binds `addr` and serves /metrics in the background
+pub async fn serve(addr: SocketAddr) -> Result<(), MetricsError> {
+    let listener = TcpListener::bind(addr)
+        .await
+        .map_err(|source| MetricsError::Bind { addr, source })?;
+    tracing::info!("Serving Prometheus metrics on http://{}/metrics", addr);
+
+    tokio::spawn(async move {
+        loop {
+            match listener.accept().await {
+                Ok((stream, _)) => {
+                    tokio::spawn(async move {
+                        if tokio::time::timeout(REQUEST_TIMEOUT, respond(stream)).await.is_err() {
+                            tracing::debug!("Metrics client timed out");
+                        }
+                    });
+                }
+                Err(e) => tracing::debug!("Metrics accept failed: {}", e),
+            }
+        }
+    });
+    Ok(())
+}
+
+/// Answers a single HTTP/1.x request, then closes the connection
+async fn respond(mut stream: TcpStream) {
+    let mut request = Vec::new();
+    let mut buf = [0u8; 1024];
+    while !request.windows(4).any(|w| w == b"\r\n\r\n") {
+        if request.len() > MAX_REQUEST {
+            break;
+        }
+        match stream.read(&mut buf).await {
+            Ok(0) | Err(_) => return,
+            Ok(n) => request.extend_from_slice(&buf[..n]),
+        }
+    }
+
+    let request = String::from_utf8_lossy(&request);
+    let (status, body) = match request.lines().next().map(|l| l.split(' ').take(2).collect::<Vec<_>>()) {
+        Some(parts) if parts == ["GET", "/metrics"] => {
+            let body = METRICS.lock().map(|m| m.render()).unwrap_or_default();
+            ("200 OK", body)
+        }
+        Some(parts) if parts.first() == Some(&"GET") => ("404 Not Found", "Not found, try /metrics\n".to_string()),
+        _ => ("400 Bad Request", String::new()),
+    };
+    let response = format!(
+        "HTTP/1.1 {}\r\nContent-Type: text/plain; version=0.0.4\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+        status,
+        body.len(),
+        body
+    );
+    let _ = stream.write_all(response.as_bytes()).await;
+    let _ = stream.shutdown().await;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_render() {
+        let metrics = Metrics {
+            refreshes: 3,
+            errors: 1,
+            last_refresh: 1700000000,
+            location: Some(GeoInfo {
+                query: "203.0.113.7".to_string(),
+                country_code: "DE".to_string(),
+                isp: "Deutsche \"Telekom\"".to_string(),
+                ..Default::default()
+            }),
+            last_failed: false,
+        };
+        let text = metrics.render();
+        assert!(text.contains("network_monitor_refresh_total 3\n"));
+        assert!(text.contains("network_monitor_refresh_errors_total 1\n"));
+        assert!(text.contains("network_monitor_last_refresh_timestamp 1700000000\n"));
+        assert!(text.contains(r#"isp="Deutsche \"Telekom\"",ip="203.0.113.7"} 1"#));
+    }
+
+    #[test]
+    fn test_no_location_yet() {
+        let text = Metrics::default().render();
+        assert!(text.contains("# TYPE network_monitor_location_info gauge\n"));
+        assert!(!text.contains("network_monitor_location_info{"));
+    }
+}
@@ -0,0 +1,348 @@
+//! Minimal MaxMind DB (MMDB) reader for offline geolocation
+//! Implements just enough of the MaxMind DB format (search tree + data section decoder)
+//! to resolve an IP address against a local GeoLite2 database.
+
+use std::net::IpAddr;
+use std::path::Path;
+use thiserror::Error;
+
+/// Marks the start of the metadata section (searched from the end of the file)
+const METADATA_MARKER: &[u8] = b"\xab\xcd\xefMaxMind.com";
+/// Gap between the search tree and the data section
+const DATA_SECTION_SEPARATOR: usize = 16;
+
+/// Errors while reading an MMDB file
+#[derive(Debug, Error)]
+pub enum MmdbError {
+    #[error("IO error: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("Invalid database: {0}")]
+    Invalid(&'static str),
+    #[error("IPv6 address lookup in an IPv4-only database")]
+    Ipv6InIpv4Database,
+}
+
+/// A decoded data section value
+#[derive(Debug, Clone, PartialEq)]
+pub enum Value {
+    String(String),
+    Double(f64),
+    Float(f32),
+    Bytes(Vec<u8>),
+    Uint(u128),
+    Int(i32),
+    Bool(bool),
+    Map(Vec<(String, Value)>),
+    Array(Vec<Value>),
+}
+
+impl Value {
+    /// Looks up a key in a map value
+    pub fn get(&self, key: &str) -> Option<&Value> {
+        match self {
+            Value::Map(entries) => entries.iter().find(|(k, _)| k == key).map(|(_, v)| v),
+            _ => None,
+        }
+    }
+
+    /// Follows a path of map keys, e.g. `["country", "iso_code"]`
+    pub fn path(&self, keys: &[&str]) -> Option<&Value> {
+        keys.iter().try_fold(self, |value, key| value.get(key))
+    }
+
+    pub fn as_str(&self) -> Option<&str> {
+        match self {
+            Value::String(s) => Some(s),
+            _ => None,
+        }
+    }
+
+    pub fn as_uint(&self) -> Option<u128> {
+        match self {
+            Value::Uint(n) => Some(*n),
+            _ => None,
+        }
+    }
+
+    pub fn as_f64(&self) -> Option<f64> {
+        match self {
+            Value::Double(v) => Some(*v),
+            Value::Float(v) => Some(*v as f64),
+            _ => None,
+        }
+    }
+}
+
+/// An MMDB database loaded into memory
+pub struct Reader {
+    data: Vec<u8>,
+    node_count: usize,
+    record_size: usize,
+    ip_version: u16,
+    tree_size: usize,
+}
+
+impl Reader {
+    /// Reads a database file
+    pub fn open(path: &Path) -> Result<Self, MmdbError> {
+        Self::from_bytes(std::fs::read(path)?)
+    }
+
+    /// Parses a database from its raw bytes
+    pub fn from_bytes(data: Vec<u8>) -> Result<Self, MmdbError> {
+        let marker = data
+            .windows(METADATA_MARKER.len())
+            .rposition(|w| w == METADATA_MARKER)
+            .ok_or(MmdbError::Invalid("metadata marker not found"))?;
+        let metadata_start = marker + METADATA_MARKER.len();
+        let (metadata, _) = Decoder { buf: &data[metadata_start..] }.decode(0)?;
+
+        let field = |key| metadata.get(key).and_then(Value::as_uint);
+        let node_count = field("node_count").ok_or(MmdbError::Invalid("missing node_count"))? as usize;
+        let record_size = field("record_size").ok_or(MmdbError::Invalid("missing record_size"))? as usize;
+        let ip_version = field("ip_version").ok_or(MmdbError::Invalid("missing ip_version"))? as u16;
+        if !matches!(record_size, 24 | 28 | 32) {
+            return Err(MmdbError::Invalid("unsupported record size"));
+        }
+
+        let tree_size = node_count * record_size * 2 / 8;
+        if tree_size + DATA_SECTION_SEPARATOR > marker {
+            return Err(MmdbError::Invalid("search tree exceeds file"));
+        }
+
+        Ok(Self {
+            data,
+            node_count,
+            record_size,
+            ip_version,
+            tree_size,
+        })
+    }
+
+    /// Returns the record for `ip`, or None if the database has no data for it
+    pub fn lookup(&self, ip: IpAddr) -> Result<Option<Value>, MmdbError> {
+        let (bits, bit_count): (u128, usize) = match ip {
+            IpAddr::V4(v4) if self.ip_version == 6 => {
+                // IPv4 lives in the IPv4-mapped subtree: 96 zero bits, then the address
+                (u32::from(v4) as u128, 128)
+            }
+            IpAddr::V4(v4) => ((u32::from(v4) as u128) << 96, 32),
+            IpAddr::V6(_) if self.ip_version == 4 => return Err(MmdbError::Ipv6InIpv4Database),
+            IpAddr::V6(v6) => (u128::from(v6), 128),
+        };
+
+        let mut node = 0;
+        for i in 0..bit_count {
+            if node >= self.node_count {
+                break;
+            }
+            let bit = (bits >> (127 - i)) & 1;
+            node = self.read_record(node, bit == 1)?;
+        }
+
+        if node == self.node_count {
+            return Ok(None);
+        }
+        if node < self.node_count {
+            return Err(MmdbError::Invalid("search tree too deep"));
+        }
+
+        let offset = node - self.node_count - DATA_SECTION_SEPARATOR;
+        let data_section = &self.data[self.tree_size + DATA_SECTION_SEPARATOR..];
+        let (value, _) = Decoder { buf: data_section }.decode(offset)?;
+        Ok(Some(value))
+    }
+
+    fn read_record(&self, node: usize, right: bool) -> Result<usize, MmdbError> {
+        let node_bytes = self.record_size * 2 / 8;
+        let b = self
+            .data
+            .get(node * node_bytes..(node + 1) * node_bytes)
+            .ok_or(MmdbError::Invalid("node out of range"))?;
+        let be = |bytes: &[u8]| bytes.iter().fold(0usize, |acc, &x| (acc << 8) | x as usize);
+
+        Ok(match (self.record_size, right) {
+            (24, false) => be(&b[0..3]),
+            (24, true) => be(&b[3..6]),
+            (28, false) => ((b[3] as usize & 0xf0) << 20) | be(&b[0..3]),
+            (28, true) => ((b[3] as usize & 0x0f) << 24) | be(&b[4..7]),
+            (_, false) => be(&b[0..4]),
+            (_, true) => be(&b[4..8]),
+        })
+    }
+}
+
+/// Decodes values from the data (or metadata) section
+struct Decoder<'a> {
+    buf: &'a [u8],
+}
+
+impl Decoder<'_> {
+    fn bytes(&self, offset: usize, len: usize) -> Result<&[u8], MmdbError> {
+        self.buf
+            .get(offset..offset + len)
+            .ok_or(MmdbError::Invalid("data section truncated"))
+    }
+
+    fn uint(&self, offset: usize, len: usize) -> Result<u128, MmdbError> {
+        Ok(self.bytes(offset, len)?.iter().fold(0u128, |acc, &b| (acc << 8) | b as u128))
+    }
+
+    /// Decodes the value at `offset`, returning it and the offset just past it
+    fn decode(&self, offset: usize) -> Result<(Value, usize), MmdbError> {
+        let ctrl = self.bytes(offset, 1)?[0];
+        let mut pos = offset + 1;
+
+        let mut kind = ctrl >> 5;
+        if kind == 1 {
+            // Pointer: decode the target, but continue after the pointer itself
+            let ss = ((ctrl >> 3) & 0x3) as usize;
+            let vvv = (ctrl & 0x7) as u128;
+            let rest = self.uint(pos, ss + 1)?;
+            let target = match ss {
+                0 => (vvv << 8) | rest,
+                1 => ((vvv << 16) | rest) + 2048,
+                2 => ((vvv << 24) | rest) + 526_336,
+                _ => rest,
+            } as usize;
+            let (value, _) = self.decode(target)?;
+            return Ok((value, pos + ss + 1));
+        }
+        if kind == 0 {
+            kind = 7 + self.bytes(pos, 1)?[0];
+            pos += 1;
+        }
+
+        let mut size = (ctrl & 0x1f) as usize;
+        match size {
+            29 => {
+                size = 29 + self.uint(pos, 1)? as usize;
+                pos += 1;
+            }
+            30 => {
+                size = 285 + self.uint(pos, 2)? as usize;
+                pos += 2;
+            }
+            31 => {
+                size = 65_821 + self.uint(pos, 3)? as usize;
+                pos += 3;
+            }
+            _ => {}
+        }
+
+        let value = match kind {
+            2 => {
+                let s = std::str::from_utf8(self.bytes(pos, size)?)
+                    .map_err(|_| MmdbError::Invalid("invalid UTF-8 string"))?;
+                pos += size;
+                Value::String(s.to_string())
+            }
+            3 => {
+                let v = f64::from_bits(self.uint(pos, 8)? as u64);
+                pos += 8;
+                Value::Double(v)
+            }
+            4 => {
+                let v = self.bytes(pos, size)?.to_vec();
+                pos += size;
+                Value::Bytes(v)
+            }
+            5 | 6 | 9 | 10 => {
+                let v = self.uint(pos, size)?;
+                pos += size;
+                Value::Uint(v)
+            }
+            8 => {
+                let v = self.uint(pos, size)? as u32 as i32;
+                pos += size;
+                Value::Int(v)
+            }
+            7 => {
+                let mut entries = Vec::with_capacity(size);
+                for _ in 0..size {
+                    let (key, next) = self.decode(pos)?;
+                    let Value::String(key) = key else {
+                        return Err(MmdbError::Invalid("map key is not a string"));
+                    };
+                    let (value, next) = self.decode(next)?;
+                    entries.push((key, value));
+                    pos = next;
+                }
+                Value::Map(entries)
+            }
+            11 => {
+                let mut items = Vec::with_capacity(size);
+                for _ in 0..size {
+                    let (value, next) = self.decode(pos)?;
+                    items.push(value);
+                    pos = next;
+                }
+                Value::Array(items)
+            }
+            14 => Value::Bool(size != 0),
+            15 => {
+                let v = f32::from_bits(self.uint(pos, 4)? as u32);
+                pos += 4;
+                Value::Float(v)
+            }
+            _ => return Err(MmdbError::Invalid("unsupported data type")),
+        };
+        Ok((value, pos))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn string(s: &str) -> Vec<u8> {
+        let mut out = vec![0x40 | s.len() as u8];
+        out.extend_from_slice(s.as_bytes());
+        out
+    }
+
+    fn map(entries: &[(&str, Vec<u8>)]) -> Vec<u8> {
+        let mut out = vec![0xe0 | entries.len() as u8];
+        for (key, value) in entries {
+            out.extend(string(key));
+            out.extend_from_slice(value);
+        }
+        out
+    }
+
+    /// One-node IPv4 tree: 0.0.0.0/1 has data, 128.0.0.0/1 has none
+    fn test_database() -> Vec<u8> {
+        let node_count = 1u32;
+        let data_record = node_count + DATA_SECTION_SEPARATOR as u32;
+        let mut db = Vec::new();
+        db.extend_from_slice(&data_record.to_be_bytes()[1..]);
+        db.extend_from_slice(&node_count.to_be_bytes()[1..]);
+        db.extend_from_slice(&[0; DATA_SECTION_SEPARATOR]);
+        db.extend(map(&[
+            ("country", map(&[("iso_code", string("VN"))])),
+            ("city", map(&[("names", map(&[("en", string("Hanoi"))]))])),
+        ]));
+        db.extend_from_slice(METADATA_MARKER);
+        db.extend(map(&[
+            ("node_count", vec![0xc1, 1]),
+            ("record_size", vec![0xa1, 24]),
+            ("ip_version", vec![0xa1, 4]),
+        ]));
+        db
+    }
+
+    #[test]
+    fn test_lookup() {
+        let reader = Reader::from_bytes(test_database()).unwrap();
+        let record = reader.lookup("1.2.3.4".parse().unwrap()).unwrap().unwrap();
+        assert_eq!(record.path(&["country", "iso_code"]).and_then(Value::as_str), Some("VN"));
+        assert_eq!(record.path(&["city", "names", "en"]).and_then(Value::as_str), Some("Hanoi"));
+        assert!(reader.lookup("200.0.0.1".parse().unwrap()).unwrap().is_none());
+        assert!(matches!(reader.lookup("::1".parse().unwrap()), Err(MmdbError::Ipv6InIpv4Database)));
+    }
+
+    #[test]
+    fn test_rejects_garbage() {
+        assert!(matches!(Reader::from_bytes(vec![0; 64]), Err(MmdbError::Invalid(_))));
+    }
+}
@@ -0,0 +1,209 @@
+//! MQTT publishing for Home Assistant
+//! Opt-in via `[mqtt] broker`: on every location change the GeoInfo is published (retained)
+//! as JSON to `<topic>/state`, along with MQTT discovery configs for one sensor per field.
+
+use crate::config::MqttConfig;
+use crate::geo::GeoInfo;
+use std::time::Duration;
+use thiserror::Error;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::TcpStream;
+
+const DEFAULT_PORT: u16 = 1883;
+const TIMEOUT: Duration = Duration::from_secs(10);
+const KEEP_ALIVE_SECS: u16 = 30;
+
+/// Sensors announced via discovery: (GeoInfo JSON field, display name, icon)
+const SENSORS: &[(&str, &str, &str)] = &[
+    ("ip", "Public IP", "mdi:ip-network"),
+    ("country", "Country", "mdi:earth"),
+    ("country_code", "Country Code", "mdi:flag"),
+    ("city", "City", "mdi:city"),
+    ("isp", "ISP", "mdi:router-network"),
+];
+
+#[derive(Debug, Error)]
+pub enum MqttError {
+    #[error("IO error: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("Broker did not answer in time")]
+    Timeout,
+    #[error("Broker refused the connection (code {0})")]
+    Refused(u8),
+    #[error("Unexpected reply from broker")]
+    Protocol,
+}
+
+/// Publishes `info` if MQTT is configured; failures are logged
+pub fn publish_location(info: &GeoInfo) {
+    let config = crate::config::current().mqtt.clone();
+    if config.broker.is_none() {
+        return;
+    }
+    let info = info.clone();
+    tokio::spawn(async move {
+        match tokio::time::timeout(TIMEOUT, publish(&config, &info)).await {
+            Ok(Ok(())) => tracing::debug!("Published location to MQTT topic {}", config.topic),
+            Ok(Err(e)) => tracing::warn!("MQTT publish failed: {}", e),
+            Err(_) => tracing::warn!("MQTT publish failed: {}", MqttError::Timeout),
+        }
+    });
+}
+
+/// Connects, sends discovery and state (all retained), and disconnects
+async fn publish(config: &MqttConfig, info: &GeoInfo) -> Result<(), MqttError> {
+    let broker = config.broker.as_deref().unwrap_or_default();
+    let address = if broker.contains(':') { broker.to_string() } else { format!("{}:{}", broker, DEFAULT_PORT) };
+    let mut stream = TcpStream::connect(address).await?;
+
+    let client_id = format!("network-monitor-{}", std::process::id());
+    let password = crate::secrets::resolve("mqtt.password", config.password.as_deref()).await;
+    stream
+        .write_all(&connect_packet(&client_id, config.username.as_deref(), password.as_deref()))
+        .await?;
+    let mut connack = [0u8; 4];
+    stream.read_exact(&mut connack).await?;
+    if connack[0] != 0x20 || connack[1] != 2 {
+        return Err(MqttError::Protocol);
+    }
+    if connack[3] != 0 {
+        return Err(MqttError::Refused(connack[3]));
+    }
+
+    let state_topic = format!("{}/state", config.topic);
+    for (topic, payload) in discovery(config, &state_topic) {
+        stream.write_all(&publish_packet(&topic, payload.as_bytes())).await?;
+    }
+    stream.write_all(&publish_packet(&state_topic, state(info).as_bytes())).await?;
+    // DISCONNECT
+    stream.write_all(&[0xe0, 0x00]).await?;
+    stream.shutdown().await?;
+    Ok(())
+}
+
+/// State payload, the fields the discovery templates read
+fn state(info: &GeoInfo) -> String {
+    serde_json::json!({
+        "ip": info.query,
+        "country": info.country,
+        "country_code": info.country_code,
+        "city": info.city,
+        "isp": info.isp,
+        "mobile": info.mobile,
+        "hosting": info.hosting,
+        "proxy": info.proxy,
+    })
+    .to_string()
+}
+
+/// Home Assistant discovery (topic, payload) pairs
+fn discovery(config: &MqttConfig, state_topic: &str) -> Vec<(String, String)> {
+    let node: String = config
+        .topic
+        .chars()
+        .map(|c| if c.is_ascii_alphanumeric() { c } else { '_' })
+        .collect();
+    SENSORS
+        .iter()
+        .map(|(field, name, icon)| {
+            let topic = format!("{}/sensor/{}/{}/config", config.discovery_prefix, node, field);
+            let payload = serde_json::json!({
+                "name": name,
+                "unique_id": format!("{}_{}", node, field),
+                "state_topic": state_topic,
+                "value_template": format!("{{{{ value_json.{} }}}}", field),
+                "icon": icon,
+                "device": {
+                    "identifiers": [node],
+                    "name": "Network Monitor",
+                    "sw_version": crate::updater::VERSION,
+                },
+            });
+            (topic, payload.to_string())
+        })
+        .collect()
+}
+
+/// MQTT 3.1.1 variable-length "remaining length"
+fn remaining_length(mut len: usize, out: &mut Vec<u8>) {
+    loop {
+        let mut byte = (len % 128) as u8;
+        len /= 128;
+        if len > 0 {
+            byte |= 0x80;
+        }
+        out.push(byte);
+        if len == 0 {
+            break;
+        }
+    }
+}
+
+/// Length-prefixed UTF-8 string
+fn string(value: &[u8], out: &mut Vec<u8>) {
+    out.extend_from_slice(&(value.len() as u16).to_be_bytes());
+    out.extend_from_slice(value);
+}
+
+fn packet(header: u8, body: Vec<u8>) -> Vec<u8> {
+    let mut out = vec![header];
+    remaining_length(body.len(), &mut out);
+    out.extend(body);
+    out
+}
+
+fn connect_packet(client_id: &str, username: Option<&str>, password: Option<&str>) -> Vec<u8> {
+    let mut flags = 0x02; // clean session
+    if username.is_some() {
+        flags |= 0x80;
+    }
+    if password.is_some() {
+        flags |= 0x40;
+    }
+    let mut body = Vec::new();
+    string(b"MQTT", &mut body);
+    body.push(4); // protocol level 3.1.1
+    body.push(flags);
+    body.extend_from_slice(&KEEP_ALIVE_SECS.to_be_bytes());
+    string(client_id.as_bytes(), &mut body);
+    for value in [username, password].into_iter().flatten() {
+        string(value.as_bytes(), &mut body);
+    }
+    packet(0x10, body)
+}
+
+/// QoS 0 PUBLISH with the retain flag, so Home Assistant gets the state after restarts
+fn publish_packet(topic: &str, payload: &[u8]) -> Vec<u8> {
+    let mut body = Vec::new();
+    string(topic.as_bytes(), &mut body);
+    body.extend_from_slice(payload);
+    packet(0x31, body)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_packets() {
+        let mut out = Vec::new();
+        remaining_length(321, &mut out);
+        assert_eq!(out, [0xc1, 0x02]);
+
+        let connect = connect_packet("nm", Some("u"), None);
+        assert_eq!(connect[..2], [0x10, 17]);
+        assert_eq!(connect[9], 0x82);
+        assert_eq!(publish_packet("a/b", b"{}"), [0x31, 7, 0, 3, b'a', b'/', b'b', b'{', b'}']);
+    }
+
+    #[test]
+    fn test_discovery() {
+        let config = MqttConfig::default();
+        let entries = discovery(&config, "network-monitor/state");
+        assert_eq!(entries.len(), SENSORS.len());
+        assert_eq!(entries[0].0, "homeassistant/sensor/network_monitor/ip/config");
+        let payload: serde_json::Value = serde_json::from_str(&entries[0].1).unwrap();
+        assert_eq!(payload["value_template"], "{{ value_json.ip }}");
+        assert_eq!(payload["unique_id"], "network_monitor_ip");
+    }
+}
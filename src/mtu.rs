@@ -0,0 +1,164 @@
+//! Path-MTU discovery diagnostic
+//! Binary-searches the largest unfragmented packet to a target using `ping -M do`
+//! and flags MTU black holes (big packets silently dropped, no ICMP "frag needed").
+
+use thiserror::Error;
+use tokio::process::Command;
+
+/// Default target for the tray diagnostic (anycast, answers ICMP everywhere)
+pub const DEFAULT_TARGET: &str = "1.1.1.1";
+
+/// IPv4 + ICMP header overhead added to the ping payload size
+const HEADER_OVERHEAD: u32 = 28;
+/// Minimum MTU every IPv4 path must support
+const MIN_MTU: u32 = 576;
+/// Standard Ethernet MTU, the upper bound of the search
+const MAX_MTU: u32 = 1500;
+
+/// Errors during path MTU measurement
+#[derive(Debug, Error)]
+pub enum MtuError {
+    #[error("Failed to run ping: {0}")]
+    Spawn(#[from] std::io::Error),
+    #[error("Target {0} is unreachable")]
+    Unreachable(String),
+}
+
+/// Result of a single DF-flagged probe
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Probe {
+    /// Echo reply received
+    Ok,
+    /// Kernel or router reported a smaller MTU via ICMP "fragmentation needed"
+    TooBig(u32),
+    /// No reply at all
+    Lost,
+}
+
+/// Outcome of a path MTU measurement
+#[derive(Debug, Clone)]
+pub struct MtuReport {
+    /// Probed host
+    pub target: String,
+    /// Largest packet size (including headers) that got through
+    pub path_mtu: u32,
+    /// MTU reported by ICMP "fragmentation needed", if any
+    pub reported_mtu: Option<u32>,
+    /// Large packets were dropped without any ICMP feedback
+    pub black_hole: bool,
+}
+
+impl MtuReport {
+    /// One-line human readable summary for notifications and logs
+    pub fn summary(&self) -> String {
+        if self.black_hole {
+            format!(
+                "Possible MTU black hole to {}: packets above {} bytes are silently dropped",
+                self.target, self.path_mtu
+            )
+        } else if self.path_mtu < MAX_MTU {
+            format!("Path MTU to {} is {} bytes (below {})", self.target, self.path_mtu, MAX_MTU)
+        } else {
+            format!("Path MTU to {} is {} bytes", self.target, self.path_mtu)
+        }
+    }
+}
+
+/// Sends one DF-flagged echo request carrying a packet of `mtu` bytes
+async fn probe(target: &str, mtu: u32) -> Result<Probe, MtuError> {
+    let payload = (mtu - HEADER_OVERHEAD).to_string();
+    let output = Command::new("ping")
+        .args(["-4", "-n", "-c", "1", "-W", "2", "-M", "do", "-s", &payload, target])
+        .output()
+        .await?;
+
+    if output.status.success() {
+        return Ok(Probe::Ok);
+    }
+
+    let text = format!(
+        "{}{}",
+        String::from_utf8_lossy(&output.stdout),
+        String::from_utf8_lossy(&output.stderr)
+    );
+    Ok(parse_reported_mtu(&text).map_or(Probe::Lost, Probe::TooBig))
+}
+
+/// Extracts the MTU from ping's "mtu = 1400" / "mtu=1400" error messages
+fn parse_reported_mtu(text: &str) -> Option<u32> {
+    let rest = &text[text.find("mtu")? + 3..];
+    let digits: String = rest
+        .trim_start_matches([' ', '='])
+        .chars()
+        .take_while(|c| c.is_ascii_digit())
+        .collect();
+    digits.parse().ok()
+}
+
+/// Measures the path MTU to `target` and detects black-hole conditions
+pub async fn measure(target: &str) -> Result<MtuReport, MtuError> {
+    if probe(target, MIN_MTU).await? != Probe::Ok {
+        return Err(MtuError::Unreachable(target.to_string()));
+    }
+
+    let mut reported_mtu = None;
+    let mut lost_at_max = false;
+    match probe(target, MAX_MTU).await? {
+        Probe::Ok => {
+            return Ok(MtuReport {
+                target: target.to_string(),
+                path_mtu: MAX_MTU,
+                reported_mtu: None,
+                black_hole: false,
+            });
+        }
+        Probe::TooBig(mtu) => reported_mtu = Some(mtu),
+        Probe::Lost => lost_at_max = true,
+    }
+
+    // Binary search: `low` always gets through, `high` never does
+    let (mut low, mut high) = (MIN_MTU, MAX_MTU);
+    while high - low > 1 {
+        let mid = (low + high) / 2;
+        match probe(target, mid).await? {
+            Probe::Ok => low = mid,
+            Probe::TooBig(mtu) => {
+                reported_mtu.get_or_insert(mtu);
+                high = mid;
+            }
+            Probe::Lost => high = mid,
+        }
+    }
+
+    let report = MtuReport {
+        target: target.to_string(),
+        path_mtu: low,
+        reported_mtu,
+        black_hole: lost_at_max && reported_mtu.is_none(),
+    };
+    tracing::info!("{}", report.summary());
+    Ok(report)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_reported_mtu() {
+        assert_eq!(parse_reported_mtu("From 10.0.0.1 icmp_seq=1 Frag needed and DF set (mtu = 1420)"), Some(1420));
+        assert_eq!(parse_reported_mtu("ping: local error: message too long, mtu=1400"), Some(1400));
+        assert_eq!(parse_reported_mtu("1 packets transmitted, 0 received, 100% packet loss"), None);
+    }
+
+    #[test]
+    fn test_report_summary() {
+        let report = MtuReport {
+            target: "1.1.1.1".to_string(),
+            path_mtu: 1420,
+            reported_mtu: None,
+            black_hole: true,
+        };
+        assert!(report.summary().contains("black hole"));
+    }
+}
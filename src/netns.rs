@@ -0,0 +1,147 @@
+//! Network namespace module for monitoring from inside a named netns
+//! Runs geo lookups, the checks made on their result and connectivity probes on worker
+//! threads joined to the namespace.
+
+use crate::dns_leak::{self, DnsLeakError};
+use crate::geo::{self, GeoError, GeoInfo};
+use crate::{network, reverse_dns, tor};
+use std::fs::File;
+use std::future::Future;
+use std::os::fd::AsRawFd;
+use std::path::PathBuf;
+use thiserror::Error;
+use tokio::sync::oneshot;
+
+/// Directory where `ip netns add` creates named namespaces
+const NETNS_DIR: &str = "/run/netns";
+
+/// Errors while entering a network namespace
+#[derive(Debug, Error)]
+pub enum NetnsError {
+    #[error("Invalid namespace name: {0}")]
+    InvalidName(String),
+    #[error("Namespace not found: {0}")]
+    NotFound(String),
+    #[error("IO error: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("setns failed: {0}")]
+    Setns(std::io::Error),
+    #[error("Namespace worker thread exited unexpectedly")]
+    WorkerGone,
+}
+
+/// Resolves a namespace name to its bind-mount path, e.g. `vpn` -> `/run/netns/vpn`
+fn namespace_path(name: &str) -> Result<PathBuf, NetnsError> {
+    if name.is_empty() || name.contains('/') || name == "." || name == ".." {
+        return Err(NetnsError::InvalidName(name.to_string()));
+    }
+
+    let path = PathBuf::from(NETNS_DIR).join(name);
+    if !path.exists() {
+        return Err(NetnsError::NotFound(name.to_string()));
+    }
+    Ok(path)
+}
+
+/// Checks that the namespace exists so startup can fail early with a clear message
+pub fn validate(name: &str) -> Result<(), NetnsError> {
+    namespace_path(name).map(|_| ())
+}
+
+/// Moves the calling thread into the network namespace at `path`
+fn enter(path: &PathBuf) -> Result<(), NetnsError> {
+    let file = File::open(path)?;
+    // SAFETY: fd is a valid open namespace file for the duration of the call
+    let ret = unsafe { libc::setns(file.as_raw_fd(), libc::CLONE_NEWNET) };
+    if ret != 0 {
+        return Err(NetnsError::Setns(std::io::Error::last_os_error()));
+    }
+    Ok(())
+}
+
+/// Runs an async task on a dedicated thread that has joined the given network namespace.
+/// Sockets opened by the task belong to that namespace; the main runtime is untouched.
+pub async fn run_in<F, Fut, T>(name: &str, task: F) -> Result<T, NetnsError>
+where
+    F: FnOnce() -> Fut + Send + 'static,
+    Fut: Future<Output = T>,
+    T: Send + 'static,
+{
+    let path = namespace_path(name)?;
+    let (tx, rx) = oneshot::channel();
+
+    std::thread::spawn(move || {
+        let result = enter(&path).and_then(|_| {
+            let runtime = tokio::runtime::Builder::new_current_thread()
+                .enable_all()
+                .build()?;
+            Ok(runtime.block_on(task()))
+        });
+        let _ = tx.send(result);
+    });
+
+    rx.await.map_err(|_| NetnsError::WorkerGone)?
+}
+
+/// Fetches geographic location as seen from inside the namespace
+pub async fn fetch_location(name: &str) -> Result<GeoInfo, GeoError> {
+    run_in(name, geo::fetch_location).await?
+}
+
+/// Whether `ip` is a Tor exit, with the exit list downloaded from inside the namespace
+pub async fn is_tor_exit(name: &str, ip: String) -> bool {
+    match run_in(name, move || async move { tor::is_exit(&ip).await }).await {
+        Ok(exit) => exit,
+        Err(e) => {
+            tracing::warn!("Tor exit check in netns '{}' failed: {}", name, e);
+            false
+        }
+    }
+}
+
+/// PTR name of `ip`, queried from inside the namespace
+pub async fn reverse_lookup(name: &str, ip: String) -> Option<String> {
+    match run_in(name, move || async move { reverse_dns::lookup(&ip).await }).await {
+        Ok(hostname) => hostname,
+        Err(e) => {
+            tracing::warn!("Reverse DNS lookup in netns '{}' failed: {}", name, e);
+            None
+        }
+    }
+}
+
+/// Runs the DNS leak check through the namespace's route to the resolver
+pub async fn check_dns_leak(name: &str, exit: GeoInfo) -> Result<dns_leak::Report, DnsLeakError> {
+    run_in(name, move || async move { dns_leak::check(&exit).await }).await?
+}
+
+/// Probes internet connectivity from inside the namespace
+pub async fn probe_connectivity(name: &str) -> bool {
+    match run_in(name, network::probe_connectivity).await {
+        Ok(connected) => connected,
+        Err(e) => {
+            tracing::warn!("Connectivity probe in netns '{}' failed: {}", name, e);
+            false
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_invalid_names_rejected() {
+        assert!(matches!(namespace_path(""), Err(NetnsError::InvalidName(_))));
+        assert!(matches!(namespace_path("../etc"), Err(NetnsError::InvalidName(_))));
+        assert!(matches!(namespace_path(".."), Err(NetnsError::InvalidName(_))));
+    }
+
+    #[test]
+    fn test_missing_namespace() {
+        assert!(matches!(
+            namespace_path("network-monitor-test-missing"),
+            Err(NetnsError::NotFound(_))
+        ));
+    }
+}
@@ -2,9 +2,28 @@
 //! Monitors connectivity state and VPN changes to trigger location refresh.
 
 use futures_util::StreamExt;
+use std::collections::HashMap;
+use std::future::Future;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{LazyLock, Mutex};
+use std::time::Duration;
 use thiserror::Error;
+use tokio::net::TcpStream;
 use tokio::sync::mpsc;
-use zbus::{proxy, Connection, zvariant::OwnedObjectPath};
+use zbus::{proxy, Connection, zvariant::{ObjectPath, OwnedObjectPath, OwnedValue}};
+
+/// Host probed when NetworkManager is unavailable (same endpoint as geo lookups)
+const PROBE_ADDR: &str = "ip-api.com:80";
+const PROBE_TIMEOUT: Duration = Duration::from_secs(5);
+/// How often the polling backend probes connectivity
+pub const POLL_INTERVAL: Duration = Duration::from_secs(15);
+
+/// Network (connection profile) we're currently on, if NetworkManager told us
+static CURRENT_CONNECTION: LazyLock<Mutex<Option<ConnectionInfo>>> = LazyLock::new(|| Mutex::new(None));
+/// Whether any VPN connection is active, for the alert rules
+static VPN_ACTIVE: AtomicBool = AtomicBool::new(false);
+
+const WIFI_TYPE: &str = "802-11-wireless";
 
 /// NetworkManager connectivity states
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
@@ -75,6 +94,236 @@ trait NetworkManager {
     /// Get active connections (changes when VPN connects/disconnects)
     #[zbus(property)]
     fn active_connections(&self) -> zbus::Result<Vec<OwnedObjectPath>>;
+
+    /// Connection owning the default route ("/" when there is none)
+    #[zbus(property)]
+    fn primary_connection(&self) -> zbus::Result<OwnedObjectPath>;
+
+    /// Brings up a saved profile; "/" lets NetworkManager pick the device
+    fn activate_connection(
+        &self,
+        connection: &ObjectPath<'_>,
+        device: &ObjectPath<'_>,
+        specific_object: &ObjectPath<'_>,
+    ) -> zbus::Result<OwnedObjectPath>;
+}
+
+/// D-Bus proxy for NetworkManager's saved connection profiles
+#[proxy(
+    interface = "org.freedesktop.NetworkManager.Settings",
+    default_service = "org.freedesktop.NetworkManager",
+    default_path = "/org/freedesktop/NetworkManager/Settings"
+)]
+trait Settings {
+    fn list_connections(&self) -> zbus::Result<Vec<OwnedObjectPath>>;
+
+    fn get_connection_by_uuid(&self, uuid: &str) -> zbus::Result<OwnedObjectPath>;
+}
+
+/// Setting name -> key -> value, as returned by GetSettings
+type ProfileSettings = HashMap<String, HashMap<String, OwnedValue>>;
+
+/// D-Bus proxy for one saved connection profile
+#[proxy(
+    interface = "org.freedesktop.NetworkManager.Settings.Connection",
+    default_service = "org.freedesktop.NetworkManager"
+)]
+trait SettingsConnection {
+    fn get_settings(&self) -> zbus::Result<ProfileSettings>;
+}
+
+/// D-Bus proxy for a NetworkManager active connection
+#[proxy(
+    interface = "org.freedesktop.NetworkManager.Connection.Active",
+    default_service = "org.freedesktop.NetworkManager"
+)]
+trait ActiveConnection {
+    #[zbus(property)]
+    fn id(&self) -> zbus::Result<String>;
+
+    #[zbus(property)]
+    fn uuid(&self) -> zbus::Result<String>;
+
+    #[zbus(property, name = "Type")]
+    fn connection_type(&self) -> zbus::Result<String>;
+
+    /// Saved profile this was activated from
+    #[zbus(property)]
+    fn connection(&self) -> zbus::Result<OwnedObjectPath>;
+}
+
+/// A NetworkManager connection profile
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ConnectionInfo {
+    /// Stable profile UUID
+    pub uuid: String,
+    /// User-visible name (e.g. the Wi-Fi SSID)
+    pub name: String,
+    /// NetworkManager type, e.g. "802-11-wireless" or "vpn"
+    pub kind: String,
+    /// Wi-Fi without a password (no 802-11-wireless-security setting)
+    pub open: bool,
+}
+
+impl ConnectionInfo {
+    /// Returns true for VPN and WireGuard connections
+    pub fn is_vpn(&self) -> bool {
+        matches!(self.kind.as_str(), "vpn" | "wireguard")
+    }
+
+    pub fn is_wifi(&self) -> bool {
+        self.kind == WIFI_TYPE
+    }
+}
+
+/// Active connections as seen by NetworkManager
+#[derive(Debug, Clone, Default)]
+pub struct ConnectionSnapshot {
+    /// Connection owning the default route
+    pub primary: Option<ConnectionInfo>,
+    /// All active connections
+    pub active: Vec<ConnectionInfo>,
+}
+
+impl ConnectionSnapshot {
+    /// Returns true if any VPN connection is up
+    pub fn vpn_active(&self) -> bool {
+        self.active.iter().any(ConnectionInfo::is_vpn)
+    }
+
+    /// The physical network we're on: the primary connection, or the first
+    /// non-VPN one when a full-tunnel VPN owns the default route
+    pub fn network(&self) -> Option<&ConnectionInfo> {
+        match &self.primary {
+            Some(primary) if !primary.is_vpn() => Some(primary),
+            _ => self.active.iter().find(|c| !c.is_vpn()),
+        }
+    }
+}
+
+/// Returns true if a VPN connection was up at the last network check
+pub fn vpn_active() -> bool {
+    VPN_ACTIVE.load(Ordering::Relaxed)
+}
+
+/// Records whether a VPN connection is up
+pub fn set_vpn_active(active: bool) {
+    VPN_ACTIVE.store(active, Ordering::Relaxed);
+}
+
+/// Returns the network we're currently on
+pub fn current_connection() -> Option<ConnectionInfo> {
+    CURRENT_CONNECTION.lock().ok().and_then(|c| c.clone())
+}
+
+/// Records the network we're currently on
+pub fn set_current_connection(connection: Option<ConnectionInfo>) {
+    if let Ok(mut current) = CURRENT_CONNECTION.lock() {
+        *current = connection;
+    }
+}
+
+async fn connection_info(connection: &Connection, path: OwnedObjectPath) -> Option<ConnectionInfo> {
+    let proxy = ActiveConnectionProxy::builder(connection).path(path).ok()?.build().await.ok()?;
+    let kind = proxy.connection_type().await.unwrap_or_default();
+    let open = if kind == WIFI_TYPE {
+        match proxy.connection().await {
+            Ok(profile) => profile_settings(connection, profile)
+                .await
+                .is_some_and(|settings| !settings.contains_key("802-11-wireless-security")),
+            Err(_) => false,
+        }
+    } else {
+        false
+    };
+    Some(ConnectionInfo {
+        uuid: proxy.uuid().await.ok()?,
+        name: proxy.id().await.unwrap_or_default(),
+        kind,
+        open,
+    })
+}
+
+async fn profile_settings(connection: &Connection, path: OwnedObjectPath) -> Option<ProfileSettings> {
+    let proxy = SettingsConnectionProxy::builder(connection).path(path).ok()?.build().await.ok()?;
+    proxy.get_settings().await.ok()
+}
+
+/// Whether neither IPv4 nor IPv6 of a profile can take the default route (`never-default`,
+/// or the family is disabled), like a management VLAN or a split-tunnel VPN
+fn never_default(settings: &ProfileSettings) -> bool {
+    let family = |name: &str| {
+        let Some(section) = settings.get(name) else { return false };
+        let never = section.get("never-default").and_then(|v| bool::try_from(&**v).ok()) == Some(true);
+        let method = section.get("method").and_then(|v| <&str>::try_from(&**v).ok());
+        never || matches!(method, Some("disabled" | "ignore"))
+    };
+    family("ipv4") && family("ipv6")
+}
+
+/// Active connections that may carry the default route; the others can't move the public IP
+async fn routable(connection: &Connection, paths: Vec<OwnedObjectPath>) -> Vec<OwnedObjectPath> {
+    let mut kept = Vec::new();
+    for path in paths {
+        let settings = async {
+            let proxy = ActiveConnectionProxy::builder(connection).path(path.clone()).ok()?.build().await.ok()?;
+            profile_settings(connection, proxy.connection().await.ok()?).await
+        };
+        if !settings.await.is_some_and(|s| never_default(&s)) {
+            kept.push(path);
+        }
+    }
+    kept
+}
+
+/// Saved VPN and WireGuard profiles, by name
+pub async fn vpn_profiles() -> Result<Vec<ConnectionInfo>, NetworkError> {
+    let connection = Connection::system().await?;
+    let settings = SettingsProxy::new(&connection).await?;
+
+    let mut profiles = Vec::new();
+    for path in settings.list_connections().await? {
+        let Some(settings) = profile_settings(&connection, path).await else { continue };
+        let Some(section) = settings.get("connection") else { continue };
+        let text = |key: &str| section.get(key).and_then(|v| <&str>::try_from(&**v).ok()).map(str::to_string);
+        let (Some(uuid), Some(kind)) = (text("uuid"), text("type")) else { continue };
+        let profile = ConnectionInfo { uuid, name: text("id").unwrap_or_default(), kind, open: false };
+        if profile.is_vpn() {
+            profiles.push(profile);
+        }
+    }
+    profiles.sort_by(|a, b| a.name.cmp(&b.name));
+    Ok(profiles)
+}
+
+/// Asks NetworkManager to bring up the saved profile `uuid`
+pub async fn activate(uuid: &str) -> Result<(), NetworkError> {
+    let connection = Connection::system().await?;
+    let profile = SettingsProxy::new(&connection).await?.get_connection_by_uuid(uuid).await?;
+    let root = ObjectPath::from_static_str_unchecked("/");
+    NetworkManagerProxy::new(&connection)
+        .await?
+        .activate_connection(&profile, &root, &root)
+        .await?;
+    Ok(())
+}
+
+/// Queries NetworkManager for the primary and active connections
+pub async fn active_connections() -> Result<ConnectionSnapshot, NetworkError> {
+    let connection = Connection::system().await?;
+    let proxy = NetworkManagerProxy::new(&connection).await?;
+
+    let mut snapshot = ConnectionSnapshot::default();
+    for path in proxy.active_connections().await? {
+        if let Some(info) = connection_info(&connection, path).await {
+            snapshot.active.push(info);
+        }
+    }
+    let primary = proxy.primary_connection().await?;
+    if primary.as_str() != "/" {
+        snapshot.primary = connection_info(&connection, primary).await;
+    }
+    Ok(snapshot)
 }
 
 /// Watches for network connectivity changes via NetworkManager D-Bus interface.
@@ -86,7 +335,7 @@ pub async fn watch_network_changes(tx: mpsc::Sender<NetworkEvent>) -> Result<(),
     // Get initial state
     let initial_state = NetworkState::from(proxy.state().await.unwrap_or(0));
     let mut was_connected = initial_state.is_connected();
-    let mut last_connections = proxy.active_connections().await.unwrap_or_default().len();
+    let mut last_connections = routable(&connection, proxy.active_connections().await.unwrap_or_default()).await;
 
     tracing::info!("Initial network state: {:?} (connected={})", initial_state, was_connected);
 
@@ -119,18 +368,101 @@ pub async fn watch_network_changes(tx: mpsc::Sender<NetworkEvent>) -> Result<(),
             }
             Some(change) = conn_stream.next() => {
                 // Active connections changed (VPN connect/disconnect)
+                // Compared by path, so swapping one VPN for another is noticed too; never-default
+                // connections are left out so e.g. a management VLAN flapping doesn't refresh
                 if let Ok(connections) = change.get().await {
-                    let new_count = connections.len();
-                    if new_count != last_connections && was_connected {
-                        tracing::info!("Active connections changed: {} -> {} (VPN change detected)", last_connections, new_count);
+                    let connections = routable(&connection, connections).await;
+                    if connections != last_connections && was_connected {
+                        tracing::info!("Active connections changed: {} -> {}", last_connections.len(), connections.len());
                         // Emit Connected event to trigger refresh
                         if tx.send(NetworkEvent::Connected).await.is_err() {
                             return Err(NetworkError::ChannelClosed);
                         }
                     }
-                    last_connections = new_count;
+                    last_connections = connections;
                 }
             }
         }
     }
 }
+
+/// Checks internet connectivity by opening a TCP connection to the geo endpoint
+pub async fn probe_connectivity() -> bool {
+    matches!(
+        tokio::time::timeout(PROBE_TIMEOUT, TcpStream::connect(PROBE_ADDR)).await,
+        Ok(Ok(_))
+    )
+}
+
+/// Polling backend for environments without NetworkManager.
+/// Calls `probe` every `POLL_INTERVAL` and emits events on connectivity transitions.
+pub async fn poll_connectivity<F, Fut>(
+    tx: mpsc::Sender<NetworkEvent>,
+    probe: F,
+) -> Result<(), NetworkError>
+where
+    F: Fn() -> Fut,
+    Fut: Future<Output = bool>,
+{
+    let mut was_connected = probe().await;
+    tracing::info!("Polling connectivity every {:?} (connected={})", POLL_INTERVAL, was_connected);
+
+    let mut interval = tokio::time::interval(POLL_INTERVAL);
+    interval.tick().await; // Skip immediate tick
+
+    loop {
+        interval.tick().await;
+        if crate::schedule::in_blackout() {
+            continue;
+        }
+        let is_connected = probe().await;
+
+        if is_connected != was_connected {
+            tracing::debug!("Connectivity changed (connected={})", is_connected);
+            let event = if is_connected {
+                NetworkEvent::Connected
+            } else {
+                NetworkEvent::Disconnected
+            };
+            if tx.send(event).await.is_err() {
+                return Err(NetworkError::ChannelClosed);
+            }
+            was_connected = is_connected;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use zbus::zvariant::Str;
+
+    /// Settings with the given `(section, key, value)` entries, where values are bools or methods
+    fn settings(entries: &[(&str, &str, &str)]) -> ProfileSettings {
+        let mut settings = ProfileSettings::new();
+        for &(section, key, value) in entries {
+            let value = match value {
+                "true" | "false" => OwnedValue::from(value == "true"),
+                method => OwnedValue::from(Str::from(method.to_string())),
+            };
+            settings.entry(section.to_string()).or_default().insert(key.to_string(), value);
+        }
+        settings
+    }
+
+    #[test]
+    fn test_never_default() {
+        // A management VLAN: IPv4 never gets the default route, IPv6 is off
+        let vlan = settings(&[("ipv4", "method", "manual"), ("ipv4", "never-default", "true"), ("ipv6", "method", "disabled")]);
+        assert!(never_default(&vlan));
+        let split_vpn = settings(&[("ipv4", "never-default", "true"), ("ipv6", "never-default", "true")]);
+        assert!(never_default(&split_vpn));
+
+        // Either family taking the default route keeps the connection
+        assert!(!never_default(&settings(&[("ipv4", "never-default", "true"), ("ipv6", "method", "auto")])));
+        assert!(!never_default(&settings(&[("ipv4", "never-default", "false"), ("ipv6", "method", "ignore")])));
+        // Unknown settings count as routable
+        assert!(!never_default(&settings(&[("ipv4", "never-default", "true")])));
+        assert!(!never_default(&ProfileSettings::new()));
+    }
+}
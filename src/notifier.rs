@@ -0,0 +1,243 @@
+//! Desktop notification module with Do Not Disturb awareness
+//! Non-critical notifications raised while DND is active are queued as missed events, and
+//! every notification is kept in a short in-memory history for the "Recent events" menu.
+
+use crate::geo::GeoInfo;
+use notify_rust::{Notification, Urgency};
+use std::collections::VecDeque;
+use std::sync::{LazyLock, Mutex};
+use std::time::SystemTime;
+use tokio::process::Command;
+use zbus::{proxy, Connection};
+
+/// Maximum number of missed notifications kept in memory
+const MAX_MISSED: usize = 20;
+/// Maximum number of recent events kept in memory
+const MAX_RECENT: usize = 30;
+
+/// Notifications suppressed while Do Not Disturb was active
+static MISSED: LazyLock<Mutex<VecDeque<MissedEvent>>> = LazyLock::new(|| Mutex::new(VecDeque::new()));
+/// All notifications raised, shown or not
+static RECENT: LazyLock<Mutex<VecDeque<MissedEvent>>> = LazyLock::new(|| Mutex::new(VecDeque::new()));
+
+/// How important a notification is
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Priority {
+    /// Suppressed and queued while DND is active
+    Normal,
+    /// Always shown, even during DND or with notifications disabled
+    Critical,
+}
+
+/// A notification with the time it was raised (missed or recent)
+#[derive(Debug, Clone)]
+pub struct MissedEvent {
+    pub message: String,
+    pub at: SystemTime,
+}
+
+/// D-Bus proxy for the notification server (KDE exposes DND as `Inhibited`)
+#[proxy(
+    interface = "org.freedesktop.Notifications",
+    default_service = "org.freedesktop.Notifications",
+    default_path = "/org/freedesktop/Notifications"
+)]
+trait Notifications {
+    #[zbus(property)]
+    fn inhibited(&self) -> zbus::Result<bool>;
+}
+
+/// Checks KDE's notification inhibition property
+async fn kde_inhibited() -> Option<bool> {
+    let connection = Connection::session().await.ok()?;
+    let proxy = NotificationsProxy::new(&connection).await.ok()?;
+    proxy.inhibited().await.ok()
+}
+
+/// Checks GNOME's "show banners" setting (false = Do Not Disturb)
+async fn gnome_banners_hidden() -> Option<bool> {
+    let output = Command::new("gsettings")
+        .args(["get", "org.gnome.desktop.notifications", "show-banners"])
+        .output()
+        .await
+        .ok()?;
+    if !output.status.success() {
+        return None;
+    }
+    Some(String::from_utf8_lossy(&output.stdout).trim() == "false")
+}
+
+/// Returns true when the desktop is in Do Not Disturb mode
+pub async fn dnd_active() -> bool {
+    if kde_inhibited().await == Some(true) {
+        return true;
+    }
+    gnome_banners_hidden().await.unwrap_or(false)
+}
+
+/// Shows a normal-priority desktop notification
+pub fn notify(body: impl Into<String>, timeout_ms: i32) {
+    notify_with(body, timeout_ms, Priority::Normal);
+}
+
+/// Shows a desktop notification, respecting the config and Do Not Disturb for non-critical ones.
+/// notify-rust uses block_on internally, so the actual call runs in spawn_blocking.
+pub fn notify_with(body: impl Into<String>, timeout_ms: i32, priority: Priority) {
+    let body = body.into();
+    push_bounded(&RECENT, MAX_RECENT, body.clone());
+    let prefs = crate::config::current().notifications.clone();
+    if priority == Priority::Normal && !prefs.enabled {
+        tracing::debug!("Notifications disabled, dropping: {}", body);
+        return;
+    }
+
+    tokio::spawn(async move {
+        if priority == Priority::Normal && prefs.respect_dnd && dnd_active().await {
+            tracing::debug!("Do Not Disturb active, queuing notification: {}", body);
+            queue_missed(body);
+            return;
+        }
+
+        let urgency = match priority {
+            Priority::Normal => Urgency::Normal,
+            Priority::Critical => Urgency::Critical,
+        };
+        let _ = tokio::task::spawn_blocking(move || {
+            let _ = Notification::new()
+                .summary("Network Monitor")
+                .body(&body)
+                .icon("network-monitor")
+                .urgency(urgency)
+                .timeout(timeout_ms)
+                .show();
+        })
+        .await;
+    });
+}
+
+/// Asks a question with "accept"/"Later" notification buttons, waiting up to `timeout_ms`.
+/// Returns None if the notification server does not support actions.
+pub async fn confirm(body: String, accept: &'static str, timeout_ms: i32) -> Option<bool> {
+    tokio::task::spawn_blocking(move || {
+        let capabilities = notify_rust::get_capabilities().unwrap_or_default();
+        if !capabilities.iter().any(|c| c == "actions") {
+            return None;
+        }
+        let handle = Notification::new()
+            .summary("Network Monitor")
+            .body(&body)
+            .icon("network-monitor")
+            .action("accept", accept)
+            .action("later", "Later")
+            .timeout(timeout_ms)
+            .show()
+            .ok()?;
+        let mut accepted = false;
+        handle.wait_for_action(|action| accepted = action == "accept");
+        Some(accepted)
+    })
+    .await
+    .ok()
+    .flatten()
+}
+
+fn queue_missed(message: String) {
+    push_bounded(&MISSED, MAX_MISSED, message);
+}
+
+/// Appends an event, dropping the oldest beyond `max`
+fn push_bounded(queue: &Mutex<VecDeque<MissedEvent>>, max: usize, message: String) {
+    let Ok(mut queue) = queue.lock() else { return };
+    if queue.len() == max {
+        queue.pop_front();
+    }
+    queue.push_back(MissedEvent {
+        message,
+        at: SystemTime::now(),
+    });
+}
+
+/// Returns missed notifications, newest first
+pub fn missed_events() -> Vec<MissedEvent> {
+    MISSED
+        .lock()
+        .map(|m| m.iter().rev().cloned().collect())
+        .unwrap_or_default()
+}
+
+/// Returns the last notifications, including missed and disabled ones, newest first
+pub fn recent_events() -> Vec<MissedEvent> {
+    RECENT
+        .lock()
+        .map(|r| r.iter().rev().cloned().collect())
+        .unwrap_or_default()
+}
+
+/// Announces a move to another country (e.g. a VPN dropping), if enabled in the config
+pub fn country_changed(old: &GeoInfo, new: &GeoInfo) {
+    if !crate::config::current().notifications.country_change {
+        return;
+    }
+    if let Some(message) = country_change_message(old, new) {
+        notify(message, 8000);
+    }
+}
+
+/// "Location changed: Germany → Netherlands, new IP x.x.x.x", or None if the country is the same
+fn country_change_message(old: &GeoInfo, new: &GeoInfo) -> Option<String> {
+    if old.country_code.is_empty() || old.country_code == new.country_code {
+        return None;
+    }
+    let ip = if crate::privacy::hide_ip() { String::new() } else { format!(", new IP {}", new.query) };
+    Some(format!("Location changed: {} → {}{}", old.country_name(), new.country_name(), ip))
+}
+
+/// Clears the missed notifications list
+pub fn clear_missed() {
+    if let Ok(mut missed) = MISSED.lock() {
+        missed.clear();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_missed_queue_is_bounded() {
+        clear_missed();
+        for i in 0..MAX_MISSED + 5 {
+            queue_missed(format!("event {}", i));
+        }
+        let missed = missed_events();
+        assert_eq!(missed.len(), MAX_MISSED);
+        assert_eq!(missed[0].message, format!("event {}", MAX_MISSED + 4));
+        clear_missed();
+        assert!(missed_events().is_empty());
+    }
+
+    #[test]
+    fn test_recent_events_newest_first() {
+        for i in 0..MAX_RECENT + 2 {
+            push_bounded(&RECENT, MAX_RECENT, format!("recent {}", i));
+        }
+        let recent = recent_events();
+        assert_eq!(recent.len(), MAX_RECENT);
+        assert_eq!(recent[0].message, format!("recent {}", MAX_RECENT + 1));
+    }
+
+    #[test]
+    fn test_country_change_message() {
+        let old = GeoInfo { country: "Germany".into(), country_code: "DE".into(), ..Default::default() };
+        let new = GeoInfo {
+            query: "198.51.100.1".into(),
+            country: "Netherlands".into(),
+            country_code: "NL".into(),
+            ..Default::default()
+        };
+        let message = country_change_message(&old, &new).unwrap();
+        assert!(message.starts_with("Location changed: "));
+        assert!(message.ends_with(", new IP 198.51.100.1"));
+        assert_eq!(country_change_message(&new, &new), None);
+    }
+}
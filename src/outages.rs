@@ -0,0 +1,228 @@
+//! ISP outage history per connection
+//! Records every disconnect with the connection (SSID/profile) and ISP it happened on,
+//! persisted in ~/.local/share/network-monitor/outages, and aggregates reliability stats.
+
+use crate::network::ConnectionInfo;
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::PathBuf;
+use std::sync::{LazyLock, Mutex};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+const OUTAGES_FILENAME: &str = "outages";
+/// Oldest records are dropped beyond this many
+const MAX_RECORDS: usize = 1000;
+/// Label used when the connection is unknown (no NetworkManager)
+const UNKNOWN_CONNECTION: &str = "default";
+
+/// Recorded outages plus the one currently in progress
+static OUTAGES: LazyLock<Mutex<OutageLog>> = LazyLock::new(|| Mutex::new(OutageLog::load()));
+
+/// A single disconnect, in Unix seconds
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Outage {
+    pub start: u64,
+    pub end: u64,
+    /// Connection UUID (empty when unknown)
+    pub uuid: String,
+    /// Connection name / SSID
+    pub connection: String,
+    /// ISP seen on the connection before it dropped
+    pub isp: String,
+}
+
+/// Reliability figures for one connection
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ConnectionStats {
+    pub connection: String,
+    pub isp: String,
+    pub outages: usize,
+    pub downtime: Duration,
+    /// Mean time between the starts of consecutive disconnects (needs two outages)
+    pub mean_time_between: Option<Duration>,
+}
+
+#[derive(Debug, Default)]
+struct OutageLog {
+    records: Vec<Outage>,
+    open: Option<Outage>,
+}
+
+impl OutageLog {
+    fn load() -> Self {
+        let records = outages_file()
+            .and_then(|p| fs::read_to_string(p).ok())
+            .map(|c| parse(&c))
+            .unwrap_or_default();
+        Self { records, open: None }
+    }
+
+    fn save(&self) {
+        // Do-not-log mode keeps outages in memory only
+        if crate::privacy::no_log() {
+            return;
+        }
+        let Some(path) = outages_file() else { return };
+        if let Some(parent) = path.parent() {
+            let _ = fs::create_dir_all(parent);
+        }
+        let content: String = self
+            .records
+            .iter()
+            .map(|o| format!("{}\t{}\t{}\t{}\t{}\n", o.start, o.end, o.uuid, o.connection, o.isp))
+            .collect();
+        if let Err(e) = fs::write(path, content) {
+            tracing::warn!("Failed to save outage history: {}", e);
+        }
+    }
+}
+
+fn outages_file() -> Option<PathBuf> {
+    dirs::data_dir().map(|p| p.join("network-monitor").join(OUTAGES_FILENAME))
+}
+
+fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or(Duration::ZERO)
+        .as_secs()
+}
+
+/// Parses "start<TAB>end<TAB>uuid<TAB>connection<TAB>isp" lines
+fn parse(content: &str) -> Vec<Outage> {
+    content
+        .lines()
+        .filter_map(|line| {
+            let mut fields = line.split('\t');
+            Some(Outage {
+                start: fields.next()?.parse().ok()?,
+                end: fields.next()?.parse().ok()?,
+                uuid: fields.next()?.to_string(),
+                connection: fields.next()?.to_string(),
+                isp: fields.next().unwrap_or_default().to_string(),
+            })
+        })
+        .collect()
+}
+
+/// Marks the start of an outage on `connection` (ignored if one is already open)
+pub fn begin(connection: Option<&ConnectionInfo>, isp: &str) {
+    let Ok(mut log) = OUTAGES.lock() else { return };
+    if log.open.is_some() {
+        return;
+    }
+    log.open = Some(Outage {
+        start: now(),
+        end: 0,
+        uuid: connection.map(|c| c.uuid.clone()).unwrap_or_default(),
+        connection: connection.map_or(UNKNOWN_CONNECTION.to_string(), |c| c.name.clone()),
+        isp: isp.to_string(),
+    });
+}
+
+/// Closes the open outage, if any, and persists it
+pub fn end() {
+    let Ok(mut log) = OUTAGES.lock() else { return };
+    let Some(mut outage) = log.open.take() else { return };
+    outage.end = now().max(outage.start);
+    tracing::info!("Outage on {} lasted {}s", outage.connection, outage.end - outage.start);
+
+    log.records.push(outage);
+    let excess = log.records.len().saturating_sub(MAX_RECORDS);
+    log.records.drain(..excess);
+    log.save();
+}
+
+/// All recorded outages, oldest first
+pub fn records() -> Vec<Outage> {
+    OUTAGES.lock().map(|l| l.records.clone()).unwrap_or_default()
+}
+
+/// Aggregates outages per connection, least reliable (most outages) first
+pub fn stats() -> Vec<ConnectionStats> {
+    aggregate(&records())
+}
+
+fn aggregate(records: &[Outage]) -> Vec<ConnectionStats> {
+    // Group by UUID when known so renamed profiles stay together
+    let mut groups: BTreeMap<&str, Vec<&Outage>> = BTreeMap::new();
+    for outage in records {
+        let key = if outage.uuid.is_empty() { &outage.connection } else { &outage.uuid };
+        groups.entry(key).or_default().push(outage);
+    }
+
+    let mut stats: Vec<ConnectionStats> = groups
+        .into_values()
+        .map(|outages| {
+            let last = outages[outages.len() - 1];
+            let downtime = outages.iter().map(|o| o.end - o.start).sum();
+            let mean_time_between = (outages.len() > 1).then(|| {
+                let span = last.start - outages[0].start;
+                Duration::from_secs(span / (outages.len() as u64 - 1))
+            });
+            ConnectionStats {
+                connection: last.connection.clone(),
+                isp: last.isp.clone(),
+                outages: outages.len(),
+                downtime: Duration::from_secs(downtime),
+                mean_time_between,
+            }
+        })
+        .collect();
+    stats.sort_by(|a, b| b.outages.cmp(&a.outages).then_with(|| a.connection.cmp(&b.connection)));
+    stats
+}
+
+/// Exports all outages as CSV
+pub fn export_csv() -> String {
+    let mut csv = String::from("start,end,duration_secs,connection,uuid,isp\n");
+    for o in records() {
+        csv.push_str(&format!(
+            "{},{},{},{},{},{}\n",
+            o.start,
+            o.end,
+            o.end - o.start,
+            csv_field(&o.connection),
+            o.uuid,
+            csv_field(&o.isp)
+        ));
+    }
+    csv
+}
+
+pub(crate) fn csv_field(value: &str) -> String {
+    if value.contains([',', '"', '\n']) {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        value.to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_aggregate() {
+        let records = parse(
+            "1000\t1060\tu1\tHome\tViettel\n\
+             2000\t2030\tu2\tOffice\tFPT\n\
+             4600\t4700\tu1\tHome Wi-Fi\tViettel\n\
+             broken line\n",
+        );
+        assert_eq!(records.len(), 3);
+
+        let stats = aggregate(&records);
+        assert_eq!(stats[0].connection, "Home Wi-Fi");
+        assert_eq!(stats[0].outages, 2);
+        assert_eq!(stats[0].downtime, Duration::from_secs(160));
+        assert_eq!(stats[0].mean_time_between, Some(Duration::from_secs(3600)));
+        assert_eq!(stats[1].mean_time_between, None);
+    }
+
+    #[test]
+    fn test_csv_field() {
+        assert_eq!(csv_field("Viettel"), "Viettel");
+        assert_eq!(csv_field("Swisscom, AG"), "\"Swisscom, AG\"");
+    }
+}
@@ -0,0 +1,111 @@
+//! Built-in 5x7 pixel font for text on generated icons
+//! Covers A-Z, 0-9 and '?', enough for country codes; glyphs are drawn as square cells
+//! and smoothed by the downscaling to the panel size.
+
+use image::{Rgba, RgbaImage};
+
+const GLYPH_WIDTH: u32 = 5;
+const GLYPH_HEIGHT: u32 = 7;
+/// Empty columns between glyphs
+const SPACING: u32 = 1;
+
+/// Rows of a glyph, most significant of the low five bits on the left
+fn glyph(c: char) -> Option<[u8; 7]> {
+    let rows = match c.to_ascii_uppercase() {
+        'A' => [0b01110, 0b10001, 0b10001, 0b11111, 0b10001, 0b10001, 0b10001],
+        'B' => [0b11110, 0b10001, 0b10001, 0b11110, 0b10001, 0b10001, 0b11110],
+        'C' => [0b01110, 0b10001, 0b10000, 0b10000, 0b10000, 0b10001, 0b01110],
+        'D' => [0b11110, 0b10001, 0b10001, 0b10001, 0b10001, 0b10001, 0b11110],
+        'E' => [0b11111, 0b10000, 0b10000, 0b11110, 0b10000, 0b10000, 0b11111],
+        'F' => [0b11111, 0b10000, 0b10000, 0b11110, 0b10000, 0b10000, 0b10000],
+        'G' => [0b01110, 0b10001, 0b10000, 0b10111, 0b10001, 0b10001, 0b01111],
+        'H' => [0b10001, 0b10001, 0b10001, 0b11111, 0b10001, 0b10001, 0b10001],
+        'I' => [0b01110, 0b00100, 0b00100, 0b00100, 0b00100, 0b00100, 0b01110],
+        'J' => [0b00111, 0b00010, 0b00010, 0b00010, 0b00010, 0b10010, 0b01100],
+        'K' => [0b10001, 0b10010, 0b10100, 0b11000, 0b10100, 0b10010, 0b10001],
+        'L' => [0b10000, 0b10000, 0b10000, 0b10000, 0b10000, 0b10000, 0b11111],
+        'M' => [0b10001, 0b11011, 0b10101, 0b10101, 0b10001, 0b10001, 0b10001],
+        'N' => [0b10001, 0b10001, 0b11001, 0b10101, 0b10011, 0b10001, 0b10001],
+        'O' => [0b01110, 0b10001, 0b10001, 0b10001, 0b10001, 0b10001, 0b01110],
+        'P' => [0b11110, 0b10001, 0b10001, 0b11110, 0b10000, 0b10000, 0b10000],
+        'Q' => [0b01110, 0b10001, 0b10001, 0b10001, 0b10101, 0b10010, 0b01101],
+        'R' => [0b11110, 0b10001, 0b10001, 0b11110, 0b10100, 0b10010, 0b10001],
+        'S' => [0b01111, 0b10000, 0b10000, 0b01110, 0b00001, 0b00001, 0b11110],
+        'T' => [0b11111, 0b00100, 0b00100, 0b00100, 0b00100, 0b00100, 0b00100],
+        'U' => [0b10001, 0b10001, 0b10001, 0b10001, 0b10001, 0b10001, 0b01110],
+        'V' => [0b10001, 0b10001, 0b10001, 0b10001, 0b10001, 0b01010, 0b00100],
+        'W' => [0b10001, 0b10001, 0b10001, 0b10101, 0b10101, 0b10101, 0b01010],
+        'X' => [0b10001, 0b10001, 0b01010, 0b00100, 0b01010, 0b10001, 0b10001],
+        'Y' => [0b10001, 0b10001, 0b01010, 0b00100, 0b00100, 0b00100, 0b00100],
+        'Z' => [0b11111, 0b00001, 0b00010, 0b00100, 0b01000, 0b10000, 0b11111],
+        '0' => [0b01110, 0b10001, 0b10011, 0b10101, 0b11001, 0b10001, 0b01110],
+        '1' => [0b00100, 0b01100, 0b00100, 0b00100, 0b00100, 0b00100, 0b01110],
+        '2' => [0b01110, 0b10001, 0b00001, 0b00010, 0b00100, 0b01000, 0b11111],
+        '3' => [0b11111, 0b00010, 0b00100, 0b00010, 0b00001, 0b10001, 0b01110],
+        '4' => [0b00010, 0b00110, 0b01010, 0b10010, 0b11111, 0b00010, 0b00010],
+        '5' => [0b11111, 0b10000, 0b11110, 0b00001, 0b00001, 0b10001, 0b01110],
+        '6' => [0b00110, 0b01000, 0b10000, 0b11110, 0b10001, 0b10001, 0b01110],
+        '7' => [0b11111, 0b00001, 0b00010, 0b00100, 0b01000, 0b01000, 0b01000],
+        '8' => [0b01110, 0b10001, 0b10001, 0b01110, 0b10001, 0b10001, 0b01110],
+        '9' => [0b01110, 0b10001, 0b10001, 0b01111, 0b00001, 0b00010, 0b01100],
+        '?' => [0b01110, 0b10001, 0b00001, 0b00010, 0b00100, 0b00000, 0b00100],
+        _ => return None,
+    };
+    Some(rows)
+}
+
+/// Width and height in cells; characters without a glyph are skipped
+pub fn text_cells(text: &str) -> (u32, u32) {
+    let count = text.chars().filter(|&c| glyph(c).is_some()).count() as u32;
+    (count * (GLYPH_WIDTH + SPACING) - SPACING.min(count), GLYPH_HEIGHT)
+}
+
+/// Largest cell size at which `text` fits in `width` x `height` pixels
+pub fn fit_cell(text: &str, width: u32, height: u32) -> u32 {
+    let (cols, rows) = text_cells(text);
+    (width / cols.max(1)).min(height / rows).max(1)
+}
+
+/// Draws `text` centered on (`cx`, `cy`) with `cell`-pixel squares in opaque `color`
+pub fn draw_text(icon: &mut RgbaImage, text: &str, cell: u32, (cx, cy): (u32, u32), color: [u8; 3]) {
+    let (cols, rows) = text_cells(text);
+    let left = cx.saturating_sub(cols * cell / 2);
+    let top = cy.saturating_sub(rows * cell / 2);
+    let glyphs = text.chars().filter_map(glyph);
+    for (index, rows) in glyphs.enumerate() {
+        let glyph_left = left + index as u32 * (GLYPH_WIDTH + SPACING) * cell;
+        for (row, bits) in rows.iter().enumerate() {
+            for col in 0..GLYPH_WIDTH {
+                if bits & (1 << (GLYPH_WIDTH - 1 - col)) == 0 {
+                    continue;
+                }
+                let (x0, y0) = (glyph_left + col * cell, top + row as u32 * cell);
+                for y in y0..(y0 + cell).min(icon.height()) {
+                    for x in x0..(x0 + cell).min(icon.width()) {
+                        icon.put_pixel(x, y, Rgba([color[0], color[1], color[2], 255]));
+                    }
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_draw_text() {
+        assert!(('A'..='Z').chain('0'..='9').all(|c| glyph(c).is_some()));
+        assert_eq!(text_cells("DE"), (11, 7));
+        assert_eq!(text_cells("d-e"), (11, 7));
+        assert_eq!(fit_cell("DE", 80, 80), 7);
+
+        let mut icon = RgbaImage::new(24, 16);
+        draw_text(&mut icon, "I", 2, (12, 8), [255, 255, 255]);
+        // Top bar of the I spans columns 1-3 of the glyph, starting at x = 12 - 5
+        assert_eq!(icon.get_pixel(7, 1)[3], 0);
+        assert_eq!(icon.get_pixel(9, 1)[3], 255);
+        assert_eq!(icon.get_pixel(11, 4)[3], 255);
+    }
+}
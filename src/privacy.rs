@@ -0,0 +1,147 @@
+//! Privacy options
+//! `[privacy] minimize_fingerprint` sends a common browser User-Agent, tries providers in
+//! random order and jitters refreshes; `no_log` keeps history off disk and IPs out of logs;
+//! `hide_ip` keeps the public IP off screen for streaming and screen sharing.
+
+use crate::geo::GeoInfo;
+use ring::rand::{SecureRandom, SystemRandom};
+use std::io::{self, Write};
+use std::time::Duration;
+
+/// Sent when fingerprint minimization is off
+pub const APP_USER_AGENT: &str = concat!("network-monitor/", env!("CARGO_PKG_VERSION"));
+/// Current mainstream browser User-Agents
+const USER_AGENTS: &[&str] = &[
+    "Mozilla/5.0 (Windows NT 10.0; Win64; x64) AppleWebKit/537.36 (KHTML, like Gecko) Chrome/140.0.0.0 Safari/537.36",
+    "Mozilla/5.0 (Windows NT 10.0; Win64; x64; rv:143.0) Gecko/20100101 Firefox/143.0",
+    "Mozilla/5.0 (Macintosh; Intel Mac OS X 10_15_7) AppleWebKit/605.1.15 (KHTML, like Gecko) Version/18.6 Safari/605.1.15",
+    "Mozilla/5.0 (X11; Linux x86_64) AppleWebKit/537.36 (KHTML, like Gecko) Chrome/140.0.0.0 Safari/537.36",
+    "Mozilla/5.0 (X11; Linux x86_64; rv:143.0) Gecko/20100101 Firefox/143.0",
+];
+/// Periodic refreshes are shifted by up to this fraction of the interval, either way
+const JITTER_RATIO: f64 = 0.25;
+
+pub fn enabled() -> bool {
+    crate::config::current().privacy.minimize_fingerprint
+}
+
+/// Do-not-log mode: no history is written to disk and logged IPs are redacted
+pub fn no_log() -> bool {
+    crate::config::current().privacy.no_log
+}
+
+/// Hide-IP mode: the menu, tooltip and notifications show only country and city, and
+/// logged IPs are redacted
+pub fn hide_ip() -> bool {
+    crate::config::current().privacy.hide_ip
+}
+
+/// Whether logged IP addresses are replaced with `<ip>`
+fn redact_logs() -> bool {
+    let privacy = &crate::config::current().privacy;
+    privacy.no_log || privacy.hide_ip
+}
+
+/// `geo.address_lines()`, or none while `hide_ip` is on
+pub fn address_lines(geo: &GeoInfo) -> Vec<String> {
+    if hide_ip() {
+        return Vec::new();
+    }
+    geo.address_lines()
+}
+
+/// Log writer that replaces IP addresses with `<ip>` while `no_log` or `hide_ip` is on
+pub struct RedactingWriter<W>(pub W);
+
+impl<W: Write> Write for RedactingWriter<W> {
+    /// tracing's fmt layer hands over one whole event per write, so addresses aren't split
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        write_redacted(&mut self.0, buf, redact_logs())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.0.flush()
+    }
+}
+
+/// Writes `buf` to `out`, with IP addresses replaced when `redact` is set
+fn write_redacted<W: Write>(out: &mut W, buf: &[u8], redact: bool) -> io::Result<usize> {
+    if !redact {
+        return out.write(buf);
+    }
+    let redacted = crate::diagnostics::redact_ips(&String::from_utf8_lossy(buf));
+    out.write_all(redacted.as_bytes())?;
+    Ok(buf.len())
+}
+
+/// Uniform random number below `bound` (0 when `bound` is 0)
+fn random_below(bound: u64) -> u64 {
+    let mut bytes = [0u8; 8];
+    if bound == 0 || SystemRandom::new().fill(&mut bytes).is_err() {
+        return 0;
+    }
+    u64::from_ne_bytes(bytes) % bound
+}
+
+/// User-Agent for the next request
+pub fn user_agent() -> &'static str {
+    if !enabled() {
+        return APP_USER_AGENT;
+    }
+    USER_AGENTS[random_below(USER_AGENTS.len() as u64) as usize]
+}
+
+/// Delay until the next periodic refresh: `period`, jittered when enabled
+pub fn next_refresh(period: Duration) -> Duration {
+    if !enabled() {
+        return period;
+    }
+    jitter(period, random_below(u64::MAX))
+}
+
+/// `period` shifted by `roll` mapped onto ±JITTER_RATIO, never below the refresh minimum
+fn jitter(period: Duration, roll: u64) -> Duration {
+    let spread = period.as_secs_f64() * JITTER_RATIO;
+    let offset = (roll as f64 / u64::MAX as f64) * 2.0 * spread - spread;
+    Duration::from_secs_f64((period.as_secs_f64() + offset).max(crate::config::MIN_REFRESH_INTERVAL.as_secs_f64()))
+}
+
+/// Fisher-Yates shuffle, used to spread lookups over the providers
+pub fn shuffle<T>(items: &mut [T]) {
+    for i in (1..items.len()).rev() {
+        items.swap(i, random_below(i as u64 + 1) as usize);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_jitter_bounds() {
+        let period = Duration::from_secs(60);
+        assert_eq!(jitter(period, 0), Duration::from_secs(45));
+        assert_eq!(jitter(period, u64::MAX), Duration::from_secs(75));
+        assert_eq!(jitter(Duration::from_secs(10), 0), crate::config::MIN_REFRESH_INTERVAL);
+    }
+
+    #[test]
+    fn test_write_redacted() {
+        let line = b"Initial location: Vietnam (VN) - 203.0.113.7\n";
+        let mut out = Vec::new();
+        assert_eq!(write_redacted(&mut out, line, true).unwrap(), line.len());
+        assert_eq!(out, b"Initial location: Vietnam (VN) - <ip>\n");
+
+        out.clear();
+        write_redacted(&mut out, line, false).unwrap();
+        assert_eq!(out, line);
+    }
+
+    #[test]
+    fn test_shuffle_keeps_items() {
+        let mut items = [1, 2, 3, 4, 5];
+        shuffle(&mut items);
+        items.sort();
+        assert_eq!(items, [1, 2, 3, 4, 5]);
+    }
+}
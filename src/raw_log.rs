@@ -0,0 +1,113 @@
+//! Rotating log of raw provider responses
+//! The last few lookup bodies are kept in ~/.local/state/network-monitor/raw_responses.json so
+//! `network-monitor debug dump` can show maintainers exactly what a provider sent.
+
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::PathBuf;
+use std::sync::Mutex;
+
+const RAW_LOG_FILENAME: &str = "raw_responses.json";
+/// Responses kept; older ones are dropped as new ones arrive
+pub const MAX_RESPONSES: usize = 5;
+
+/// Serializes read-modify-write of the file between concurrent lookups
+static LOCK: Mutex<()> = Mutex::new(());
+
+/// One provider response, timestamp in Unix seconds
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Response {
+    pub timestamp: u64,
+    pub provider: String,
+    pub body: String,
+}
+
+fn raw_log_file() -> Option<PathBuf> {
+    dirs::state_dir().map(|p| p.join("network-monitor").join(RAW_LOG_FILENAME))
+}
+
+/// Pushes `response`, keeping the newest `MAX_RESPONSES`
+fn rotate(responses: &mut Vec<Response>, response: Response) {
+    responses.push(response);
+    responses.drain(..responses.len().saturating_sub(MAX_RESPONSES));
+}
+
+/// Stored responses, oldest first
+pub fn load() -> Vec<Response> {
+    raw_log_file()
+        .and_then(|p| fs::read_to_string(p).ok())
+        .and_then(|c| serde_json::from_str(&c).ok())
+        .unwrap_or_default()
+}
+
+/// Keeps `body` from `provider`; nothing is written while `[privacy] no_log` is on, and IPs
+/// are redacted while `hide_ip` is
+pub fn record(provider: &str, body: &[u8]) {
+    if crate::privacy::no_log() {
+        return;
+    }
+    let Some(path) = raw_log_file() else { return };
+    let mut body = String::from_utf8_lossy(body).into_owned();
+    if crate::privacy::hide_ip() {
+        body = crate::diagnostics::redact_ips(&body);
+    }
+    let response = Response { timestamp: crate::history::now(), provider: provider.to_string(), body };
+
+    let _guard = LOCK.lock().unwrap_or_else(|e| e.into_inner());
+    let mut responses = load();
+    rotate(&mut responses, response);
+    if let Some(parent) = path.parent() {
+        let _ = fs::create_dir_all(parent);
+    }
+    let content = serde_json::to_string_pretty(&responses).unwrap_or_default();
+    if let Err(e) = fs::write(&path, content) {
+        tracing::warn!("Failed to save raw responses: {}", e);
+    }
+}
+
+/// A header per response followed by its body verbatim; `redact` strips IPs and the home
+/// directory for pasting into a public issue
+pub fn render(responses: &[Response], redact: bool) -> String {
+    let mut out = String::new();
+    for r in responses {
+        let time = std::time::UNIX_EPOCH + std::time::Duration::from_secs(r.timestamp);
+        let body = if redact { crate::diagnostics::sanitize(&r.body) } else { r.body.clone() };
+        out.push_str(&format!(
+            "=== {} from {} ({} bytes) ===\n{}\n",
+            crate::schedule::format_local_date(time),
+            r.provider,
+            r.body.len(),
+            body.trim_end()
+        ));
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn response(timestamp: u64, body: &str) -> Response {
+        Response { timestamp, provider: "ipinfo".into(), body: body.into() }
+    }
+
+    #[test]
+    fn test_rotate() {
+        let mut responses = Vec::new();
+        for t in 0..MAX_RESPONSES as u64 + 2 {
+            rotate(&mut responses, response(t, "{}"));
+        }
+        assert_eq!(responses.len(), MAX_RESPONSES);
+        assert_eq!(responses[0].timestamp, 2);
+        assert_eq!(responses.last().unwrap().timestamp, MAX_RESPONSES as u64 + 1);
+    }
+
+    #[test]
+    fn test_render() {
+        let responses = vec![response(0, "{\"ip\":\"203.0.113.7\"}\n")];
+        let plain = render(&responses, false);
+        assert!(plain.contains("from ipinfo (21 bytes)"));
+        assert!(plain.ends_with("{\"ip\":\"203.0.113.7\"}\n"));
+        assert!(render(&responses, true).ends_with("{\"ip\":\"<ip>\"}\n"));
+    }
+}
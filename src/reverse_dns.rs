@@ -0,0 +1,65 @@
+//! Reverse DNS of the public IP
+//! A PTR lookup through the system resolver (`getnameinfo`); hostnames like
+//! `static.vodafone.de` or `*.cgnat.example` tell residential, datacenter and CGNAT apart.
+
+use std::ffi::CStr;
+use std::net::IpAddr;
+use std::time::Duration;
+
+const LOOKUP_TIMEOUT: Duration = Duration::from_secs(3);
+/// NI_MAXHOST from <netdb.h>
+const MAX_HOST: usize = 1025;
+
+/// PTR name of `ip`, None if it has none, isn't an address or the lookup is slow
+pub async fn lookup(ip: &str) -> Option<String> {
+    let ip: IpAddr = ip.parse().ok()?;
+    let task = tokio::task::spawn_blocking(move || ptr(ip));
+    tokio::time::timeout(LOOKUP_TIMEOUT, task).await.ok()?.ok()?
+}
+
+fn ptr(ip: IpAddr) -> Option<String> {
+    // SAFETY: zeroed sockaddr_storage is valid, the family-specific fields are filled in
+    // below, and getnameinfo writes at most MAX_HOST bytes (NUL-terminated) into `host`
+    unsafe {
+        let mut storage: libc::sockaddr_storage = std::mem::zeroed();
+        let len = match ip {
+            IpAddr::V4(v4) => {
+                let addr = &mut *(&mut storage as *mut libc::sockaddr_storage).cast::<libc::sockaddr_in>();
+                addr.sin_family = libc::AF_INET as libc::sa_family_t;
+                addr.sin_addr.s_addr = u32::from_ne_bytes(v4.octets());
+                std::mem::size_of::<libc::sockaddr_in>()
+            }
+            IpAddr::V6(v6) => {
+                let addr = &mut *(&mut storage as *mut libc::sockaddr_storage).cast::<libc::sockaddr_in6>();
+                addr.sin6_family = libc::AF_INET6 as libc::sa_family_t;
+                addr.sin6_addr.s6_addr = v6.octets();
+                std::mem::size_of::<libc::sockaddr_in6>()
+            }
+        };
+        let mut host = [0 as libc::c_char; MAX_HOST];
+        let result = libc::getnameinfo(
+            (&storage as *const libc::sockaddr_storage).cast(),
+            len as libc::socklen_t,
+            host.as_mut_ptr(),
+            host.len() as libc::socklen_t,
+            std::ptr::null_mut(),
+            0,
+            libc::NI_NAMEREQD,
+        );
+        if result != 0 {
+            return None;
+        }
+        CStr::from_ptr(host.as_ptr()).to_str().ok().map(str::to_string)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_lookup_rejects_non_addresses() {
+        assert_eq!(lookup("not an address").await, None);
+        assert_eq!(lookup("").await, None);
+    }
+}
@@ -0,0 +1,134 @@
+//! Alert rules
+//! `[[rules]]` in config.toml pair conditions on country, ASN, ISP and VPN state with actions
+//! (notify, run a script, badge the icon), taken when a rule starts matching after a refresh.
+
+use crate::geo::GeoInfo;
+use crate::notifier::{notify, notify_with, Priority};
+use std::collections::HashSet;
+use std::path::PathBuf;
+use std::sync::{LazyLock, Mutex};
+
+/// Names of the rules that matched on the last refresh
+static MATCHING: LazyLock<Mutex<HashSet<String>>> = LazyLock::new(|| Mutex::new(HashSet::new()));
+
+/// A `[[rules]]` entry; it matches when all conditions hold
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Rule {
+    pub name: String,
+    pub conditions: Vec<Condition>,
+    pub actions: Vec<Action>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Condition {
+    /// Country code is (or with `negate`, is not) this one
+    Country { code: String, negate: bool },
+    Asn { asn: u32, negate: bool },
+    /// ISP name contains this text, ignoring case
+    Isp { text: String, negate: bool },
+    /// A VPN connection is (or isn't) up
+    Vpn(bool),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Action {
+    /// Show `message`; `{name}`, `{country}`, `{country_code}`, `{isp}`, `{asn}` and `{ip}` are filled in
+    Notify { message: String, critical: bool },
+    /// Run a script with the location in `NETWORK_MONITOR_*` variables
+    Run(PathBuf),
+    /// Put the attention badge on the tray icon while the rule matches
+    Badge,
+}
+
+impl Condition {
+    fn holds(&self, info: &GeoInfo, vpn_active: bool) -> bool {
+        match self {
+            Condition::Country { code, negate } => info.country_code.eq_ignore_ascii_case(code) != *negate,
+            Condition::Asn { asn, negate } => (info.asn == Some(*asn)) != *negate,
+            Condition::Isp { text, negate } => {
+                info.isp.to_lowercase().contains(&text.to_lowercase()) != *negate
+            }
+            Condition::Vpn(wanted) => vpn_active == *wanted,
+        }
+    }
+}
+
+impl Rule {
+    pub fn matches(&self, info: &GeoInfo, vpn_active: bool) -> bool {
+        self.conditions.iter().all(|c| c.holds(info, vpn_active))
+    }
+}
+
+fn fill(template: &str, rule: &Rule, info: &GeoInfo) -> String {
+    template
+        .replace("{name}", &rule.name)
+        .replace("{country}", &info.country_name())
+        .replace("{country_code}", &info.country_code)
+        .replace("{isp}", &info.isp)
+        .replace("{asn}", &info.asn.map(|n| format!("AS{}", n)).unwrap_or_default())
+        .replace("{ip}", &info.query)
+}
+
+/// Evaluates the configured rules against a fresh location; actions run for rules that
+/// did not match on the previous refresh. `quiet` (an unstable connection) only updates
+/// which rules match, for the badge.
+pub fn evaluate(info: &GeoInfo, quiet: bool) {
+    let config = crate::config::current();
+    let vpn_active = crate::network::vpn_active();
+    let Ok(mut matching) = MATCHING.lock() else { return };
+    let previous = std::mem::take(&mut *matching);
+
+    for rule in config.rules.iter().filter(|r| r.matches(info, vpn_active)) {
+        matching.insert(rule.name.clone());
+        if previous.contains(&rule.name) || quiet {
+            continue;
+        }
+        tracing::info!("Rule \"{}\" matched", rule.name);
+        for action in &rule.actions {
+            match action {
+                Action::Notify { message, critical: true } => {
+                    notify_with(fill(message, rule, info), 0, Priority::Critical)
+                }
+                Action::Notify { message, critical: false } => notify(fill(message, rule, info), 8000),
+                Action::Run(script) => crate::hooks::run_for_rule(script, &rule.name, info),
+                Action::Badge => {}
+            }
+        }
+    }
+}
+
+/// Whether a currently matching rule asks for the attention badge
+pub fn badge_active() -> bool {
+    let Ok(matching) = MATCHING.lock() else { return false };
+    crate::config::current()
+        .rules
+        .iter()
+        .any(|r| matching.contains(&r.name) && r.actions.contains(&Action::Badge))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_rule_matching() {
+        let rule = Rule {
+            name: "Leaking VPN".to_string(),
+            conditions: vec![
+                Condition::Isp { text: "mullvad".to_string(), negate: true },
+                Condition::Vpn(true),
+            ],
+            actions: vec![],
+        };
+        let info = GeoInfo { isp: "Deutsche Telekom AG".to_string(), asn: Some(3320), ..Default::default() };
+        assert!(rule.matches(&info, true));
+        assert!(!rule.matches(&info, false));
+        let mullvad = GeoInfo { isp: "Mullvad VPN AB".to_string(), ..info.clone() };
+        assert!(!rule.matches(&mullvad, true));
+
+        assert_eq!(
+            fill("{name}: {isp} ({asn})", &rule, &info),
+            "Leaking VPN: Deutsche Telekom AG (AS3320)"
+        );
+    }
+}
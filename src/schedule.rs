@@ -0,0 +1,135 @@
+//! Refresh blackout windows
+//! During a configured local-time window (e.g. nightly backups on a metered link)
+//! automatic refreshes and connectivity probes are suspended.
+
+use std::fmt;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// A daily local-time window, minutes since midnight; may wrap past midnight
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BlackoutWindow {
+    pub start: u16,
+    pub end: u16,
+}
+
+impl BlackoutWindow {
+    /// Parses "HH:MM-HH:MM"
+    pub fn parse(s: &str) -> Option<Self> {
+        let (start, end) = s.split_once('-')?;
+        let window = Self {
+            start: parse_time(start.trim())?,
+            end: parse_time(end.trim())?,
+        };
+        (window.start != window.end).then_some(window)
+    }
+
+    /// Returns true if `minute` (since local midnight) falls inside the window
+    pub fn contains(&self, minute: u16) -> bool {
+        if self.start < self.end {
+            (self.start..self.end).contains(&minute)
+        } else {
+            minute >= self.start || minute < self.end
+        }
+    }
+}
+
+impl fmt::Display for BlackoutWindow {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}-{}", format_time(self.start), format_time(self.end))
+    }
+}
+
+fn parse_time(s: &str) -> Option<u16> {
+    let (hours, minutes) = s.split_once(':')?;
+    let hours: u16 = hours.parse().ok()?;
+    let minutes: u16 = minutes.parse().ok()?;
+    (hours < 24 && minutes < 60).then_some(hours * 60 + minutes)
+}
+
+/// Formats minutes since midnight as "HH:MM"
+pub fn format_time(minute: u16) -> String {
+    format!("{:02}:{:02}", minute / 60, minute % 60)
+}
+
+/// Current local time as minutes since midnight
+fn local_minute_of_day() -> u16 {
+    local_minute(SystemTime::now())
+}
+
+/// Local wall-clock time of `at` as "HH:MM"
+pub fn format_local(at: SystemTime) -> String {
+    format_time(local_minute(at))
+}
+
+/// Local date and time of `at` as "YYYY-MM-DD HH:MM"
+pub fn format_local_date(at: SystemTime) -> String {
+    match local_tm(at) {
+        Some(tm) => format!(
+            "{:04}-{:02}-{:02} {:02}:{:02}",
+            tm.tm_year + 1900,
+            tm.tm_mon + 1,
+            tm.tm_mday,
+            tm.tm_hour,
+            tm.tm_min
+        ),
+        None => format_local(at),
+    }
+}
+
+/// Local time of `at` as minutes since midnight
+fn local_minute(at: SystemTime) -> u16 {
+    local_tm(at).map_or(0, |tm| (tm.tm_hour * 60 + tm.tm_min) as u16)
+}
+
+fn local_tm(at: SystemTime) -> Option<libc::tm> {
+    let secs = at.duration_since(UNIX_EPOCH).map_or(0, |d| d.as_secs()) as libc::time_t;
+    // SAFETY: localtime_r writes into our own tm; libc handles the timezone lookup
+    unsafe {
+        let mut tm: libc::tm = std::mem::zeroed();
+        (!libc::localtime_r(&secs, &mut tm).is_null()).then_some(tm)
+    }
+}
+
+/// Returns the configured blackout window active right now, if any
+pub fn active_blackout() -> Option<BlackoutWindow> {
+    let minute = local_minute_of_day();
+    crate::config::current()
+        .schedule
+        .blackout
+        .iter()
+        .find(|w| w.contains(minute))
+        .copied()
+}
+
+/// Returns true while automatic refreshes and probes are suspended
+pub fn in_blackout() -> bool {
+    active_blackout().is_some()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_window() {
+        let w = BlackoutWindow::parse("01:30-05:00").unwrap();
+        assert_eq!((w.start, w.end), (90, 300));
+        assert_eq!(w.to_string(), "01:30-05:00");
+        assert!(BlackoutWindow::parse("25:00-05:00").is_none());
+        assert!(BlackoutWindow::parse("01:00").is_none());
+        assert!(BlackoutWindow::parse("03:00-03:00").is_none());
+    }
+
+    #[test]
+    fn test_contains_wraps_midnight() {
+        let nightly = BlackoutWindow::parse("23:00-02:00").unwrap();
+        assert!(nightly.contains(23 * 60 + 30));
+        assert!(nightly.contains(60));
+        assert!(!nightly.contains(2 * 60));
+        assert!(!nightly.contains(12 * 60));
+
+        let day = BlackoutWindow::parse("09:00-17:00").unwrap();
+        assert!(day.contains(9 * 60));
+        assert!(!day.contains(17 * 60));
+    }
+}
@@ -0,0 +1,139 @@
+//! Secret Service (libsecret keyring) storage for config secrets
+//! A secret set to `"@keyring"` in config.toml (e.g. `[mqtt] password`) is read from the
+//! desktop keyring at use time; `network-monitor secrets migrate` moves plaintext ones there.
+
+use std::collections::HashMap;
+use thiserror::Error;
+use zbus::zvariant::{ObjectPath, OwnedObjectPath, OwnedValue, Value};
+use zbus::{proxy, Connection};
+
+/// Config value meaning "look this up in the keyring"
+pub const KEYRING_MARKER: &str = "@keyring";
+/// Config keys holding secrets, as "section.key"
+pub const SECRET_KEYS: &[&str] = &["mqtt.password", "webhook.url"];
+const APPLICATION: &str = "network-monitor";
+const DEFAULT_COLLECTION: &str = "/org/freedesktop/secrets/aliases/default";
+/// "/" as a prompt path means no user interaction is needed
+const NO_PROMPT: &str = "/";
+
+/// (session, parameters, value, content type), the Secret Service `Secret` struct
+type Secret = (OwnedObjectPath, Vec<u8>, Vec<u8>, String);
+
+#[derive(Debug, Error)]
+pub enum SecretError {
+    #[error("Keyring unavailable: {0}")]
+    Unavailable(#[from] zbus::Error),
+    #[error("The keyring is locked")]
+    Locked,
+    #[error("No '{0}' in the keyring")]
+    NotFound(String),
+    #[error("'{0}' is not a secret config key")]
+    UnknownKey(String),
+}
+
+#[proxy(
+    interface = "org.freedesktop.Secret.Service",
+    default_service = "org.freedesktop.secrets",
+    default_path = "/org/freedesktop/secrets"
+)]
+trait Service {
+    fn open_session(&self, algorithm: &str, input: &Value<'_>) -> zbus::Result<(OwnedValue, OwnedObjectPath)>;
+
+    fn search_items(
+        &self,
+        attributes: HashMap<&str, &str>,
+    ) -> zbus::Result<(Vec<OwnedObjectPath>, Vec<OwnedObjectPath>)>;
+
+    fn get_secrets(
+        &self,
+        items: &[ObjectPath<'_>],
+        session: &ObjectPath<'_>,
+    ) -> zbus::Result<HashMap<OwnedObjectPath, Secret>>;
+}
+
+#[proxy(interface = "org.freedesktop.Secret.Collection", default_service = "org.freedesktop.secrets")]
+trait Collection {
+    fn create_item(
+        &self,
+        properties: HashMap<&str, Value<'_>>,
+        secret: &Secret,
+        replace: bool,
+    ) -> zbus::Result<(OwnedObjectPath, OwnedObjectPath)>;
+}
+
+/// Whether a config value is the keyring marker
+pub fn is_keyring(value: &str) -> bool {
+    value == KEYRING_MARKER
+}
+
+fn attributes(key: &str) -> HashMap<&str, &str> {
+    HashMap::from([("application", APPLICATION), ("key", key)])
+}
+
+/// Opens an unencrypted transfer session; the session bus is local to the user
+async fn session(connection: &Connection) -> Result<(ServiceProxy<'_>, OwnedObjectPath), SecretError> {
+    let service = ServiceProxy::new(connection).await?;
+    let (_, session) = service.open_session("plain", &Value::from("")).await?;
+    Ok((service, session))
+}
+
+/// Reads `key` (e.g. "mqtt.password") from the keyring
+pub async fn get(key: &str) -> Result<String, SecretError> {
+    let connection = Connection::session().await?;
+    let (service, session) = session(&connection).await?;
+    let (unlocked, locked) = service.search_items(attributes(key)).await?;
+    let Some(item) = unlocked.into_iter().next() else {
+        return Err(if locked.is_empty() { SecretError::NotFound(key.to_string()) } else { SecretError::Locked });
+    };
+    let secrets = service.get_secrets(&[item.as_ref()], &session.as_ref()).await?;
+    let (_, _, value, _) = secrets.into_values().next().ok_or_else(|| SecretError::NotFound(key.to_string()))?;
+    Ok(String::from_utf8_lossy(&value).into_owned())
+}
+
+/// Stores `value` under `key` in the default collection, replacing an older entry
+pub async fn set(key: &str, value: &str) -> Result<(), SecretError> {
+    if !SECRET_KEYS.contains(&key) {
+        return Err(SecretError::UnknownKey(key.to_string()));
+    }
+    let connection = Connection::session().await?;
+    let (_, session) = session(&connection).await?;
+    let collection = CollectionProxy::builder(&connection).path(DEFAULT_COLLECTION)?.build().await?;
+    let properties = HashMap::from([
+        ("org.freedesktop.Secret.Item.Label", Value::from(format!("Network Monitor: {}", key))),
+        ("org.freedesktop.Secret.Item.Attributes", Value::from(attributes(key))),
+    ]);
+    let secret: Secret = (session, Vec::new(), value.as_bytes().to_vec(), "text/plain".to_string());
+    let (_, prompt) = collection.create_item(properties, &secret, true).await?;
+    if prompt.as_str() != NO_PROMPT {
+        return Err(SecretError::Locked);
+    }
+    Ok(())
+}
+
+/// The usable value of a secret config entry: itself, or the keyring entry for `@keyring`.
+/// Keyring failures are logged and yield None, which leaves the feature off.
+pub async fn resolve(key: &str, value: Option<&str>) -> Option<String> {
+    let value = value?;
+    if !is_keyring(value) {
+        return Some(value.to_string());
+    }
+    match get(key).await {
+        Ok(secret) => Some(secret),
+        Err(e) => {
+            tracing::warn!("Could not read {} from the keyring: {}", key, e);
+            None
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_resolve_plain_values() {
+        assert_eq!(resolve("mqtt.password", Some("hunter2")).await.as_deref(), Some("hunter2"));
+        assert_eq!(resolve("mqtt.password", None).await, None);
+        assert!(is_keyring("@keyring"));
+    }
+}
@@ -0,0 +1,327 @@
+//! Session bus service on org.btxtruong.NetworkMonitor
+//! Publishes the current status as D-Bus properties (with PropertiesChanged) and offers
+//! GetLocation/Refresh/Pause plus a LocationChanged signal for scripts and shell extensions.
+//! Update state (version, available release, channel, last check) is exposed for fleet audits.
+
+use crate::geo::GeoInfo;
+use crate::tray::TrayCommand;
+use crate::updater::{self, UpdateState};
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use tokio::sync::mpsc;
+use zbus::object_server::SignalEmitter;
+use zbus::{fdo, interface, Connection};
+
+pub const BUS_NAME: &str = "org.btxtruong.NetworkMonitor";
+pub const OBJECT_PATH: &str = "/org/btxtruong/NetworkMonitor";
+pub const INTERFACE: &str = "org.btxtruong.NetworkMonitor1";
+
+/// Session bus connection, set once the service is registered (again after the bus
+/// restarted, see session_bus)
+static CONNECTION: Mutex<Option<Connection>> = Mutex::new(None);
+
+/// Status exposed on the bus
+#[derive(Debug, Default)]
+struct Status {
+    country_code: String,
+    public_ip: String,
+    connectivity: Connectivity,
+    location: Option<HashMap<String, String>>,
+    /// Unix time of the last successful lookup (kept out of `location` so
+    /// LocationChanged only fires on real changes)
+    refreshed_at: u64,
+    /// Release found by the last update check, empty if none
+    available_version: String,
+    update_channel: String,
+    /// Unix time of the last update check, 0 if never
+    last_update_check: u64,
+    /// Main loop, for Refresh()/Pause()
+    commands: Option<mpsc::Sender<TrayCommand>>,
+}
+
+impl Status {
+    fn set_update_state(&mut self, state: UpdateState) {
+        self.available_version = state.available.unwrap_or_default();
+        self.update_channel = state.channel.as_str().to_string();
+        self.last_update_check = state.last_check.unwrap_or(0);
+    }
+
+    fn send(&self, command: TrayCommand) -> fdo::Result<()> {
+        self.commands
+            .as_ref()
+            .and_then(|tx| tx.try_send(command).ok())
+            .ok_or_else(|| fdo::Error::Failed("network-monitor is busy or shutting down".to_string()))
+    }
+}
+
+/// GeoInfo as a string dict, the payload of GetLocation() and LocationChanged
+fn location_dict(info: &GeoInfo) -> HashMap<String, String> {
+    let mut dict: HashMap<String, String> = [
+        ("ip", info.query.clone()),
+        ("country_code", info.country_code.clone()),
+        ("country", info.country.clone()),
+        ("city", info.city.clone()),
+        ("isp", info.isp.clone()),
+        ("mobile", info.mobile.to_string()),
+        ("hosting", info.hosting.to_string()),
+    ]
+    .into_iter()
+    .map(|(k, v)| (k.to_string(), v))
+    .collect();
+    for (key, address) in [("ipv4", &info.ipv4), ("ipv6", &info.ipv6)] {
+        if let Some(address) = address {
+            dict.insert(key.to_string(), address.clone());
+        }
+    }
+    dict
+}
+
+/// Connectivity as exposed on the bus
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Connectivity {
+    #[default]
+    Unknown,
+    Connected,
+    Disconnected,
+}
+
+impl Connectivity {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Connectivity::Unknown => "unknown",
+            Connectivity::Connected => "connected",
+            Connectivity::Disconnected => "disconnected",
+        }
+    }
+}
+
+// Keep in sync with INTERFACE
+#[interface(name = "org.btxtruong.NetworkMonitor1")]
+impl Status {
+    /// ISO 3166-1 alpha-2 code of the displayed location
+    #[zbus(property)]
+    fn country_code(&self) -> String {
+        self.country_code.clone()
+    }
+
+    /// Public IP address of the displayed location
+    #[zbus(property, name = "PublicIP")]
+    fn public_ip(&self) -> String {
+        self.public_ip.clone()
+    }
+
+    /// "connected", "disconnected" or "unknown"
+    #[zbus(property)]
+    fn connectivity(&self) -> String {
+        self.connectivity.as_str().to_string()
+    }
+
+    /// Running version
+    #[zbus(property(emits_changed_signal = "const"))]
+    fn version(&self) -> String {
+        updater::VERSION.to_string()
+    }
+
+    /// Newer release found by the last update check, empty if none
+    #[zbus(property)]
+    fn available_version(&self) -> String {
+        self.available_version.clone()
+    }
+
+    /// "stable" or "beta"
+    #[zbus(property)]
+    fn update_channel(&self) -> String {
+        self.update_channel.clone()
+    }
+
+    /// Unix time of the last update check, 0 if there was none
+    #[zbus(property)]
+    fn last_update_check(&self) -> u64 {
+        self.last_update_check
+    }
+
+    /// Current location: ip, country_code, country, city, isp, mobile, hosting,
+    /// refreshed_at (Unix time) and, when known, ipv4/ipv6
+    fn get_location(&self) -> fdo::Result<HashMap<String, String>> {
+        let mut location = self
+            .location
+            .clone()
+            .ok_or_else(|| fdo::Error::Failed("Location not known yet".to_string()))?;
+        location.insert("refreshed_at".to_string(), self.refreshed_at.to_string());
+        Ok(location)
+    }
+
+    /// Refreshes the location now (same cooldown as the menu item)
+    fn refresh(&self) -> fdo::Result<()> {
+        self.send(TrayCommand::Refresh)
+    }
+
+    /// Pauses automatic refreshes for `seconds`; 0 resumes them
+    fn pause(&self, seconds: u32) -> fdo::Result<()> {
+        self.send(TrayCommand::Pause(Duration::from_secs(seconds.into())))
+    }
+
+    /// Emitted with the GetLocation() dict whenever the location changes
+    #[zbus(signal)]
+    async fn location_changed(emitter: &SignalEmitter<'_>, location: HashMap<String, String>) -> zbus::Result<()>;
+}
+
+/// Registers the service object and claims the bus name, queueing behind a previous owner;
+/// method calls are forwarded to the main loop through `commands`
+pub async fn start(commands: mpsc::Sender<TrayCommand>) -> zbus::Result<()> {
+    let mut status = Status {
+        commands: Some(commands),
+        ..Default::default()
+    };
+    status.set_update_state(updater::state());
+    let connection = zbus::connection::Builder::session()?
+        .serve_at(OBJECT_PATH, status)?
+        .build()
+        .await?;
+    // Queued rather than refused: right after an update the old instance still owns the name,
+    // and the bus hands it over once that one exits
+    let reply = connection.request_name_with_flags(BUS_NAME, Default::default()).await?;
+    if matches!(reply, fdo::RequestNameReply::InQueue) {
+        tracing::info!("{} is still owned by another instance, waiting for it to exit", BUS_NAME);
+    }
+    if let Ok(mut current) = CONNECTION.lock() {
+        *current = Some(connection);
+    }
+    Ok(())
+}
+
+/// Updates CountryCode/PublicIP and the GetLocation() data
+pub fn publish_location(info: &GeoInfo) {
+    let country_code = info.country_code.clone();
+    let public_ip = info.query.clone();
+    let location = location_dict(info);
+    let refreshed_at = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or(Duration::ZERO)
+        .as_secs();
+    update(move |status| {
+        status.country_code = country_code;
+        status.public_ip = public_ip;
+        status.location = Some(location);
+        status.refreshed_at = refreshed_at;
+    });
+}
+
+/// Updates Connectivity
+pub fn publish_connectivity(connectivity: Connectivity) {
+    update(move |status| status.connectivity = connectivity);
+}
+
+/// Updates AvailableVersion, UpdateChannel and LastUpdateCheck
+pub fn publish_update_state(state: UpdateState) {
+    update(move |status| status.set_update_state(state));
+}
+
+/// Asks the running instance for its update state
+pub async fn query_update_state() -> zbus::Result<UpdateState> {
+    let connection = Connection::session().await?;
+    let proxy = zbus::Proxy::new(&connection, BUS_NAME, OBJECT_PATH, INTERFACE).await?;
+    let version: String = proxy.get_property("Version").await?;
+    let available: String = proxy.get_property("AvailableVersion").await?;
+    let channel: String = proxy.get_property("UpdateChannel").await?;
+    let last_check: u64 = proxy.get_property("LastUpdateCheck").await?;
+    Ok(UpdateState {
+        version,
+        available: Some(available).filter(|v| !v.is_empty()),
+        channel: updater::Channel::parse(&channel).unwrap_or_default(),
+        last_check: Some(last_check).filter(|&t| t > 0),
+    })
+}
+
+/// Asks the running instance for its location (client side of GetLocation)
+pub async fn query_location() -> zbus::Result<HashMap<String, String>> {
+    let connection = Connection::session().await?;
+    let reply = connection
+        .call_method(Some(BUS_NAME), OBJECT_PATH, Some(INTERFACE), "GetLocation", &())
+        .await?;
+    reply.body().deserialize()
+}
+
+/// Applies `f` to the status and emits PropertiesChanged for each property that changed,
+/// and LocationChanged if the location did
+fn update<F>(f: F)
+where
+    F: FnOnce(&mut Status) + Send + 'static,
+{
+    let Some(connection) = CONNECTION.lock().ok().and_then(|c| c.clone()) else { return };
+    tokio::spawn(async move {
+        let result: zbus::Result<()> = async {
+            let iface = connection.object_server().interface::<_, Status>(OBJECT_PATH).await?;
+            let emitter = iface.signal_emitter();
+            let mut status = iface.get_mut().await;
+            let before = (status.country_code.clone(), status.public_ip.clone(), status.connectivity);
+            let update_before =
+                (status.available_version.clone(), status.update_channel.clone(), status.last_update_check);
+            let location_before = status.location.clone();
+            f(&mut status);
+
+            if status.country_code != before.0 {
+                status.country_code_changed(emitter).await?;
+            }
+            if status.public_ip != before.1 {
+                // zbus derives the signal helper name from "PublicIP"
+                status.public_i_p_changed(emitter).await?;
+            }
+            if status.connectivity != before.2 {
+                status.connectivity_changed(emitter).await?;
+            }
+            if status.available_version != update_before.0 {
+                status.available_version_changed(emitter).await?;
+            }
+            if status.update_channel != update_before.1 {
+                status.update_channel_changed(emitter).await?;
+            }
+            if status.last_update_check != update_before.2 {
+                status.last_update_check_changed(emitter).await?;
+            }
+            if let Some(location) = status.location.as_ref().filter(|l| Some(*l) != location_before.as_ref()) {
+                Status::location_changed(emitter, location.clone()).await?;
+            }
+            Ok(())
+        }
+        .await;
+        if let Err(e) = result {
+            tracing::debug!("Failed to publish status on D-Bus: {}", e);
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_location_dict() {
+        let info = GeoInfo {
+            query: "203.0.113.7".to_string(),
+            country_code: "VN".to_string(),
+            country: "Vietnam".to_string(),
+            city: "Hanoi".to_string(),
+            ipv6: Some("2001:db8::7".to_string()),
+            ..Default::default()
+        };
+        let dict = location_dict(&info);
+        assert_eq!(dict["ip"], "203.0.113.7");
+        assert_eq!(dict["country"], "Vietnam");
+        assert_eq!(dict["ipv6"], "2001:db8::7");
+        assert!(!dict.contains_key("ipv4"));
+    }
+
+    #[test]
+    fn test_update_state() {
+        let mut status = Status::default();
+        status.set_update_state(UpdateState {
+            version: updater::VERSION.to_string(),
+            available: None,
+            channel: updater::Channel::Beta,
+            last_check: None,
+        });
+        assert_eq!((status.available_version.as_str(), status.update_channel.as_str(), status.last_update_check), ("", "beta", 0));
+    }
+}
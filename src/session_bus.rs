@@ -0,0 +1,57 @@
+//! Session bus loss detection
+//! The tray, the D-Bus API and the shortcut portal each hold a session bus connection that
+//! dies with the bus (session restart, crashed daemon); `spawn` pings it and reports when a
+//! new bus is up so they can register again. Notifications connect per call and recover alone.
+
+use crate::tray::TrayCommand;
+use std::time::Duration;
+use tokio::sync::mpsc;
+use zbus::fdo::DBusProxy;
+use zbus::Connection;
+
+/// How often the bus is pinged
+const CHECK_INTERVAL: Duration = Duration::from_secs(15);
+/// A dead socket fails fast, but a wedged daemon may not answer at all
+const PING_TIMEOUT: Duration = Duration::from_secs(5);
+/// Delay between attempts to reach a new bus after the old one went away
+const RECONNECT_INTERVAL: Duration = Duration::from_secs(2);
+
+/// Whether the bus behind `connection` still answers
+async fn alive(connection: &Connection) -> bool {
+    let ping = async { DBusProxy::new(connection).await?.get_id().await };
+    matches!(tokio::time::timeout(PING_TIMEOUT, ping).await, Ok(Ok(_)))
+}
+
+/// Connects to the session bus, retrying until one answers
+async fn reconnect() -> Connection {
+    loop {
+        tokio::time::sleep(RECONNECT_INTERVAL).await;
+        if let Ok(connection) = Connection::session().await {
+            if alive(&connection).await {
+                return connection;
+            }
+        }
+    }
+}
+
+/// Pings the session bus and sends `TrayCommand::SessionBusRestored` whenever it came back
+/// after being lost; does nothing without a session bus to begin with
+pub fn spawn(commands: mpsc::Sender<TrayCommand>) {
+    tokio::spawn(async move {
+        let Ok(mut connection) = Connection::session().await else { return };
+        let mut timer = tokio::time::interval(CHECK_INTERVAL);
+        timer.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Skip);
+        loop {
+            timer.tick().await;
+            if alive(&connection).await {
+                continue;
+            }
+            tracing::warn!("Lost the session bus connection, waiting for the bus to come back");
+            connection = reconnect().await;
+            tracing::info!("Session bus is back");
+            if commands.send(TrayCommand::SessionBusRestored).await.is_err() {
+                return;
+            }
+        }
+    });
+}
@@ -0,0 +1,158 @@
+//! Global keyboard shortcut via the XDG GlobalShortcuts portal
+//! Binds `[shortcuts] show_status` (e.g. "CTRL+ALT+L"); the desktop may let the user pick
+//! another trigger. Each activation pops a notification with the current location.
+
+use crate::tray::TrayCommand;
+use futures_util::StreamExt;
+use std::collections::HashMap;
+use thiserror::Error;
+use tokio::sync::mpsc;
+use zbus::zvariant::{ObjectPath, OwnedObjectPath, OwnedValue, Value};
+use zbus::{proxy, Connection};
+
+const SHORTCUT_ID: &str = "show-status";
+const PORTAL_SERVICE: &str = "org.freedesktop.portal.Desktop";
+
+#[derive(Debug, Error)]
+pub enum ShortcutError {
+    #[error("D-Bus error: {0}")]
+    DBus(#[from] zbus::Error),
+    #[error("Global shortcuts portal unavailable")]
+    Unavailable,
+    #[error("Shortcut request was cancelled or denied")]
+    Denied,
+}
+
+#[proxy(
+    interface = "org.freedesktop.portal.GlobalShortcuts",
+    default_service = "org.freedesktop.portal.Desktop",
+    default_path = "/org/freedesktop/portal/desktop"
+)]
+trait GlobalShortcuts {
+    fn create_session(&self, options: HashMap<&str, Value<'_>>) -> zbus::Result<OwnedObjectPath>;
+
+    fn bind_shortcuts(
+        &self,
+        session_handle: &ObjectPath<'_>,
+        shortcuts: &[(&str, HashMap<&str, Value<'_>>)],
+        parent_window: &str,
+        options: HashMap<&str, Value<'_>>,
+    ) -> zbus::Result<OwnedObjectPath>;
+
+    #[zbus(signal)]
+    fn activated(
+        &self,
+        session_handle: OwnedObjectPath,
+        shortcut_id: String,
+        timestamp: u64,
+        options: HashMap<String, OwnedValue>,
+    ) -> zbus::Result<()>;
+}
+
+#[proxy(interface = "org.freedesktop.portal.Request", default_service = "org.freedesktop.portal.Desktop")]
+trait Request {
+    #[zbus(signal)]
+    fn response(&self, response: u32, results: HashMap<String, OwnedValue>) -> zbus::Result<()>;
+}
+
+/// Binds the configured shortcut and forwards activations as `TrayCommand::ShowStatus`
+pub fn spawn(command_tx: mpsc::Sender<TrayCommand>) {
+    let Some(trigger) = crate::config::current().shortcuts.show_status.clone() else { return };
+    tokio::spawn(async move {
+        if let Err(e) = listen(&trigger, command_tx).await {
+            tracing::warn!("Global shortcut {} not available: {}", trigger, e);
+        }
+    });
+}
+
+async fn listen(trigger: &str, command_tx: mpsc::Sender<TrayCommand>) -> Result<(), ShortcutError> {
+    let connection = Connection::session().await?;
+    let portal = GlobalShortcutsProxy::new(&connection).await?;
+
+    let token = handle_token("session");
+    let options = HashMap::from([
+        ("handle_token", Value::from(handle_token("create"))),
+        ("session_handle_token", Value::from(token.as_str())),
+    ]);
+    let results = request(&connection, &options, portal.create_session(options.clone())).await?;
+    let session = results
+        .get("session_handle")
+        .and_then(|v| String::try_from(v.clone()).ok())
+        .ok_or(ShortcutError::Unavailable)?;
+    let session = ObjectPath::try_from(session.as_str()).map_err(zbus::Error::from)?;
+
+    let shortcut = HashMap::from([
+        ("description", Value::from("Show network location")),
+        ("preferred_trigger", Value::from(trigger)),
+    ]);
+    let options = HashMap::from([("handle_token", Value::from(handle_token("bind")))]);
+    request(
+        &connection,
+        &options,
+        portal.bind_shortcuts(&session, &[(SHORTCUT_ID, shortcut)], "", options.clone()),
+    )
+    .await?;
+    tracing::info!("Global shortcut {} bound", trigger);
+
+    let mut activations = portal.receive_activated().await?;
+    while let Some(signal) = activations.next().await {
+        let Ok(args) = signal.args() else { continue };
+        if args.shortcut_id == SHORTCUT_ID && command_tx.send(TrayCommand::ShowStatus).await.is_err() {
+            break;
+        }
+    }
+    Ok(())
+}
+
+/// Unique token for a portal request or session handle
+fn handle_token(kind: &str) -> String {
+    format!("network_monitor_{}_{}", kind, std::process::id())
+}
+
+/// Makes a portal call and waits for its Response; the request object path is predictable,
+/// so the signal is subscribed to before the call to avoid missing a quick response
+async fn request(
+    connection: &Connection,
+    options: &HashMap<&str, Value<'_>>,
+    call: impl std::future::Future<Output = zbus::Result<OwnedObjectPath>>,
+) -> Result<HashMap<String, OwnedValue>, ShortcutError> {
+    let token = options
+        .get("handle_token")
+        .and_then(|v| v.downcast_ref::<&str>().ok())
+        .ok_or(ShortcutError::Unavailable)?;
+    let sender = connection.unique_name().ok_or(ShortcutError::Unavailable)?;
+    let path = request_path(sender.as_str(), token);
+    let proxy = RequestProxy::builder(connection).destination(PORTAL_SERVICE)?.path(path)?.build().await?;
+    let mut responses = proxy.receive_response().await?;
+
+    call.await?;
+    let response = responses.next().await.ok_or(ShortcutError::Unavailable)?;
+    let args = response.args()?;
+    if args.response != 0 {
+        return Err(ShortcutError::Denied);
+    }
+    Ok(args.results)
+}
+
+/// `/org/freedesktop/portal/desktop/request/<sender>/<token>`, with the sender's
+/// leading ':' dropped and '.' replaced by '_'
+fn request_path(sender: &str, token: &str) -> String {
+    format!(
+        "/org/freedesktop/portal/desktop/request/{}/{}",
+        sender.trim_start_matches(':').replace('.', "_"),
+        token
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_request_path() {
+        assert_eq!(
+            request_path(":1.42", "network_monitor_bind_7"),
+            "/org/freedesktop/portal/desktop/request/1_42/network_monitor_bind_7"
+        );
+    }
+}
@@ -0,0 +1,66 @@
+//! Suspend detection
+//! Tokio timers run on CLOCK_MONOTONIC, which stops while the machine sleeps; the growing gap
+//! to CLOCK_BOOTTIME tells us a resume happened, so the refresh timer can be rescheduled.
+
+use std::time::Duration;
+
+/// Smaller jumps are scheduler noise, not a suspend
+const THRESHOLD: Duration = Duration::from_secs(5);
+
+fn clock(id: libc::clockid_t) -> Duration {
+    let mut ts = libc::timespec { tv_sec: 0, tv_nsec: 0 };
+    // SAFETY: ts is a valid timespec to write into; the clock ids are always supported on Linux
+    if unsafe { libc::clock_gettime(id, &mut ts) } != 0 {
+        return Duration::ZERO;
+    }
+    Duration::new(ts.tv_sec as u64, ts.tv_nsec as u32)
+}
+
+/// Total time spent suspended since boot
+fn asleep_since_boot() -> Duration {
+    clock(libc::CLOCK_BOOTTIME).saturating_sub(clock(libc::CLOCK_MONOTONIC))
+}
+
+/// Notices resumes by watching the time spent suspended grow
+#[derive(Debug)]
+pub struct Detector {
+    asleep: Duration,
+}
+
+impl Default for Detector {
+    fn default() -> Self {
+        Self { asleep: asleep_since_boot() }
+    }
+}
+
+impl Detector {
+    /// How long the machine slept since the last call, if it did.
+    /// Each suspend is reported once, to whichever caller notices it first.
+    pub fn check(&mut self) -> Option<Duration> {
+        self.observe(asleep_since_boot())
+    }
+
+    fn observe(&mut self, asleep: Duration) -> Option<Duration> {
+        let slept = asleep.saturating_sub(self.asleep);
+        if slept < THRESHOLD {
+            return None;
+        }
+        self.asleep = asleep;
+        Some(slept)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_observe() {
+        let mut detector = Detector { asleep: Duration::from_secs(100) };
+        assert_eq!(detector.observe(Duration::from_secs(102)), None);
+        // Small jumps add up until they cross the threshold
+        assert_eq!(detector.observe(Duration::from_secs(106)), Some(Duration::from_secs(6)));
+        assert_eq!(detector.observe(Duration::from_secs(106)), None);
+        assert_eq!(detector.observe(Duration::from_secs(4000)), Some(Duration::from_secs(3894)));
+    }
+}
@@ -0,0 +1,111 @@
+//! Monochrome icons for `[icons] mode = "symbolic"`
+//! A globe outline with the country code inside, in one color like GNOME's symbolic
+//! icons; a plain globe while the country is unknown, struck through when offline.
+
+use crate::icons::ICON_SIZE;
+use crate::pixel_font;
+use image::{Rgba, RgbaImage};
+
+/// Adwaita's light foreground, as on GNOME's dark top bar
+pub const DEFAULT_COLOR: [u8; 3] = [0xee, 0xee, 0xec];
+
+/// Ring stroke as a fraction of the icon size
+const STROKE_RATIO: f32 = 0.08;
+/// Share of the ring's inner diameter the country code may use
+const TEXT_RATIO: f32 = 0.78;
+
+/// Parses "#rrggbb"
+pub fn parse_color(text: &str) -> Option<[u8; 3]> {
+    let hex = text.strip_prefix('#').filter(|h| h.len() == 6 && h.is_ascii())?;
+    let channel = |i: usize| u8::from_str_radix(&hex[i..i + 2], 16).ok();
+    Some([channel(0)?, channel(2)?, channel(4)?])
+}
+
+/// Formats a color as "#rrggbb"
+pub fn format_color([r, g, b]: [u8; 3]) -> String {
+    format!("#{:02x}{:02x}{:02x}", r, g, b)
+}
+
+/// Renders the symbolic icon at `ICON_SIZE`
+pub fn render(country_code: Option<&str>, offline: bool, color: [u8; 3]) -> RgbaImage {
+    let size = ICON_SIZE as f32;
+    let center = size / 2.0;
+    let half_stroke = size * STROKE_RATIO / 2.0;
+    let radius = center - half_stroke - 1.0;
+    let code = country_code.filter(|_| !offline);
+
+    let mut icon = RgbaImage::new(ICON_SIZE, ICON_SIZE);
+    cover(&mut icon, color, |x, y| {
+        let (dx, dy) = (x - center, y - center);
+        let distance = (dx * dx + dy * dy).sqrt();
+        let ring = (distance - radius).abs() - half_stroke;
+        if code.is_some() {
+            return ring;
+        }
+        // Equator and one meridian ellipse, clipped to the globe
+        let inside = distance - radius;
+        let equator = (dy.abs() - half_stroke * 0.7).max(inside);
+        let (rx, ry) = (radius * 0.45, radius);
+        let meridian = (((dx / rx).powi(2) + (dy / ry).powi(2)).sqrt() - 1.0).abs() * rx - half_stroke * 0.7;
+        ring.min(equator).min(meridian.max(inside))
+    });
+
+    if let Some(code) = code {
+        let room = ((radius - half_stroke) * 2.0 * TEXT_RATIO) as u32;
+        let cell = pixel_font::fit_cell(code, room, room);
+        pixel_font::draw_text(&mut icon, code, cell, (ICON_SIZE / 2, ICON_SIZE / 2), color);
+    } else if offline {
+        // Slash from top left to bottom right within the ring, with a transparent gap around it
+        let slash = |x: f32, y: f32, width: f32, reach: f32| {
+            let outside = ((x - center).powi(2) + (y - center).powi(2)).sqrt() - reach;
+            (((x - y) / std::f32::consts::SQRT_2).abs() - width).max(outside)
+        };
+        erase(&mut icon, |x, y| slash(x, y, half_stroke * 2.5, radius - half_stroke));
+        cover(&mut icon, color, |x, y| slash(x, y, half_stroke, radius));
+    }
+    icon
+}
+
+/// Paints `color` where the signed distance `shape` is below zero, with a one-pixel soft edge
+pub(crate) fn cover(icon: &mut RgbaImage, color: [u8; 3], shape: impl Fn(f32, f32) -> f32) {
+    for (x, y, pixel) in icon.enumerate_pixels_mut() {
+        let alpha = (0.5 - shape(x as f32 + 0.5, y as f32 + 0.5)).clamp(0.0, 1.0);
+        if alpha > 0.0 {
+            let alpha = ((alpha * 255.0) as u8).max(pixel[3]);
+            *pixel = Rgba([color[0], color[1], color[2], alpha]);
+        }
+    }
+}
+
+/// Clears pixels inside `shape`, keeping the soft edge
+fn erase(icon: &mut RgbaImage, shape: impl Fn(f32, f32) -> f32) {
+    for (x, y, pixel) in icon.enumerate_pixels_mut() {
+        let coverage = (0.5 - shape(x as f32 + 0.5, y as f32 + 0.5)).clamp(0.0, 1.0);
+        pixel[3] = (pixel[3] as f32 * (1.0 - coverage)).round() as u8;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_color() {
+        assert_eq!(parse_color("#eeeeec"), Some(DEFAULT_COLOR));
+        assert_eq!(format_color(DEFAULT_COLOR), "#eeeeec");
+        assert_eq!(parse_color("#2E3436"), Some([0x2e, 0x34, 0x36]));
+        assert_eq!(parse_color("eeeeec"), None);
+        assert_eq!(parse_color("#eeeee"), None);
+    }
+
+    #[test]
+    fn test_render() {
+        let color = DEFAULT_COLOR;
+        let de = render(Some("DE"), false, color);
+        assert!(de.pixels().all(|p| p[3] == 0 || p.0[..3] == color));
+        // Ring at the edge, text in the middle differs between countries
+        assert_eq!(de.get_pixel(ICON_SIZE / 2, 5)[3], 255);
+        assert_ne!(de, render(Some("FR"), false, color));
+        assert_ne!(render(None, false, color), render(None, true, color));
+    }
+}
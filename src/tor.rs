@@ -0,0 +1,73 @@
+//! Tor exit node detection
+//! Checks the public IP against the Tor Project's bulk exit list, downloaded at most once
+//! an hour, so Tor users see "Via Tor" instead of just the exit's country.
+
+use std::collections::HashSet;
+use std::net::IpAddr;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+const EXIT_LIST_URL: &str = "https://check.torproject.org/torbulkexitlist";
+const EXIT_LIST_HOST: &str = "check.torproject.org";
+/// The list is regenerated about every half hour; an hour old is close enough
+const LIST_TTL: Duration = Duration::from_secs(60 * 60);
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Known exit addresses and when they were downloaded
+static EXITS: Mutex<Option<(Instant, HashSet<IpAddr>)>> = Mutex::new(None);
+
+/// One address per line; anything else is skipped
+fn parse_list(text: &str) -> HashSet<IpAddr> {
+    text.lines().filter_map(|line| line.trim().parse().ok()).collect()
+}
+
+async fn download() -> Result<HashSet<IpAddr>, reqwest::Error> {
+    let client = reqwest::Client::builder().timeout(REQUEST_TIMEOUT).build()?;
+    crate::usage::record(EXIT_LIST_HOST);
+    let text = client
+        .get(EXIT_LIST_URL)
+        .header(reqwest::header::USER_AGENT, crate::privacy::user_agent())
+        .send()
+        .await?
+        .error_for_status()?
+        .text()
+        .await?;
+    Ok(parse_list(&text))
+}
+
+/// Whether `ip` is a Tor exit; false when it can't be told (the list keeps its last
+/// good copy if a download fails)
+pub async fn is_exit(ip: &str) -> bool {
+    let Ok(ip) = ip.parse::<IpAddr>() else { return false };
+    let stale = EXITS
+        .lock()
+        .map(|exits| exits.as_ref().is_none_or(|(at, _)| at.elapsed() >= LIST_TTL))
+        .unwrap_or(false);
+    if stale {
+        match download().await {
+            Ok(list) => {
+                tracing::debug!("Loaded {} Tor exit addresses", list.len());
+                if let Ok(mut exits) = EXITS.lock() {
+                    *exits = Some((Instant::now(), list));
+                }
+            }
+            Err(e) => tracing::warn!("Failed to download the Tor exit list: {}", e.without_url()),
+        }
+    }
+    EXITS
+        .lock()
+        .map(|exits| exits.as_ref().is_some_and(|(_, list)| list.contains(&ip)))
+        .unwrap_or(false)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_list() {
+        let list = parse_list("185.220.101.1\n2001:db8::7\n\n<html>\n");
+        assert_eq!(list.len(), 2);
+        assert!(list.contains(&"185.220.101.1".parse().unwrap()));
+    }
+}
@@ -1,48 +1,330 @@
 //! System tray module using ksni (StatusNotifierItem protocol)
 //! Displays country flag icon with network info menu.
 
+use crate::badge::{self, Badge};
+use crate::code_flag;
+use crate::confidence::{self, Confidence};
+use crate::diagnostics;
+use crate::environment::Environment;
 use crate::geo::GeoInfo;
-use crate::icons::{get_flag, ICON_SIZE};
-use ksni::{menu::{CheckmarkItem, StandardItem}, Icon, MenuItem, Tray};
+use crate::history;
+use crate::hosts;
+use crate::network::ConnectionInfo;
+use crate::icons::{self, get_styled_flag, resolve_styled_icon, status_icon, FlagIcon, FlagStyle, IconMode, StatusArt};
+use crate::schedule::{self, BlackoutWindow};
+use crate::service::Connectivity;
+use crate::symbolic;
+use crate::usage;
+use crate::waybar;
+use crate::watchdog;
+use crate::notifier::{self, notify};
+use crate::outages;
+use crate::privacy;
+use crate::rules;
+use crate::updater::Channel;
+use ksni::{menu::{CheckmarkItem, RadioGroup, RadioItem, StandardItem, SubMenu}, Icon, MenuItem, Tray, TrayMethods};
 use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant, SystemTime};
 use tokio::sync::mpsc;
 
+/// Entries in the "Recent locations" submenu
+const RECENT_LOCATIONS: usize = 10;
+/// Seconds offered under Settings → Refresh Interval; 0 is manual only
+const REFRESH_CHOICES: &[u64] = &[60, 300, 900, 0];
+/// How far the flag is grayed while monitoring is paused
+const PAUSED_DESATURATION: f32 = 0.6;
+/// Stale data is shown in plain grayscale
+const STALE_DESATURATION: f32 = 1.0;
+/// Alpha taken from the symbolic icon per unit of desaturation
+const SYMBOLIC_FADE: f32 = 0.5;
+/// Time per frame of the refresh spinner badge
+const SPINNER_FRAME: Duration = Duration::from_millis(125);
+
 /// Commands that can be sent from tray menu
 #[derive(Debug, Clone)]
 pub enum TrayCommand {
     Refresh,
+    DiagnoseMtu,
+    /// Trace the route and geo-locate the first public hop
+    LocateFirstHop,
     ToggleAutostart,
+    ToggleTrust,
+    ToggleBetaChannel,
+    /// Pause automatic refreshes for this long (zero resumes)
+    Pause(Duration),
     CheckUpdate,
     RunUpdate,
+    ReportIssue,
+    /// Compare the DNS resolver with the exit location
+    CheckDnsLeak,
+    /// Turn on NTP after clock skew was detected
+    SyncClock,
+    /// Start or stop keeping the raw geo response (debug)
+    ToggleRawResponse,
+    /// Copy the kept raw geo response to the clipboard
+    CopyRawResponse,
+    /// Open OpenStreetMap at the detected coordinates
+    OpenMap,
+    /// Stop or restart periodic and network-event refreshes
+    TogglePauseMonitoring,
+    /// Set and persist `refresh_interval` (zero: manual only)
+    SetRefreshInterval(Duration),
+    /// The session bus restarted; register the tray and D-Bus service again
+    SessionBusRestored,
+    /// Open `[menu] ip_details_url` for the public IP
+    OpenIpDetails,
+    /// Flip and persist `[privacy] hide_ip`
+    ToggleHideIp,
+    /// Switch and persist `[icons] style` between circle and rectangular flags
+    ToggleFlagStyle,
+    /// Bring up the saved VPN profile with this UUID
+    ConnectVpn(String),
+    /// Pop a notification with the current location (global shortcut)
+    ShowStatus,
     Quit,
 }
 
+/// A submenu of the tray menu. Sections register in `SECTIONS`, and `[menu] sections`
+/// picks and orders them by name.
+pub trait InfoProvider: Sync {
+    /// Name used in config.toml
+    fn name(&self) -> &'static str;
+    /// Submenu label, which may carry state
+    fn label(&self, tray: &NetworkTray) -> String;
+    /// The submenu's items; a section with none is left out of the menu
+    fn items(&self, tray: &NetworkTray, info: Option<&GeoInfo>) -> Vec<MenuItem<NetworkTray>>;
+}
+
+/// All sections, in default order
+pub static SECTIONS: &[&dyn InfoProvider] = &[&InfoSection, &DiagnosticsSection, &VpnSection, &SettingsSection, &UpdateSection];
+
+/// Looks up a section by its config name
+pub fn find_section(name: &str) -> Option<&'static dyn InfoProvider> {
+    SECTIONS.iter().find(|s| s.name() == name).copied()
+}
+
+struct InfoSection;
+
+impl InfoProvider for InfoSection {
+    fn name(&self) -> &'static str {
+        "info"
+    }
+
+    fn label(&self, _tray: &NetworkTray) -> String {
+        "Info".to_string()
+    }
+
+    fn items(&self, tray: &NetworkTray, info: Option<&GeoInfo>) -> Vec<MenuItem<NetworkTray>> {
+        tray.info_items(info)
+    }
+}
+
+struct DiagnosticsSection;
+
+impl InfoProvider for DiagnosticsSection {
+    fn name(&self) -> &'static str {
+        "diagnostics"
+    }
+
+    fn label(&self, _tray: &NetworkTray) -> String {
+        "Diagnostics".to_string()
+    }
+
+    fn items(&self, tray: &NetworkTray, _info: Option<&GeoInfo>) -> Vec<MenuItem<NetworkTray>> {
+        tray.diagnostics_items()
+    }
+}
+
+struct VpnSection;
+
+impl InfoProvider for VpnSection {
+    fn name(&self) -> &'static str {
+        "vpn"
+    }
+
+    fn label(&self, _tray: &NetworkTray) -> String {
+        "VPN".to_string()
+    }
+
+    fn items(&self, tray: &NetworkTray, _info: Option<&GeoInfo>) -> Vec<MenuItem<NetworkTray>> {
+        tray.vpn_items()
+    }
+}
+
+struct SettingsSection;
+
+impl InfoProvider for SettingsSection {
+    fn name(&self) -> &'static str {
+        "settings"
+    }
+
+    fn label(&self, _tray: &NetworkTray) -> String {
+        "Settings".to_string()
+    }
+
+    fn items(&self, tray: &NetworkTray, _info: Option<&GeoInfo>) -> Vec<MenuItem<NetworkTray>> {
+        tray.settings_items()
+    }
+}
+
+struct UpdateSection;
+
+impl InfoProvider for UpdateSection {
+    fn name(&self) -> &'static str {
+        "update"
+    }
+
+    fn label(&self, tray: &NetworkTray) -> String {
+        match tray.update_available {
+            Some(_) => "🔴 Update".to_string(),
+            None => "Update".to_string(),
+        }
+    }
+
+    fn items(&self, tray: &NetworkTray, _info: Option<&GeoInfo>) -> Vec<MenuItem<NetworkTray>> {
+        tray.update_items()
+    }
+}
+
+/// Location shown before the last change of country or city
+#[derive(Debug, Clone)]
+pub struct PreviousLocation {
+    pub info: GeoInfo,
+    /// When the newer location replaced it
+    pub replaced_at: SystemTime,
+}
+
+impl PreviousLocation {
+    /// Returns `old` as the previous location if `new` is somewhere else
+    /// (a new IP in the same city is not a move)
+    pub fn replaced(old: GeoInfo, new: &GeoInfo) -> Option<Self> {
+        (old.country_code != new.country_code || old.city != new.city).then(|| Self {
+            info: old,
+            replaced_at: SystemTime::now(),
+        })
+    }
+
+    /// Menu label, e.g. "Previous: DE, Frankfurt (2h ago)"
+    fn label(&self) -> String {
+        let age = self.replaced_at.elapsed().unwrap_or(Duration::ZERO);
+        let place = match self.info.city.as_str() {
+            "" => self.info.country_code.clone(),
+            city => format!("{}, {}", self.info.country_code, city),
+        };
+        format!("Previous: {} ({})", place, format_ago(age))
+    }
+}
+
+/// Artwork the tray icon is drawn from
+#[derive(Debug, Clone, PartialEq)]
+enum Art {
+    Embedded(FlagIcon),
+    /// Country code on a circle, for codes without a flag
+    Generated(String),
+    /// Country code, offline, color
+    Symbolic(Option<String>, bool, [u8; 3]),
+}
+
+/// Everything the tray pixmaps depend on
+#[derive(Debug, Clone, PartialEq)]
+struct PixmapKey {
+    art: Art,
+    /// Desaturation amount, as bits so the key can be compared exactly
+    dim: Option<u32>,
+    badges: Vec<Badge>,
+}
+
+type PixmapCache = Arc<Mutex<Option<(PixmapKey, FlagIcon)>>>;
+
 /// Network monitor tray application
+#[derive(Clone)]
 pub struct NetworkTray {
     /// Current geo-location info (shared with refresh task)
     geo_info: Arc<Mutex<Option<GeoInfo>>>,
+    /// Location before the last move (shared with refresh task)
+    previous: Arc<Mutex<Option<PreviousLocation>>>,
     /// Channel to send commands to main loop
     command_tx: mpsc::Sender<TrayCommand>,
     /// Whether autostart is enabled
     pub autostart_enabled: bool,
+    /// Detected runtime environment (WSL, container, VM)
+    environment: Environment,
     /// New version available (if any)
     pub update_available: Option<String>,
     /// Whether currently checking for updates
     pub checking_update: bool,
+    /// Whether the update confirmation is waiting for an answer
+    pub confirming_update: bool,
+    /// Whether an update is being downloaded and installed
+    pub installing_update: bool,
+    /// Whether a path MTU diagnostic is running
+    pub measuring_mtu: bool,
+    /// Whether a first-hop trace is running
+    pub locating_hop: bool,
+    /// DNS leak check in progress
+    pub checking_dns: bool,
+    /// Whether a manual refresh is in flight
+    pub refreshing: bool,
+    /// Spinner badge frame while any lookup is in flight
+    pub spinner: Option<u8>,
+    /// Manual refresh is throttled until this instant
+    pub refresh_cooldown_until: Option<Instant>,
+    /// Active blackout window (automatic refreshes paused)
+    pub blackout: Option<BlackoutWindow>,
+    /// Automatic refreshes paused via D-Bus until this instant
+    pub paused_until: Option<Instant>,
+    /// "Pause Monitoring": no periodic or network-event refreshes until toggled off
+    pub monitoring_paused: bool,
+    /// Current NetworkManager connection (None without NetworkManager)
+    pub network: Option<ConnectionInfo>,
+    /// Whether the current connection is marked trusted
+    pub trusted: bool,
+    /// Names of the active VPN connections
+    pub vpns: Vec<String>,
+    /// Saved VPN profiles offered by "Connect VPN"
+    pub vpn_profiles: Vec<ConnectionInfo>,
+    /// Whether the network is up, as last reported by the network watcher
+    pub connectivity: Connectivity,
+    /// Refresh interval in effect: the per-network, untrusted or configured one
+    pub refresh_interval: Duration,
+    /// Last pixmaps handed to the host and what they were drawn from
+    pixmap_cache: PixmapCache,
 }
 
 impl NetworkTray {
     pub fn new(
         geo_info: Arc<Mutex<Option<GeoInfo>>>,
+        previous: Arc<Mutex<Option<PreviousLocation>>>,
         command_tx: mpsc::Sender<TrayCommand>,
         autostart_enabled: bool,
+        environment: Environment,
     ) -> Self {
         Self {
             geo_info,
+            previous,
             command_tx,
             autostart_enabled,
+            environment,
             update_available: None,
             checking_update: false,
+            confirming_update: false,
+            installing_update: false,
+            measuring_mtu: false,
+            locating_hop: false,
+            checking_dns: false,
+            refreshing: false,
+            spinner: None,
+            refresh_cooldown_until: None,
+            blackout: None,
+            paused_until: None,
+            monitoring_paused: false,
+            network: None,
+            trusted: false,
+            vpns: Vec::new(),
+            vpn_profiles: Vec::new(),
+            connectivity: Connectivity::Unknown,
+            refresh_interval: crate::config::current().refresh_interval,
+            pixmap_cache: Arc::new(Mutex::new(None)),
         }
     }
 
@@ -55,12 +337,11 @@ impl NetworkTray {
     }
 
     /// Gets current country code for icon lookup
-    fn current_country_code(&self) -> String {
+    fn current_country_code(&self) -> Option<String> {
         self.geo_info
             .lock()
             .ok()
             .and_then(|g| g.as_ref().map(|i| i.country_code.clone()))
-            .unwrap_or_else(|| "xx".to_string())
     }
 
     /// Gets display text for current location
@@ -71,11 +352,305 @@ impl NetworkTray {
             .ok()
             .and_then(|g| {
                 g.as_ref().map(|i| {
-                    format!("{}, {} ({})", i.city, i.country, i.country_code)
+                    format!("{}, {} ({})", i.city, i.country_name(), i.country_code)
                 })
             })
             .unwrap_or_else(|| "Unknown location".to_string())
     }
+
+    /// `key.art` dimmed and badged, or drawn at runtime. The last result is kept, since
+    /// menu changes update the tray too.
+    fn decorated(&self, key: PixmapKey) -> FlagIcon {
+        let mut cache = self.pixmap_cache.lock().unwrap_or_else(|e| e.into_inner());
+        if let Some((_, icon)) = cache.as_ref().filter(|(cached, _)| *cached == key) {
+            return icon.clone();
+        }
+        let base = match &key.art {
+            Art::Embedded(flag) => flag.clone(),
+            Art::Symbolic(code, offline, color) => FlagIcon::from_rgba(&symbolic::render(code.as_deref(), *offline, *color)),
+            Art::Generated(code) => FlagIcon::from_rgba(&code_flag::render(code)),
+        };
+        let dim = key.dim.map(f32::from_bits);
+        let pixmaps = base
+            .pixmaps
+            .iter()
+            .cloned()
+            .map(|mut pixmap| {
+                // The symbolic icon has no color to take away, so it fades instead
+                match dim {
+                    Some(amount) if matches!(key.art, Art::Symbolic(..)) => {
+                        for pixel in pixmap.argb.chunks_exact_mut(4) {
+                            pixel[0] = (pixel[0] as f32 * (1.0 - amount * SYMBOLIC_FADE)).round() as u8;
+                        }
+                    }
+                    Some(amount) => icons::desaturate(&mut pixmap.argb, amount),
+                    None => {}
+                }
+                badge::composite(&mut pixmap, &key.badges);
+                pixmap
+            })
+            .collect();
+        let icon = FlagIcon { pixmaps };
+        *cache = Some((key, icon.clone()));
+        icon
+    }
+
+    /// Badges composited onto the flag
+    fn badges(&self) -> Vec<Badge> {
+        let mut badges = Vec::new();
+        if self.update_available.is_some() {
+            badges.push(Badge::Update);
+        }
+        if self.needs_attention() {
+            badges.push(Badge::Attention);
+        }
+        if let Some(frame) = self.spinner {
+            badges.push(Badge::Spinner(frame));
+        }
+        // Tor and VPN describe a connection that is gone
+        if self.is_offline() {
+            return badges;
+        }
+        if self.geo_info.lock().ok().is_some_and(|g| g.as_ref().is_some_and(|i| i.tor)) {
+            badges.push(Badge::Tor);
+        }
+        if self.unprotected_wifi().is_some() {
+            badges.push(Badge::PublicWifi);
+        }
+        let icons = &crate::config::current().icons;
+        // The connection glyph already says VPN
+        if !self.vpns.is_empty() && icons.vpn_badge && icons.mode != IconMode::Connection {
+            badges.push(Badge::Vpn);
+        }
+        badges
+    }
+
+    /// The last lookup failed, or none succeeded for `[icons] stale_after` refresh intervals
+    fn is_stale(&self) -> bool {
+        is_stale(
+            crate::metrics::last_refresh_failed(),
+            crate::metrics::last_refresh_age(),
+            crate::config::current().icons.stale_after,
+            self.refresh_interval,
+        )
+    }
+
+    /// Warning for untrusted Wi-Fi without a VPN (`[untrusted] public_wifi_warning`)
+    fn unprotected_wifi(&self) -> Option<String> {
+        let network = self.network.as_ref().filter(|n| n.is_wifi())?;
+        if self.trusted || !self.vpns.is_empty() || self.is_offline() || !crate::config::current().untrusted.public_wifi_warning {
+            return None;
+        }
+        let kind = if network.open { "Open" } else { "Untrusted" };
+        Some(format!("⚠ {} Wi-Fi \"{}\" without VPN", kind, network.name))
+    }
+
+    /// NetworkManager (or the connectivity probe) reported no connection
+    fn is_offline(&self) -> bool {
+        self.connectivity == Connectivity::Disconnected
+    }
+
+    /// Glyph for `IconMode::Connection`: VPN over the link type, offline when down
+    fn connection_art(&self) -> StatusArt {
+        if self.is_offline() {
+            return StatusArt::Offline;
+        }
+        if !self.vpns.is_empty() {
+            return StatusArt::Vpn;
+        }
+        match self.network.as_ref().map(|n| n.kind.as_str()) {
+            Some("802-11-wireless") => StatusArt::Wifi,
+            Some("802-3-ethernet") => StatusArt::Ethernet,
+            _ => StatusArt::Globe,
+        }
+    }
+
+    /// Watchdog mismatch or a matching alert rule with `badge = true`
+    fn needs_attention(&self) -> bool {
+        self.unexpected_country().is_some() || rules::badge_active()
+    }
+
+    /// Expected country from the watchdog config, when the current location is elsewhere
+    fn unexpected_country(&self) -> Option<String> {
+        let info = self.geo_info.lock().ok()?.clone()?;
+        watchdog::mismatch(&info)
+    }
+}
+
+/// Handle to the running tray, or to a headless fallback when the session has
+/// no StatusNotifier host (e.g. vanilla GNOME without the AppIndicator extension)
+#[derive(Clone)]
+pub enum TrayHandle {
+    Tray(ksni::Handle<NetworkTray>),
+    /// State is kept locally and location changes are announced via notifications
+    Headless(Arc<tokio::sync::Mutex<HeadlessTray>>),
+    /// State is kept locally and printed as Waybar JSON on stdout
+    Waybar(Arc<tokio::sync::Mutex<WaybarTray>>),
+}
+
+/// Tray state kept without a tray host
+pub struct HeadlessTray {
+    tray: NetworkTray,
+    /// Country code of the last location notification
+    announced: Option<String>,
+}
+
+/// Tray state kept for `--waybar`
+pub struct WaybarTray {
+    tray: NetworkTray,
+    /// Last line printed, so unchanged state is not repeated
+    printed: Option<String>,
+}
+
+impl TrayHandle {
+    /// Spawns the tray service, falling back to headless mode if it can't be shown
+    pub async fn spawn(tray: NetworkTray) -> Self {
+        let config = crate::config::current().tray.clone();
+        let session = zbus::Connection::session().await.ok();
+        if let (Some(session), false) = (&session, config.prefer_hosts.is_empty()) {
+            tracing::info!("Waiting up to {:?} for tray host {}", hosts::PREFER_WAIT, config.prefer_hosts.join(" or "));
+            if !hosts::wait_for_preferred(session, &config.prefer_hosts).await {
+                tracing::warn!("No preferred tray host appeared, registering with what is running");
+            }
+        }
+
+        let message = match tray.clone().spawn().await {
+            Ok(handle) => {
+                let running = match &session {
+                    Some(session) => hosts::list(session).await.unwrap_or_default(),
+                    None => Vec::new(),
+                };
+                if !hosts::only_excluded(&running, &config.exclude_hosts) {
+                    return TrayHandle::Tray(handle);
+                }
+                let names: Vec<&str> = running.iter().map(hosts::Host::label).collect();
+                tracing::warn!("Only excluded tray hosts are running ({}), running in notification mode", names.join(", "));
+                handle.shutdown().await;
+                format!(
+                    "Only excluded tray hosts are running ({}).\nLocation changes will be shown as notifications instead.",
+                    names.join(", ")
+                )
+            }
+            Err(e) => {
+                tracing::warn!("System tray unavailable ({}), running in notification mode", e);
+                no_tray_message(&e)
+            }
+        };
+        notify(message, 15000);
+
+        let headless = HeadlessTray { tray, announced: None };
+        let handle = TrayHandle::Headless(Arc::new(tokio::sync::Mutex::new(headless)));
+        handle.update(|_| {}).await;
+        handle
+    }
+
+    /// Runs without a tray, printing the state for a Waybar custom module
+    pub async fn waybar(tray: NetworkTray) -> Self {
+        let waybar = WaybarTray { tray, printed: None };
+        let handle = TrayHandle::Waybar(Arc::new(tokio::sync::Mutex::new(waybar)));
+        handle.update(|_| {}).await;
+        handle
+    }
+
+    /// Registers the tray again on a new session bus, keeping its state (a headless tray
+    /// gets another chance at a tray host); Waybar output doesn't use the bus
+    pub async fn respawn(self) -> Self {
+        let tray = match &self {
+            TrayHandle::Tray(handle) => {
+                let tray = handle.update(|tray| tray.clone()).await;
+                handle.shutdown().await;
+                tray
+            }
+            TrayHandle::Headless(headless) => Some(headless.lock().await.tray.clone()),
+            TrayHandle::Waybar(_) => return self,
+        };
+        match tray {
+            Some(tray) => TrayHandle::spawn(tray).await,
+            None => self,
+        }
+    }
+
+    /// Runs `lookup` with the spinner badge turning on the icon
+    pub async fn spinning<T>(&self, lookup: impl std::future::Future<Output = T>) -> T {
+        // Only a real tray shows the icon
+        if !matches!(self, TrayHandle::Tray(_)) {
+            return lookup.await;
+        }
+        let handle = self.clone();
+        let animation = tokio::spawn(async move {
+            let mut timer = tokio::time::interval(SPINNER_FRAME);
+            for frame in (0..badge::SPINNER_FRAMES).cycle() {
+                timer.tick().await;
+                handle.update(move |tray| tray.spinner = Some(frame)).await;
+            }
+        });
+        let result = lookup.await;
+        animation.abort();
+        self.update(|tray| tray.spinner = None).await;
+        result
+    }
+
+    /// Reads from the tray state (None if the tray service has shut down)
+    pub async fn read<R, F: FnOnce(&NetworkTray) -> R + Send>(&self, f: F) -> Option<R>
+    where
+        R: Send,
+    {
+        match self {
+            // ksni only offers mutable access, which also schedules a (cheap) property refresh
+            TrayHandle::Tray(handle) => handle.update(|tray| f(tray)).await,
+            TrayHandle::Headless(headless) => Some(f(&headless.lock().await.tray)),
+            TrayHandle::Waybar(waybar) => Some(f(&waybar.lock().await.tray)),
+        }
+    }
+
+    /// Applies `f` to the tray state and refreshes the tray (or announces changes when headless)
+    pub async fn update<F: FnOnce(&mut NetworkTray)>(&self, f: F) {
+        match self {
+            TrayHandle::Tray(handle) => {
+                handle.update(f).await;
+            }
+            TrayHandle::Headless(headless) => {
+                let mut headless = headless.lock().await;
+                f(&mut headless.tray);
+
+                let info = headless.tray.geo_info.lock().ok().and_then(|g| g.clone());
+                let Some(geo) = info else { return };
+                if headless.announced.as_deref() != Some(geo.country_code.as_str()) {
+                    headless.announced = Some(geo.country_code.clone());
+                    let ip = if privacy::hide_ip() { String::new() } else { format!("\nIP: {}", geo.query) };
+                    notify(format!("Location: {} ({}){}", geo.country_name(), geo.country_code, ip), 5000);
+                }
+            }
+            TrayHandle::Waybar(waybar) => {
+                let mut waybar = waybar.lock().await;
+                f(&mut waybar.tray);
+
+                let info = waybar.tray.geo_info.lock().ok().and_then(|g| g.clone());
+                let tooltip = waybar.tray.tool_tip();
+                let tooltip = format!("{}\n{}", tooltip.title, tooltip.description);
+                let line = waybar::render(info.as_ref(), waybar.tray.connectivity, &tooltip);
+                if waybar.printed.as_ref() != Some(&line) {
+                    println!("{}", line);
+                    waybar.printed = Some(line);
+                }
+            }
+        }
+    }
+}
+
+/// Explains why the tray icon is missing and what to do about it
+fn no_tray_message(error: &ksni::Error) -> String {
+    let desktop = std::env::var("XDG_CURRENT_DESKTOP").unwrap_or_default();
+    let hint = if desktop.to_uppercase().contains("GNOME") {
+        "Install and enable the AppIndicator extension to see the flag icon."
+    } else {
+        "Your desktop has no system tray (StatusNotifier host) running."
+    };
+    let reason = match error {
+        ksni::Error::WontShow => "No system tray host is registered.",
+        _ => "No StatusNotifierWatcher is available.",
+    };
+    format!("{} {}\nLocation changes will be shown as notifications instead.", reason, hint)
 }
 
 impl Tray for NetworkTray {
@@ -83,51 +658,86 @@ impl Tray for NetworkTray {
         "network-monitor".to_string()
     }
 
+    /// ksni registers the icon again by itself
+    fn watcher_online(&self) {
+        tracing::info!("Tray host is back, icon registered again");
+    }
+
+    /// Keep the service running: panels restart, and docks come and go
+    fn watcher_offline(&self, reason: ksni::OfflineReason) -> bool {
+        tracing::warn!("Tray host went away ({:?}), waiting for it to return", reason);
+        true
+    }
+
     fn title(&self) -> String {
         "Network Monitor".to_string()
     }
 
-    fn icon_pixmap(&self) -> Vec<Icon> {
-        let country_code = self.current_country_code();
-        let flag = get_flag(&country_code);
-
-        // Decode PNG to get RGBA pixels
-        if let Ok(img) = image::load_from_memory(flag.data) {
-            let rgba = img.to_rgba8();
-            let (width, height) = rgba.dimensions();
-
-            // Convert to ARGB format (ksni expects ARGB)
-            let mut argb_data = Vec::with_capacity((width * height * 4) as usize);
-            for pixel in rgba.pixels() {
-                // ARGB order: Alpha, Red, Green, Blue
-                argb_data.push(pixel[3]); // A
-                argb_data.push(pixel[0]); // R
-                argb_data.push(pixel[1]); // G
-                argb_data.push(pixel[2]); // B
-            }
-
-            vec![Icon {
-                width: width as i32,
-                height: height as i32,
-                data: argb_data,
-            }]
+    fn status(&self) -> ksni::Status {
+        if self.needs_attention() {
+            ksni::Status::NeedsAttention
         } else {
-            // Fallback: empty icon (shouldn't happen)
-            vec![Icon {
-                width: ICON_SIZE as i32,
-                height: ICON_SIZE as i32,
-                data: vec![0; (ICON_SIZE * ICON_SIZE * 4) as usize],
-            }]
+            ksni::Status::Active
         }
     }
 
+    fn icon_pixmap(&self) -> Vec<Icon> {
+        let icons = &crate::config::current().icons;
+        let code = self.current_country_code();
+        // A code with no artwork still gets named, rather than the fallback policy's globe
+        let generated = code
+            .as_deref()
+            .filter(|c| get_styled_flag(c, icons.style).is_none() && code_flag::can_render(c));
+        let art = match (icons.mode, generated) {
+            (IconMode::Symbolic, _) => Art::Symbolic(code.clone(), self.is_offline(), icons.symbolic_color),
+            (IconMode::Flag, Some(generated)) if !self.is_offline() => Art::Generated(generated.to_string()),
+            (mode, _) => Art::Embedded(match mode {
+                // The last flag would claim a location we no longer have
+                _ if self.is_offline() => status_icon(StatusArt::Offline),
+                IconMode::Connection => status_icon(self.connection_art()),
+                _ => resolve_styled_icon(code.as_deref(), icons.fallback, icons.style),
+            }),
+        };
+        let dim = desaturation(self.is_stale(), self.monitoring_paused);
+        let badges = self.badges();
+        let icon = match art {
+            // Nothing drawn on it: the pixmaps shared with the icons cache
+            Art::Embedded(flag) if dim.is_none() && badges.is_empty() => flag,
+            art => self.decorated(PixmapKey { art, dim: dim.map(f32::to_bits), badges }),
+        };
+        // ksni takes the pixmaps by value, so this is the one copy each update makes
+        icon.pixmaps
+            .iter()
+            .map(|p| Icon { width: p.size as i32, height: p.size as i32, data: p.argb.clone() })
+            .collect()
+    }
+
     fn tool_tip(&self) -> ksni::ToolTip {
         let info = self.geo_info.lock().ok().and_then(|g| g.clone());
 
-        let (title, description) = if let Some(geo) = info {
+        let (title, description) = if self.is_offline() {
+            let last = info
+                .map(|geo| format!("Last location: {} ({})", geo.country_name(), geo.country_code))
+                .unwrap_or_else(|| "No location yet".to_string());
+            ("Disconnected".to_string(), format!("No network connection\n{}", last))
+        } else if let Some(geo) = info {
+            let cached = if geo.cached_at.is_some() { " · cached" } else { "" };
+            let tor = if geo.tor { " · Via Tor" } else { "" };
+            let paused = if self.monitoring_paused { " · paused" } else { "" };
+            let mut lines = privacy::address_lines(&geo);
+            lines.push(format!("City: {}", city_label(&geo)));
+            lines.push(format!("ISP: {}", geo.isp));
+            // PTR names often spell out the address
+            if let Some(hostname) = geo.hostname.as_ref().filter(|_| !privacy::hide_ip()) {
+                lines.push(format!("Host: {}", hostname));
+            }
+            if let Some(age) = crate::metrics::last_refresh_age() {
+                lines.push(format!("Refreshed {}", format_ago(age)));
+            }
+            lines.extend(self.unprotected_wifi());
             (
-                format!("{} ({})", geo.country, geo.country_code),
-                format!("IP: {}\nCity: {}\nISP: {}", geo.query, geo.city, geo.isp),
+                format!("{} ({}){}{}{}", geo.country_name(), geo.country_code, tor, cached, paused),
+                lines.join("\n"),
             )
         } else {
             ("Network Monitor".to_string(), "Fetching location...".to_string())
@@ -146,63 +756,560 @@ impl Tray for NetworkTray {
 
         let mut items: Vec<MenuItem<Self>> = Vec::new();
 
-        // Network info items (non-clickable labels)
-        if let Some(geo) = info {
+        // Compact summary; the details live in the Info submenu
+        items.push(MenuItem::Standard(StandardItem {
+            label: match info {
+                Some(ref geo) if self.is_offline() => format!("Disconnected · last in {} ({})", geo.country_name(), geo.country_code),
+                _ if self.is_offline() => "Disconnected".to_string(),
+                Some(ref geo) if privacy::hide_ip() => format!("{} ({})", geo.country_name(), geo.country_code),
+                Some(ref geo) => format!("{} ({}) · {}", geo.country_name(), geo.country_code, geo.query),
+                None => "Fetching location...".to_string(),
+            },
+            enabled: false,
+            ..Default::default()
+        }));
+
+        if let Some(seen) = info.as_ref().and_then(|geo| geo.cached_at) {
+            let age = seen.elapsed().unwrap_or(Duration::ZERO);
             items.push(MenuItem::Standard(StandardItem {
-                label: format!("IP: {}", geo.query),
+                label: format!("⚠ Cached: lookup failed, last seen on this network {}", format_ago(age)),
                 enabled: false,
                 ..Default::default()
             }));
+        }
+
+        if let Some(skew) = crate::clock::skew() {
+            let clock_tx = self.command_tx.clone();
             items.push(MenuItem::Standard(StandardItem {
-                label: format!("Country: {} ({})", geo.country, geo.country_code),
+                label: format!("⚠ Clock is {} · Sync Clock", crate::clock::describe(skew)),
+                activate: Box::new(move |_| {
+                    let _ = clock_tx.try_send(TrayCommand::SyncClock);
+                }),
+                ..Default::default()
+            }));
+        }
+
+        if let Some(expected) = self.unexpected_country() {
+            items.push(MenuItem::Standard(StandardItem {
+                label: format!("⚠ Not in {} (expected country)", expected),
                 enabled: false,
                 ..Default::default()
             }));
+        }
+
+        if let Some(remaining) = self.paused_until.and_then(|t| t.checked_duration_since(Instant::now())) {
+            let resume_tx = self.command_tx.clone();
             items.push(MenuItem::Standard(StandardItem {
-                label: format!("City: {}", geo.city),
+                label: format!("⏸ Auto-refresh paused for {} (click to resume)", format_duration(remaining)),
+                activate: Box::new(move |_| {
+                    let _ = resume_tx.try_send(TrayCommand::Pause(Duration::ZERO));
+                }),
+                ..Default::default()
+            }));
+        }
+
+        // Notifications suppressed by Do Not Disturb
+        let missed = notifier::missed_events();
+        if !missed.is_empty() {
+            let mut submenu: Vec<MenuItem<Self>> = missed
+                .iter()
+                .map(|event| {
+                    let age = event.at.elapsed().unwrap_or(Duration::ZERO);
+                    MenuItem::Standard(StandardItem {
+                        label: format!("{} ({})", event.message, format_ago(age)),
+                        enabled: false,
+                        ..Default::default()
+                    })
+                })
+                .collect();
+            submenu.push(MenuItem::Separator);
+            submenu.push(MenuItem::Standard(StandardItem {
+                label: "Clear".to_string(),
+                activate: Box::new(|_| notifier::clear_missed()),
+                ..Default::default()
+            }));
+            items.push(MenuItem::SubMenu(SubMenu {
+                label: format!("Missed notifications ({})", missed.len()),
+                submenu,
+                ..Default::default()
+            }));
+        }
+
+        items.push(MenuItem::Separator);
+
+        for section in crate::config::current().menu.sections.iter().filter_map(|name| find_section(name)) {
+            let submenu = section.items(self, info.as_ref());
+            if submenu.is_empty() {
+                continue;
+            }
+            items.push(MenuItem::SubMenu(SubMenu {
+                label: section.label(self),
+                submenu,
+                ..Default::default()
+            }));
+        }
+
+        items.push(MenuItem::Separator);
+
+        // Actions
+        let cooldown = self
+            .refresh_cooldown_until
+            .map(|until| until.saturating_duration_since(Instant::now()))
+            .filter(|remaining| !remaining.is_zero());
+
+        if self.refreshing {
+            items.push(MenuItem::Standard(StandardItem {
+                label: "⏳ Refreshing…".to_string(),
                 enabled: false,
                 ..Default::default()
             }));
+        } else if let Some(remaining) = cooldown {
             items.push(MenuItem::Standard(StandardItem {
-                label: format!("ISP: {}", geo.isp),
+                label: format!("Refresh (wait {}s)", remaining.as_secs() + 1),
                 enabled: false,
                 ..Default::default()
             }));
         } else {
+            let refresh_tx = self.command_tx.clone();
             items.push(MenuItem::Standard(StandardItem {
-                label: "Fetching location...".to_string(),
+                label: "Refresh".to_string(),
+                activate: Box::new(move |_| {
+                    let _ = refresh_tx.try_send(TrayCommand::Refresh);
+                }),
+                ..Default::default()
+            }));
+        }
+
+        let pause_tx = self.command_tx.clone();
+        items.push(MenuItem::Checkmark(CheckmarkItem {
+            label: "Pause Monitoring".to_string(),
+            checked: self.monitoring_paused,
+            activate: Box::new(move |_| {
+                let _ = pause_tx.try_send(TrayCommand::TogglePauseMonitoring);
+            }),
+            ..Default::default()
+        }));
+
+        let quit_tx = self.command_tx.clone();
+        items.push(MenuItem::Standard(StandardItem {
+            label: "Quit".to_string(),
+            activate: Box::new(move |_| {
+                let _ = quit_tx.try_send(TrayCommand::Quit);
+            }),
+            ..Default::default()
+        }));
+
+        items
+    }
+}
+
+impl NetworkTray {
+    /// Network info items (non-clickable labels)
+    /// Informational rows about the lookup, at the top of the Info submenu; warnings that
+    /// call for action stay in the main menu
+    fn status_lines(&self, info: Option<&GeoInfo>) -> Vec<String> {
+        let mut lines = Vec::new();
+        let classes = info.map(confidence::classification).unwrap_or_default();
+        if !classes.is_empty() {
+            lines.push(classes.join(" · "));
+        }
+        if info.is_some_and(|geo| geo.tor) {
+            lines.push("🧅 Via Tor (exit node)".to_string());
+        }
+        // DHCP churn and ISP renumbering show up as a short stable time
+        if let Some(stable) = history::ip_stable_for() {
+            lines.push(format!("IP stable for {}", format_span(stable)));
+        }
+        // How old the flag is; kept current by the main loop's menu refresh
+        if let Some(age) = crate::metrics::last_refresh_age() {
+            lines.push(format!("Refreshed {}", format_ago(age)));
+        }
+        // What just changed, for when a country alert fires
+        if let Some(previous) = self.previous.lock().ok().and_then(|p| p.clone()) {
+            lines.push(previous.label());
+        }
+        if let Some(window) = self.blackout {
+            lines.push(format!("⏸ Auto-refresh paused until {}", schedule::format_time(window.end)));
+        }
+        lines
+    }
+
+    fn info_items(&self, info: Option<&GeoInfo>) -> Vec<MenuItem<Self>> {
+        let mut items = Vec::new();
+        let label = |label: String| {
+            MenuItem::Standard(StandardItem {
+                label,
+                enabled: false,
+                ..Default::default()
+            })
+        };
+
+        let status = self.status_lines(info);
+        let has_status = !status.is_empty();
+        items.extend(status.into_iter().map(label));
+        if has_status && info.is_some() {
+            items.push(MenuItem::Separator);
+        }
+
+        if let Some(geo) = info {
+            items.extend(privacy::address_lines(geo).into_iter().map(label));
+            items.push(label(format!("Country: {} ({})", geo.country_name(), geo.country_code)));
+            items.push(label(format!("City: {}", city_label(geo))));
+            items.push(label(format!("ISP: {}", geo.isp)));
+            if let Some(hostname) = geo.hostname.as_ref().filter(|_| !privacy::hide_ip()) {
+                items.push(label(format!("Hostname: {}", hostname)));
+            }
+            // Tells apart VPN providers renting from the same datacenter
+            if let Some(asn) = geo.asn {
+                let name = if geo.as_name.is_empty() { String::new() } else { format!(" ({})", geo.as_name) };
+                items.push(label(format!("ASN: AS{}{}", asn, name)));
+            }
+            if !geo.org.is_empty() && geo.org != geo.isp {
+                items.push(label(format!("Organization: {}", geo.org)));
+            }
+            if geo.coordinates.is_some() {
+                let map_tx = self.command_tx.clone();
+                items.push(MenuItem::Standard(StandardItem {
+                    label: "Open on Map".to_string(),
+                    activate: Box::new(move |_| {
+                        let _ = map_tx.try_send(TrayCommand::OpenMap);
+                    }),
+                    ..Default::default()
+                }));
+            }
+            let details_tx = self.command_tx.clone();
+            items.push(MenuItem::Standard(StandardItem {
+                label: "Open IP Details".to_string(),
+                activate: Box::new(move |_| {
+                    let _ = details_tx.try_send(TrayCommand::OpenIpDetails);
+                }),
+                ..Default::default()
+            }));
+        }
+        if self.environment != Environment::Native {
+            items.push(label(format!("Environment: {}", self.environment.label())));
+        }
+        for permission in crate::flatpak::missing() {
+            items.push(label(format!("⚠ Flatpak: {}", permission.describe())));
+            items.push(label(format!("    {}", permission.fix())));
+        }
+        items
+    }
+
+    fn diagnostics_items(&self) -> Vec<MenuItem<Self>> {
+        let mut items = Vec::new();
+
+        for provider in usage::snapshot() {
+            let warning = if provider.near_limit { "⚠ " } else { "" };
+            items.push(MenuItem::Standard(StandardItem {
+                label: format!("{}API calls today: {} ({})", warning, provider.today, provider.provider),
                 enabled: false,
                 ..Default::default()
             }));
         }
 
-        // Separator
-        items.push(MenuItem::Separator);
+        // Outage statistics per connection
+        let reliability = outages::stats();
+        if !reliability.is_empty() {
+            let submenu = reliability
+                .iter()
+                .map(|stats| {
+                    let every = stats
+                        .mean_time_between
+                        .map(|d| format!(", every ~{}", format_duration(d)))
+                        .unwrap_or_default();
+                    MenuItem::Standard(StandardItem {
+                        label: format!(
+                            "{} ({}): {} outages, {} down{}",
+                            stats.connection,
+                            stats.isp,
+                            stats.outages,
+                            format_duration(stats.downtime),
+                            every
+                        ),
+                        enabled: false,
+                        ..Default::default()
+                    })
+                })
+                .collect();
+            items.push(MenuItem::SubMenu(SubMenu {
+                label: "Connection Reliability".to_string(),
+                submenu,
+                ..Default::default()
+            }));
+        }
 
-        // Actions
-        let refresh_tx = self.command_tx.clone();
+        let locations = history::recent(RECENT_LOCATIONS);
+        if !locations.is_empty() {
+            let submenu = locations
+                .iter()
+                .map(|entry| {
+                    let place = [entry.country_code.as_str(), entry.city.as_str()]
+                        .into_iter()
+                        .filter(|s| !s.is_empty())
+                        .collect::<Vec<_>>()
+                        .join(", ");
+                    let ip = if privacy::hide_ip() { String::new() } else { format!(" · {}", entry.ip) };
+                    MenuItem::Standard(StandardItem {
+                        label: format!("{}  {}{} ({})", schedule::format_local_date(entry.time()), place, ip, entry.isp),
+                        enabled: false,
+                        ..Default::default()
+                    })
+                })
+                .collect();
+            items.push(MenuItem::SubMenu(SubMenu {
+                label: "Recent locations".to_string(),
+                submenu,
+                ..Default::default()
+            }));
+        }
+
+        // Notification history, for the ones that disappeared before being read
+        let recent = notifier::recent_events();
+        if !recent.is_empty() {
+            let submenu = recent
+                .iter()
+                .map(|event| {
+                    MenuItem::Standard(StandardItem {
+                        label: format!("{}  {}", schedule::format_local(event.at), event.message),
+                        enabled: false,
+                        ..Default::default()
+                    })
+                })
+                .collect();
+            items.push(MenuItem::SubMenu(SubMenu {
+                label: "Recent events".to_string(),
+                submenu,
+                ..Default::default()
+            }));
+        }
+
+        if self.measuring_mtu {
+            items.push(MenuItem::Standard(StandardItem {
+                label: "⏳ Measuring path MTU...".to_string(),
+                enabled: false,
+                ..Default::default()
+            }));
+        } else {
+            let mtu_tx = self.command_tx.clone();
+            items.push(MenuItem::Standard(StandardItem {
+                label: "Diagnose Path MTU".to_string(),
+                activate: Box::new(move |_| {
+                    let _ = mtu_tx.try_send(TrayCommand::DiagnoseMtu);
+                }),
+                ..Default::default()
+            }));
+        }
+
+        if let Some(report) = crate::first_hop::last() {
+            items.push(MenuItem::Standard(StandardItem {
+                label: report.summary(),
+                enabled: false,
+                ..Default::default()
+            }));
+        }
+        let hop_tx = self.command_tx.clone();
         items.push(MenuItem::Standard(StandardItem {
-            label: "Refresh".to_string(),
+            label: if self.locating_hop { "⏳ Tracing route...".to_string() } else { "Locate First External Hop".to_string() },
+            enabled: !self.locating_hop,
             activate: Box::new(move |_| {
-                let _ = refresh_tx.try_send(TrayCommand::Refresh);
+                let _ = hop_tx.try_send(TrayCommand::LocateFirstHop);
             }),
             ..Default::default()
         }));
 
-        let autostart_tx = self.command_tx.clone();
+        // Makes provider-mapping bugs reportable without trace logging
+        let raw_tx = self.command_tx.clone();
         items.push(MenuItem::Checkmark(CheckmarkItem {
-            label: "Launch on Login".to_string(),
-            checked: self.autostart_enabled,
+            label: "Capture Raw Provider Response".to_string(),
+            checked: diagnostics::raw_capture(),
             activate: Box::new(move |_| {
-                let _ = autostart_tx.try_send(TrayCommand::ToggleAutostart);
+                let _ = raw_tx.try_send(TrayCommand::ToggleRawResponse);
             }),
             ..Default::default()
         }));
+        if let Some((provider, _)) = diagnostics::raw_response() {
+            let copy_tx = self.command_tx.clone();
+            items.push(MenuItem::Standard(StandardItem {
+                label: format!("Copy Raw Response ({})", provider),
+                activate: Box::new(move |_| {
+                    let _ = copy_tx.try_send(TrayCommand::CopyRawResponse);
+                }),
+                ..Default::default()
+            }));
+        }
 
-        // Update section
-        items.push(MenuItem::Separator);
+        let report_tx = self.command_tx.clone();
+        items.push(MenuItem::Standard(StandardItem {
+            label: "Report an Issue…".to_string(),
+            activate: Box::new(move |_| {
+                let _ = report_tx.try_send(TrayCommand::ReportIssue);
+            }),
+            ..Default::default()
+        }));
+        items
+    }
+
+    /// VPN state and trust of the current network (needs NetworkManager)
+    fn vpn_items(&self) -> Vec<MenuItem<Self>> {
+        let Some(ref network) = self.network else { return Vec::new() };
+        let mut items = Vec::new();
 
-        if self.checking_update {
+        if let Some(warning) = self.unprotected_wifi() {
+            items.push(MenuItem::Standard(StandardItem {
+                label: warning,
+                enabled: false,
+                ..Default::default()
+            }));
+            for profile in &self.vpn_profiles {
+                let connect_tx = self.command_tx.clone();
+                let uuid = profile.uuid.clone();
+                items.push(MenuItem::Standard(StandardItem {
+                    label: format!("Connect VPN: {}", profile.name),
+                    activate: Box::new(move |_| {
+                        let _ = connect_tx.try_send(TrayCommand::ConnectVpn(uuid.clone()));
+                    }),
+                    ..Default::default()
+                }));
+            }
+        } else if self.vpns.is_empty() {
+            items.push(MenuItem::Standard(StandardItem {
+                label: "No VPN connected".to_string(),
+                enabled: false,
+                ..Default::default()
+            }));
+        }
+        for vpn in &self.vpns {
+            items.push(MenuItem::Standard(StandardItem {
+                label: format!("🔒 VPN: {}", vpn),
+                enabled: false,
+                ..Default::default()
+            }));
+        }
+
+        if let Some(report) = crate::dns_leak::last() {
+            items.push(MenuItem::Standard(StandardItem {
+                label: report.summary(),
+                enabled: false,
+                ..Default::default()
+            }));
+        }
+        let dns_tx = self.command_tx.clone();
+        items.push(MenuItem::Standard(StandardItem {
+            label: if self.checking_dns { "⏳ Checking for DNS leak...".to_string() } else { "Check for DNS Leak".to_string() },
+            enabled: !self.checking_dns,
+            activate: Box::new(move |_| {
+                let _ = dns_tx.try_send(TrayCommand::CheckDnsLeak);
+            }),
+            ..Default::default()
+        }));
+
+        let trust_tx = self.command_tx.clone();
+        items.push(MenuItem::Checkmark(CheckmarkItem {
+            label: format!("Trusted Network ({})", network.name),
+            checked: self.trusted,
+            activate: Box::new(move |_| {
+                let _ = trust_tx.try_send(TrayCommand::ToggleTrust);
+            }),
+            ..Default::default()
+        }));
+        items
+    }
+
+    fn settings_items(&self) -> Vec<MenuItem<Self>> {
+        let autostart_tx = self.command_tx.clone();
+        let hide_ip_tx = self.command_tx.clone();
+        let style_tx = self.command_tx.clone();
+        vec![
+            MenuItem::Checkmark(CheckmarkItem {
+                label: "Launch on Login".to_string(),
+                checked: self.autostart_enabled,
+                enabled: self.environment.supports_autostart(),
+                activate: Box::new(move |_| {
+                    let _ = autostart_tx.try_send(TrayCommand::ToggleAutostart);
+                }),
+                ..Default::default()
+            }),
+            MenuItem::SubMenu(SubMenu {
+                label: "Refresh Interval".to_string(),
+                submenu: self.interval_items(),
+                ..Default::default()
+            }),
+            MenuItem::Checkmark(CheckmarkItem {
+                label: "Hide IP Address".to_string(),
+                checked: privacy::hide_ip(),
+                activate: Box::new(move |_| {
+                    let _ = hide_ip_tx.try_send(TrayCommand::ToggleHideIp);
+                }),
+                ..Default::default()
+            }),
+            MenuItem::Checkmark(CheckmarkItem {
+                label: "Rectangular Flags".to_string(),
+                checked: crate::config::current().icons.style == FlagStyle::Rectangle,
+                activate: Box::new(move |_| {
+                    let _ = style_tx.try_send(TrayCommand::ToggleFlagStyle);
+                }),
+                ..Default::default()
+            }),
+        ]
+    }
+
+    /// The interval choices, and the interval in effect when this network uses another one
+    fn interval_items(&self) -> Vec<MenuItem<Self>> {
+        let mut items = vec![self.interval_choices()];
+        if let Some(label) = interval_in_effect(crate::config::current().refresh_interval, self.refresh_interval) {
+            items.push(MenuItem::Separator);
+            items.push(MenuItem::Standard(StandardItem { label, enabled: false, ..Default::default() }));
+        }
+        items
+    }
+
+    /// Radio items for the common intervals, plus the configured one if it is none of them
+    fn interval_choices(&self) -> MenuItem<Self> {
+        let current = crate::config::current().refresh_interval;
+        let mut choices: Vec<Duration> = REFRESH_CHOICES.iter().map(|&secs| Duration::from_secs(secs)).collect();
+        if !choices.contains(&current) {
+            choices.push(current);
+        }
+        let options = choices
+            .iter()
+            .map(|&interval| RadioItem {
+                label: match interval.as_secs() {
+                    0 => "Manual Only".to_string(),
+                    secs if REFRESH_CHOICES.contains(&secs) => format!("Every {}", format_duration(interval)),
+                    _ => format!("Every {} (config.toml)", format_duration(interval)),
+                },
+                ..Default::default()
+            })
+            .collect();
+        let interval_tx = self.command_tx.clone();
+        MenuItem::RadioGroup(RadioGroup {
+            selected: choices.iter().position(|&c| c == current).unwrap_or_default(),
+            select: Box::new(move |_, index| {
+                if let Some(&interval) = choices.get(index) {
+                    let _ = interval_tx.try_send(TrayCommand::SetRefreshInterval(interval));
+                }
+            }),
+            options,
+        })
+    }
+
+    fn update_items(&self) -> Vec<MenuItem<Self>> {
+        let mut items = Vec::new();
+
+        if self.installing_update {
+            items.push(MenuItem::Standard(StandardItem {
+                label: "⏳ Installing update...".to_string(),
+                enabled: false,
+                ..Default::default()
+            }));
+        } else if self.confirming_update {
+            items.push(MenuItem::Standard(StandardItem {
+                label: "⏳ Confirm the update in the notification...".to_string(),
+                enabled: false,
+                ..Default::default()
+            }));
+        } else if self.checking_update {
             // Show spinner while checking
             items.push(MenuItem::Standard(StandardItem {
                 label: "⏳ Checking for updates...".to_string(),
@@ -212,7 +1319,7 @@ impl Tray for NetworkTray {
         } else if let Some(ref version) = self.update_available {
             // Show clickable update button
             items.push(MenuItem::Standard(StandardItem {
-                label: format!("🔴 Update to {} (click to install)", version),
+                label: format!("🔴 Update to {}...", version),
                 activate: Box::new({
                     let tx = self.command_tx.clone();
                     move |_| {
@@ -233,17 +1340,163 @@ impl Tray for NetworkTray {
             }));
         }
 
-        items.push(MenuItem::Separator);
-
-        let quit_tx = self.command_tx.clone();
-        items.push(MenuItem::Standard(StandardItem {
-            label: "Quit".to_string(),
+        let channel_tx = self.command_tx.clone();
+        items.push(MenuItem::Checkmark(CheckmarkItem {
+            label: "Beta Updates".to_string(),
+            checked: crate::config::current().updates.channel == Channel::Beta,
             activate: Box::new(move |_| {
-                let _ = quit_tx.try_send(TrayCommand::Quit);
+                let _ = channel_tx.try_send(TrayCommand::ToggleBetaChannel);
             }),
             ..Default::default()
         }));
-
         items
     }
 }
+
+/// City with an accuracy hint when the location is probably not the user's town
+fn city_label(geo: &GeoInfo) -> String {
+    let assessment = confidence::assess(geo);
+    if assessment.confidence == Confidence::High {
+        geo.city.clone()
+    } else {
+        format!("{} ({})", geo.city, assessment.hint())
+    }
+}
+
+/// Whether the shown location may be out of date: the last lookup failed, or the last
+/// success is older than `intervals` periods (never with either of them zero)
+fn is_stale(failed: bool, age: Option<Duration>, intervals: u32, period: Duration) -> bool {
+    failed || (intervals > 0 && !period.is_zero() && age.is_some_and(|age| age > period * intervals))
+}
+
+/// How much to gray the flag: fully while it may no longer be accurate, partly while paused
+fn desaturation(stale: bool, paused: bool) -> Option<f32> {
+    match (stale, paused) {
+        (true, _) => Some(STALE_DESATURATION),
+        (false, true) => Some(PAUSED_DESATURATION),
+        (false, false) => None,
+    }
+}
+
+/// Notes the interval actually used when a `[networks]` or `[untrusted]` one replaces the
+/// selected one, so picking another choice there doesn't seem to do nothing
+fn interval_in_effect(selected: Duration, effective: Duration) -> Option<String> {
+    (selected != effective).then(|| match effective.as_secs() {
+        0 => "On this network: manual only".to_string(),
+        _ => format!("On this network: every {}", format_duration(effective)),
+    })
+}
+
+/// Formats an elapsed duration as a short relative time, e.g. "5m ago"
+fn format_ago(elapsed: Duration) -> String {
+    match elapsed.as_secs() {
+        0..=59 => "just now".to_string(),
+        _ => format!("{} ago", format_duration(elapsed)),
+    }
+}
+
+/// Formats a duration in its two largest units, e.g. "45s", "12m", "5h 2m", "3d 14h"
+fn format_span(duration: Duration) -> String {
+    let secs = duration.as_secs();
+    match secs {
+        0..=3599 => format_duration(duration),
+        3600..=86399 => format!("{}h {}m", secs / 3600, secs % 3600 / 60),
+        _ => format!("{}d {}h", secs / 86400, secs % 86400 / 3600),
+    }
+}
+
+/// Formats a duration in its largest whole unit, e.g. "45s", "12m", "3h", "2d"
+pub fn format_duration(duration: Duration) -> String {
+    let secs = duration.as_secs();
+    match secs {
+        0..=59 => format!("{}s", secs),
+        60..=3599 => format!("{}m", secs / 60),
+        3600..=86399 => format!("{}h", secs / 3600),
+        _ => format!("{}d", secs / 86400),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn tray(info: Option<GeoInfo>) -> NetworkTray {
+        let (tx, _) = mpsc::channel(1);
+        NetworkTray::new(Arc::new(Mutex::new(info)), Arc::new(Mutex::new(None)), tx, false, Environment::Native)
+    }
+
+    #[test]
+    fn test_sections() {
+        let menu = crate::config::parse("[menu]\nsections = [\"vpn\", \"info\"]\n").unwrap().menu;
+        let order: Vec<_> = menu.sections.iter().filter_map(|name| find_section(name)).map(|s| s.name()).collect();
+        assert_eq!(order, ["vpn", "info"]);
+        assert_eq!(crate::config::MenuConfig::default().sections, ["info", "diagnostics", "vpn", "settings", "update"]);
+
+        let mut tray = tray(None);
+        let update = find_section("update").unwrap();
+        assert_eq!(update.label(&tray), "Update");
+        tray.update_available = Some("0.2.0".to_string());
+        assert_eq!(update.label(&tray), "🔴 Update");
+        assert!(find_section("history").is_none());
+    }
+
+    #[test]
+    fn test_status_lines() {
+        let exit = GeoInfo { country_code: "DE".to_string(), tor: true, ..Default::default() };
+        let tray = tray(Some(exit.clone()));
+        assert!(tray.status_lines(Some(&exit)).contains(&"🧅 Via Tor (exit node)".to_string()));
+        let labels: Vec<String> = InfoSection
+            .items(&tray, Some(&exit))
+            .into_iter()
+            .filter_map(|item| match item {
+                MenuItem::Standard(item) => Some(item.label),
+                _ => None,
+            })
+            .collect();
+        assert!(labels.contains(&"🧅 Via Tor (exit node)".to_string()));
+        assert!(!tray.status_lines(None).iter().any(|l| l.contains("Tor")));
+    }
+
+    #[test]
+    fn test_badges() {
+        let exit = GeoInfo { country_code: "DE".to_string(), tor: true, ..Default::default() };
+        let mut tray = tray(Some(exit));
+        assert_eq!(tray.badges(), [Badge::Tor]);
+        tray.update_available = Some("0.2.0".to_string());
+        tray.vpns = vec!["Mullvad".to_string()];
+        assert_eq!(tray.badges(), [Badge::Update, Badge::Tor, Badge::Vpn]);
+
+        // Tor and VPN describe a connection that is gone
+        tray.spinner = Some(2);
+        tray.connectivity = Connectivity::Disconnected;
+        assert_eq!(tray.badges(), [Badge::Update, Badge::Spinner(2)]);
+    }
+
+    #[test]
+    fn test_is_stale() {
+        let minute = Duration::from_secs(60);
+        assert!(is_stale(true, Some(Duration::ZERO), 3, minute));
+        assert!(!is_stale(false, Some(minute * 3), 3, minute));
+        assert!(is_stale(false, Some(minute * 4), 3, minute));
+        // A 15 minute hotspot interval keeps a 4 minute old lookup current
+        assert!(!is_stale(false, Some(minute * 4), 3, minute * 15));
+        assert!(!is_stale(false, Some(minute * 60), 0, minute));
+        assert!(!is_stale(false, Some(minute * 60), 3, Duration::ZERO));
+        assert!(!is_stale(false, None, 3, minute));
+    }
+
+    #[test]
+    fn test_interval_in_effect() {
+        let minute = Duration::from_secs(60);
+        assert_eq!(interval_in_effect(minute, minute), None);
+        assert_eq!(interval_in_effect(minute * 5, Duration::from_secs(30)).as_deref(), Some("On this network: every 30s"));
+        assert_eq!(interval_in_effect(minute, Duration::ZERO).as_deref(), Some("On this network: manual only"));
+    }
+
+    #[test]
+    fn test_desaturation() {
+        assert_eq!(desaturation(true, true), Some(STALE_DESATURATION));
+        assert_eq!(desaturation(false, true), Some(PAUSED_DESATURATION));
+        assert_eq!(desaturation(false, false), None);
+    }
+}
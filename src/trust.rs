@@ -0,0 +1,150 @@
+//! Trusted network memory and untrusted-network policy
+//! Connections the user marked as trusted are remembered by NetworkManager UUID in
+//! ~/.config/network-monitor/trusted-networks; everything else gets stricter checks.
+
+use crate::config::Config;
+use crate::network::{ConnectionInfo, ConnectionSnapshot};
+use crate::notifier::{notify, notify_with, Priority};
+use std::fs;
+use std::path::PathBuf;
+use std::time::Duration;
+use tokio::process::Command;
+
+const TRUSTED_FILENAME: &str = "trusted-networks";
+/// Firewall services checked via systemd
+const FIREWALL_UNITS: &[&str] = &["ufw", "firewalld", "nftables"];
+
+fn trusted_path() -> Option<PathBuf> {
+    crate::config::config_dir().map(|p| p.join(TRUSTED_FILENAME))
+}
+
+/// Parses "uuid<TAB>name" lines
+fn parse(content: &str) -> Vec<(String, String)> {
+    content
+        .lines()
+        .filter(|l| !l.trim().is_empty())
+        .map(|l| match l.split_once('\t') {
+            Some((uuid, name)) => (uuid.to_string(), name.to_string()),
+            None => (l.to_string(), String::new()),
+        })
+        .collect()
+}
+
+fn load() -> Vec<(String, String)> {
+    trusted_path()
+        .and_then(|p| fs::read_to_string(p).ok())
+        .map(|c| parse(&c))
+        .unwrap_or_default()
+}
+
+/// Returns true if the connection with this UUID was marked trusted
+pub fn is_trusted(uuid: &str) -> bool {
+    load().iter().any(|(u, _)| u == uuid)
+}
+
+/// Marks or unmarks a connection as trusted
+pub fn set_trusted(connection: &ConnectionInfo, trusted: bool) -> std::io::Result<()> {
+    let path = trusted_path().ok_or_else(|| std::io::Error::other("no config directory"))?;
+    let mut entries = load();
+    entries.retain(|(uuid, _)| *uuid != connection.uuid);
+    if trusted {
+        entries.push((connection.uuid.clone(), connection.name.clone()));
+    }
+
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    let content: String = entries.iter().map(|(uuid, name)| format!("{}\t{}\n", uuid, name)).collect();
+    fs::write(path, content)
+}
+
+/// Refresh interval for the current network and whether it is trusted: the
+/// `[networks]` override if any, otherwise untrusted networks are checked more often
+pub fn refresh_interval(network: Option<&(ConnectionInfo, bool)>) -> Duration {
+    interval_for(&crate::config::current(), network)
+}
+
+fn interval_for(config: &Config, network: Option<&(ConnectionInfo, bool)>) -> Duration {
+    let connection = network.map(|(connection, _)| connection);
+    // An explicit per-network interval, like a metered hotspot's, beats the tightening
+    if let Some(interval) = config.network_override(connection).and_then(|o| o.refresh_interval) {
+        return interval;
+    }
+    match (network, config.untrusted.refresh_interval) {
+        (Some((_, false)), Some(untrusted)) => config.refresh_interval.min(untrusted),
+        _ => config.refresh_interval,
+    }
+}
+
+/// Returns true if one of the common firewall services is running
+async fn firewall_active() -> bool {
+    for unit in FIREWALL_UNITS {
+        let status = Command::new("systemctl").args(["is-active", "--quiet", unit]).status().await;
+        if status.is_ok_and(|s| s.success()) {
+            return true;
+        }
+    }
+    false
+}
+
+/// Applies the untrusted-network rules: firewall warning on join, VPN requirement always
+pub async fn check_untrusted(snapshot: &ConnectionSnapshot, newly_joined: bool) {
+    let Some(network) = snapshot.network() else { return };
+    let config = crate::config::current();
+
+    if newly_joined && config.untrusted.firewall_warning && !firewall_active().await {
+        notify(format!("Untrusted network \"{}\": no firewall is active", network.name), 8000);
+    }
+    if config.untrusted.require_vpn && !snapshot.vpn_active() {
+        notify_with(format!("VPN required on untrusted network \"{}\"", network.name), 10000, Priority::Critical);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_trusted_file() {
+        let entries = parse("0b3c-11\tHome Wi-Fi\n\n7d2e-42\n");
+        assert_eq!(entries.len(), 2);
+        assert_eq!(entries[0], ("0b3c-11".to_string(), "Home Wi-Fi".to_string()));
+        assert_eq!(entries[1].1, "");
+    }
+
+    #[test]
+    fn test_interval_for() {
+        let cafe = ConnectionInfo {
+            uuid: "9a1e".to_string(),
+            name: "Cafe".to_string(),
+            kind: "802-11-wireless".to_string(),
+            open: true,
+        };
+        let mut config = Config::default();
+        // Untrusted networks keep the normal interval unless one is set for them
+        assert_eq!(interval_for(&config, Some(&(cafe.clone(), false))), config.refresh_interval);
+
+        config.untrusted.refresh_interval = Some(Duration::from_secs(30));
+        assert_eq!(interval_for(&config, Some(&(cafe.clone(), false))), Duration::from_secs(30));
+        assert_eq!(interval_for(&config, Some(&(cafe.clone(), true))), config.refresh_interval);
+        assert_eq!(interval_for(&config, None), config.refresh_interval);
+        config.refresh_interval = Duration::from_secs(20);
+        assert_eq!(interval_for(&config, Some(&(cafe, false))), Duration::from_secs(20));
+    }
+
+    #[test]
+    fn test_network_override_beats_untrusted() {
+        let mut config = crate::config::parse("[untrusted]\nrefresh_interval = 30\n\n[networks.\"Pixel Hotspot\"]\nrefresh_interval = 900\n").unwrap();
+        let hotspot = ConnectionInfo {
+            uuid: "c4d0".to_string(),
+            name: "Pixel Hotspot".to_string(),
+            kind: "802-11-wireless".to_string(),
+            open: false,
+        };
+        assert_eq!(interval_for(&config, Some(&(hotspot.clone(), false))), Duration::from_secs(900));
+        assert_eq!(interval_for(&config, Some(&(hotspot.clone(), true))), Duration::from_secs(900));
+
+        config.networks.get_mut("Pixel Hotspot").unwrap().refresh_interval = None;
+        assert_eq!(interval_for(&config, Some(&(hotspot, false))), Duration::from_secs(30));
+    }
+}
@@ -1,17 +1,96 @@
-//! Auto-update checker module
-//! Checks for new versions once per day and notifies user.
+//! Auto-update module
+//! Checks for new versions once per day, and installs releases in-process:
+//! download, checksum and signature verification, atomic executable swap and relaunch.
 
+use base64::Engine;
+use flate2::read::GzDecoder;
+use ring::signature::{UnparsedPublicKey, ED25519};
+use sha2::{Digest, Sha256};
 use std::fs;
-use std::path::PathBuf;
+use std::io::Read;
+use std::os::unix::fs::PermissionsExt;
+use std::path::{Path, PathBuf};
 use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use thiserror::Error;
 
 const CHECK_INTERVAL_SECS: u64 = 86400; // 24 hours
 const GITHUB_API_URL: &str =
     "https://api.github.com/repos/btxTruong/network-monitor/releases/latest";
+/// Recent releases including pre-releases, newest first (beta channel)
+const GITHUB_RELEASES_URL: &str =
+    "https://api.github.com/repos/btxTruong/network-monitor/releases?per_page=20";
+/// A single release by tag, for the confirmation summary
+const GITHUB_RELEASE_TAG_URL: &str = "https://api.github.com/repos/btxTruong/network-monitor/releases/tags";
+const RELEASE_DOWNLOAD_URL: &str = "https://github.com/btxTruong/network-monitor/releases/download";
+/// Release tarball and its `sha256sum` sidecar (see .github/workflows/release.yml)
+const RELEASE_ASSET: &str = "network-monitor-linux-x86_64.tar.gz";
+const BINARY_NAME: &str = "network-monitor";
+/// Minisign public key the releases are signed with, embedded at build time
+/// (see .github/workflows/release.yml). Builds without it cannot self-update.
+const UPDATE_PUBLIC_KEY: Option<&str> = option_env!("NETWORK_MONITOR_UPDATE_PUBKEY");
+/// Minisign algorithm tag for plain (non-prehashed) Ed25519 signatures
+const MINISIGN_ED25519: &[u8; 2] = b"Ed";
+const DOWNLOAD_TIMEOUT: Duration = Duration::from_secs(120);
+/// Changelog lines shown before asking to install
+const CHANGELOG_LINES: usize = 6;
 
 /// Current app version from Cargo.toml
 pub const VERSION: &str = env!("CARGO_PKG_VERSION");
 
+/// Which releases are offered as updates
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Channel {
+    /// Regular releases only
+    #[default]
+    Stable,
+    /// Also GitHub pre-releases
+    Beta,
+}
+
+impl Channel {
+    pub const NAMES: &'static [&'static str] = &["stable", "beta"];
+
+    pub fn parse(name: &str) -> Option<Self> {
+        match name {
+            "stable" => Some(Self::Stable),
+            "beta" => Some(Self::Beta),
+            _ => None,
+        }
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Channel::Stable => "stable",
+            Channel::Beta => "beta",
+        }
+    }
+}
+
+/// Errors while checking for or installing an update
+#[derive(Debug, Error)]
+pub enum UpdateError {
+    #[error("Update check failed: {0}")]
+    Check(reqwest::Error),
+    #[error("Download failed: {0}")]
+    Request(#[from] reqwest::Error),
+    #[error("IO error: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("Release has no checksum file")]
+    ChecksumMissing,
+    #[error("Checksum mismatch (expected {expected}, got {actual})")]
+    ChecksumMismatch { expected: String, actual: String },
+    #[error("Release has no signature file")]
+    SignatureMissing,
+    #[error("Invalid signature: {0}")]
+    BadSignature(&'static str),
+    #[error("This build has no update signing key, install updates manually")]
+    NoPublicKey,
+    #[error("Release archive does not contain {0}")]
+    BinaryNotFound(&'static str),
+    #[error("Invalid release archive")]
+    InvalidArchive,
+}
+
 /// Gets the config directory path
 fn config_dir() -> Option<PathBuf> {
     dirs::config_dir().map(|p| p.join("network-monitor"))
@@ -19,20 +98,10 @@ fn config_dir() -> Option<PathBuf> {
 
 /// Checks if we should check for updates (once per day)
 fn should_check() -> bool {
-    let Some(config) = config_dir() else {
+    if config_dir().is_none() {
         return false;
-    };
-
-    let last_check_file = config.join("last-check");
-    if !last_check_file.exists() {
-        return true;
     }
-
-    let Ok(content) = fs::read_to_string(&last_check_file) else {
-        return true;
-    };
-
-    let Ok(last_check) = content.trim().parse::<u64>() else {
+    let Some(last_check) = last_check() else {
         return true;
     };
 
@@ -41,7 +110,35 @@ fn should_check() -> bool {
         .unwrap_or(Duration::ZERO)
         .as_secs();
 
-    now - last_check >= CHECK_INTERVAL_SECS
+    now.saturating_sub(last_check) >= CHECK_INTERVAL_SECS
+}
+
+/// Unix time of the last update check, None if there was none
+pub fn last_check() -> Option<u64> {
+    let content = fs::read_to_string(config_dir()?.join("last-check")).ok()?;
+    content.trim().parse().ok()
+}
+
+/// What `status` and the D-Bus interface report about updates
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct UpdateState {
+    /// Running version
+    pub version: String,
+    /// Newer release found by the last check
+    pub available: Option<String>,
+    pub channel: Channel,
+    /// Unix time of the last check
+    pub last_check: Option<u64>,
+}
+
+/// Current update state, from the persisted check results
+pub fn state() -> UpdateState {
+    UpdateState {
+        version: VERSION.to_string(),
+        available: load_available_update(),
+        channel: crate::config::current().updates.channel,
+        last_check: last_check(),
+    }
 }
 
 /// Saves the current timestamp as last check time
@@ -65,6 +162,67 @@ fn save_last_check() {
 #[derive(Debug, serde::Deserialize)]
 struct GitHubRelease {
     tag_name: String,
+    #[serde(default)]
+    draft: bool,
+    #[serde(default)]
+    prerelease: bool,
+    /// Release notes (Markdown)
+    #[serde(default)]
+    body: Option<String>,
+    #[serde(default)]
+    assets: Vec<GitHubAsset>,
+}
+
+#[derive(Debug, serde::Deserialize)]
+struct GitHubAsset {
+    name: String,
+    size: u64,
+}
+
+/// What an update brings, shown before it is installed
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ReleaseSummary {
+    pub tag: String,
+    /// Download size of the release archive
+    pub size: Option<u64>,
+    /// First lines of the release notes, Markdown headings and bullets stripped
+    pub changelog: Vec<String>,
+}
+
+impl ReleaseSummary {
+    /// Summary without release details, when GitHub can't be reached
+    pub fn bare(tag: &str) -> Self {
+        Self { tag: tag.to_string(), size: None, changelog: Vec::new() }
+    }
+
+    /// Confirmation text: version and size, then the changelog excerpt
+    pub fn describe(&self) -> String {
+        let mut text = format!("Install Network Monitor {}", self.tag);
+        if let Some(size) = self.size {
+            text.push_str(&format!(" ({:.1} MB)", size as f64 / 1_048_576.0));
+        }
+        text.push('?');
+        for line in &self.changelog {
+            text.push_str(&format!("\n• {}", line));
+        }
+        text.push_str("\nThe app restarts after installing.");
+        text
+    }
+
+    fn from_release(release: GitHubRelease) -> Self {
+        let size = release.assets.iter().find(|a| a.name == RELEASE_ASSET).map(|a| a.size);
+        let changelog = release
+            .body
+            .as_deref()
+            .unwrap_or_default()
+            .lines()
+            .map(|l| l.trim().trim_start_matches(['#', '-', '*']).trim())
+            .filter(|l| !l.is_empty())
+            .take(CHANGELOG_LINES)
+            .map(str::to_string)
+            .collect();
+        Self { tag: release.tag_name, size, changelog }
+    }
 }
 
 /// Checks for updates and returns new version if available (respects daily limit)
@@ -76,24 +234,62 @@ pub async fn check_for_update() -> Option<String> {
     check_for_update_internal().await
 }
 
+/// The channel a one-shot check uses: `requested` (`update --channel`), otherwise
+/// `[updates] channel` from `config`
+pub fn resolve_channel(requested: Option<Channel>, config: &crate::config::Config) -> Channel {
+    requested.unwrap_or(config.updates.channel)
+}
+
 /// Checks for updates immediately (ignores daily limit)
 pub async fn check_for_update_forced() -> Option<String> {
-    check_for_update_internal().await
+    check_channel(resolve_channel(None, &crate::config::current())).await.unwrap_or_else(|e| {
+        tracing::debug!("{}", e);
+        None
+    })
+}
+
+/// Checks `channel` for updates immediately, reporting failures instead of
+/// treating them as "up to date" (for `update` in scripts)
+pub async fn check_channel(channel: Channel) -> Result<Option<String>, UpdateError> {
+    save_last_check();
+    latest_update(channel).await
 }
 
 async fn check_for_update_internal() -> Option<String> {
-    tracing::debug!("Checking for updates...");
+    latest_update(crate::config::current().updates.channel).await.unwrap_or_else(|e| {
+        tracing::debug!("{}", e);
+        None
+    })
+}
+
+async fn latest_update(channel: Channel) -> Result<Option<String>, UpdateError> {
+    tracing::debug!("Checking for updates ({} channel)...", channel.as_str());
 
     let client = reqwest::Client::new();
+    let url = match channel {
+        Channel::Stable => GITHUB_API_URL,
+        Channel::Beta => GITHUB_RELEASES_URL,
+    };
     let response = client
-        .get(GITHUB_API_URL)
+        .get(url)
         .header("User-Agent", "network-monitor")
         .timeout(std::time::Duration::from_secs(10))
         .send()
         .await
-        .ok()?;
+        .and_then(|r| r.error_for_status())
+        .map_err(UpdateError::Check)?;
 
-    let release: GitHubRelease = response.json().await.ok()?;
+    // /releases/latest never returns pre-releases; the list needs filtering
+    let release = match channel {
+        Channel::Stable => response.json::<GitHubRelease>().await.map_err(UpdateError::Check)?,
+        Channel::Beta => {
+            let releases = response.json::<Vec<GitHubRelease>>().await.map_err(UpdateError::Check)?;
+            match newest_release(releases, channel) {
+                Some(release) => release,
+                None => return Ok(None),
+            }
+        }
+    };
 
     // Remove 'v' prefix if present for comparison
     let latest = release.tag_name.trim_start_matches('v');
@@ -101,13 +297,38 @@ async fn check_for_update_internal() -> Option<String> {
 
     if latest != current && is_newer_version(latest, current) {
         tracing::info!("New version available: v{} (current: v{})", latest, current);
-        Some(release.tag_name)
+        Ok(Some(release.tag_name))
     } else {
         tracing::debug!("Already on latest version: v{}", current);
-        None
+        Ok(None)
     }
 }
 
+/// Picks the highest version offered on `channel`
+fn newest_release(releases: Vec<GitHubRelease>, channel: Channel) -> Option<GitHubRelease> {
+    releases
+        .into_iter()
+        .filter(|r| !r.draft && (channel == Channel::Beta || !r.prerelease))
+        .reduce(|best, r| {
+            let newer = is_newer_version(r.tag_name.trim_start_matches('v'), best.tag_name.trim_start_matches('v'));
+            if newer { r } else { best }
+        })
+}
+
+/// Fetches the size and release notes of `tag`
+pub async fn release_summary(tag: &str) -> Result<ReleaseSummary, UpdateError> {
+    let release: GitHubRelease = reqwest::Client::new()
+        .get(format!("{}/{}", GITHUB_RELEASE_TAG_URL, tag))
+        .header("User-Agent", "network-monitor")
+        .timeout(Duration::from_secs(10))
+        .send()
+        .await?
+        .error_for_status()?
+        .json()
+        .await?;
+    Ok(ReleaseSummary::from_release(release))
+}
+
 /// Saves available update version to persist across restarts
 pub fn save_available_update(version: &str) {
     let Some(config) = config_dir() else { return };
@@ -137,16 +358,182 @@ pub fn clear_available_update() {
     }
 }
 
-/// Simple version comparison (assumes semver x.y.z)
+async fn download(client: &reqwest::Client, url: &str) -> Result<Vec<u8>, UpdateError> {
+    let response = client
+        .get(url)
+        .header("User-Agent", "network-monitor")
+        .timeout(DOWNLOAD_TIMEOUT)
+        .send()
+        .await?
+        .error_for_status()?;
+    Ok(response.bytes().await?.to_vec())
+}
+
+/// Extracts the hex digest from `sha256sum` output ("<hex>  <file>")
+fn parse_checksum(content: &str) -> Option<String> {
+    let digest = content.split_whitespace().next()?;
+    (digest.len() == 64 && digest.chars().all(|c| c.is_ascii_hexdigit())).then(|| digest.to_lowercase())
+}
+
+fn verify_checksum(data: &[u8], expected: &str) -> Result<(), UpdateError> {
+    let actual = hex::encode(Sha256::digest(data));
+    if actual != expected {
+        return Err(UpdateError::ChecksumMismatch {
+            expected: expected.to_string(),
+            actual,
+        });
+    }
+    Ok(())
+}
+
+/// Decodes the base64 payload of a minisign key or signature line
+fn decode_minisign(line: &str) -> Result<Vec<u8>, UpdateError> {
+    base64::engine::general_purpose::STANDARD
+        .decode(line.trim())
+        .map_err(|_| UpdateError::BadSignature("malformed base64"))
+}
+
+/// Verifies `data` against a minisign signature file made with `minisign -S -l`.
+/// `public_key` is the key line of the `.pub` file (the comment line may be included).
+fn verify_signature(data: &[u8], signature: &str, public_key: &str) -> Result<(), UpdateError> {
+    let key_line = public_key.lines().rfind(|l| !l.trim().is_empty()).unwrap_or_default();
+    let key = decode_minisign(key_line)?;
+    if key.len() != 42 || &key[..2] != MINISIGN_ED25519 {
+        return Err(UpdateError::BadSignature("unsupported public key"));
+    }
+
+    // untrusted comment, signature, trusted comment, global signature
+    let lines: Vec<&str> = signature.lines().collect();
+    let [_, sig_line, trusted_line, global_line, ..] = lines[..] else {
+        return Err(UpdateError::BadSignature("truncated signature file"));
+    };
+    let sig = decode_minisign(sig_line)?;
+    if sig.len() != 74 {
+        return Err(UpdateError::BadSignature("malformed signature"));
+    }
+    if &sig[..2] != MINISIGN_ED25519 {
+        return Err(UpdateError::BadSignature("only legacy (-l) Ed25519 signatures are supported"));
+    }
+    if sig[2..10] != key[2..10] {
+        return Err(UpdateError::BadSignature("signed with a different key"));
+    }
+
+    let verifier = UnparsedPublicKey::new(&ED25519, &key[10..]);
+    verifier
+        .verify(data, &sig[10..])
+        .map_err(|_| UpdateError::BadSignature("signature does not match the download"))?;
+
+    // The global signature covers the signature plus the trusted comment
+    let trusted_comment = trusted_line
+        .strip_prefix("trusted comment: ")
+        .ok_or(UpdateError::BadSignature("missing trusted comment"))?;
+    let mut signed = sig[10..].to_vec();
+    signed.extend_from_slice(trusted_comment.as_bytes());
+    verifier
+        .verify(&signed, &decode_minisign(global_line)?)
+        .map_err(|_| UpdateError::BadSignature("trusted comment was tampered with"))
+}
+
+/// Finds the binary in a gzipped (ustar) tarball
+fn extract_binary(tarball: &[u8]) -> Result<Vec<u8>, UpdateError> {
+    let mut data = Vec::new();
+    GzDecoder::new(tarball).read_to_end(&mut data)?;
+
+    let mut offset = 0;
+    while offset + 512 <= data.len() {
+        let header = &data[offset..offset + 512];
+        if header.iter().all(|b| *b == 0) {
+            break;
+        }
+        let name_end = header[..100].iter().position(|b| *b == 0).unwrap_or(100);
+        let name = std::str::from_utf8(&header[..name_end]).map_err(|_| UpdateError::InvalidArchive)?;
+        let size_field = std::str::from_utf8(&header[124..136]).map_err(|_| UpdateError::InvalidArchive)?;
+        let size = usize::from_str_radix(size_field.trim_matches(|c: char| c == '\0' || c == ' '), 8)
+            .map_err(|_| UpdateError::InvalidArchive)?;
+        let regular_file = matches!(header[156], b'0' | 0);
+
+        let body = offset + 512;
+        if regular_file && Path::new(name).file_name().is_some_and(|f| f == BINARY_NAME) {
+            return data
+                .get(body..body + size)
+                .map(<[u8]>::to_vec)
+                .ok_or(UpdateError::InvalidArchive);
+        }
+        offset = body + size.div_ceil(512) * 512;
+    }
+    Err(UpdateError::BinaryNotFound(BINARY_NAME))
+}
+
+/// Atomically replaces `exe` with `binary` (rename within the same directory)
+fn replace_executable(exe: &Path, binary: &[u8]) -> Result<(), UpdateError> {
+    let staged = exe.with_file_name(format!(".{}.new", BINARY_NAME));
+    fs::write(&staged, binary)?;
+    fs::set_permissions(&staged, fs::Permissions::from_mode(0o755))?;
+    if let Err(e) = fs::rename(&staged, exe) {
+        let _ = fs::remove_file(&staged);
+        return Err(e.into());
+    }
+    Ok(())
+}
+
+/// Downloads release `tag`, verifies it and replaces the running executable.
+/// Returns the path of the installed binary.
+pub async fn install_update(tag: &str) -> Result<PathBuf, UpdateError> {
+    let public_key = UPDATE_PUBLIC_KEY.ok_or(UpdateError::NoPublicKey)?;
+    let client = reqwest::Client::new();
+    let base = format!("{}/{}/{}", RELEASE_DOWNLOAD_URL, tag, RELEASE_ASSET);
+
+    tracing::info!("Downloading {}", base);
+    let checksum = download(&client, &format!("{}.sha256", base))
+        .await
+        .ok()
+        .and_then(|c| parse_checksum(&String::from_utf8_lossy(&c)))
+        .ok_or(UpdateError::ChecksumMissing)?;
+    let signature = download(&client, &format!("{}.sig", base))
+        .await
+        .map(|s| String::from_utf8_lossy(&s).into_owned())
+        .map_err(|_| UpdateError::SignatureMissing)?;
+    let tarball = download(&client, &base).await?;
+
+    let exe = std::env::current_exe()?;
+    let target = exe.clone();
+    tokio::task::spawn_blocking(move || -> Result<(), UpdateError> {
+        verify_checksum(&tarball, &checksum)?;
+        verify_signature(&tarball, &signature, public_key)?;
+        replace_executable(&target, &extract_binary(&tarball)?)
+    })
+    .await
+    .map_err(|e| std::io::Error::other(e.to_string()))??;
+
+    // Keep install.sh's notion of the installed version in sync
+    if let Some(config) = config_dir() {
+        let _ = fs::create_dir_all(&config);
+        let _ = fs::write(config.join("version"), format!("{}\n", tag));
+    }
+    clear_available_update();
+    tracing::info!("Installed {} to {}", tag, exe.display());
+    Ok(exe)
+}
+
+/// Starts the freshly installed binary (the caller should exit afterwards); it waits in the
+/// bus name queue until then, see `service::start`
+pub fn relaunch(exe: &Path) -> std::io::Result<()> {
+    std::process::Command::new(exe).spawn().map(|_| ())
+}
+
+/// Version comparison for semver x.y.z with an optional pre-release
+/// suffix ("0.2.0-beta.1"), which sorts before the release itself
 fn is_newer_version(latest: &str, current: &str) -> bool {
-    let parse = |v: &str| -> Vec<u32> {
-        v.split('.')
-            .filter_map(|s| s.parse().ok())
-            .collect()
+    let parse = |v: &str| -> (Vec<u32>, Option<String>) {
+        let (core, pre) = match v.split_once('-') {
+            Some((core, pre)) => (core, Some(pre.to_string())),
+            None => (v, None),
+        };
+        (core.split('.').filter_map(|s| s.parse().ok()).collect(), pre)
     };
 
-    let latest_parts = parse(latest);
-    let current_parts = parse(current);
+    let (latest_parts, latest_pre) = parse(latest);
+    let (current_parts, current_pre) = parse(current);
 
     for i in 0..3 {
         let l = latest_parts.get(i).copied().unwrap_or(0);
@@ -158,13 +545,136 @@ fn is_newer_version(latest: &str, current: &str) -> bool {
             return false;
         }
     }
-    false
+    match (latest_pre, current_pre) {
+        (None, Some(_)) => true,
+        (Some(l), Some(c)) => compare_prerelease(&l, &c).is_gt(),
+        _ => false,
+    }
+}
+
+/// Compares dot-separated pre-release identifiers, numerically where both are numbers
+fn compare_prerelease(a: &str, b: &str) -> std::cmp::Ordering {
+    let mut a = a.split('.');
+    let mut b = b.split('.');
+    loop {
+        match (a.next(), b.next()) {
+            (Some(x), Some(y)) => {
+                let ordering = match (x.parse::<u64>(), y.parse::<u64>()) {
+                    (Ok(x), Ok(y)) => x.cmp(&y),
+                    _ => x.cmp(y),
+                };
+                if ordering.is_ne() {
+                    return ordering;
+                }
+            }
+            (x, y) => return x.is_some().cmp(&y.is_some()),
+        }
+    }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
 
+    #[test]
+    fn test_release_summary() {
+        let release: GitHubRelease = serde_json::from_str(
+            r###"{"tag_name": "v0.2.0", "body": "## What's new\r\n\r\n- Waybar mode\r\n* Metrics",
+                "assets": [{"name": "network-monitor-linux-x86_64.tar.gz", "size": 4194304},
+                           {"name": "network-monitor-linux-x86_64.tar.gz.sig", "size": 300}]}"###,
+        )
+        .unwrap();
+        let summary = ReleaseSummary::from_release(release);
+        assert_eq!(summary.size, Some(4194304));
+        assert_eq!(summary.changelog, ["What's new", "Waybar mode", "Metrics"]);
+        assert!(summary.describe().starts_with("Install Network Monitor v0.2.0 (4.0 MB)?\n• What's new"));
+        assert_eq!(
+            ReleaseSummary::bare("v0.2.0").describe(),
+            "Install Network Monitor v0.2.0?\nThe app restarts after installing."
+        );
+    }
+
+    #[test]
+    fn test_resolve_channel() {
+        let config = crate::config::parse("[updates]\nchannel = \"beta\"\n").unwrap();
+        assert_eq!(resolve_channel(None, &config), Channel::Beta);
+        assert_eq!(resolve_channel(Some(Channel::Stable), &config), Channel::Stable);
+        let defaults = crate::config::parse("").unwrap();
+        assert_eq!(resolve_channel(None, &defaults), Channel::Stable);
+    }
+
+    /// Builds a one-file ustar archive, gzipped
+    fn tarball(name: &str, contents: &[u8]) -> Vec<u8> {
+        let mut header = [0u8; 512];
+        header[..name.len()].copy_from_slice(name.as_bytes());
+        let size = format!("{:011o}\0", contents.len());
+        header[124..136].copy_from_slice(size.as_bytes());
+        header[156] = b'0';
+
+        let mut tar = header.to_vec();
+        tar.extend_from_slice(contents);
+        tar.resize(512 + contents.len().div_ceil(512) * 512 + 1024, 0);
+
+        let mut encoder = flate2::write::GzEncoder::new(Vec::new(), flate2::Compression::fast());
+        std::io::Write::write_all(&mut encoder, &tar).unwrap();
+        encoder.finish().unwrap()
+    }
+
+    #[test]
+    fn test_extract_and_verify() {
+        let archive = tarball("./network-monitor", b"\x7fELF binary");
+        assert_eq!(extract_binary(&archive).unwrap(), b"\x7fELF binary");
+        assert!(matches!(
+            extract_binary(&tarball("./README.md", b"docs")),
+            Err(UpdateError::BinaryNotFound(_))
+        ));
+
+        let digest = hex::encode(Sha256::digest(&archive));
+        let sidecar = format!("{}  network-monitor-linux-x86_64.tar.gz\n", digest.to_uppercase());
+        assert_eq!(parse_checksum(&sidecar).as_deref(), Some(digest.as_str()));
+        assert!(verify_checksum(&archive, &digest).is_ok());
+        assert!(matches!(
+            verify_checksum(b"tampered", &digest),
+            Err(UpdateError::ChecksumMismatch { .. })
+        ));
+    }
+
+    /// Signs `data` the way `minisign -S -l` does
+    fn minisign(data: &[u8], trusted_comment: &str) -> (String, String) {
+        use ring::signature::{Ed25519KeyPair, KeyPair};
+        let rng = ring::rand::SystemRandom::new();
+        let pkcs8 = Ed25519KeyPair::generate_pkcs8(&rng).unwrap();
+        let pair = Ed25519KeyPair::from_pkcs8(pkcs8.as_ref()).unwrap();
+        let b64 = |b: &[u8]| base64::engine::general_purpose::STANDARD.encode(b);
+        let key_id = [7u8; 8];
+
+        let public_key = [&MINISIGN_ED25519[..], &key_id, pair.public_key().as_ref()].concat();
+        let sig = pair.sign(data);
+        let global = pair.sign(&[sig.as_ref(), trusted_comment.as_bytes()].concat());
+        let signature = format!(
+            "untrusted comment: signature from minisign secret key\n{}\ntrusted comment: {}\n{}\n",
+            b64(&[&MINISIGN_ED25519[..], &key_id, sig.as_ref()].concat()),
+            trusted_comment,
+            b64(global.as_ref())
+        );
+        (format!("untrusted comment: minisign public key\n{}\n", b64(&public_key)), signature)
+    }
+
+    #[test]
+    fn test_verify_signature() {
+        let (public_key, signature) = minisign(b"release", "timestamp:1700000000\tfile:release");
+        assert!(verify_signature(b"release", &signature, &public_key).is_ok());
+        assert!(matches!(
+            verify_signature(b"tampered", &signature, &public_key),
+            Err(UpdateError::BadSignature(_))
+        ));
+
+        let forged = signature.replace("timestamp:1700000000", "timestamp:1800000000");
+        assert!(verify_signature(b"release", &forged, &public_key).is_err());
+        let (other_key, _) = minisign(b"", "");
+        assert!(verify_signature(b"release", &signature, &other_key).is_err());
+    }
+
     #[test]
     fn test_version_comparison() {
         assert!(is_newer_version("0.2.0", "0.1.0"));
@@ -172,5 +682,23 @@ mod tests {
         assert!(is_newer_version("0.1.1", "0.1.0"));
         assert!(!is_newer_version("0.1.0", "0.1.0"));
         assert!(!is_newer_version("0.1.0", "0.2.0"));
+        assert!(is_newer_version("0.2.0-beta.1", "0.1.4"));
+        assert!(is_newer_version("0.2.0", "0.2.0-beta.2"));
+        assert!(is_newer_version("0.2.0-beta.10", "0.2.0-beta.2"));
+        assert!(!is_newer_version("0.2.0-beta.1", "0.2.0"));
+    }
+
+    #[test]
+    fn test_newest_release_per_channel() {
+        let release = |tag: &str, prerelease| GitHubRelease {
+            tag_name: tag.to_string(),
+            draft: false,
+            prerelease,
+            body: None,
+            assets: Vec::new(),
+        };
+        let releases = || vec![release("v0.3.0-beta.1", true), release("v0.2.1", false), release("v0.2.0", false)];
+        assert_eq!(newest_release(releases(), Channel::Beta).unwrap().tag_name, "v0.3.0-beta.1");
+        assert_eq!(newest_release(releases(), Channel::Stable).unwrap().tag_name, "v0.2.1");
     }
 }
@@ -0,0 +1,205 @@
+//! Geo API usage accounting
+//! Counts requests per provider per (UTC) day, persists the counters, and warns
+//! when approaching known free-tier limits.
+
+use std::collections::{BTreeMap, HashMap, VecDeque};
+use std::fs;
+use std::path::PathBuf;
+use std::sync::{LazyLock, Mutex};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+/// Fraction of a limit at which we start warning
+const WARN_RATIO: f64 = 0.8;
+
+/// Known free-tier limits: (provider, requests per minute, requests per day)
+const KNOWN_LIMITS: &[(&str, Option<u32>, Option<u32>)] = &[("ip-api.com", Some(45), None)];
+
+/// Process-wide counters, loaded lazily from disk
+static USAGE: LazyLock<Mutex<UsageCounter>> = LazyLock::new(|| Mutex::new(UsageCounter::load()));
+
+/// Gets the persisted counters path
+fn usage_file() -> Option<PathBuf> {
+    dirs::config_dir().map(|p| p.join("network-monitor").join("api-usage"))
+}
+
+/// Current day number since the Unix epoch (UTC)
+fn today() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or(Duration::ZERO)
+        .as_secs()
+        / 86400
+}
+
+/// Usage snapshot for a single provider
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ProviderUsage {
+    pub provider: String,
+    /// Requests made today
+    pub today: u32,
+    /// Requests made in the last minute
+    pub last_minute: u32,
+    /// Whether usage is close to a known free-tier limit
+    pub near_limit: bool,
+}
+
+#[derive(Debug, Default)]
+struct UsageCounter {
+    /// Day the counts belong to
+    day: u64,
+    /// Requests per provider for `day`
+    counts: BTreeMap<String, u32>,
+    /// Recent request instants per provider (sliding one-minute window, not persisted)
+    recent: HashMap<String, VecDeque<Instant>>,
+}
+
+impl UsageCounter {
+    fn load() -> Self {
+        let mut counter = Self {
+            day: today(),
+            ..Default::default()
+        };
+        let Some(content) = usage_file().and_then(|p| fs::read_to_string(p).ok()) else {
+            return counter;
+        };
+        counter.parse(&content);
+        counter
+    }
+
+    /// Parses `day=N` followed by `provider=count` lines; stale days are dropped
+    fn parse(&mut self, content: &str) {
+        let mut lines = content.lines().filter_map(|l| l.split_once('='));
+        let Some(("day", day)) = lines.next() else { return };
+        if day.trim().parse::<u64>().ok() != Some(self.day) {
+            return;
+        }
+        for (provider, count) in lines {
+            if let Ok(count) = count.trim().parse() {
+                self.counts.insert(provider.trim().to_string(), count);
+            }
+        }
+    }
+
+    fn serialize(&self) -> String {
+        let mut out = format!("day={}\n", self.day);
+        for (provider, count) in &self.counts {
+            out.push_str(&format!("{}={}\n", provider, count));
+        }
+        out
+    }
+
+    fn save(&self) {
+        let Some(path) = usage_file() else { return };
+        if let Some(dir) = path.parent() {
+            let _ = fs::create_dir_all(dir);
+        }
+        let _ = fs::write(path, self.serialize());
+    }
+
+    fn record_at(&mut self, provider: &str, day: u64, now: Instant) -> ProviderUsage {
+        if day != self.day {
+            self.day = day;
+            self.counts.clear();
+        }
+        *self.counts.entry(provider.to_string()).or_insert(0) += 1;
+        self.recent.entry(provider.to_string()).or_default().push_back(now);
+        self.usage_at(provider, now)
+    }
+
+    fn usage_at(&mut self, provider: &str, now: Instant) -> ProviderUsage {
+        let recent = self.recent.entry(provider.to_string()).or_default();
+        while recent.front().is_some_and(|t| now.duration_since(*t) >= Duration::from_secs(60)) {
+            recent.pop_front();
+        }
+
+        let today = self.counts.get(provider).copied().unwrap_or(0);
+        let last_minute = recent.len() as u32;
+        let near = |count: u32, limit: Option<u32>| {
+            limit.is_some_and(|l| count as f64 >= l as f64 * WARN_RATIO)
+        };
+        let near_limit = KNOWN_LIMITS
+            .iter()
+            .find(|(name, _, _)| *name == provider)
+            .is_some_and(|(_, per_minute, per_day)| {
+                near(last_minute, *per_minute) || near(today, *per_day)
+            });
+
+        ProviderUsage {
+            provider: provider.to_string(),
+            today,
+            last_minute,
+            near_limit,
+        }
+    }
+}
+
+/// Records one request to `provider`, persisting the daily counter
+pub fn record(provider: &str) {
+    let Ok(mut usage) = USAGE.lock() else { return };
+    let snapshot = usage.record_at(provider, today(), Instant::now());
+    usage.save();
+
+    if snapshot.near_limit {
+        tracing::warn!(
+            "Approaching {} free-tier limit: {} requests in the last minute, {} today",
+            provider,
+            snapshot.last_minute,
+            snapshot.today
+        );
+    }
+}
+
+/// Returns today's usage for every provider that has been called
+pub fn snapshot() -> Vec<ProviderUsage> {
+    let Ok(mut usage) = USAGE.lock() else { return Vec::new() };
+    if usage.day != today() {
+        usage.day = today();
+        usage.counts.clear();
+    }
+    let now = Instant::now();
+    let providers: Vec<String> = usage.counts.keys().cloned().collect();
+    providers.iter().map(|p| usage.usage_at(p, now)).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_counts_reset_on_new_day() {
+        let mut counter = UsageCounter { day: 100, ..Default::default() };
+        let now = Instant::now();
+        counter.record_at("ip-api.com", 100, now);
+        counter.record_at("ip-api.com", 100, now);
+        assert_eq!(counter.counts["ip-api.com"], 2);
+
+        let usage = counter.record_at("ip-api.com", 101, now);
+        assert_eq!(usage.today, 1);
+    }
+
+    #[test]
+    fn test_near_minute_limit() {
+        let mut counter = UsageCounter { day: 1, ..Default::default() };
+        let now = Instant::now();
+        let mut usage = counter.record_at("ip-api.com", 1, now);
+        for _ in 0..35 {
+            usage = counter.record_at("ip-api.com", 1, now);
+        }
+        assert!(usage.near_limit);
+        assert!(!counter.usage_at("ip-api.com", now + Duration::from_secs(61)).near_limit);
+    }
+
+    #[test]
+    fn test_roundtrip_serialization() {
+        let mut counter = UsageCounter { day: 7, ..Default::default() };
+        counter.counts.insert("ip-api.com".to_string(), 12);
+
+        let mut loaded = UsageCounter { day: 7, ..Default::default() };
+        loaded.parse(&counter.serialize());
+        assert_eq!(loaded.counts, counter.counts);
+
+        let mut next_day = UsageCounter { day: 8, ..Default::default() };
+        next_day.parse(&counter.serialize());
+        assert!(next_day.counts.is_empty());
+    }
+}
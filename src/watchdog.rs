@@ -0,0 +1,67 @@
+//! Expected-country watchdog
+//! With `[watchdog] expected_country` set (e.g. "CH" while a VPN should be up), a refresh
+//! reporting another country raises a critical alert and puts the tray in attention state.
+
+use crate::geo::GeoInfo;
+use crate::notifier::{notify, notify_with, Priority};
+
+/// A change in whether the location matches the expected country
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Transition {
+    Left,
+    Returned,
+}
+
+/// The expected country code if `info` is somewhere else
+pub fn mismatch(info: &GeoInfo) -> Option<String> {
+    let expected = crate::config::current().watchdog.expected_country.clone()?;
+    (!info.country_code.eq_ignore_ascii_case(&expected)).then_some(expected)
+}
+
+/// Alerts when the location stops (or starts again) matching the expected country
+pub fn observe(old: Option<&GeoInfo>, new: &GeoInfo) {
+    let config = crate::config::current();
+    let Some(expected) = config.watchdog.expected_country.as_deref() else { return };
+    match transition(expected, old, new) {
+        Some(Transition::Left) => notify_with(
+            format!(
+                "Not in {}: now in {} ({}){}",
+                expected,
+                new.country_name(),
+                new.country_code,
+                if crate::privacy::hide_ip() { String::new() } else { format!(", IP {}", new.query) }
+            ),
+            0,
+            Priority::Critical,
+        ),
+        Some(Transition::Returned) => notify(format!("Back in {}", new.country_name()), 5000),
+        None => {}
+    }
+}
+
+fn transition(expected: &str, old: Option<&GeoInfo>, new: &GeoInfo) -> Option<Transition> {
+    let matches = |info: &GeoInfo| info.country_code.eq_ignore_ascii_case(expected);
+    match (old.map(matches), matches(new)) {
+        (Some(false), true) => Some(Transition::Returned),
+        (Some(true) | None, false) => Some(Transition::Left),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn geo(code: &str) -> GeoInfo {
+        GeoInfo { country_code: code.to_string(), ..Default::default() }
+    }
+
+    #[test]
+    fn test_transition() {
+        assert_eq!(transition("CH", None, &geo("DE")), Some(Transition::Left));
+        assert_eq!(transition("CH", Some(&geo("CH")), &geo("DE")), Some(Transition::Left));
+        assert_eq!(transition("CH", Some(&geo("DE")), &geo("FR")), None);
+        assert_eq!(transition("CH", Some(&geo("DE")), &geo("CH")), Some(Transition::Returned));
+        assert_eq!(transition("ch", None, &geo("CH")), None);
+    }
+}
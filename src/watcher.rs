@@ -0,0 +1,204 @@
+//! Location change stream for library users
+//! `LocationWatcher` bundles what the app does internally — NetworkManager (or polling)
+//! change detection, a settle delay and the geo lookup — into one `Stream` of changes.
+
+use crate::geo::{self, GeoError};
+use crate::network::{self, NetworkError, NetworkEvent};
+use futures_util::stream::{self, BoxStream};
+use std::future::Future;
+use std::time::Duration;
+use tokio::sync::mpsc;
+
+pub use crate::geo::GeoInfo;
+
+/// Wait after a network event before looking up, bursts of events collapse into one lookup
+const DEFAULT_DEBOUNCE: Duration = Duration::from_secs(2);
+
+/// The public location changed (another country, ISP or IP); `old` is None for the first
+/// lookup
+#[derive(Debug, Clone, PartialEq)]
+pub struct LocationChange {
+    pub old: Option<GeoInfo>,
+    pub new: GeoInfo,
+}
+
+/// Builds a stream of location changes.
+///
+/// ```no_run
+/// use futures_util::StreamExt;
+/// use network_monitor::watcher::LocationWatcher;
+///
+/// # async fn run() {
+/// let mut changes = LocationWatcher::new().interval(std::time::Duration::from_secs(300)).watch();
+/// while let Some(change) = changes.next().await {
+///     println!("now in {}", change.new.country_code);
+/// }
+/// # }
+/// ```
+#[derive(Debug, Clone)]
+pub struct LocationWatcher {
+    debounce: Duration,
+    interval: Option<Duration>,
+}
+
+impl Default for LocationWatcher {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl LocationWatcher {
+    pub fn new() -> Self {
+        Self { debounce: DEFAULT_DEBOUNCE, interval: None }
+    }
+
+    /// Settle delay after a network event (default 2s)
+    pub fn debounce(mut self, debounce: Duration) -> Self {
+        self.debounce = debounce;
+        self
+    }
+
+    /// Also look up periodically, for changes no network event announces (off by default)
+    pub fn interval(mut self, interval: Duration) -> Self {
+        self.interval = Some(interval);
+        self
+    }
+
+    /// Starts watching on the current tokio runtime; dropping the stream stops it.
+    /// Failed lookups are logged and skipped.
+    pub fn watch(self) -> BoxStream<'static, LocationChange> {
+        let (tx, rx) = mpsc::channel(8);
+        tokio::spawn(self.run(tx));
+        Box::pin(stream::unfold(rx, |mut rx| async move { rx.recv().await.map(|change| (change, rx)) }))
+    }
+
+    async fn run(self, tx: mpsc::Sender<LocationChange>) {
+        let (net_tx, net_rx) = mpsc::channel(8);
+        tokio::spawn(async move {
+            let result = match network::watch_network_changes(net_tx.clone()).await {
+                Err(NetworkError::Connection(e)) => {
+                    tracing::debug!("NetworkManager unavailable ({}), polling connectivity", e);
+                    network::poll_connectivity(net_tx, network::probe_connectivity).await
+                }
+                result => result,
+            };
+            if let Err(e) = result {
+                tracing::debug!("Network watch ended: {}", e);
+            }
+        });
+        self.follow(net_rx, tx, geo::fetch_location).await;
+    }
+
+    /// Looks up once, then again after each settled network event or tick, sending changes
+    async fn follow<F, Fut>(self, mut net_rx: mpsc::Receiver<NetworkEvent>, tx: mpsc::Sender<LocationChange>, mut lookup: F)
+    where
+        F: FnMut() -> Fut,
+        Fut: Future<Output = Result<GeoInfo, GeoError>>,
+    {
+        let mut timer = self.interval.map(|period| {
+            let mut timer = tokio::time::interval_at(tokio::time::Instant::now() + period, period);
+            timer.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+            timer
+        });
+        let mut backend_alive = true;
+        let mut last: Option<GeoInfo> = None;
+        loop {
+            match lookup().await {
+                Ok(new) if last.as_ref().is_none_or(|old| geo::is_change(old, &new)) => {
+                    let change = LocationChange { old: last.replace(new.clone()), new };
+                    if tx.send(change).await.is_err() {
+                        return;
+                    }
+                }
+                Ok(_) => {}
+                Err(e) => tracing::debug!("Location lookup failed: {}", e),
+            }
+
+            // Until the next reason to look up
+            loop {
+                tokio::select! {
+                    _ = tick(&mut timer) => break,
+                    event = net_rx.recv(), if backend_alive => match event {
+                        Some(NetworkEvent::Connected) => {
+                            tokio::time::sleep(self.debounce).await;
+                            while net_rx.try_recv().is_ok() {}
+                            break;
+                        }
+                        Some(NetworkEvent::Disconnected) => {}
+                        None if timer.is_some() => backend_alive = false,
+                        None => return,
+                    },
+                    _ = tx.closed() => return,
+                }
+            }
+        }
+    }
+}
+
+/// Next tick of the periodic timer, never without one
+async fn tick(timer: &mut Option<tokio::time::Interval>) {
+    match timer {
+        Some(timer) => {
+            timer.tick().await;
+        }
+        None => std::future::pending().await,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::VecDeque;
+    use std::sync::{Arc, Mutex};
+
+    fn geo(code: &str, ip: &str) -> GeoInfo {
+        GeoInfo { query: ip.to_string(), country_code: code.to_string(), ..Default::default() }
+    }
+
+    /// Runs `follow` with scripted lookup results; returns the event sender, the changes and
+    /// how many lookups were made
+    fn start(results: Vec<GeoInfo>) -> (mpsc::Sender<NetworkEvent>, mpsc::Receiver<LocationChange>, Arc<Mutex<usize>>) {
+        let (net_tx, net_rx) = mpsc::channel(8);
+        let (tx, rx) = mpsc::channel(8);
+        let results = Arc::new(Mutex::new(VecDeque::from(results)));
+        let lookups = Arc::new(Mutex::new(0));
+        let counter = lookups.clone();
+        let lookup = move || {
+            *counter.lock().unwrap() += 1;
+            let next = results.lock().unwrap().pop_front();
+            async move { next.ok_or_else(|| GeoError::ApiError("no more results".to_string())) }
+        };
+        let watcher = LocationWatcher::new().debounce(Duration::from_millis(50));
+        tokio::spawn(watcher.follow(net_rx, tx, lookup));
+        (net_tx, rx, lookups)
+    }
+
+    #[tokio::test]
+    async fn test_changes() {
+        let (net_tx, mut rx, _) = start(vec![geo("VN", "203.0.113.7"), geo("VN", "203.0.113.7"), geo("DE", "198.51.100.1")]);
+        let first = rx.recv().await.unwrap();
+        assert_eq!((first.old, first.new.country_code.as_str()), (None, "VN"));
+
+        // The same location again is not a change
+        net_tx.send(NetworkEvent::Connected).await.unwrap();
+        net_tx.send(NetworkEvent::Disconnected).await.unwrap();
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        net_tx.send(NetworkEvent::Connected).await.unwrap();
+        let moved = rx.recv().await.unwrap();
+        assert_eq!(moved.old.map(|o| o.country_code).as_deref(), Some("VN"));
+        assert_eq!(moved.new.country_code, "DE");
+    }
+
+    #[tokio::test]
+    async fn test_debounce() {
+        let (net_tx, mut rx, lookups) = start(vec![geo("VN", "203.0.113.7"), geo("VN", "203.0.113.8"), geo("VN", "203.0.113.9")]);
+        rx.recv().await.unwrap();
+        for _ in 0..3 {
+            net_tx.send(NetworkEvent::Connected).await.unwrap();
+        }
+        assert_eq!(rx.recv().await.unwrap().new.query, "203.0.113.8");
+        // The burst was one lookup
+        assert!(tokio::time::timeout(Duration::from_millis(200), rx.recv()).await.is_err());
+        assert_eq!(*lookups.lock().unwrap(), 2);
+    }
+}
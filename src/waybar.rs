@@ -0,0 +1,61 @@
+//! Waybar custom-module output
+//! `network-monitor run --waybar` prints one JSON object per line on stdout, for bars
+//! without a StatusNotifier host (Sway/Hyprland): `{"text", "tooltip", "class"}`.
+
+use crate::geo::GeoInfo;
+use crate::service::Connectivity;
+
+/// Flag emoji for an ISO 3166-1 alpha-2 code, e.g. "VN" -> 🇻🇳
+pub fn flag_emoji(code: &str) -> Option<String> {
+    if code.len() != 2 || !code.chars().all(|c| c.is_ascii_alphabetic()) {
+        return None;
+    }
+    code.to_ascii_uppercase()
+        .chars()
+        .map(|c| char::from_u32(0x1F1E6 + (c as u32 - 'A' as u32)))
+        .collect()
+}
+
+/// Renders one Waybar line; `class` is the connectivity ("connected", "disconnected", "unknown")
+pub fn render(info: Option<&GeoInfo>, connectivity: Connectivity, tooltip: &str) -> String {
+    let text = match info {
+        Some(geo) => match flag_emoji(&geo.country_code) {
+            Some(flag) => format!("{} {}", flag, geo.country_code),
+            None => geo.country_code.clone(),
+        },
+        None => "…".to_string(),
+    };
+    serde_json::json!({
+        "text": text,
+        "tooltip": tooltip,
+        "class": connectivity.as_str(),
+    })
+    .to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_flag_emoji() {
+        assert_eq!(flag_emoji("VN").as_deref(), Some("🇻🇳"));
+        assert_eq!(flag_emoji("de").as_deref(), Some("🇩🇪"));
+        assert_eq!(flag_emoji("USA"), None);
+        assert_eq!(flag_emoji("1A"), None);
+    }
+
+    #[test]
+    fn test_render() {
+        let info = GeoInfo {
+            country_code: "VN".to_string(),
+            ..Default::default()
+        };
+        let line = render(Some(&info), Connectivity::Connected, "Vietnam (VN)\n\"quoted\"");
+        let json: serde_json::Value = serde_json::from_str(&line).unwrap();
+        assert_eq!(json["text"], "🇻🇳 VN");
+        assert_eq!(json["class"], "connected");
+        assert_eq!(json["tooltip"], "Vietnam (VN)\n\"quoted\"");
+        assert!(!line.contains('\n'));
+    }
+}
@@ -0,0 +1,127 @@
+//! Webhook notifications on location change
+//! POSTs the old and new location as JSON to `[webhook] url` whenever the country, ISP or
+//! public IP changes, retrying with exponential backoff.
+
+use crate::geo::GeoInfo;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use thiserror::Error;
+
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(10);
+/// Delay before the first retry, doubled for every further one
+const FIRST_RETRY_DELAY: Duration = Duration::from_secs(2);
+
+#[derive(Debug, Error)]
+pub enum WebhookError {
+    /// The URL is stripped, webhook URLs usually embed a secret
+    #[error("Request failed: {0}")]
+    Request(reqwest::Error),
+    #[error("Server answered {0}")]
+    Status(reqwest::StatusCode),
+}
+
+impl WebhookError {
+    /// Network errors, rate limits and server errors are worth retrying
+    fn is_transient(&self) -> bool {
+        match self {
+            WebhookError::Request(_) => true,
+            WebhookError::Status(status) => status.is_server_error() || status.as_u16() == 429,
+        }
+    }
+}
+
+/// Sends the change in the background if a webhook is configured
+pub fn send(old: &GeoInfo, new: &GeoInfo) {
+    let config = crate::config::current().webhook.clone();
+    let Some(url) = config.url else { return };
+    let body = payload(old, new);
+    tokio::spawn(async move {
+        let Some(url) = crate::secrets::resolve("webhook.url", Some(&url)).await else { return };
+        let client = reqwest::Client::new();
+        let mut delay = FIRST_RETRY_DELAY;
+        for attempt in 0..=config.retries {
+            match post(&client, &url, &body).await {
+                Ok(()) => {
+                    tracing::debug!("Webhook delivered");
+                    return;
+                }
+                Err(e) if e.is_transient() && attempt < config.retries => {
+                    tracing::debug!("Webhook attempt {} failed ({}), retrying in {:?}", attempt + 1, e, delay);
+                    tokio::time::sleep(delay).await;
+                    delay *= 2;
+                }
+                Err(e) => {
+                    tracing::warn!("Webhook failed: {}", e);
+                    crate::diagnostics::record_error(format!("Webhook failed: {}", e));
+                    return;
+                }
+            }
+        }
+    });
+}
+
+async fn post(client: &reqwest::Client, url: &str, body: &serde_json::Value) -> Result<(), WebhookError> {
+    let response = client
+        .post(url)
+        .header("User-Agent", "network-monitor")
+        .timeout(REQUEST_TIMEOUT)
+        .json(body)
+        .send()
+        .await
+        .map_err(|e| WebhookError::Request(e.without_url()))?;
+    match response.status() {
+        status if status.is_success() => Ok(()),
+        status => Err(WebhookError::Status(status)),
+    }
+}
+
+fn location(info: &GeoInfo) -> serde_json::Value {
+    serde_json::json!({
+        "ip": info.query,
+        "country": info.country,
+        "country_code": info.country_code,
+        "city": info.city,
+        "isp": info.isp,
+    })
+}
+
+/// `{"event", "timestamp", "old", "new", "text"}`; `text` is a one-line summary for
+/// chat webhooks that display it (Slack, Discord's `/slack` endpoint)
+fn payload(old: &GeoInfo, new: &GeoInfo) -> serde_json::Value {
+    let timestamp = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or(Duration::ZERO)
+        .as_secs();
+    serde_json::json!({
+        "event": "location_changed",
+        "timestamp": timestamp,
+        "old": location(old),
+        "new": location(new),
+        "text": format!(
+            "Network location changed: {} ({}, {}) → {} ({}, {})",
+            old.country_code, old.query, old.isp, new.country_code, new.query, new.isp
+        ),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn geo(country_code: &str, ip: &str, city: &str) -> GeoInfo {
+        GeoInfo {
+            query: ip.to_string(),
+            country_code: country_code.to_string(),
+            city: city.to_string(),
+            isp: "Viettel".to_string(),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_payload() {
+        let body = payload(&geo("VN", "203.0.113.7", "Hanoi"), &geo("DE", "198.51.100.1", "Frankfurt"));
+        assert_eq!(body["old"]["country_code"], "VN");
+        assert_eq!(body["new"]["city"], "Frankfurt");
+        assert_eq!(body["text"], "Network location changed: VN (203.0.113.7, Viettel) → DE (198.51.100.1, Viettel)");
+    }
+}
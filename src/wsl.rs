@@ -0,0 +1,179 @@
+//! WSL2 network change detection via /proc/net
+//! Watches the virtual adapter's addresses and default route, plus periodic probes
+//! of Windows host connectivity, since NetworkManager is not available under WSL.
+
+use crate::network::{probe_connectivity, NetworkError, NetworkEvent};
+use std::collections::BTreeSet;
+use std::fs;
+use std::net::Ipv4Addr;
+use std::time::Duration;
+use tokio::sync::mpsc;
+
+/// Address/route polling is cheap (procfs reads), so poll faster than the probe backend
+const WATCH_INTERVAL: Duration = Duration::from_secs(5);
+/// Connectivity is probed every Nth watch tick
+const PROBE_EVERY: u32 = 3;
+
+/// Returns true when running on a WSL2 kernel (WSL1 has no real /proc/net)
+pub fn is_wsl2() -> bool {
+    fs::read_to_string("/proc/sys/kernel/osrelease")
+        .map(|r| r.to_lowercase().contains("wsl2"))
+        .unwrap_or(false)
+}
+
+/// Snapshot of the adapter state that changes when Windows switches networks
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+struct AdapterState {
+    /// Default routes as (interface, gateway)
+    routes: BTreeSet<(String, Ipv4Addr)>,
+    /// Local non-loopback addresses (IPv4 and IPv6)
+    addresses: BTreeSet<String>,
+}
+
+impl AdapterState {
+    fn read() -> Self {
+        let route = fs::read_to_string("/proc/net/route").unwrap_or_default();
+        let fib_trie = fs::read_to_string("/proc/net/fib_trie").unwrap_or_default();
+        let if_inet6 = fs::read_to_string("/proc/net/if_inet6").unwrap_or_default();
+
+        let mut addresses = parse_local_ipv4(&fib_trie);
+        addresses.extend(parse_ipv6(&if_inet6));
+
+        Self {
+            routes: parse_default_routes(&route),
+            addresses,
+        }
+    }
+}
+
+/// Parses default routes from /proc/net/route (hex, little-endian addresses)
+fn parse_default_routes(content: &str) -> BTreeSet<(String, Ipv4Addr)> {
+    content
+        .lines()
+        .skip(1)
+        .filter_map(|line| {
+            let fields: Vec<&str> = line.split_whitespace().collect();
+            let (iface, dest, gateway) = (fields.first()?, fields.get(1)?, fields.get(2)?);
+            if *dest != "00000000" {
+                return None;
+            }
+            let gw = u32::from_str_radix(gateway, 16).ok()?;
+            Some((iface.to_string(), Ipv4Addr::from(gw.swap_bytes())))
+        })
+        .collect()
+}
+
+/// Parses local host addresses from /proc/net/fib_trie, skipping loopback
+fn parse_local_ipv4(content: &str) -> BTreeSet<String> {
+    let mut addresses = BTreeSet::new();
+    let mut last_addr: Option<&str> = None;
+
+    for line in content.lines() {
+        let trimmed = line.trim();
+        if let Some(addr) = trimmed.strip_prefix("|-- ") {
+            last_addr = Some(addr);
+        } else if trimmed.starts_with("/32 host LOCAL") {
+            if let Some(addr) = last_addr.take() {
+                if !addr.starts_with("127.") {
+                    addresses.insert(addr.to_string());
+                }
+            }
+        }
+    }
+    addresses
+}
+
+/// Parses global IPv6 addresses from /proc/net/if_inet6 (scope 00 = global)
+fn parse_ipv6(content: &str) -> BTreeSet<String> {
+    content
+        .lines()
+        .filter_map(|line| {
+            let fields: Vec<&str> = line.split_whitespace().collect();
+            let (addr, scope, iface) = (fields.first()?, fields.get(3)?, fields.get(5)?);
+            (*scope == "00" && *iface != "lo").then(|| addr.to_string())
+        })
+        .collect()
+}
+
+/// Watches WSL2 adapter changes and Windows host connectivity.
+/// Emits `Connected` when connectivity returns or the adapter state changes while online.
+pub async fn watch_wsl2_changes(tx: mpsc::Sender<NetworkEvent>) -> Result<(), NetworkError> {
+    let mut state = AdapterState::read();
+    let mut was_connected = probe_connectivity().await;
+    tracing::info!(
+        "Watching WSL2 adapter ({} routes, {} addresses, connected={})",
+        state.routes.len(),
+        state.addresses.len(),
+        was_connected
+    );
+
+    let mut interval = tokio::time::interval(WATCH_INTERVAL);
+    interval.tick().await; // Skip immediate tick
+    let mut ticks: u32 = 0;
+
+    loop {
+        interval.tick().await;
+        ticks = ticks.wrapping_add(1);
+
+        let new_state = AdapterState::read();
+        let adapter_changed = new_state != state;
+        state = new_state;
+
+        // Probe on schedule, or immediately after an adapter change
+        if crate::schedule::in_blackout() || (!adapter_changed && !ticks.is_multiple_of(PROBE_EVERY)) {
+            continue;
+        }
+        let is_connected = probe_connectivity().await;
+
+        let event = if is_connected != was_connected {
+            Some(if is_connected { NetworkEvent::Connected } else { NetworkEvent::Disconnected })
+        } else if adapter_changed && is_connected {
+            tracing::info!("WSL2 adapter changed (Windows network switch detected)");
+            Some(NetworkEvent::Connected)
+        } else {
+            None
+        };
+        was_connected = is_connected;
+
+        if let Some(event) = event {
+            if tx.send(event).await.is_err() {
+                return Err(NetworkError::ChannelClosed);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_default_routes() {
+        let content = "Iface\tDestination\tGateway \tFlags\tRefCnt\tUse\tMetric\tMask\t\tMTU\tWindow\tIRTT\n\
+                       eth0\t00000000\t0100A8C0\t0003\t0\t0\t0\t00000000\t0\t0\t0\n\
+                       eth0\t0000A8C0\t00000000\t0001\t0\t0\t0\t00FFFFFF\t0\t0\t0\n";
+        let routes = parse_default_routes(content);
+        assert_eq!(routes.len(), 1);
+        assert!(routes.contains(&("eth0".to_string(), Ipv4Addr::new(192, 168, 0, 1))));
+    }
+
+    #[test]
+    fn test_parse_local_ipv4() {
+        let content = "Main:\n  +-- 0.0.0.0/0 3 0 5\n     |-- 0.0.0.0\n        /0 universe UNICAST\n\
+                       \x20    |-- 127.0.0.1\n        /32 host LOCAL\n\
+                       \x20    |-- 172.20.10.5\n        /32 host LOCAL\n\
+                       \x20    |-- 172.20.15.255\n        /32 link BROADCAST\n";
+        let addrs = parse_local_ipv4(content);
+        assert_eq!(addrs.into_iter().collect::<Vec<_>>(), vec!["172.20.10.5".to_string()]);
+    }
+
+    #[test]
+    fn test_parse_ipv6() {
+        let content = "fe8000000000000002155dfffe012345 02 40 20 80     eth0\n\
+                       20010db8000000000000000000000001 02 40 00 00     eth0\n\
+                       00000000000000000000000000000001 01 80 10 80       lo\n";
+        let addrs = parse_ipv6(content);
+        assert_eq!(addrs.len(), 1);
+        assert!(addrs.contains("20010db8000000000000000000000001"));
+    }
+}